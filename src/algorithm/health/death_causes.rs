@@ -0,0 +1,427 @@
+//! Cause-of-death diagnoses from the Register of Causes of Death (DODSAARSAG)
+//!
+//! Death certificates record an underlying cause of death (`C_AARSAG`) and a contributing
+//! condition (`C_TILSTAND`). Combined with the death date from the death register (DOD),
+//! they are used in two ways:
+//!
+//! - as a diagnosis source for disease definitions with `sources = ["lpr", "death_cause"]`:
+//!   each death becomes a record in the harmonised LPR layout (contact type
+//!   [`DEATH_CONTACT_TYPE`], dated at death), so the SCD algorithm and the phenotype
+//!   engine treat "recorded as cause of death" like a hospital diagnosis;
+//! - for cause-specific mortality: [`cause_specific_mortality`] matches the causes of
+//!   every death against the categories of a phenotype.
+//!
+//! Causes of death before 1994 are coded in ICD-8 and are mapped to ICD-10 like LPR2
+//! diagnoses ([`crate::model::icd8`]); ICD-8 causes without a mapping are left out.
+
+use arrow::array::{new_null_array, Array, ArrayRef, BooleanArray, Date32Array, StringArray};
+use arrow::compute::concat_batches;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::algorithm::health::diagnosis::secondary::{
+    create_secondary_diagnoses_array, SecondaryDiagnosis,
+};
+use crate::algorithm::health::phenotype::Phenotype;
+use crate::data::registry::loaders::dod::DodRegister;
+use crate::data::registry::loaders::dodsaarsag::DodsaarsagRegister;
+use crate::data::registry::traits::RegisterLoader;
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::normalize_diagnosis_code;
+use crate::model::icd10::Icd10Dictionary;
+use crate::model::icd8::harmonize_diagnosis_code;
+use crate::utils::date_utils::days_since_epoch_to_date;
+
+/// Contact type of the records created from death certificates
+pub const DEATH_CONTACT_TYPE: &str = "death";
+
+/// Causes of death of one person
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeathRecord {
+    /// Patient identifier
+    pub patient_id: String,
+    /// Date of death (days since epoch), if the person is in the death register
+    pub death_date: Option<i32>,
+    /// Underlying cause of death, harmonised to ICD-10
+    pub underlying_cause: Option<String>,
+    /// Contributing condition, harmonised to ICD-10
+    pub contributing_cause: Option<String>,
+}
+
+/// Collect death dates from standardized DOD batches (`PNR`, `DEATH_DATE`)
+pub fn collect_death_dates(batches: &[RecordBatch]) -> Result<HashMap<String, i32>> {
+    let mut death_dates = HashMap::new();
+    for batch in batches {
+        let pnr_array = batch
+            .column_by_name("PNR")
+            .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| IdsError::Data("PNR column not found in DOD data".to_string()))?;
+        let date_array = batch
+            .column_by_name("DEATH_DATE")
+            .and_then(|column| column.as_any().downcast_ref::<Date32Array>())
+            .ok_or_else(|| IdsError::Data("DEATH_DATE column not found in DOD data".to_string()))?;
+
+        for row in 0..batch.num_rows() {
+            if !pnr_array.is_null(row) && !date_array.is_null(row) {
+                death_dates.insert(pnr_array.value(row).to_string(), date_array.value(row));
+            }
+        }
+    }
+    Ok(death_dates)
+}
+
+/// Build death records from standardized DODSAARSAG batches and death dates
+///
+/// Cause codes are harmonised with the death date, so ICD-8 causes of death before 1994
+/// are mapped to ICD-10.
+pub fn collect_death_records(
+    death_causes: &[RecordBatch],
+    death_dates: &HashMap<String, i32>,
+) -> Result<Vec<DeathRecord>> {
+    let mut records = Vec::new();
+    let mut without_date = 0usize;
+
+    for batch in death_causes {
+        let string_column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| {
+                    IdsError::Data(format!("{name} column not found in DODSAARSAG data"))
+                })
+        };
+        let pnr_array = string_column("PNR")?;
+        let cause_array = string_column("DEATH_CAUSE")?;
+        let condition_array = string_column("DEATH_CONDITION")?;
+
+        for row in 0..batch.num_rows() {
+            if pnr_array.is_null(row) {
+                continue;
+            }
+            let patient_id = pnr_array.value(row);
+            let death_date = death_dates.get(patient_id).copied();
+            if death_date.is_none() {
+                without_date += 1;
+            }
+            let harmonize = |array: &StringArray| {
+                if array.is_null(row) {
+                    return None;
+                }
+                let code = array.value(row);
+                match harmonize_diagnosis_code(code, death_date.map(days_since_epoch_to_date)) {
+                    Some(h) => h.icd10_code().map(str::to_string),
+                    None => Some(code.to_string()),
+                }
+            };
+
+            records.push(DeathRecord {
+                patient_id: patient_id.to_string(),
+                death_date,
+                underlying_cause: harmonize(cause_array),
+                contributing_cause: harmonize(condition_array),
+            });
+        }
+    }
+
+    if without_date > 0 {
+        log::warn!("{without_date} causes of death have no death date in the death register");
+    }
+    log::info!("Collected {} causes of death", records.len());
+
+    Ok(records)
+}
+
+/// Load death records from DODSAARSAG and, for the death dates, DOD data
+pub async fn load_death_records(
+    death_causes_path: &Path,
+    deaths_path: Option<&Path>,
+) -> Result<Vec<DeathRecord>> {
+    let death_causes = DodsaarsagRegister
+        .load(&death_causes_path.to_string_lossy(), None)
+        .await?;
+    let death_dates = match deaths_path {
+        Some(path) => collect_death_dates(&DodRegister.load(&path.to_string_lossy(), None).await?)?,
+        None => {
+            log::warn!(
+                "No death register data given; causes of death are undated and cannot be \
+                 used for dates or time-window rules"
+            );
+            HashMap::new()
+        }
+    };
+    collect_death_records(&death_causes, &death_dates)
+}
+
+/// Convert death records to records with the schema of harmonised LPR data
+///
+/// The underlying cause becomes the primary diagnosis and the contributing condition a
+/// secondary diagnosis; the admission and discharge dates are the death date and the
+/// contact type is [`DEATH_CONTACT_TYPE`]. Other columns of the schema are left null.
+pub fn death_records_to_health_records(
+    records: &[DeathRecord],
+    schema: &SchemaRef,
+) -> Result<RecordBatch> {
+    let dictionary = Icd10Dictionary::bundled();
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+
+    for field in schema.fields() {
+        let column: ArrayRef = match (field.name().as_str(), field.data_type()) {
+            ("patient_id", DataType::Utf8) => Arc::new(StringArray::from_iter_values(
+                records.iter().map(|record| record.patient_id.as_str()),
+            )),
+            ("primary_diagnosis", DataType::Utf8) => Arc::new(StringArray::from_iter(
+                records
+                    .iter()
+                    .map(|record| record.underlying_cause.as_deref()),
+            )),
+            ("secondary_diagnoses", DataType::List(_)) => {
+                let diagnoses: Vec<Option<Vec<SecondaryDiagnosis>>> = records
+                    .iter()
+                    .map(|record| {
+                        record.contributing_cause.as_ref().map(|code| {
+                            vec![SecondaryDiagnosis::new(code.clone(), "B".to_string(), None)]
+                        })
+                    })
+                    .collect();
+                create_secondary_diagnoses_array(&diagnoses)
+            }
+            ("diagnosis_chapter", DataType::Utf8) => {
+                Arc::new(StringArray::from_iter(records.iter().map(|record| {
+                    record
+                        .underlying_cause
+                        .as_deref()
+                        .and_then(crate::model::icd10::Icd10Chapter::from_code)
+                        .map(|chapter| chapter.description())
+                })))
+            }
+            ("primary_diagnosis_description", DataType::Utf8) => {
+                Arc::new(StringArray::from_iter(records.iter().map(|record| {
+                    record
+                        .underlying_cause
+                        .as_deref()
+                        .and_then(|code| dictionary.nearest(code))
+                        .map(|entry| entry.description.as_str())
+                })))
+            }
+            ("admission_date" | "discharge_date", DataType::Date32) => Arc::new(
+                Date32Array::from_iter(records.iter().map(|record| record.death_date)),
+            ),
+            ("admission_type", DataType::Utf8) => Arc::new(StringArray::from_iter_values(
+                records.iter().map(|_| DEATH_CONTACT_TYPE),
+            )),
+            _ => new_null_array(field.data_type(), records.len()),
+        };
+        columns.push(column);
+    }
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| IdsError::Data(format!("Failed to create cause-of-death records: {e}")))
+}
+
+/// Add cause-of-death records to harmonised LPR data
+///
+/// With `include_lpr` false only the cause-of-death records are returned, for
+/// definitions that use death certificates as their only source. The commands call this
+/// for one partition of the harmonised data at a time.
+pub fn append_death_causes(
+    lpr_data: &RecordBatch,
+    records: &[DeathRecord],
+    include_lpr: bool,
+) -> Result<RecordBatch> {
+    let schema = lpr_data.schema();
+    let death_batch = death_records_to_health_records(records, &schema)?;
+    if !include_lpr {
+        return Ok(death_batch);
+    }
+    concat_batches(&schema, [lpr_data, &death_batch])
+        .map_err(|e| IdsError::Data(format!("Failed to add cause-of-death records: {e}")))
+}
+
+/// Cause-specific mortality of one deceased person
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MortalityOutcome {
+    /// Patient identifier
+    pub patient_id: String,
+    /// Date of death, if known
+    pub death_date: Option<NaiveDate>,
+    /// Underlying cause of death
+    pub underlying_cause: Option<String>,
+    /// Per category, in definition order: the underlying cause matches the category
+    pub underlying: Vec<bool>,
+    /// Per category: the underlying cause or the contributing condition matches
+    pub any_cause: Vec<bool>,
+}
+
+/// Derive cause-specific mortality outcomes for every death record
+///
+/// The causes of death are matched against the diagnosis patterns of the phenotype's
+/// categories; contact types, procedures and confirmation rules do not apply.
+#[must_use]
+pub fn cause_specific_mortality(
+    records: &[DeathRecord],
+    phenotype: &Phenotype,
+) -> Vec<MortalityOutcome> {
+    let category_count = phenotype.category_names().len();
+    let matches = |code: Option<&str>| {
+        let mut hits = vec![false; category_count];
+        if let Some(normalized) = code.and_then(normalize_diagnosis_code) {
+            for index in phenotype.diagnosis_categories(&normalized) {
+                hits[index] = true;
+            }
+        }
+        hits
+    };
+
+    records
+        .iter()
+        .map(|record| {
+            let underlying = matches(record.underlying_cause.as_deref());
+            let contributing = matches(record.contributing_cause.as_deref());
+            MortalityOutcome {
+                patient_id: record.patient_id.clone(),
+                death_date: record.death_date.map(days_since_epoch_to_date),
+                underlying_cause: record.underlying_cause.clone(),
+                any_cause: underlying
+                    .iter()
+                    .zip(&contributing)
+                    .map(|(a, b)| *a || *b)
+                    .collect(),
+                underlying,
+            }
+        })
+        .collect()
+}
+
+/// Convert mortality outcomes to a `RecordBatch`
+///
+/// Columns are `patient_id`, `death_date`, `underlying_cause`, and per category
+/// `death_<category>` (underlying cause) and `death_<category>_any` (any cause).
+pub fn mortality_to_record_batch(
+    phenotype: &Phenotype,
+    outcomes: &[MortalityOutcome],
+) -> Result<RecordBatch> {
+    let mut fields = vec![
+        Field::new("patient_id", DataType::Utf8, false),
+        Field::new("death_date", DataType::Date32, true),
+        Field::new("underlying_cause", DataType::Utf8, true),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            outcomes.iter().map(|outcome| outcome.patient_id.as_str()),
+        )),
+        Arc::new(Date32Array::from_iter(outcomes.iter().map(|outcome| {
+            outcome
+                .death_date
+                .map(crate::utils::date_utils::date_to_days_since_epoch)
+        }))),
+        Arc::new(StringArray::from_iter(
+            outcomes
+                .iter()
+                .map(|outcome| outcome.underlying_cause.as_deref()),
+        )),
+    ];
+
+    for (index, category) in phenotype.category_names().iter().enumerate() {
+        fields.push(Field::new(
+            format!("death_{category}"),
+            DataType::Boolean,
+            false,
+        ));
+        columns.push(Arc::new(BooleanArray::from_iter(
+            outcomes
+                .iter()
+                .map(|outcome| Some(outcome.underlying[index])),
+        )));
+        fields.push(Field::new(
+            format!("death_{category}_any"),
+            DataType::Boolean,
+            false,
+        ));
+        columns.push(Arc::new(BooleanArray::from_iter(
+            outcomes
+                .iter()
+                .map(|outcome| Some(outcome.any_cause[index])),
+        )));
+    }
+
+    let schema =
+        Schema::new(fields).with_metadata(phenotype.definition_info().metadata("mortality"));
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .map_err(|e| IdsError::Data(format!("Failed to create mortality batch: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::health::diagnosis::definitions::DiseaseDefinitions;
+
+    #[test]
+    fn test_death_causes_as_diagnosis_source() {
+        let definitions = DiseaseDefinitions::from_toml(
+            r#"
+                format_version = 1
+                name = "copd"
+                version = "1"
+                sources = ["lpr", "death_cause"]
+
+                [[categories]]
+                name = "copd"
+                include = [{ type = "prefix", codes = ["J44"] }]
+            "#,
+            "test.toml",
+        )
+        .unwrap();
+        let phenotype = Phenotype::from_definitions(&definitions).unwrap();
+        assert!(
+            phenotype.uses_source(crate::algorithm::health::diagnosis::DiagnosisSource::DeathCause)
+        );
+
+        let death_causes = RecordBatch::try_from_iter(vec![
+            (
+                "PNR",
+                Arc::new(StringArray::from(vec!["p1", "p2"])) as ArrayRef,
+            ),
+            (
+                "DEATH_CAUSE",
+                Arc::new(StringArray::from(vec!["I219", "49199"])) as ArrayRef,
+            ),
+            (
+                "DEATH_CONDITION",
+                Arc::new(StringArray::from(vec![Some("J449"), None])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        // p2 died in 1990, so the ICD-8 cause 491.99 (chronic bronchitis) maps to J42
+        let death_dates = HashMap::from([("p1".to_string(), 18_000), ("p2".to_string(), 7_400)]);
+        let records = collect_death_records(&[death_causes], &death_dates).unwrap();
+        assert_eq!(records[1].underlying_cause.as_deref(), Some("J42"));
+
+        let outcomes = cause_specific_mortality(&records, &phenotype);
+        assert_eq!(outcomes[0].underlying, vec![false]);
+        assert_eq!(outcomes[0].any_cause, vec![true]);
+        assert_eq!(outcomes[1].any_cause, vec![false]);
+        assert_eq!(
+            mortality_to_record_batch(&phenotype, &outcomes)
+                .unwrap()
+                .num_columns(),
+            5
+        );
+
+        let lpr_schema: SchemaRef = Arc::new(Schema::new(vec![
+            Field::new("patient_id", DataType::Utf8, true),
+            Field::new("primary_diagnosis", DataType::Utf8, true),
+            Field::new("admission_date", DataType::Date32, true),
+            Field::new("admission_type", DataType::Utf8, true),
+            Field::new("hospital_code", DataType::Utf8, true),
+        ]));
+        let lpr_data = RecordBatch::new_empty(lpr_schema);
+        let combined = append_death_causes(&lpr_data, &records, true).unwrap();
+        assert_eq!(combined.num_rows(), 2);
+        assert_eq!(combined.column(3).null_count(), 0);
+        assert_eq!(combined.column(4).null_count(), 2);
+    }
+}
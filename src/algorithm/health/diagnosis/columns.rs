@@ -0,0 +1,196 @@
+//! Access to the diagnosis and procedure columns of harmonised health data
+//!
+//! Shared by the SCD algorithm and the phenotype engine so both read single-code and
+//! list-of-struct diagnosis columns, contact dates and types, and the harmonised
+//! procedure table, the same way.
+
+use arrow::array::{Array, Date32Array, ListArray, StringArray, StructArray};
+use arrow::record_batch::RecordBatch;
+
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::{normalize_procedure_code, NormalizedDiagnosis};
+
+/// Diagnosis code columns of a health data batch
+///
+/// String columns hold one code per row; list columns (such as the harmonised
+/// `secondary_diagnoses`) hold structs with a `code` field.
+pub(crate) struct DiagnosisColumns<'a> {
+    single: Vec<&'a StringArray>,
+    lists: Vec<(&'a ListArray, &'a StringArray)>,
+}
+
+impl<'a> DiagnosisColumns<'a> {
+    /// Find the configured diagnosis columns, skipping missing or unsupported ones
+    pub(crate) fn new(health_data: &'a RecordBatch, column_names: &[String]) -> Self {
+        let mut single = Vec::with_capacity(column_names.len());
+        let mut lists = Vec::new();
+        for name in column_names {
+            let Some(column) = health_data.column_by_name(name) else {
+                continue;
+            };
+            if let Some(array) = column.as_any().downcast_ref::<StringArray>() {
+                single.push(array);
+            } else if let Some(list_array) = column.as_any().downcast_ref::<ListArray>() {
+                let codes = list_array
+                    .values()
+                    .as_any()
+                    .downcast_ref::<StructArray>()
+                    .and_then(|values| values.column_by_name("code"))
+                    .and_then(|codes| codes.as_any().downcast_ref::<StringArray>());
+                if let Some(codes) = codes {
+                    lists.push((list_array, codes));
+                }
+            }
+        }
+        Self { single, lists }
+    }
+
+    /// All non-null diagnosis codes in a row
+    pub(crate) fn codes(&self, row: usize) -> impl Iterator<Item = &'a str> + '_ {
+        let single = self
+            .single
+            .iter()
+            .filter(move |array| !array.is_null(row))
+            .map(move |array| array.value(row));
+        let lists = self
+            .lists
+            .iter()
+            .filter(move |(list_array, _)| !list_array.is_null(row))
+            .flat_map(move |(list_array, codes)| {
+                let offsets = list_array.value_offsets();
+                (offsets[row] as usize..offsets[row + 1] as usize)
+                    .filter(|&j| !codes.is_null(j))
+                    .map(|j| codes.value(j))
+            });
+        single.chain(lists)
+    }
+}
+
+/// Patient ID, contact date and contact type columns of a health data batch
+pub(crate) struct ContactColumns<'a> {
+    patient_ids: &'a StringArray,
+    dates: Option<&'a Date32Array>,
+    discharge_dates: Option<&'a Date32Array>,
+    contact_types: Option<&'a StringArray>,
+    inpatient_types: &'a [String],
+}
+
+impl<'a> ContactColumns<'a> {
+    /// Find the patient ID column and the (optional) date, discharge date and contact
+    /// type columns
+    pub(crate) fn new(
+        health_data: &'a RecordBatch,
+        patient_id_column: &str,
+        date_column: &str,
+        discharge_date_column: &str,
+        contact_type_column: &str,
+        inpatient_types: &'a [String],
+    ) -> Result<Self> {
+        let date_array = |name: &str| {
+            health_data
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<Date32Array>())
+        };
+        Ok(Self {
+            patient_ids: health_data
+                .column_by_name(patient_id_column)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| {
+                    IdsError::Data(format!(
+                        "Patient ID column '{patient_id_column}' not found or not a string array"
+                    ))
+                })?,
+            dates: date_array(date_column),
+            discharge_dates: date_array(discharge_date_column),
+            contact_types: health_data
+                .column_by_name(contact_type_column)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>()),
+            inpatient_types,
+        })
+    }
+
+    /// Whether the contact date column was found
+    pub(crate) fn has_dates(&self) -> bool {
+        self.dates.is_some()
+    }
+
+    /// Patient ID of a row
+    pub(crate) fn patient_id(&self, row: usize) -> Option<&'a str> {
+        (!self.patient_ids.is_null(row)).then(|| self.patient_ids.value(row))
+    }
+
+    /// Contact date of a row in days since epoch
+    pub(crate) fn date(&self, row: usize) -> Option<i32> {
+        self.dates
+            .filter(|dates| !dates.is_null(row))
+            .map(|dates| dates.value(row))
+    }
+
+    /// Contact type of a row
+    pub(crate) fn contact_type(&self, row: usize) -> Option<&'a str> {
+        self.contact_types
+            .filter(|types| !types.is_null(row))
+            .map(|types| types.value(row))
+    }
+
+    /// Whether a row is an inpatient contact: an inpatient contact type, or discharged
+    /// after the contact date
+    pub(crate) fn inpatient(&self, row: usize) -> bool {
+        self.contact_type(row)
+            .is_some_and(|value| self.inpatient_types.iter().any(|t| t == value))
+            || self
+                .discharge_dates
+                .zip(self.date(row))
+                .is_some_and(|(discharge, date)| {
+                    !discharge.is_null(row) && discharge.value(row) > date
+                })
+    }
+}
+
+/// Columns of the harmonised procedure table
+pub(crate) struct ProcedureColumns<'a> {
+    patient_ids: &'a StringArray,
+    codes: &'a StringArray,
+    dates: Option<&'a Date32Array>,
+}
+
+impl<'a> ProcedureColumns<'a> {
+    /// Find the patient ID, procedure code and (optional) procedure date columns
+    pub(crate) fn new(
+        procedures: &'a RecordBatch,
+        patient_id_column: &str,
+        code_column: &str,
+        date_column: &str,
+    ) -> Result<Self> {
+        let string_column = |name: &str| {
+            procedures
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| {
+                    IdsError::Data(format!(
+                        "Procedure column '{name}' not found or not a string array"
+                    ))
+                })
+        };
+        Ok(Self {
+            patient_ids: string_column(patient_id_column)?,
+            codes: string_column(code_column)?,
+            dates: procedures
+                .column_by_name(date_column)
+                .and_then(|column| column.as_any().downcast_ref::<Date32Array>()),
+        })
+    }
+
+    /// Patient ID, normalised procedure code and date of a row, if it has an ID and code
+    pub(crate) fn row(&self, row: usize) -> Option<(&'a str, NormalizedDiagnosis, Option<i32>)> {
+        if self.patient_ids.is_null(row) || self.codes.is_null(row) {
+            return None;
+        }
+        let code = normalize_procedure_code(self.codes.value(row))?;
+        let date = self
+            .dates
+            .filter(|dates| !dates.is_null(row))
+            .map(|dates| dates.value(row));
+        Some((self.patient_ids.value(row), code, date))
+    }
+}
@@ -0,0 +1,304 @@
+//! Confirmation rules for chronic disease phenotypes
+//!
+//! A single matching diagnosis is often not enough to establish a chronic condition:
+//! one-off coding errors and rule-out diagnoses inflate prevalence. Confirmation rules
+//! are attached to disease definitions (for the whole file or per category) and
+//! evaluated on all matching diagnosis events of a patient:
+//!
+//! - `min_contacts`: diagnoses on at least N distinct contact dates
+//! - `min_days_between`: first and last diagnosis at least D days apart
+//! - `max_age_years`: first diagnosis before age A (requires birth dates)
+//! - `require_inpatient`: at least one diagnosis from an inpatient contact
+//! - `first_diagnosis_from`/`first_diagnosis_to`: first diagnosis within the study window
+
+use chrono::{Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::utils::date_utils::{date_to_days_since_epoch, days_since_epoch_to_date};
+
+/// A diagnosis event used to evaluate confirmation rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosisEvent {
+    /// Contact (admission) date in days since epoch
+    pub date: i32,
+    /// Whether the diagnosis came from an inpatient contact
+    pub inpatient: bool,
+}
+
+/// Matching records of one patient per category
+#[derive(Debug, Clone)]
+pub(crate) struct CategoryEvents {
+    /// Number of records matching any category
+    pub(crate) count: usize,
+    /// Whether any record matched, per category
+    pub(crate) matched: Vec<bool>,
+    /// Dated events per category
+    pub(crate) events: Vec<Vec<DiagnosisEvent>>,
+}
+
+impl CategoryEvents {
+    pub(crate) fn new(categories: usize) -> Self {
+        Self {
+            count: 0,
+            matched: vec![false; categories],
+            events: vec![Vec::new(); categories],
+        }
+    }
+
+    /// Record a record matching the categories flagged in `hits`
+    pub(crate) fn record(&mut self, hits: &[bool], date: Option<i32>, inpatient: bool) {
+        if !hits.contains(&true) {
+            return;
+        }
+        self.count += 1;
+        for (index, _) in hits.iter().enumerate().filter(|(_, hit)| **hit) {
+            self.matched[index] = true;
+            if let Some(date) = date {
+                self.events[index].push(DiagnosisEvent { date, inpatient });
+            }
+        }
+    }
+}
+
+/// Confirm each category of a patient from its rules, flag and events
+///
+/// Categories with rules are confirmed by [`ConfirmationRules::confirm`]; categories
+/// without rules are confirmed by their flag and dated by their earliest event. Returns
+/// the confirmed flag per category and the earliest first date of a confirmed category.
+pub(crate) fn confirm_categories<'a>(
+    categories: impl IntoIterator<Item = (&'a ConfirmationRules, bool, &'a [DiagnosisEvent])>,
+    birth_date: Option<NaiveDate>,
+) -> (Vec<bool>, Option<NaiveDate>) {
+    let mut first_date: Option<NaiveDate> = None;
+    let confirmed = categories
+        .into_iter()
+        .map(|(rules, flagged, events)| {
+            let confirmed = if rules.is_empty() {
+                flagged.then(|| {
+                    events
+                        .iter()
+                        .map(|event| event.date)
+                        .min()
+                        .map(days_since_epoch_to_date)
+                })
+            } else {
+                rules.confirm(events, birth_date).map(Some)
+            };
+            if let Some(Some(date)) = confirmed {
+                first_date = Some(first_date.map_or(date, |first| first.min(date)));
+            }
+            confirmed.is_some()
+        })
+        .collect();
+    (confirmed, first_date)
+}
+
+/// Rules a patient's matching diagnoses must satisfy for a phenotype to be confirmed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfirmationRules {
+    /// Minimum number of distinct contact dates with a matching diagnosis
+    pub min_contacts: Option<u32>,
+    /// Minimum number of days between the first and last matching diagnosis
+    pub min_days_between: Option<u32>,
+    /// The first matching diagnosis must be before this age in years
+    pub max_age_years: Option<u32>,
+    /// At least one matching diagnosis must come from an inpatient contact
+    pub require_inpatient: bool,
+    /// Earliest allowed date of the first matching diagnosis
+    pub first_diagnosis_from: Option<NaiveDate>,
+    /// Latest allowed date of the first matching diagnosis
+    pub first_diagnosis_to: Option<NaiveDate>,
+}
+
+impl ConfirmationRules {
+    /// Whether no rule is set, i.e. any single diagnosis confirms the phenotype
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check that the rules can be evaluated
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self
+            .max_age_years
+            .is_some_and(|years| years.checked_mul(12).is_none())
+        {
+            return Err("max_age_years is too large".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.first_diagnosis_from, self.first_diagnosis_to) {
+            if from > to {
+                return Err("the first diagnosis window ends before it starts".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the rules need the patient's birth date
+    #[must_use]
+    pub const fn requires_birth_date(&self) -> bool {
+        self.max_age_years.is_some()
+    }
+
+    /// Evaluate the rules on a patient's matching diagnosis events
+    ///
+    /// Returns the date of the first diagnosis if the phenotype is confirmed.
+    #[must_use]
+    pub fn confirm(
+        &self,
+        events: &[DiagnosisEvent],
+        birth_date: Option<NaiveDate>,
+    ) -> Option<NaiveDate> {
+        let first = events.iter().map(|event| event.date).min()?;
+        let last = events.iter().map(|event| event.date).max()?;
+        let first_date = days_since_epoch_to_date(first);
+
+        if self
+            .first_diagnosis_from
+            .is_some_and(|from| first_date < from)
+            || self.first_diagnosis_to.is_some_and(|to| first_date > to)
+        {
+            return None;
+        }
+
+        if let Some(min_contacts) = self.min_contacts {
+            let contacts: HashSet<i32> = events.iter().map(|event| event.date).collect();
+            if contacts.len() < min_contacts as usize {
+                return None;
+            }
+        }
+
+        if let Some(min_days) = self.min_days_between {
+            if i64::from(last) - i64::from(first) < i64::from(min_days) {
+                return None;
+            }
+        }
+
+        if self.require_inpatient && !events.iter().any(|event| event.inpatient) {
+            return None;
+        }
+
+        if let Some(max_age) = self.max_age_years {
+            let limit = birth_date?.checked_add_months(Months::new(max_age.checked_mul(12)?))?;
+            if first >= date_to_days_since_epoch(limit) {
+                return None;
+            }
+        }
+
+        Some(first_date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(date: &str, inpatient: bool) -> DiagnosisEvent {
+        DiagnosisEvent {
+            date: date_to_days_since_epoch(NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()),
+            inpatient,
+        }
+    }
+
+    #[test]
+    fn test_confirmation_rules() {
+        let events = [event("2010-03-01", false), event("2010-03-01", false), event("2010-06-01", true)];
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(ConfirmationRules::default().confirm(&events, None), Some(date("2010-03-01")));
+
+        let two_contacts = ConfirmationRules {
+            min_contacts: Some(2),
+            ..ConfirmationRules::default()
+        };
+        assert!(two_contacts.confirm(&events, None).is_some());
+        assert!(two_contacts.confirm(&events[..2], None).is_none());
+
+        let apart = ConfirmationRules {
+            min_days_between: Some(100),
+            ..ConfirmationRules::default()
+        };
+        assert!(apart.confirm(&events, None).is_none());
+
+        let inpatient = ConfirmationRules {
+            require_inpatient: true,
+            ..ConfirmationRules::default()
+        };
+        assert!(inpatient.confirm(&events[..2], None).is_none());
+
+        let before_age = ConfirmationRules {
+            max_age_years: Some(5),
+            ..ConfirmationRules::default()
+        };
+        assert!(before_age.confirm(&events, Some(date("2005-03-02"))).is_some());
+        assert!(before_age.confirm(&events, Some(date("2005-03-01"))).is_none());
+        assert!(before_age.confirm(&events, None).is_none());
+
+        let overflowing_age = ConfirmationRules {
+            max_age_years: Some(u32::MAX),
+            ..ConfirmationRules::default()
+        };
+        assert!(overflowing_age.validate().is_err());
+        assert!(overflowing_age.confirm(&events, Some(date("2005-03-02"))).is_none());
+
+        let window = ConfirmationRules {
+            first_diagnosis_from: Some(date("2010-04-01")),
+            ..ConfirmationRules::default()
+        };
+        assert!(window.confirm(&events, None).is_none());
+    }
+
+    #[test]
+    fn test_category_events() {
+        let mut events = CategoryEvents::new(3);
+        events.record(&[false, false, false], Some(1), false);
+        assert_eq!(events.count, 0);
+
+        events.record(&[true, false, true], Some(10), true);
+        events.record(&[false, true, false], None, false);
+        assert_eq!(events.count, 2);
+        assert_eq!(events.matched, vec![true, true, true]);
+        assert_eq!(events.events[0], vec![DiagnosisEvent { date: 10, inpatient: true }]);
+        assert_eq!(events.events[2], events.events[0]);
+        // Undated records flag the category without adding an event
+        assert!(events.events[1].is_empty());
+    }
+
+    #[test]
+    fn test_confirm_categories() {
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let single = [event("2012-01-01", false)];
+        let repeated = [event("2011-01-01", false), event("2011-02-01", false)];
+        let no_rules = ConfirmationRules::default();
+        let two_contacts = ConfirmationRules {
+            min_contacts: Some(2),
+            ..ConfirmationRules::default()
+        };
+
+        // Unflagged categories without rules stay unconfirmed even with events
+        let (confirmed, first_date) = confirm_categories(
+            [
+                (&no_rules, true, &single[..]),
+                (&no_rules, false, &repeated[..]),
+                (&two_contacts, true, &single[..]),
+            ],
+            None,
+        );
+        assert_eq!(confirmed, vec![true, false, false]);
+        assert_eq!(first_date, Some(date("2012-01-01")));
+
+        // The first date is the earliest of the confirmed categories
+        let (confirmed, first_date) = confirm_categories(
+            [(&no_rules, true, &single[..]), (&two_contacts, false, &repeated[..])],
+            None,
+        );
+        assert_eq!(confirmed, vec![true, true]);
+        assert_eq!(first_date, Some(date("2011-01-01")));
+
+        // A flagged category without dated events is confirmed but undated
+        let (confirmed, first_date) = confirm_categories([(&no_rules, true, &[][..])], None);
+        assert_eq!(confirmed, vec![true]);
+        assert_eq!(first_date, None);
+    }
+}
@@ -0,0 +1,462 @@
+//! Versioned disease-code definition files
+//!
+//! Disease definitions (such as the SCD categories) are loaded from JSON or TOML files
+//! rather than being compiled into the crate, so that a study can pin, review and
+//! extend the exact code lists it uses. A definition file has a name and version, and
+//! a list of categories with include and exclude patterns:
+//!
+//! ```json
+//! {
+//!   "format_version": 1,
+//!   "name": "scd",
+//!   "version": "1.0.0",
+//!   "categories": [
+//!     {
+//!       "name": "cardiovascular",
+//!       "description": "Cardiovascular disorders",
+//!       "include": [
+//!         { "type": "prefix", "codes": ["I27", "I42"] },
+//!         { "type": "range", "from": "I30", "to": "I52" },
+//!         { "type": "regex", "pattern": "Q2[0-8]" }
+//!       ],
+//!       "exclude": [{ "type": "prefix", "codes": ["I519"] }]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Confirmation rules (see [`super::confirmation`]) can be set for the whole file with a
+//! top-level `confirmation` table and overridden per category. Categories can also list
+//! SKS `procedures` patterns (e.g. dialysis `BJFD2` or gastrostomy `KJDB`), matched
+//! against the harmonised LPR procedure table, and the contact types (`contact_types`)
+//! their diagnoses must come from; contact types are only applied by the phenotype engine
+//! ([`crate::algorithm::health::phenotype`]).
+//!
+//! The top-level `sources` list selects the registers diagnoses are taken from: `lpr`
+//! (the default) and/or `death_cause`, the underlying and contributing causes of death in
+//! the Register of Causes of Death (see [`crate::algorithm::health::death_causes`]).
+//!
+//! The SHA-256 hash of the file contents is recorded alongside the name and version so
+//! outputs can be traced back to the exact definitions that produced them.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::algorithm::health::diagnosis::confirmation::ConfirmationRules;
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::DiagnosisPattern;
+
+/// The definition file format version understood by this crate
+pub const DEFINITIONS_FORMAT_VERSION: u32 = 1;
+
+/// The built-in SCD definitions, used when no definition file is given
+const BUILTIN_SCD_DEFINITIONS: &str =
+    include_str!("../../../../schemas/definitions/scd_default.json");
+
+/// SCD definitions reproducing the reference `docs/references/SCDalgo.R` flag
+const SCDALGO_R_DEFINITIONS: &str =
+    include_str!("../../../../schemas/definitions/scdalgo_r.json");
+
+/// Source label recorded for the built-in definitions
+pub const BUILTIN_SOURCE: &str = "built-in";
+
+/// Source label recorded for the bundled `SCDalgo.R` definitions
+pub const SCDALGO_R_SOURCE: &str = "built-in:scdalgo_r";
+
+/// A single pattern in a definition file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PatternDefinition {
+    /// One or more code prefixes, e.g. `["I27", "I42"]` or `["I519"]`, matched against the
+    /// start of the full code
+    Prefix {
+        codes: Vec<String>,
+        #[serde(default)]
+        description: String,
+    },
+    /// A regular expression anchored at the start of the code
+    Regex {
+        pattern: String,
+        #[serde(default)]
+        description: String,
+    },
+    /// An inclusive code range, e.g. `I30` to `I52`
+    Range {
+        from: String,
+        to: String,
+        #[serde(default)]
+        description: String,
+    },
+}
+
+impl PatternDefinition {
+    /// Compile the definition into diagnosis patterns
+    pub fn compile(&self) -> Result<Vec<DiagnosisPattern>> {
+        match self {
+            Self::Prefix { codes, description } => Ok(codes
+                .iter()
+                .map(|code| DiagnosisPattern::new_prefix(&code.trim().to_ascii_uppercase(), description))
+                .collect()),
+            Self::Regex {
+                pattern,
+                description,
+            } => DiagnosisPattern::new_regex(pattern, description)
+                .map(|pattern| vec![pattern])
+                .map_err(|e| IdsError::Validation(format!("Invalid regex '{pattern}': {e}"))),
+            Self::Range {
+                from,
+                to,
+                description,
+            } => DiagnosisPattern::new_range(from, to, description)
+                .map(|pattern| vec![pattern])
+                .map_err(IdsError::Validation),
+        }
+    }
+}
+
+/// A register disease definitions take diagnoses from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosisSource {
+    /// Hospital contacts in the National Patient Register (LPR)
+    Lpr,
+    /// Causes of death in the Register of Causes of Death (DODSAARSAG)
+    DeathCause,
+}
+
+impl std::fmt::Display for DiagnosisSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lpr => write!(f, "lpr"),
+            Self::DeathCause => write!(f, "death_cause"),
+        }
+    }
+}
+
+fn default_sources() -> Vec<DiagnosisSource> {
+    vec![DiagnosisSource::Lpr]
+}
+
+/// A disease category with the patterns that include and exclude codes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryDefinition {
+    /// Category name, used for output column names
+    pub name: String,
+    /// Human-readable description
+    #[serde(default)]
+    pub description: String,
+    /// Patterns a code must match to belong to the category
+    #[serde(default)]
+    pub include: Vec<PatternDefinition>,
+    /// Patterns that remove otherwise included codes from the category
+    #[serde(default)]
+    pub exclude: Vec<PatternDefinition>,
+    /// SKS procedure code patterns that also place a patient in the category
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub procedures: Vec<PatternDefinition>,
+    /// Contact types overriding the file-level contact types for this category
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact_types: Option<Vec<String>>,
+    /// Confirmation rules overriding the file-level rules for this category
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<ConfirmationRules>,
+}
+
+/// Compile a list of pattern definitions into diagnosis patterns
+pub fn compile_patterns(patterns: &[PatternDefinition]) -> Result<Vec<DiagnosisPattern>> {
+    let mut compiled = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        compiled.extend(pattern.compile()?);
+    }
+    Ok(compiled)
+}
+
+/// Identification of the definitions used for a run, for recording in outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionInfo {
+    /// Definition set name
+    pub name: String,
+    /// Definition set version
+    pub version: String,
+    /// File path the definitions were loaded from, or `built-in`
+    pub source: String,
+    /// Hex-encoded SHA-256 hash of the definition file contents
+    pub sha256: String,
+}
+
+impl DefinitionInfo {
+    /// Key-value pairs for report rows and Parquet schema metadata
+    #[must_use]
+    pub fn metadata(&self, prefix: &str) -> HashMap<String, String> {
+        HashMap::from([
+            (format!("{prefix}_definitions_name"), self.name.clone()),
+            (format!("{prefix}_definitions_version"), self.version.clone()),
+            (format!("{prefix}_definitions_source"), self.source.clone()),
+            (format!("{prefix}_definitions_sha256"), self.sha256.clone()),
+        ])
+    }
+}
+
+/// A versioned set of disease category definitions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiseaseDefinitions {
+    /// Definition file format version
+    pub format_version: u32,
+    /// Definition set name
+    pub name: String,
+    /// Definition set version
+    pub version: String,
+    /// Human-readable description
+    #[serde(default)]
+    pub description: String,
+    /// Confirmation rules applied to every category without its own rules
+    #[serde(default)]
+    pub confirmation: ConfirmationRules,
+    /// Contact (admission) types diagnoses must come from; empty means all contacts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contact_types: Vec<String>,
+    /// Registers diagnoses are taken from; LPR only by default
+    #[serde(default = "default_sources")]
+    pub sources: Vec<DiagnosisSource>,
+    /// Disease categories in file order
+    pub categories: Vec<CategoryDefinition>,
+    /// Where the definitions were loaded from
+    #[serde(skip)]
+    pub source: String,
+    /// SHA-256 hash of the file contents
+    #[serde(skip)]
+    pub sha256: String,
+}
+
+impl DiseaseDefinitions {
+    /// Parse definitions from JSON text
+    pub fn from_json(text: &str, source: &str) -> Result<Self> {
+        let definitions: Self = serde_json::from_str(text).map_err(|e| {
+            IdsError::Validation(format!("Failed to parse definitions from {source}: {e}"))
+        })?;
+        definitions.finish(text, source)
+    }
+
+    /// Parse definitions from TOML text
+    pub fn from_toml(text: &str, source: &str) -> Result<Self> {
+        let definitions: Self = toml::from_str(text).map_err(|e| {
+            IdsError::Validation(format!("Failed to parse definitions from {source}: {e}"))
+        })?;
+        definitions.finish(text, source)
+    }
+
+    /// Load definitions from a `.json` or `.toml` file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(IdsError::Io)?;
+        let source = path.display().to_string();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::from_toml(&text, &source),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::from_json(&text, &source),
+            _ => Err(IdsError::Validation(format!(
+                "Unsupported definition file '{source}'; expected a .json or .toml file"
+            ))),
+        }
+    }
+
+    /// Load definitions from a file, or the built-in SCD definitions when no path is given
+    ///
+    /// The path `scdalgo-r` selects the bundled [`Self::scdalgo_r`] definitions.
+    pub fn load_or_builtin_scd(path: Option<&Path>) -> Result<Self> {
+        match path {
+            None => Ok(Self::builtin_scd()),
+            Some(path) if path.as_os_str() == "scdalgo-r" => Ok(Self::scdalgo_r()),
+            Some(path) => Self::from_file(path),
+        }
+    }
+
+    /// The built-in SCD definitions shipped with the crate
+    #[must_use]
+    pub fn builtin_scd() -> Self {
+        Self::from_json(BUILTIN_SCD_DEFINITIONS, BUILTIN_SOURCE)
+            .expect("built-in SCD definitions are valid")
+    }
+
+    /// The bundled definitions reproducing the reference `SCDalgo.R` implementation
+    ///
+    /// Unlike [`Self::builtin_scd`], these use the code ranges of the R algorithm
+    /// (e.g. all malignant neoplasms C00-C99) and its exact four-character codes.
+    #[must_use]
+    pub fn scdalgo_r() -> Self {
+        Self::from_json(SCDALGO_R_DEFINITIONS, SCDALGO_R_SOURCE)
+            .expect("bundled SCDalgo.R definitions are valid")
+    }
+
+    /// The confirmation rules in effect for a category
+    #[must_use]
+    pub fn confirmation_for(&self, category: &CategoryDefinition) -> ConfirmationRules {
+        category
+            .confirmation
+            .clone()
+            .unwrap_or_else(|| self.confirmation.clone())
+    }
+
+    /// The contact types in effect for a category; empty means all contacts
+    #[must_use]
+    pub fn contact_types_for(&self, category: &CategoryDefinition) -> Vec<String> {
+        category
+            .contact_types
+            .clone()
+            .unwrap_or_else(|| self.contact_types.clone())
+    }
+
+    /// Whether diagnoses are taken from the given register
+    #[must_use]
+    pub fn uses_source(&self, source: DiagnosisSource) -> bool {
+        self.sources.contains(&source)
+    }
+
+    /// Identification of these definitions for outputs
+    #[must_use]
+    pub fn info(&self) -> DefinitionInfo {
+        DefinitionInfo {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            source: self.source.clone(),
+            sha256: self.sha256.clone(),
+        }
+    }
+
+    /// Validate the parsed definitions and record their source and hash
+    fn finish(mut self, text: &str, source: &str) -> Result<Self> {
+        if self.format_version != DEFINITIONS_FORMAT_VERSION {
+            return Err(IdsError::Validation(format!(
+                "Unsupported definition format version {} in {source} (expected {DEFINITIONS_FORMAT_VERSION})",
+                self.format_version
+            )));
+        }
+
+        if self.sources.is_empty() {
+            return Err(IdsError::Validation(format!(
+                "No diagnosis sources in {source}; use \"lpr\" and/or \"death_cause\""
+            )));
+        }
+
+        // Phenotype output columns are `<name>_first_date`, `<name>_count` and one
+        // `<name>_<category>` flag per category
+        let summary_columns = ["first_date", "count"];
+        let mut seen = std::collections::HashSet::new();
+        for category in &self.categories {
+            if !seen.insert(category.name.as_str()) {
+                return Err(IdsError::Validation(format!(
+                    "Duplicate category '{}' in {source}",
+                    category.name
+                )));
+            }
+            if summary_columns.contains(&category.name.as_str()) {
+                return Err(IdsError::Validation(format!(
+                    "Category '{}' in {source} clashes with the output column '{}_{}'",
+                    category.name, self.name, category.name
+                )));
+            }
+            if category.include.is_empty() && category.procedures.is_empty() {
+                return Err(IdsError::Validation(format!(
+                    "Category '{}' in {source} has no include or procedure patterns",
+                    category.name
+                )));
+            }
+            for pattern in category
+                .include
+                .iter()
+                .chain(&category.exclude)
+                .chain(&category.procedures)
+            {
+                pattern.compile()?;
+            }
+            self.confirmation_for(category).validate().map_err(|reason| {
+                IdsError::Validation(format!(
+                    "Invalid confirmation rules for category '{}' in {source}: {reason}",
+                    category.name
+                ))
+            })?;
+        }
+
+        self.source = source.to_string();
+        self.sha256 = format!("{:x}", Sha256::digest(text.as_bytes()));
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_definitions_parse() {
+        let definitions = DiseaseDefinitions::builtin_scd();
+        assert_eq!(definitions.name, "scd");
+        assert_eq!(definitions.source, BUILTIN_SOURCE);
+        assert_eq!(definitions.sha256.len(), 64);
+        assert_eq!(definitions.categories.len(), 10);
+    }
+
+    #[test]
+    fn test_toml_definitions() {
+        let text = r#"
+format_version = 1
+name = "custom"
+version = "0.1"
+
+[[categories]]
+name = "heart"
+include = [{ type = "range", from = "I30", to = "I52" }]
+exclude = [{ type = "prefix", codes = ["I519"] }]
+"#;
+        let definitions = DiseaseDefinitions::from_toml(text, "test.toml").unwrap();
+        assert_eq!(definitions.categories[0].exclude.len(), 1);
+        assert!(definitions.confirmation_for(&definitions.categories[0]).is_empty());
+
+        let confirmed = text.replace(
+            "[[categories]]",
+            "[confirmation]\nmin_contacts = 2\n\n[[categories]]",
+        );
+        let definitions = DiseaseDefinitions::from_toml(&confirmed, "test.toml").unwrap();
+        assert_eq!(
+            definitions.confirmation_for(&definitions.categories[0]).min_contacts,
+            Some(2)
+        );
+
+        let bad = text.replace("format_version = 1", "format_version = 2");
+        assert!(DiseaseDefinitions::from_toml(&bad, "test.toml").is_err());
+    }
+
+    #[test]
+    fn test_category_output_columns_must_be_unique() {
+        let text = r#"
+format_version = 1
+name = "custom"
+version = "0.1"
+
+[[categories]]
+name = "heart"
+include = [{ type = "prefix", codes = ["I50"] }]
+"#;
+        for clashing in ["count", "first_date"] {
+            let error = DiseaseDefinitions::from_toml(
+                &text.replace("\"heart\"", &format!("\"{clashing}\"")),
+                "test.toml",
+            )
+            .unwrap_err();
+            assert!(
+                error.to_string().contains(&format!("custom_{clashing}")),
+                "{error}"
+            );
+        }
+
+        let too_old = text.replace(
+            "[[categories]]",
+            "[confirmation]\nmax_age_years = 4294967295\n\n[[categories]]",
+        );
+        let error = DiseaseDefinitions::from_toml(&too_old, "test.toml").unwrap_err();
+        assert!(error.to_string().contains("max_age_years"), "{error}");
+
+        let duplicate = format!("{text}{}", &text[text.find("[[categories]]").unwrap()..]);
+        assert!(DiseaseDefinitions::from_toml(&duplicate, "test.toml").is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Diagnosis type and status filtering for LPR data
+//!
+//! LPR2 `LPR_DIAG` and LPR3 `diagnoser` record a diagnosis type for every code (action
+//! diagnosis `A`, secondary `B`, referral `H`, supplementary `+`/`M`, ...), and LPR3
+//! additionally flags diagnoses that were later disproven (`senere_afkraeftet`) and
+//! supplementary codes attached to a parent diagnosis. This module defines the rules
+//! used during LPR integration to decide which of these codes enter the harmonised
+//! output.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{IdsError, Result};
+
+/// Which diagnosis types are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiagnosisTypeScope {
+    /// Only action (primary) diagnoses, type `A`
+    ActionOnly,
+    /// Action and secondary (bi-) diagnoses, types `A` and `B`
+    ActionAndSecondary,
+    /// All diagnosis types
+    #[default]
+    All,
+}
+
+impl DiagnosisTypeScope {
+    /// Whether a diagnosis type is within the scope
+    #[must_use]
+    pub fn includes(&self, diagnosis_type: &str) -> bool {
+        match self {
+            Self::ActionOnly => diagnosis_type == "A",
+            Self::ActionAndSecondary => matches!(diagnosis_type, "A" | "B"),
+            Self::All => true,
+        }
+    }
+}
+
+impl fmt::Display for DiagnosisTypeScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ActionOnly => write!(f, "action"),
+            Self::ActionAndSecondary => write!(f, "action-secondary"),
+            Self::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for DiagnosisTypeScope {
+    type Err = IdsError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "action" | "primary" => Ok(Self::ActionOnly),
+            "action-secondary" | "primary-secondary" => Ok(Self::ActionAndSecondary),
+            "all" => Ok(Self::All),
+            other => Err(IdsError::Validation(format!(
+                "Unknown diagnosis type scope '{other}'. Expected one of: action, \
+                 action-secondary, all"
+            ))),
+        }
+    }
+}
+
+/// Rules deciding which LPR diagnoses enter the harmonised output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiagnosisFilter {
+    /// Diagnosis types to keep
+    pub types: DiagnosisTypeScope,
+    /// Drop diagnoses flagged as later disproven (LPR3 `senere_afkraeftet`)
+    pub drop_disproven: bool,
+    /// Drop referral diagnoses (type `H`)
+    pub ignore_referral: bool,
+    /// Drop supplementary codes (types `+` and `M`, and LPR3 codes with a parent code)
+    pub ignore_supplementary: bool,
+}
+
+impl DiagnosisFilter {
+    /// Keep every diagnosis, as the LPR integration did before filtering was configurable
+    #[must_use]
+    pub fn keep_all() -> Self {
+        Self::default()
+    }
+
+    /// Whether a diagnosis with the given type and status is kept
+    ///
+    /// `disproven` and `has_parent` are only known for LPR3 and should be `false` for LPR2.
+    #[must_use]
+    pub fn accepts(&self, diagnosis_type: &str, disproven: bool, has_parent: bool) -> bool {
+        let diagnosis_type = diagnosis_type.trim();
+        if self.drop_disproven && disproven {
+            return false;
+        }
+        if self.ignore_referral && diagnosis_type.eq_ignore_ascii_case("H") {
+            return false;
+        }
+        if self.ignore_supplementary
+            && (has_parent || matches!(diagnosis_type, "+" | "M" | "m"))
+        {
+            return false;
+        }
+        self.types.includes(&diagnosis_type.to_ascii_uppercase())
+    }
+
+    /// Whether the filter keeps everything
+    #[must_use]
+    pub fn is_keep_all(&self) -> bool {
+        *self == Self::keep_all()
+    }
+}
+
+impl fmt::Display for DiagnosisFilter {
+    /// Compact description recorded in the harmonised output's schema metadata
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "types={}", self.types)?;
+        if self.drop_disproven {
+            write!(f, ";drop-disproven")?;
+        }
+        if self.ignore_referral {
+            write!(f, ";ignore-referral")?;
+        }
+        if self.ignore_supplementary {
+            write!(f, ";ignore-supplementary")?;
+        }
+        Ok(())
+    }
+}
+
+/// Interpret an LPR3 `senere_afkraeftet` value as a boolean
+#[must_use]
+pub fn is_disproven_flag(value: &str) -> bool {
+    matches!(
+        value.trim().to_lowercase().as_str(),
+        "ja" | "j" | "1" | "true" | "y" | "yes"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnosis_filter_rules() {
+        let keep_all = DiagnosisFilter::keep_all();
+        assert!(keep_all.accepts("H", true, true));
+
+        let filter = DiagnosisFilter {
+            types: DiagnosisTypeScope::ActionAndSecondary,
+            drop_disproven: true,
+            ignore_referral: true,
+            ignore_supplementary: true,
+        };
+        assert!(filter.accepts("A", false, false));
+        assert!(filter.accepts("b", false, false));
+        assert!(!filter.accepts("A", true, false));
+        assert!(!filter.accepts("B", false, true));
+        assert!(!filter.accepts("G", false, false));
+        assert_eq!(
+            filter.to_string(),
+            "types=action-secondary;drop-disproven;ignore-referral;ignore-supplementary"
+        );
+
+        let action_only = DiagnosisFilter {
+            types: "action".parse().unwrap(),
+            ..DiagnosisFilter::default()
+        };
+        assert!(action_only.accepts("A", true, false));
+        assert!(!action_only.accepts("B", false, false));
+    }
+}
@@ -1,11 +1,19 @@
 //! Diagnosis processing algorithms
 //!
 //! This module implements algorithms for processing medical diagnoses,
-//! including secondary diagnoses and SCD classification.
+//! including secondary diagnoses, diagnosis type filtering, SCD classification and
+//! the disease-code definition files used to configure it and the phenotype engine.
 
+pub mod columns;
+pub mod confirmation;
+pub mod definitions;
+pub mod filter;
 pub mod secondary;
 pub mod scd;
 
 // Re-export common types
 pub use secondary::SecondaryDiagnosis;
-pub use scd::ScdDiseaseCodes;
+pub use confirmation::ConfirmationRules;
+pub use definitions::{DefinitionInfo, DiagnosisSource, DiseaseDefinitions};
+pub use filter::{DiagnosisFilter, DiagnosisTypeScope};
+pub use scd::{scd_diagnosis_columns, ScdDiseaseCodes};
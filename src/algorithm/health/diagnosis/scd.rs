@@ -2,191 +2,158 @@
 //!
 //! This module implements the Severe Chronic Disease (SCD) algorithm for
 //! identifying patients with severe chronic diseases based on ICD-10 diagnosis codes.
+//! The disease categories and their codes are read from a versioned definition file
+//! (see [`super::definitions`]); the built-in file is used by default. Categories can
+//! also be identified from SKS procedure codes in the harmonised procedure table (see
+//! [`crate::algorithm::health::lpr::process_lpr_procedure_data`]).
 
 use arrow::array::{Array, ArrayRef, BooleanArray, Date32Array, StringArray};
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use chrono::NaiveDate;
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
+use crate::algorithm::health::diagnosis::confirmation::{
+    confirm_categories, CategoryEvents, ConfirmationRules,
+};
+use crate::algorithm::health::diagnosis::columns::{
+    ContactColumns, DiagnosisColumns, ProcedureColumns,
+};
+use crate::algorithm::health::diagnosis::definitions::{
+    compile_patterns, DefinitionInfo, DiagnosisSource, DiseaseDefinitions,
+};
 use crate::error::{IdsError, Result};
-use crate::model::icd10::diagnosis_pattern::{DiagnosisPattern, normalize_diagnosis_code};
+use crate::utils::date_utils::days_since_epoch_to_date;
+use crate::model::icd10::diagnosis_pattern::{
+    DiagnosisPattern, NormalizedDiagnosis, normalize_diagnosis_code,
+};
+
+/// Maximum number of distinct diagnosis strings kept in the per-instance lookup cache
+const CATEGORY_CACHE_LIMIT: usize = 100_000;
+
+/// Compiled patterns for a single disease category
+struct CategoryPatterns {
+    name: String,
+    include: Vec<DiagnosisPattern>,
+    exclude: Vec<DiagnosisPattern>,
+    procedures: Vec<DiagnosisPattern>,
+    confirmation: ConfirmationRules,
+}
+
+impl CategoryPatterns {
+    fn matches(&self, diagnosis: &NormalizedDiagnosis) -> bool {
+        self.include.iter().any(|pattern| pattern.matches(diagnosis))
+            && !self.exclude.iter().any(|pattern| pattern.matches(diagnosis))
+    }
+}
 
 /// SCD disease categories with their associated ICD-10 codes
 pub struct ScdDiseaseCodes {
-    // Map of disease category to patterns
-    patterns: HashMap<String, Vec<DiagnosisPattern>>,
-    // Cached flat set of all prefix codes for quick lookup
+    // Disease categories in definition file order
+    categories: Vec<CategoryPatterns>,
+    // Cached flat set of all included prefix codes
     all_codes_cache: HashSet<String>,
+    // Identification of the definitions the codes were built from
+    info: DefinitionInfo,
+    // Registers diagnoses are taken from
+    sources: Vec<DiagnosisSource>,
+    // Cache of previously seen diagnosis strings and their category indices
+    category_cache: Mutex<HashMap<String, Vec<usize>>>,
 }
 
 impl ScdDiseaseCodes {
-    /// Create a new `ScdDiseaseCodes` instance with predefined disease categories and codes
+    /// Create a new `ScdDiseaseCodes` instance from the built-in SCD definitions
     #[must_use] 
     pub fn new() -> Self {
-        let mut codes = Self {
-            patterns: HashMap::with_capacity(10), // Pre-allocate for the 10 categories
-            all_codes_cache: HashSet::with_capacity(150), // Approximate total codes
-        };
-        
-        // Initialize with basic prefix patterns
-        codes.add_basic_patterns();
-        
-        // Add more detailed patterns from reference implementation
-        codes.add_detailed_patterns();
-        
-        codes
-    }
-    
-    /// Add a prefix pattern to the codes
-    fn add_prefix_pattern(&mut self, category: &str, prefix: &str, description: &str) {
-        let pattern = DiagnosisPattern::new_prefix(prefix, description);
-        
-        // Add to the cache for fast lookup
-        self.all_codes_cache.insert(prefix.to_string());
-        
-        // Add to the patterns map
-        self.patterns
-            .entry(category.to_string())
-            .or_default()
-            .push(pattern);
+        Self::from_definitions(&DiseaseDefinitions::builtin_scd())
+            .expect("built-in SCD definitions compile")
     }
-    
-    /// Add a regex pattern to the codes
-    fn add_regex_pattern(&mut self, category: &str, regex: &str, description: &str) {
-        if let Ok(pattern) = DiagnosisPattern::new_regex(regex, description) {
-            // Add to the patterns map
-            self.patterns
-                .entry(category.to_string())
-                .or_default()
-                .push(pattern);
-                
-            // Note: Regex patterns aren't added to the prefix cache
-        }
+
+    /// Load SCD codes from a definition file, or the built-in definitions if no path is given
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        Self::from_definitions(&DiseaseDefinitions::load_or_builtin_scd(path)?)
     }
-    
-    /// Add the basic prefix patterns (from the original implementation)
-    fn add_basic_patterns(&mut self) {
-        // Blood Disorders
-        for prefix in &[
-            "D55", "D56", "D57", "D58", "D59", "D60", "D61",
-            "D64", "D65", "D66", "D67", "D68", "D69", "D70", "D71", "D72", "D73",
-            "D76"
-        ] {
-            self.add_prefix_pattern("blood_disorders", prefix, "Blood disorders");
-        }
-        
-        // Immune System Disorders
-        for prefix in &["D80", "D81", "D82", "D83", "D84", "D86", "D89"] {
-            self.add_prefix_pattern("immune_system", prefix, "Immune system disorders");
-        }
-        
-        // Endocrine Disorders
-        for prefix in &[
-            "E22", "E23", "E24", "E25", "E26", "E27", "E31", "E34",
-            "E70", "E71", "E72", "E73", "E74", "E75", "E76", "E77", 
-            "E78", "E79", "E80", "E83", "E84", "E85", "E88"
-        ] {
-            self.add_prefix_pattern("endocrine", prefix, "Endocrine disorders");
-        }
-        
-        // Neurological Disorders
-        for prefix in &[
-            "F84", "G11", "G12", "G13", "G23", "G24", "G25", "G31", 
-            "G40", "G41", "G70", "G71", "G72", "G80", "G81", "G82"
-        ] {
-            self.add_prefix_pattern("neurological", prefix, "Neurological disorders");
-        }
-        
-        // Cardiovascular Disorders
-        for prefix in &["I27", "I42", "I43", "I50", "I81", "I82", "I83"] {
-            self.add_prefix_pattern("cardiovascular", prefix, "Cardiovascular disorders");
-        }
-        
-        // Respiratory Disorders
-        for prefix in &[
-            "J41", "J42", "J43", "J44", "J45", "J47", "J60", "J61", "J62", 
-            "J63", "J64", "J65", "J66", "J67", "J68", "J69", "J70", "J84", "J96"
-        ] {
-            self.add_prefix_pattern("respiratory", prefix, "Respiratory disorders");
-        }
-        
-        // Gastrointestinal Disorders
-        for prefix in &["K50", "K51", "K73", "K74", "K86", "K87", "K90"] {
-            self.add_prefix_pattern("gastrointestinal", prefix, "Gastrointestinal disorders");
-        }
-        
-        // Musculoskeletal Disorders
-        for prefix in &[
-            "M05", "M06", "M07", "M08", "M09", "M30", "M31", "M32", "M33",
-            "M34", "M35", "M40", "M41", "M42", "M43", "M45", "M46"
-        ] {
-            self.add_prefix_pattern("musculoskeletal", prefix, "Musculoskeletal disorders");
-        }
-        
-        // Renal Disorders
-        for prefix in &[
-            "N01", "N02", "N03", "N04", "N05", "N06", "N07", "N08", 
-            "N11", "N12", "N13", "N14", "N15", "N16", "N18", "N19", 
-            "N20", "N21", "N22", "N23", "N24", "N25", "N26", "N27", "N28", "N29"
-        ] {
-            self.add_prefix_pattern("renal", prefix, "Renal disorders");
+
+    /// Compile SCD codes from parsed disease definitions
+    pub fn from_definitions(definitions: &DiseaseDefinitions) -> Result<Self> {
+        let mut categories = Vec::with_capacity(definitions.categories.len());
+        let mut all_codes_cache = HashSet::new();
+
+        for category in &definitions.categories {
+            let include = compile_patterns(&category.include)?;
+            let exclude = compile_patterns(&category.exclude)?;
+            let procedures = compile_patterns(&category.procedures)?;
+
+            all_codes_cache.extend(include.iter().filter_map(|pattern| pattern.prefix.clone()));
+            categories.push(CategoryPatterns {
+                name: category.name.clone(),
+                include,
+                exclude,
+                procedures,
+                confirmation: definitions.confirmation_for(category),
+            });
         }
-        
-        // Congenital Disorders
-        for prefix in &[
-            "P27", "Q01", "Q02", "Q03", "Q04", "Q05", "Q06", "Q07", 
-            "Q20", "Q21", "Q22", "Q23", "Q24", "Q25", "Q26", "Q27", "Q28",
-            "Q30", "Q31", "Q32", "Q33", "Q34", "Q35", "Q36", "Q37", 
-            "Q38", "Q39", "Q40", "Q41", "Q42", "Q43", "Q44", "Q45", 
-            "Q60", "Q61", "Q62", "Q63", "Q64", "Q77", "Q78", "Q79", 
-            "Q80", "Q81", "Q82", "Q83", "Q84", "Q85", "Q86", "Q87", "Q89"
-        ] {
-            self.add_prefix_pattern("congenital", prefix, "Congenital disorders");
+
+        if definitions
+            .categories
+            .iter()
+            .any(|category| !definitions.contact_types_for(category).is_empty())
+        {
+            log::warn!(
+                "Contact type restrictions in '{}' are ignored by the SCD algorithm; use the \
+                 phenotype command to apply them",
+                definitions.name
+            );
         }
+
+        log::info!(
+            "Loaded {} SCD categories from '{}' version {} ({})",
+            categories.len(),
+            definitions.name,
+            definitions.version,
+            definitions.source
+        );
+
+        Ok(Self {
+            categories,
+            all_codes_cache,
+            info: definitions.info(),
+            sources: definitions.sources.clone(),
+            category_cache: Mutex::new(HashMap::with_capacity(10000)),
+        })
     }
-    
-    /// Add detailed patterns from the reference implementation
-    fn add_detailed_patterns(&mut self) {
-        // Blood disorders - specific regex patterns
-        self.add_regex_pattern("blood_disorders", "D61[0389]", "Aplastic anaemias");
-        self.add_regex_pattern("blood_disorders", "D762", "Haemophagocytic syndrome");
-        
-        // Immune system - specific patterns 
-        self.add_regex_pattern("immune_system", "D8[012]", "Immunodeficiencies");
-        
-        // Neurological disorders - specific patterns
-        self.add_regex_pattern("neurological", "G11[01234789]", "Hereditary ataxias");
-        self.add_regex_pattern("neurological", "G12[012345]", "Motor neuron diseases");
-        self.add_regex_pattern("neurological", "G23[123]", "Other degenerative diseases of basal ganglia");
-        self.add_regex_pattern("neurological", "G24[01345]", "Dystonia");
-        self.add_regex_pattern("neurological", "G40[0123456789]", "Epilepsy");
-        self.add_regex_pattern("neurological", "G41[0129]", "Status epilepticus");
-        
-        // Respiratory disorders - specific patterns
-        self.add_regex_pattern("respiratory", "J43[012]", "Emphysema");
-        self.add_regex_pattern("respiratory", "J44[019]", "COPD");
-        self.add_regex_pattern("respiratory", "J47", "Bronchiectasis");
-        self.add_regex_pattern("respiratory", "J84[189]", "Other interstitial pulmonary diseases");
-        
-        // Gastrointestinal disorders - specific patterns
-        self.add_regex_pattern("gastrointestinal", "K50[012345678]", "Crohn's disease");
-        self.add_regex_pattern("gastrointestinal", "K51[012345678]", "Ulcerative colitis");
-        self.add_regex_pattern("gastrointestinal", "K74[0123456]", "Hepatic fibrosis and cirrhosis");
-        
-        // Musculoskeletal disorders - specific patterns
-        self.add_regex_pattern("musculoskeletal", "M05[0123489]", "Rheumatoid arthritis with other organ involvement");
-        self.add_regex_pattern("musculoskeletal", "M06[0123489]", "Other rheumatoid arthritis");
-        self.add_regex_pattern("musculoskeletal", "M32[18]", "Systemic lupus erythematosus");
-        self.add_regex_pattern("musculoskeletal", "M33[012]", "Dermatopolymyositis");
-        self.add_regex_pattern("musculoskeletal", "M34[0189]", "Systemic sclerosis");
-        
-        // Renal disorders - specific patterns
-        self.add_regex_pattern("renal", "N18[12345]", "Chronic kidney disease");
+
+    /// Identification of the definitions these codes were built from
+    #[must_use]
+    pub const fn definition_info(&self) -> &DefinitionInfo {
+        &self.info
     }
     
-    /// Get all SCD codes as a flat set (returns a reference to pre-computed set)
+    /// Whether the definitions take diagnoses from the given register
+    #[must_use]
+    pub fn uses_source(&self, source: DiagnosisSource) -> bool {
+        self.sources.contains(&source)
+    }
+
+    /// Whether any category has confirmation rules beyond a single diagnosis
+    #[must_use]
+    pub fn has_confirmation_rules(&self) -> bool {
+        self.categories
+            .iter()
+            .any(|category| !category.confirmation.is_empty())
+    }
+
+    /// Whether any category is identified from procedure codes
+    #[must_use]
+    pub fn uses_procedures(&self) -> bool {
+        self.categories
+            .iter()
+            .any(|category| !category.procedures.is_empty())
+    }
+
+    /// Get all included SCD prefix codes as a flat set (returns a reference to pre-computed set)
     #[must_use] 
     pub const fn all_codes(&self) -> &HashSet<String> {
         &self.all_codes_cache
@@ -195,126 +162,66 @@ impl ScdDiseaseCodes {
     /// Check if a diagnosis code is a SCD code with caching for better performance
     #[must_use] 
     pub fn is_scd_code(&self, diagnosis: &str) -> bool {
-        use std::sync::Mutex;
-        use once_cell::sync::Lazy;
-        
-        // Cache of previously seen diagnosis codes and their SCD status
-        // This avoids repeated normalization and pattern matching for common codes
-        static DIAGNOSIS_CACHE: Lazy<Mutex<HashMap<String, bool>>> = 
-            Lazy::new(|| Mutex::new(HashMap::with_capacity(10000)));
-        
-        // Check cache first for this exact diagnosis string
-        {
-            let cache = DIAGNOSIS_CACHE.lock().unwrap();
-            if let Some(&result) = cache.get(diagnosis) {
-                return result;
-            }
-        }
-        
-        // If not in cache, normalize the code
-        let normalized = match normalize_diagnosis_code(diagnosis) {
-            Some(norm) => norm,
-            None => {
-                // Cache negative result
-                let mut cache = DIAGNOSIS_CACHE.lock().unwrap();
-                if cache.len() < 100000 { // Limit cache size
-                    cache.insert(diagnosis.to_string(), false);
-                }
-                return false;
-            },
-        };
-        
-        // First check if it's a simple prefix match (faster)
-        for prefix in &self.all_codes_cache {
-            if normalized.full_code.starts_with(prefix) {
-                // Cache positive result
-                let mut cache = DIAGNOSIS_CACHE.lock().unwrap();
-                if cache.len() < 100000 { // Limit cache size
-                    cache.insert(diagnosis.to_string(), true);
-                }
-                return true;
-            }
-        }
-        
-        // If not found in prefix cache, check regex patterns
-        let mut result = false;
-        for patterns in self.patterns.values() {
-            for pattern in patterns {
-                if pattern.matches(&normalized) {
-                    result = true;
-                    break;
-                }
-            }
-            if result {
-                break;
-            }
-        }
-        
-        // Cache the result before returning
-        let mut cache = DIAGNOSIS_CACHE.lock().unwrap();
-        if cache.len() < 100000 { // Limit cache size
-            cache.insert(diagnosis.to_string(), result);
-        }
-        
-        result
+        !self.matching_categories(diagnosis).is_empty()
     }
     
     /// Get the disease categories for a diagnosis code with caching for better performance
     #[must_use] 
     pub fn get_disease_categories(&self, diagnosis: &str) -> HashSet<String> {
-        use std::sync::Mutex;
-        use once_cell::sync::Lazy;
-        
-        // Cache of previously seen diagnosis codes and their disease categories
-        static CATEGORY_CACHE: Lazy<Mutex<HashMap<String, HashSet<String>>>> = 
-            Lazy::new(|| Mutex::new(HashMap::with_capacity(10000)));
-        
+        self.matching_categories(diagnosis)
+            .into_iter()
+            .map(|index| self.categories[index].name.clone())
+            .collect()
+    }
+    
+    /// Get all available disease categories in definition order
+    #[must_use] 
+    pub fn get_all_categories(&self) -> Vec<String> {
+        self.categories.iter().map(|category| category.name.clone()).collect()
+    }
+
+    /// Indices of the categories matching a diagnosis, using the lookup cache
+    fn matching_categories(&self, diagnosis: &str) -> Vec<usize> {
         // Check cache first for this exact diagnosis string
         {
-            let cache = CATEGORY_CACHE.lock().unwrap();
-            if let Some(cached_categories) = cache.get(diagnosis) {
-                return cached_categories.clone();
-            }
-        }
-        
-        let mut categories = HashSet::new();
-        
-        // First normalize the code
-        let normalized = match normalize_diagnosis_code(diagnosis) {
-            Some(norm) => norm,
-            None => {
-                // Cache empty result
-                let mut cache = CATEGORY_CACHE.lock().unwrap();
-                if cache.len() < 100000 { // Limit cache size
-                    cache.insert(diagnosis.to_string(), categories.clone());
-                }
-                return categories;
-            },
-        };
-        
-        // Check each category's patterns
-        for (category, patterns) in &self.patterns {
-            for pattern in patterns {
-                if pattern.matches(&normalized) {
-                    categories.insert(category.clone());
-                    break; // No need to check other patterns in this category
-                }
+            let cache = self.category_cache.lock().unwrap();
+            if let Some(cached) = cache.get(diagnosis) {
+                return cached.clone();
             }
         }
-        
-        // Cache the result before returning
-        let mut cache = CATEGORY_CACHE.lock().unwrap();
-        if cache.len() < 100000 { // Limit cache size
-            cache.insert(diagnosis.to_string(), categories.clone());
+
+        let matches: Vec<usize> = normalize_diagnosis_code(diagnosis).map_or_else(Vec::new, |normalized| {
+            self.categories
+                .iter()
+                .enumerate()
+                .filter(|(_, category)| category.matches(&normalized))
+                .map(|(index, _)| index)
+                .collect()
+        });
+
+        let mut cache = self.category_cache.lock().unwrap();
+        if cache.len() < CATEGORY_CACHE_LIMIT {
+            cache.insert(diagnosis.to_string(), matches.clone());
         }
-        
-        categories
+
+        matches
     }
-    
-    /// Get all available disease categories
-    #[must_use] 
-    pub fn get_all_categories(&self) -> Vec<String> {
-        self.patterns.keys().cloned().collect()
+}
+
+impl ScdDiseaseCodes {
+    /// Indices of the categories whose procedure patterns match a procedure code
+    fn procedure_categories(&self, procedure: &NormalizedDiagnosis) -> Vec<usize> {
+        self.categories
+            .iter()
+            .enumerate()
+            .filter(|(_, category)| {
+                category
+                    .procedures
+                    .iter()
+                    .any(|pattern| pattern.matches(procedure))
+            })
+            .map(|(index, _)| index)
+            .collect()
     }
 }
 
@@ -324,6 +231,21 @@ impl Default for ScdDiseaseCodes {
     }
 }
 
+/// Diagnosis columns of the harmonised LPR data counted by the SCD algorithm
+///
+/// Only the primary (action) diagnosis by default, as in the reference algorithm. With
+/// `include_secondary`, the contact's secondary diagnoses are counted too; which types they
+/// include (B, H, +/M) is decided by the [`DiagnosisFilter`](super::DiagnosisFilter) used
+/// when harmonising the LPR data.
+#[must_use]
+pub fn scd_diagnosis_columns(include_secondary: bool) -> Vec<String> {
+    let mut columns = vec!["primary_diagnosis".to_string()];
+    if include_secondary {
+        columns.push("secondary_diagnoses".to_string());
+    }
+    columns
+}
+
 /// Configuration for SCD algorithm
 pub struct ScdConfig {
     /// Diagnosis columns to check for SCD codes
@@ -332,14 +254,33 @@ pub struct ScdConfig {
     pub date_column: String,
     /// Column containing the patient ID
     pub patient_id_column: String,
+    /// Disease definition file (JSON or TOML); the built-in SCD definitions if `None`
+    pub definitions_path: Option<PathBuf>,
+    /// Column containing the contact end date, used to identify inpatient contacts
+    pub discharge_date_column: String,
+    /// Column containing the contact or patient type
+    pub admission_type_column: String,
+    /// Contact types counted as inpatient (LPR2 `C_PATTYPE` 0); contacts ending on a
+    /// later day than they started are also counted as inpatient
+    pub inpatient_types: Vec<String>,
+    /// Column of the procedure table containing the SKS procedure code
+    pub procedure_code_column: String,
+    /// Column of the procedure table containing the procedure date
+    pub procedure_date_column: String,
 }
 
 impl Default for ScdConfig {
     fn default() -> Self {
         Self {
-            diagnosis_columns: vec!["primary_diagnosis".to_string()],
+            diagnosis_columns: scd_diagnosis_columns(false),
             date_column: "diagnosis_date".to_string(),
             patient_id_column: "patient_id".to_string(),
+            definitions_path: None,
+            discharge_date_column: "discharge_date".to_string(),
+            admission_type_column: "admission_type".to_string(),
+            inpatient_types: vec!["0".to_string()],
+            procedure_code_column: "procedure_code".to_string(),
+            procedure_date_column: "procedure_date".to_string(),
         }
     }
 }
@@ -358,15 +299,140 @@ pub struct ScdResult {
 }
 
 /// Apply the SCD algorithm to health data with parallel processing
+///
+/// The disease codes are loaded from `config.definitions_path`; use
+/// [`apply_scd_algorithm_with_codes`] to reuse already loaded codes.
 pub fn apply_scd_algorithm(
     health_data: &RecordBatch,
     config: &ScdConfig,
+) -> Result<Vec<ScdResult>> {
+    let scd_codes = ScdDiseaseCodes::load(config.definitions_path.as_deref())?;
+    apply_scd_algorithm_with_codes(health_data, config, &scd_codes)
+}
+
+/// Apply the SCD algorithm to health data using the given disease codes
+pub fn apply_scd_algorithm_with_codes(
+    health_data: &RecordBatch,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
+) -> Result<Vec<ScdResult>> {
+    apply_scd_algorithm_with_birth_dates(health_data, config, scd_codes, None)
+}
+
+/// Apply the SCD algorithm and its confirmation rules using patient birth dates
+///
+/// Birth dates are only needed for the `max_age_years` confirmation rule; patients
+/// without a birth date never satisfy that rule.
+pub fn apply_scd_algorithm_with_birth_dates(
+    health_data: &RecordBatch,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
+    birth_dates: Option<&HashMap<String, NaiveDate>>,
+) -> Result<Vec<ScdResult>> {
+    apply_scd_algorithm_with_procedures(health_data, None, config, scd_codes, birth_dates)
+}
+
+/// Apply the SCD algorithm to health data and the harmonised procedure table
+///
+/// Categories with procedure patterns are also flagged from matching procedures, and
+/// procedures count as events for the confirmation rules.
+pub fn apply_scd_algorithm_with_procedures(
+    health_data: &RecordBatch,
+    procedures: Option<&RecordBatch>,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
+    birth_dates: Option<&HashMap<String, NaiveDate>>,
+) -> Result<Vec<ScdResult>> {
+    let procedures = procedures.filter(|_| scd_codes.uses_procedures());
+    let mut results = flag_scd_diagnoses(health_data, config, scd_codes)?;
+    if let Some(procedures) = procedures {
+        flag_scd_procedures(procedures, config, scd_codes, &mut results)?;
+    }
+    if scd_codes.has_confirmation_rules() {
+        confirm_scd_results(
+            health_data,
+            procedures,
+            config,
+            scd_codes,
+            &mut results,
+            birth_dates,
+        )?;
+    }
+    Ok(results)
+}
+
+/// Flag patients with a procedure matching an SCD category
+///
+/// Patients only found in the procedure table are added to the results.
+fn flag_scd_procedures(
+    procedures: &RecordBatch,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
+    results: &mut Vec<ScdResult>,
+) -> Result<()> {
+    let columns = ProcedureColumns::new(
+        procedures,
+        &config.patient_id_column,
+        &config.procedure_code_column,
+        &config.procedure_date_column,
+    )?;
+    let mut index_by_patient: HashMap<String, usize> = results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| (result.patient_id.clone(), index))
+        .collect();
+
+    let mut flagged = 0usize;
+    for row in 0..procedures.num_rows() {
+        let Some((patient_id, code, date)) = columns.row(row) else {
+            continue;
+        };
+        let categories = scd_codes.procedure_categories(&code);
+        if categories.is_empty() {
+            continue;
+        }
+
+        let index = *index_by_patient
+            .entry(patient_id.to_string())
+            .or_insert_with(|| {
+                results.push(ScdResult {
+                    patient_id: patient_id.to_string(),
+                    is_scd: false,
+                    first_scd_date: None,
+                    disease_categories: scd_codes
+                        .categories
+                        .iter()
+                        .map(|category| (category.name.clone(), false))
+                        .collect(),
+                });
+                results.len() - 1
+            });
+        let result = &mut results[index];
+        result.is_scd = true;
+        if let Some(date) = date.map(days_since_epoch_to_date) {
+            result.first_scd_date =
+                Some(result.first_scd_date.map_or(date, |first| first.min(date)));
+        }
+        for category in categories {
+            result
+                .disease_categories
+                .insert(scd_codes.categories[category].name.clone(), true);
+        }
+        flagged += 1;
+    }
+
+    log::info!("Flagged {flagged} SCD procedures");
+    Ok(())
+}
+
+/// Flag patients with any diagnosis matching an SCD category
+fn flag_scd_diagnoses(
+    health_data: &RecordBatch,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
 ) -> Result<Vec<ScdResult>> {
     use rayon::prelude::*;
-    use std::sync::{Arc, Mutex};
     
-    // Initialize SCD disease codes (shared across threads)
-    let scd_codes = Arc::new(ScdDiseaseCodes::new());
     let all_categories = scd_codes.get_all_categories();
     
     // Get required columns for the algorithm
@@ -412,14 +478,7 @@ pub fn apply_scd_algorithm(
     };
     
     // Extract diagnosis columns for parallel processing
-    let mut diag_arrays = Vec::with_capacity(config.diagnosis_columns.len());
-    for diag_col_name in &config.diagnosis_columns {
-        if let Some(diag_col) = health_data.column_by_name(diag_col_name) {
-            if let Some(diag_array) = diag_col.as_any().downcast_ref::<StringArray>() {
-                diag_arrays.push(Arc::new(diag_array));
-            }
-        }
-    }
+    let diagnosis_columns = DiagnosisColumns::new(health_data, &config.diagnosis_columns);
     
     // Process records in configurable chunk sizes for better cache efficiency
     const CHUNK_SIZE: usize = 10000;
@@ -500,14 +559,7 @@ pub fn apply_scd_algorithm(
             let mut found_scd = false;
             let mut scd_categories = HashSet::new();
             
-            for diag_array in &diag_arrays {
-                if diag_array.is_null(row_idx) {
-                    continue;
-                }
-                
-                // Extract diagnosis code (without cloning)
-                let diagnosis = diag_array.value(row_idx);
-                
+            for diagnosis in diagnosis_columns.codes(row_idx) {
                 // Check if it's a SCD code
                 if scd_codes.is_scd_code(diagnosis) {
                     found_scd = true;
@@ -590,6 +642,149 @@ pub fn apply_scd_algorithm(
     Ok(results)
 }
 
+/// Re-evaluate flagged patients against the categories' confirmation rules
+///
+/// Collects every dated diagnosis and procedure event of the flagged patients per
+/// category and keeps a category only if its rules are satisfied. Categories without
+/// rules keep their single-record flag.
+fn confirm_scd_results(
+    health_data: &RecordBatch,
+    procedures: Option<&RecordBatch>,
+    config: &ScdConfig,
+    scd_codes: &ScdDiseaseCodes,
+    results: &mut [ScdResult],
+    birth_dates: Option<&HashMap<String, NaiveDate>>,
+) -> Result<()> {
+    if birth_dates.is_none()
+        && scd_codes
+            .categories
+            .iter()
+            .any(|category| category.confirmation.requires_birth_date())
+    {
+        log::warn!("SCD confirmation rules use age but no birth dates were provided");
+    }
+
+    let contacts = ContactColumns::new(
+        health_data,
+        &config.patient_id_column,
+        &config.date_column,
+        &config.discharge_date_column,
+        &config.admission_type_column,
+        &config.inpatient_types,
+    )?;
+    if !contacts.has_dates() {
+        return Err(IdsError::Data(format!(
+            "Date column '{}' is required for SCD confirmation rules",
+            config.date_column
+        )));
+    }
+    let diagnosis_columns = DiagnosisColumns::new(health_data, &config.diagnosis_columns);
+
+    let flagged: HashSet<String> = results
+        .iter()
+        .filter(|result| result.is_scd)
+        .map(|result| result.patient_id.clone())
+        .collect();
+
+    // Dated diagnosis and procedure events of the flagged patients per category
+    let category_count = scd_codes.categories.len();
+    let mut patients: HashMap<&str, CategoryEvents> = HashMap::new();
+    let mut hits = vec![false; category_count];
+    for row in 0..health_data.num_rows() {
+        let Some(patient_id) = contacts.patient_id(row) else {
+            continue;
+        };
+        if !flagged.contains(patient_id) {
+            continue;
+        }
+
+        hits.fill(false);
+        for diagnosis in diagnosis_columns.codes(row) {
+            for index in scd_codes.matching_categories(diagnosis) {
+                hits[index] = true;
+            }
+        }
+        patients
+            .entry(patient_id)
+            .or_insert_with(|| CategoryEvents::new(category_count))
+            .record(&hits, contacts.date(row), contacts.inpatient(row));
+    }
+
+    if let Some(procedures) = procedures {
+        let columns = ProcedureColumns::new(
+            procedures,
+            &config.patient_id_column,
+            &config.procedure_code_column,
+            &config.procedure_date_column,
+        )?;
+        for row in 0..procedures.num_rows() {
+            let Some((patient_id, code, date)) = columns.row(row) else {
+                continue;
+            };
+            if !flagged.contains(patient_id) {
+                continue;
+            }
+
+            hits.fill(false);
+            for index in scd_codes.procedure_categories(&code) {
+                hits[index] = true;
+            }
+            patients
+                .entry(patient_id)
+                .or_insert_with(|| CategoryEvents::new(category_count))
+                .record(&hits, date, false);
+        }
+    }
+
+    let mut unconfirmed = 0;
+    for result in results.iter_mut().filter(|result| result.is_scd) {
+        let birth_date = birth_dates.and_then(|dates| dates.get(&result.patient_id).copied());
+        let no_events = CategoryEvents::new(category_count);
+        let events = patients
+            .get(result.patient_id.as_str())
+            .unwrap_or(&no_events);
+        // Categories without rules keep their single-record flag
+        let (confirmed, first_date) = confirm_categories(
+            scd_codes.categories.iter().enumerate().map(|(index, category)| {
+                let flagged = result
+                    .disease_categories
+                    .get(&category.name)
+                    .copied()
+                    .unwrap_or(false);
+                (
+                    &category.confirmation,
+                    flagged,
+                    events.events[index].as_slice(),
+                )
+            }),
+            birth_date,
+        );
+        for (category, confirmed) in scd_codes.categories.iter().zip(&confirmed) {
+            result
+                .disease_categories
+                .insert(category.name.clone(), *confirmed);
+        }
+        let any_category = confirmed.contains(&true);
+
+        if !any_category {
+            unconfirmed += 1;
+        }
+        result.is_scd = any_category;
+        result.first_scd_date = if any_category {
+            first_date.or(result.first_scd_date)
+        } else {
+            None
+        };
+    }
+
+    log::info!(
+        "SCD confirmation rules removed {unconfirmed} of {} flagged patients",
+        flagged.len()
+    );
+
+    Ok(())
+}
+
 /// Convert SCD results to a `RecordBatch`
 pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch> {
     if results.is_empty() {
@@ -602,7 +797,17 @@ pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch>
         all_categories.extend(result.disease_categories.keys().cloned());
     }
     let categories: Vec<String> = all_categories.into_iter().collect();
-    
+    scd_results_to_record_batch_with_categories(results, &categories)
+}
+
+/// Convert SCD results to a `RecordBatch` with one `category_` column per given category
+///
+/// Unlike [`scd_results_to_record_batch`] the columns do not depend on the results, so
+/// batches of results converted separately share a schema.
+pub fn scd_results_to_record_batch_with_categories(
+    results: &[ScdResult],
+    categories: &[String],
+) -> Result<RecordBatch> {
     // Prepare data for the batch
     let mut patient_ids = Vec::with_capacity(results.len());
     let mut is_scd_values = Vec::with_capacity(results.len());
@@ -610,7 +815,7 @@ pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch>
     
     // One vector per category
     let mut category_values: HashMap<String, Vec<Option<bool>>> = HashMap::new();
-    for category in &categories {
+    for category in categories {
         category_values.insert(category.clone(), Vec::with_capacity(results.len()));
     }
     
@@ -630,7 +835,7 @@ pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch>
         }
         
         // Add category values
-        for category in &categories {
+        for category in categories {
             if let Some(&has_category) = result.disease_categories.get(category) {
                 category_values.get_mut(category).unwrap().push(Some(has_category));
             } else {
@@ -658,7 +863,7 @@ pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch>
     ];
     
     // Add category columns
-    for category in &categories {
+    for category in categories {
         let field_name = format!("category_{category}");
         fields.push(Field::new(&field_name, DataType::Boolean, true));
         
@@ -672,4 +877,96 @@ pub fn scd_results_to_record_batch(results: &[ScdResult]) -> Result<RecordBatch>
         .map_err(|e| IdsError::Data(format!("Failed to create SCD result batch: {e}")))?;
     
     Ok(batch)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run every ICD-10 code in the SKS dump through both bundled definitions and
+    /// compare with the committed expected flags
+    #[test]
+    fn test_scd_conformance_with_sks_codes() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let sks = std::fs::read_to_string(root.join("schemas/icd10/sks_dump.tsv")).unwrap();
+        let expected = std::fs::read_to_string(root.join("schemas/definitions/scd_conformance.tsv")).unwrap();
+
+        let expected: HashMap<&str, (bool, bool)> = expected
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[0], (fields[1] == "1", fields[2] == "1"))
+            })
+            .collect();
+
+        let reference = ScdDiseaseCodes::from_definitions(&DiseaseDefinitions::scdalgo_r()).unwrap();
+        let default = ScdDiseaseCodes::new();
+
+        let mut checked = 0;
+        for line in sks.lines().skip(1) {
+            let Some(code) = line.split('\t').nth(1).map(str::trim) else {
+                continue;
+            };
+            if code.len() < 2 || !code.starts_with('D') || !code.as_bytes()[1].is_ascii_uppercase() {
+                continue;
+            }
+
+            let (expected_r, expected_default) = expected
+                .get(code)
+                .unwrap_or_else(|| panic!("{code} missing from conformance file"));
+            assert_eq!(reference.is_scd_code(code), *expected_r, "SCDalgo.R flag for {code}");
+            assert_eq!(default.is_scd_code(code), *expected_default, "default flag for {code}");
+            checked += 1;
+        }
+
+        assert_eq!(checked, expected.len());
+    }
+
+    #[test]
+    fn test_scd_procedure_rules() {
+        let definitions = DiseaseDefinitions::from_json(
+            r#"{
+                "format_version": 1,
+                "name": "scd-procedures",
+                "version": "1",
+                "categories": [{
+                    "name": "renal",
+                    "include": [{ "type": "prefix", "codes": ["N18"] }],
+                    "procedures": [{ "type": "prefix", "codes": ["BJFD2", "KKAS"] }]
+                }]
+            }"#,
+            "test.json",
+        )
+        .unwrap();
+        let codes = ScdDiseaseCodes::from_definitions(&definitions).unwrap();
+        assert!(codes.uses_procedures());
+
+        let health_data = RecordBatch::try_from_iter(vec![
+            ("patient_id", Arc::new(StringArray::from(vec!["p1"])) as ArrayRef),
+            ("primary_diagnosis", Arc::new(StringArray::from(vec!["DJ45"])) as ArrayRef),
+        ])
+        .unwrap();
+        let procedures = RecordBatch::try_from_iter(vec![
+            ("patient_id", Arc::new(StringArray::from(vec!["p1", "p2"])) as ArrayRef),
+            ("procedure_code", Arc::new(StringArray::from(vec!["BJFD20", "KKAS00"])) as ArrayRef),
+            ("procedure_date", Arc::new(Date32Array::from(vec![Some(15000), None])) as ArrayRef),
+        ])
+        .unwrap();
+
+        let mut results = apply_scd_algorithm_with_procedures(
+            &health_data,
+            Some(&procedures),
+            &ScdConfig::default(),
+            &codes,
+            None,
+        )
+        .unwrap();
+        results.sort_by(|a, b| a.patient_id.cmp(&b.patient_id));
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_scd && result.disease_categories["renal"]));
+        assert_eq!(results[0].first_scd_date, Some(days_since_epoch_to_date(15000)));
+        assert_eq!(results[1].first_scd_date, None);
+    }
+}
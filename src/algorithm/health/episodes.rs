@@ -0,0 +1,616 @@
+//! Contact-to-episode collapsing over harmonised LPR data
+//!
+//! LPR3 splits one hospitalisation into many contacts (linked by `DW_EK_FORLOEB`), and
+//! LPR2 has overlapping admissions when patients are transferred between departments.
+//! The episode builder merges a patient's overlapping or adjacent contacts into
+//! episodes, derives the length of stay from the contact start and end dates and times
+//! (and the registered LPR2 bed days), and classifies each episode as inpatient,
+//! outpatient or emergency.
+
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Date32Array, Float64Array, Int32Array, StringArray,
+    Time32SecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::{IdsError, Result};
+
+/// Seconds in a day, for combining dates and times of day
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Classification of an episode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpisodeType {
+    /// Admission: long enough, spanning several days, or an inpatient contact type
+    Inpatient,
+    /// Short acute contact that is not an admission
+    Emergency,
+    /// Planned short contact
+    Outpatient,
+}
+
+impl fmt::Display for EpisodeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inpatient => write!(f, "inpatient"),
+            Self::Emergency => write!(f, "emergency"),
+            Self::Outpatient => write!(f, "outpatient"),
+        }
+    }
+}
+
+/// Configuration for building episodes from harmonised LPR contacts
+pub struct EpisodeConfig {
+    /// Contacts starting at most this many days after the current episode ends are
+    /// merged into it; 0 merges overlapping contacts and contacts starting on the day the
+    /// episode ends
+    pub max_gap_days: i32,
+    /// Also merge contacts sharing a course ID (LPR3 `DW_EK_FORLOEB`) within
+    /// `course_gap_days`; on by default
+    pub merge_courses: bool,
+    /// Contacts in the same course starting at most this many days after the current
+    /// episode ends are merged into it, so a course of outpatient visits spanning years
+    /// does not become one episode
+    pub course_gap_days: i32,
+    /// Episodes with a continuous stay of at least this many hours are inpatient (the
+    /// LPR3 admission definition)
+    pub inpatient_min_hours: f64,
+    /// Contact types that make an episode inpatient (LPR2 `C_PATTYPE` 0)
+    pub inpatient_types: Vec<String>,
+    /// Column containing the patient ID
+    pub patient_id_column: String,
+}
+
+impl Default for EpisodeConfig {
+    fn default() -> Self {
+        Self {
+            max_gap_days: 0,
+            merge_courses: true,
+            course_gap_days: 30,
+            inpatient_min_hours: 12.0,
+            inpatient_types: vec!["0".to_string()],
+            patient_id_column: "patient_id".to_string(),
+        }
+    }
+}
+
+/// An episode of care made of one or more contacts
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    /// Patient identifier
+    pub patient_id: String,
+    /// Start date (days since epoch) of the first contact
+    pub start_date: i32,
+    /// Start time (seconds after midnight) of the first contact, if registered
+    pub start_time: Option<i32>,
+    /// Latest end date (days since epoch) of the contacts
+    pub end_date: i32,
+    /// End time (seconds after midnight) of the contact ending last, if registered
+    pub end_time: Option<i32>,
+    /// Number of contacts merged into the episode
+    pub contacts: usize,
+    /// Length of stay in calendar days (0 for same-day episodes)
+    pub length_of_stay_days: i32,
+    /// Length of stay in hours, if start and end times are registered
+    pub length_of_stay_hours: Option<f64>,
+    /// Sum of the registered LPR2 bed days (`V_SENGDAGE`) of the contacts
+    pub bed_days: Option<i32>,
+    /// Course ID of the first contact with one
+    pub course_id: Option<String>,
+    /// Hospital code of the first contact
+    pub hospital_code: Option<String>,
+    /// Episode classification
+    pub episode_type: EpisodeType,
+}
+
+/// A single contact read from harmonised LPR data
+#[derive(Debug, Clone)]
+struct Contact {
+    start_date: i32,
+    start_time: Option<i32>,
+    end_date: i32,
+    end_time: Option<i32>,
+    course_id: Option<String>,
+    hospital_code: Option<String>,
+    bed_days: Option<i32>,
+    inpatient_type: bool,
+    emergency: bool,
+}
+
+impl Contact {
+    /// End as seconds since epoch, treating a missing end time as the end of the day
+    fn end_instant(&self) -> i64 {
+        i64::from(self.end_date) * SECONDS_PER_DAY
+            + self.end_time.map_or(SECONDS_PER_DAY - 1, i64::from)
+    }
+}
+
+/// Length of stay in days and, if start and end times are registered, hours
+fn stay(first: &Contact, last_end: &Contact) -> (i32, Option<f64>) {
+    let days = last_end.end_date - first.start_date;
+    let hours = first.start_time.zip(last_end.end_time).map(|(start, end)| {
+        let seconds = i64::from(days) * SECONDS_PER_DAY + i64::from(end) - i64::from(start);
+        seconds.max(0) as f64 / 3600.0
+    });
+    (days, hours)
+}
+
+/// An episode while contacts are being merged into it
+struct EpisodeBuilder {
+    patient_id: String,
+    first: Contact,
+    last_end: Contact,
+    /// First contact of the current continuous stay; contacts merged only by course
+    /// start a new stay
+    stay_first: Contact,
+    /// Whether an earlier continuous stay was long enough to be an admission
+    admitted: bool,
+    contacts: usize,
+    bed_days: Option<i32>,
+    course_id: Option<String>,
+    inpatient_type: bool,
+    emergency: bool,
+}
+
+impl EpisodeBuilder {
+    fn new(patient_id: &str, contact: Contact) -> Self {
+        Self {
+            patient_id: patient_id.to_string(),
+            bed_days: contact.bed_days,
+            course_id: contact.course_id.clone(),
+            inpatient_type: contact.inpatient_type,
+            emergency: contact.emergency,
+            first: contact.clone(),
+            stay_first: contact.clone(),
+            last_end: contact,
+            admitted: false,
+            contacts: 1,
+        }
+    }
+
+    fn within_days(&self, contact: &Contact, days: i32) -> bool {
+        i64::from(contact.start_date) <= i64::from(self.last_end.end_date) + i64::from(days)
+    }
+
+    fn accepts(&self, contact: &Contact, config: &EpisodeConfig) -> bool {
+        let same_course = config.merge_courses
+            && contact.course_id.is_some()
+            && contact.course_id == self.course_id
+            && self.within_days(contact, config.course_gap_days);
+        self.within_days(contact, config.max_gap_days) || same_course
+    }
+
+    /// Whether the current continuous stay is long enough to be an admission
+    fn stay_admitted(&self, config: &EpisodeConfig) -> bool {
+        let (days, hours) = stay(&self.stay_first, &self.last_end);
+        hours.map_or(days > 0, |hours| hours >= config.inpatient_min_hours)
+    }
+
+    fn add(&mut self, contact: Contact, config: &EpisodeConfig) {
+        if !self.within_days(&contact, config.max_gap_days) {
+            self.admitted |= self.stay_admitted(config);
+            self.stay_first = contact.clone();
+        }
+        self.contacts += 1;
+        self.bed_days = match (self.bed_days, contact.bed_days) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        if self.course_id.is_none() {
+            self.course_id.clone_from(&contact.course_id);
+        }
+        self.inpatient_type |= contact.inpatient_type;
+        self.emergency |= contact.emergency;
+        if contact.end_instant() > self.last_end.end_instant() {
+            self.last_end = contact;
+        }
+    }
+
+    fn finish(self, config: &EpisodeConfig) -> Episode {
+        let (length_of_stay_days, length_of_stay_hours) = stay(&self.first, &self.last_end);
+
+        // Classified by the continuous stays, not the span of a merged course
+        let inpatient = self.inpatient_type || self.admitted || self.stay_admitted(config);
+        let episode_type = if inpatient {
+            EpisodeType::Inpatient
+        } else if self.emergency {
+            EpisodeType::Emergency
+        } else {
+            EpisodeType::Outpatient
+        };
+
+        Episode {
+            patient_id: self.patient_id,
+            start_date: self.first.start_date,
+            start_time: self.first.start_time,
+            end_date: self.last_end.end_date,
+            end_time: self.last_end.end_time,
+            contacts: self.contacts,
+            length_of_stay_days,
+            length_of_stay_hours,
+            bed_days: self.bed_days,
+            course_id: self.course_id,
+            hospital_code: self.first.hospital_code,
+            episode_type,
+        }
+    }
+}
+
+/// Non-null string value of an optional column
+fn string_at(array: Option<&StringArray>, row: usize) -> Option<&str> {
+    array
+        .filter(|array| !array.is_null(row))
+        .map(|array| array.value(row))
+}
+
+/// Build episodes from harmonised LPR data
+///
+/// Uses the `admission_date`, `discharge_date`, `admission_time`, `discharge_time`,
+/// `course_id`, `bed_days`, `emergency`, `admission_type` and `hospital_code` columns
+/// of [`super::lpr::process_lpr_data`]'s output; only the patient ID and admission date
+/// are required. Contacts without a discharge date end on their admission date.
+/// Episodes are returned sorted by patient and start.
+pub fn build_episodes(lpr_data: &RecordBatch, config: &EpisodeConfig) -> Result<Vec<Episode>> {
+    let patient_id_array = lpr_data
+        .column_by_name(&config.patient_id_column)
+        .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| {
+            IdsError::Data(format!(
+                "Patient ID column '{}' not found or not a string array",
+                config.patient_id_column
+            ))
+        })?;
+    let start_dates = lpr_data
+        .column_by_name("admission_date")
+        .and_then(|column| column.as_any().downcast_ref::<Date32Array>())
+        .ok_or_else(|| {
+            IdsError::Data("admission_date column not found or not a date array".to_string())
+        })?;
+    let column = |name: &str| lpr_data.column_by_name(name);
+    let end_dates =
+        column("discharge_date").and_then(|column| column.as_any().downcast_ref::<Date32Array>());
+    let start_times = column("admission_time")
+        .and_then(|column| column.as_any().downcast_ref::<Time32SecondArray>());
+    let end_times = column("discharge_time")
+        .and_then(|column| column.as_any().downcast_ref::<Time32SecondArray>());
+    let bed_days =
+        column("bed_days").and_then(|column| column.as_any().downcast_ref::<Int32Array>());
+    let emergency =
+        column("emergency").and_then(|column| column.as_any().downcast_ref::<BooleanArray>());
+    let string_column =
+        |name: &str| column(name).and_then(|column| column.as_any().downcast_ref::<StringArray>());
+    let course_ids = string_column("course_id");
+    let hospital_codes = string_column("hospital_code");
+    let admission_types = string_column("admission_type");
+
+    let value_at = |array: Option<&Date32Array>, row: usize| {
+        array
+            .filter(|array| !array.is_null(row))
+            .map(|array| array.value(row))
+    };
+    let time_at = |array: Option<&Time32SecondArray>, row: usize| {
+        array
+            .filter(|array| !array.is_null(row))
+            .map(|array| array.value(row))
+    };
+
+    // Contacts by patient
+    let mut contacts: HashMap<&str, Vec<Contact>> = HashMap::new();
+    let mut skipped = 0usize;
+    for row in 0..lpr_data.num_rows() {
+        if patient_id_array.is_null(row) || start_dates.is_null(row) {
+            skipped += 1;
+            continue;
+        }
+        let start_date = start_dates.value(row);
+        let end_date = value_at(end_dates, row).map_or(start_date, |end| end.max(start_date));
+        // A registered end time only applies if the registered end date was used
+        let end_time = time_at(end_times, row)
+            .filter(|_| value_at(end_dates, row).is_some_and(|end| end >= start_date));
+
+        contacts
+            .entry(patient_id_array.value(row))
+            .or_default()
+            .push(Contact {
+                start_date,
+                start_time: time_at(start_times, row),
+                end_date,
+                end_time,
+                course_id: string_at(course_ids, row).map(str::to_string),
+                hospital_code: string_at(hospital_codes, row).map(str::to_string),
+                bed_days: bed_days
+                    .filter(|array| !array.is_null(row))
+                    .map(|array| array.value(row)),
+                inpatient_type: string_at(admission_types, row)
+                    .is_some_and(|value| config.inpatient_types.iter().any(|t| t == value.trim())),
+                emergency: emergency.is_some_and(|array| !array.is_null(row) && array.value(row)),
+            });
+    }
+    if skipped > 0 {
+        log::warn!("Skipped {skipped} contacts without a patient ID or admission date");
+    }
+
+    let mut patients: Vec<&str> = contacts.keys().copied().collect();
+    patients.sort_unstable();
+
+    let mut episodes = Vec::new();
+    for patient_id in patients {
+        let mut patient_contacts = contacts.remove(patient_id).unwrap_or_default();
+        patient_contacts
+            .sort_by_key(|contact| (contact.start_date, contact.start_time.unwrap_or(0)));
+
+        let mut current: Option<EpisodeBuilder> = None;
+        for contact in patient_contacts {
+            match current.as_mut() {
+                Some(episode) if episode.accepts(&contact, config) => episode.add(contact, config),
+                _ => {
+                    if let Some(episode) = current.replace(EpisodeBuilder::new(patient_id, contact))
+                    {
+                        episodes.push(episode.finish(config));
+                    }
+                }
+            }
+        }
+        if let Some(episode) = current {
+            episodes.push(episode.finish(config));
+        }
+    }
+
+    log::info!(
+        "Built {} episodes from {} contacts",
+        episodes.len(),
+        lpr_data.num_rows() - skipped
+    );
+
+    Ok(episodes)
+}
+
+/// Convert episodes to a `RecordBatch`
+pub fn episodes_to_record_batch(episodes: &[Episode]) -> Result<RecordBatch> {
+    let schema = Schema::new(vec![
+        Field::new("patient_id", DataType::Utf8, false),
+        Field::new("episode_start", DataType::Date32, false),
+        Field::new("episode_end", DataType::Date32, false),
+        Field::new("contacts", DataType::Int32, false),
+        Field::new("length_of_stay_days", DataType::Int32, false),
+        Field::new("length_of_stay_hours", DataType::Float64, true),
+        Field::new("bed_days", DataType::Int32, true),
+        Field::new("episode_type", DataType::Utf8, false),
+        Field::new("course_id", DataType::Utf8, true),
+        Field::new("hospital_code", DataType::Utf8, true),
+    ]);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            episodes.iter().map(|episode| episode.patient_id.as_str()),
+        )),
+        Arc::new(Date32Array::from_iter_values(
+            episodes.iter().map(|episode| episode.start_date),
+        )),
+        Arc::new(Date32Array::from_iter_values(
+            episodes.iter().map(|episode| episode.end_date),
+        )),
+        Arc::new(Int32Array::from_iter_values(episodes.iter().map(
+            |episode| i32::try_from(episode.contacts).unwrap_or(i32::MAX),
+        ))),
+        Arc::new(Int32Array::from_iter_values(
+            episodes.iter().map(|episode| episode.length_of_stay_days),
+        )),
+        Arc::new(Float64Array::from_iter(
+            episodes.iter().map(|episode| episode.length_of_stay_hours),
+        )),
+        Arc::new(Int32Array::from_iter(
+            episodes.iter().map(|episode| episode.bed_days),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            episodes
+                .iter()
+                .map(|episode| episode.episode_type.to_string()),
+        )),
+        Arc::new(StringArray::from_iter(
+            episodes.iter().map(|episode| episode.course_id.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            episodes
+                .iter()
+                .map(|episode| episode.hospital_code.as_deref()),
+        )),
+    ];
+
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .map_err(|e| IdsError::Data(format!("Failed to create episode batch: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_episodes() {
+        let hours = |h: i32| h * 3600;
+        let lpr_data = RecordBatch::try_from_iter(vec![
+            (
+                "patient_id",
+                Arc::new(StringArray::from(vec!["p1", "p1", "p1", "p2", "p3"])) as ArrayRef,
+            ),
+            (
+                "admission_date",
+                Arc::new(Date32Array::from(vec![100, 101, 110, 200, 300])) as ArrayRef,
+            ),
+            (
+                "discharge_date",
+                Arc::new(Date32Array::from(vec![
+                    Some(101),
+                    Some(103),
+                    Some(110),
+                    Some(200),
+                    None,
+                ])) as ArrayRef,
+            ),
+            (
+                "admission_time",
+                Arc::new(Time32SecondArray::from(vec![
+                    Some(hours(8)),
+                    Some(hours(12)),
+                    Some(hours(9)),
+                    Some(hours(1)),
+                    None,
+                ])) as ArrayRef,
+            ),
+            (
+                "discharge_time",
+                Arc::new(Time32SecondArray::from(vec![
+                    Some(hours(12)),
+                    Some(hours(10)),
+                    Some(hours(10)),
+                    Some(hours(4)),
+                    None,
+                ])) as ArrayRef,
+            ),
+            (
+                "emergency",
+                Arc::new(BooleanArray::from(vec![false, false, false, true, false])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let episodes = build_episodes(&lpr_data, &EpisodeConfig::default()).unwrap();
+        assert_eq!(episodes.len(), 4);
+
+        // A transfer on day 101 is merged into the first admission
+        assert_eq!(episodes[0].contacts, 2);
+        assert_eq!(episodes[0].length_of_stay_days, 3);
+        assert_eq!(episodes[0].length_of_stay_hours, Some(74.0));
+        assert_eq!(episodes[0].episode_type, EpisodeType::Inpatient);
+        assert_eq!(episodes[1].episode_type, EpisodeType::Outpatient);
+        assert_eq!(episodes[2].episode_type, EpisodeType::Emergency);
+        assert_eq!(episodes[3].episode_type, EpisodeType::Outpatient);
+
+        let batch = episodes_to_record_batch(&episodes).unwrap();
+        assert_eq!(batch.num_rows(), 4);
+    }
+
+    /// Contacts of one patient as (admission date, discharge date, course ID)
+    fn contacts(rows: &[(i32, i32, Option<&str>)]) -> RecordBatch {
+        RecordBatch::try_from_iter(vec![
+            (
+                "patient_id",
+                Arc::new(StringArray::from(vec!["p1"; rows.len()])) as ArrayRef,
+            ),
+            (
+                "admission_date",
+                Arc::new(Date32Array::from_iter_values(rows.iter().map(|row| row.0))) as ArrayRef,
+            ),
+            (
+                "discharge_date",
+                Arc::new(Date32Array::from_iter_values(rows.iter().map(|row| row.1))) as ArrayRef,
+            ),
+            (
+                "course_id",
+                Arc::new(StringArray::from_iter(rows.iter().map(|row| row.2))) as ArrayRef,
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn contact_counts(lpr_data: &RecordBatch, config: &EpisodeConfig) -> Vec<usize> {
+        build_episodes(lpr_data, config)
+            .unwrap()
+            .iter()
+            .map(|episode| episode.contacts)
+            .collect()
+    }
+
+    #[test]
+    fn test_gaps_between_contacts() {
+        // A contact starting on the day the previous one ends is merged
+        let lpr_data = contacts(&[(100, 101, None), (101, 101, None)]);
+        assert_eq!(
+            contact_counts(&lpr_data, &EpisodeConfig::default()),
+            vec![2]
+        );
+
+        // One day and three days after the previous contact ends
+        let lpr_data = contacts(&[(100, 101, None), (102, 102, None), (105, 105, None)]);
+        assert_eq!(
+            contact_counts(&lpr_data, &EpisodeConfig::default()),
+            vec![1, 1, 1]
+        );
+
+        let one_day = EpisodeConfig {
+            max_gap_days: 1,
+            ..EpisodeConfig::default()
+        };
+        assert_eq!(contact_counts(&lpr_data, &one_day), vec![2, 1]);
+
+        // The gap is measured from the latest end, not the last contact's end
+        let lpr_data = contacts(&[(100, 110, None), (102, 103, None), (111, 111, None)]);
+        assert_eq!(contact_counts(&lpr_data, &one_day), vec![3]);
+
+        let episodes = build_episodes(&lpr_data, &one_day).unwrap();
+        assert_eq!(episodes[0].start_date, 100);
+        assert_eq!(episodes[0].end_date, 111);
+    }
+
+    #[test]
+    fn test_course_merging() {
+        // Contacts weeks apart in the same course, then one in another course
+        let lpr_data = contacts(&[
+            (100, 100, Some("c1")),
+            (120, 121, Some("c1")),
+            (140, 140, Some("c2")),
+            (160, 160, None),
+        ]);
+
+        assert!(EpisodeConfig::default().merge_courses);
+        let episodes = build_episodes(&lpr_data, &EpisodeConfig::default()).unwrap();
+        assert_eq!(
+            episodes.iter().map(|e| e.contacts).collect::<Vec<_>>(),
+            vec![2, 1, 1]
+        );
+        assert_eq!(episodes[0].course_id.as_deref(), Some("c1"));
+        assert_eq!(episodes[0].end_date, 121);
+        assert_eq!(episodes[0].length_of_stay_days, 21);
+
+        let by_gap_only = EpisodeConfig {
+            merge_courses: false,
+            ..EpisodeConfig::default()
+        };
+        assert_eq!(contact_counts(&lpr_data, &by_gap_only), vec![1, 1, 1, 1]);
+
+        let short_course_gap = EpisodeConfig {
+            course_gap_days: 10,
+            ..EpisodeConfig::default()
+        };
+        assert_eq!(
+            contact_counts(&lpr_data, &short_course_gap),
+            vec![1, 1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn test_course_contacts_far_apart() {
+        // Two same-day outpatient visits a year apart in the same course
+        let lpr_data = contacts(&[(100, 100, Some("c1")), (465, 465, Some("c1"))]);
+        let episodes = build_episodes(&lpr_data, &EpisodeConfig::default()).unwrap();
+        assert_eq!(episodes.len(), 2);
+        assert!(episodes
+            .iter()
+            .all(|episode| episode.episode_type == EpisodeType::Outpatient
+                && episode.length_of_stay_days == 0));
+
+        // Even merged, the course span does not make them an admission
+        let whole_course = EpisodeConfig {
+            course_gap_days: 400,
+            ..EpisodeConfig::default()
+        };
+        let episodes = build_episodes(&lpr_data, &whole_course).unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].length_of_stay_days, 365);
+        assert_eq!(episodes[0].episode_type, EpisodeType::Outpatient);
+    }
+}
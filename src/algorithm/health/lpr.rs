@@ -2,25 +2,46 @@
 //!
 //! This module implements data processing operations for the Danish National Patient Registry (LPR)
 //! including integration of LPR2 and LPR3 data, data harmonization, and preparation for SCD analysis.
-
-use arrow::array::{Array, BooleanArray, Date32Array, StringArray};
+//!
+//! LPR2 diagnoses from contacts before 1994 are coded in ICD-8; they are mapped to ICD-10
+//! with the bundled crosswalk ([`crate::model::icd8`]), and the registered primary ICD-8
+//! code is kept in the `primary_diagnosis_icd8` column. ICD-8 codes without a mapping
+//! are only kept there: their `primary_diagnosis` is null, and unmapped secondary
+//! ICD-8 diagnoses are left out.
+//!
+//! The functions here work on batches in memory. Whole registers are harmonised by
+//! [`partition_lpr`](super::lpr_partitioned::partition_lpr), which streams the components
+//! to disk partitions and calls [`integrate_lpr2_components`] and
+//! [`integrate_lpr3_components`] for one partition at a time. [`process_lpr_data`],
+//! [`load_and_process_lpr`] and the other functions taking whole components hold all of
+//! their input and output in memory, and are meant for PNR-filtered data.
+
+use arrow::array::{
+    Array, ArrowPrimitiveType, BooleanArray, Date32Array, Int32Array, PrimitiveArray, StringArray,
+    Time32SecondArray,
+};
 use arrow::compute::concat_batches;
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema, Time32SecondType, TimeUnit};
 use arrow::record_batch::RecordBatch;
 use chrono::NaiveDate;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use crate::algorithm::health::diagnosis::filter::{is_disproven_flag, DiagnosisFilter};
 use crate::algorithm::health::diagnosis::secondary::{
     create_secondary_diagnoses_array, create_secondary_diagnoses_field,
     process_secondary_diagnoses, SecondaryDiagnosis,
 };
 use crate::data::registry::loaders::lpr::LprRegistry;
 use crate::error::{IdsError, Result};
-use crate::model::icd10::{diagnosis_pattern::normalize_diagnosis_code, Icd10Chapter};
+use crate::model::icd10::{
+    diagnosis_pattern::normalize_diagnosis_code, Icd10Chapter, Icd10Dictionary,
+};
+use crate::model::icd8::{harmonize_diagnosis_code, CodeSystem, HarmonizedDiagnosis};
 use crate::utils::date_utils;
 
 /// Configuration for LPR data processing
+#[derive(Debug, Clone)]
 pub struct LprConfig {
     /// Whether to include LPR2 data
     pub include_lpr2: bool,
@@ -30,6 +51,8 @@ pub struct LprConfig {
     pub start_date: Option<NaiveDate>,
     /// End date for filtering (inclusive)
     pub end_date: Option<NaiveDate>,
+    /// Diagnosis type and status rules applied when integrating diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
 }
 
 impl Default for LprConfig {
@@ -39,6 +62,7 @@ impl Default for LprConfig {
             include_lpr3: true,
             start_date: None,
             end_date: None,
+            diagnosis_filter: DiagnosisFilter::default(),
         }
     }
 }
@@ -161,6 +185,41 @@ fn get_string_column(batch: &RecordBatch, column_name: &str) -> Result<Arc<Strin
     Ok(string_array)
 }
 
+/// Extracts an optional string column, returning `None` if the column is absent
+fn get_optional_string_column(
+    batch: &RecordBatch,
+    column_name: &str,
+) -> Result<Option<Arc<StringArray>>> {
+    if batch.schema().index_of(column_name).is_err() {
+        return Ok(None);
+    }
+    get_string_column(batch, column_name).map(Some)
+}
+
+/// Extracts an optional column cast to the given primitive type, returning `None` if the
+/// column is absent
+fn get_optional_primitive_column<T: ArrowPrimitiveType>(
+    batch: &RecordBatch,
+    column_name: &str,
+) -> Result<Option<PrimitiveArray<T>>> {
+    let Some(column) = batch.column_by_name(column_name) else {
+        return Ok(None);
+    };
+    let cast = arrow::compute::cast(column, &T::DATA_TYPE)
+        .map_err(|e| IdsError::Data(format!("Failed to convert {column_name} column: {e}")))?;
+    Ok(cast.as_any().downcast_ref::<PrimitiveArray<T>>().cloned())
+}
+
+/// The value of an optional primitive column at a row
+fn primitive_value<T: ArrowPrimitiveType>(
+    array: Option<&PrimitiveArray<T>>,
+    row: usize,
+) -> Option<T::Native> {
+    array
+        .filter(|array| !array.is_null(row))
+        .map(|array| array.value(row))
+}
+
 /// Builds a map of record number to row index
 fn build_recnum_index(recnum_array: &StringArray) -> HashMap<String, usize> {
     let mut recnum_to_row = HashMap::new();
@@ -175,12 +234,19 @@ fn build_recnum_index(recnum_array: &StringArray) -> HashMap<String, usize> {
 }
 
 /// Maps diagnoses with their types by record number
+///
+/// Diagnoses rejected by the filter are left out. The disproven flag and parent code
+/// columns are only available for LPR3.
 fn map_diagnoses_by_recnum(
     recnum_array: &StringArray,
     diag_array: &StringArray,
     diag_type_array: &StringArray,
+    disproven_array: Option<&StringArray>,
+    parent_array: Option<&StringArray>,
+    filter: &DiagnosisFilter,
 ) -> HashMap<String, Vec<(String, String)>> {
     let mut recnum_to_diagnoses = HashMap::new();
+    let mut rejected = 0usize;
 
     for i in 0..recnum_array.len() {
         if recnum_array.is_null(i) || diag_array.is_null(i) {
@@ -195,6 +261,15 @@ fn map_diagnoses_by_recnum(
             diag_type_array.value(i)
         };
 
+        let disproven = disproven_array
+            .is_some_and(|array| !array.is_null(i) && is_disproven_flag(array.value(i)));
+        let has_parent = parent_array
+            .is_some_and(|array| !array.is_null(i) && !array.value(i).trim().is_empty());
+        if !filter.accepts(diag_type, disproven, has_parent) {
+            rejected += 1;
+            continue;
+        }
+
         let diagnoses = recnum_to_diagnoses
             .entry(recnum.to_string())
             .or_insert_with(Vec::new);
@@ -202,6 +277,10 @@ fn map_diagnoses_by_recnum(
         diagnoses.push((diagnosis.to_string(), diag_type.to_string()));
     }
 
+    if rejected > 0 {
+        log::info!("Diagnosis filter ({filter}) removed {rejected} diagnoses");
+    }
+
     recnum_to_diagnoses
 }
 
@@ -237,8 +316,21 @@ fn get_diagnosis_chapter(diagnosis: &str) -> Option<String> {
         .map(|chapter| chapter.description().to_string())
 }
 
+/// Get the SKS description of a normalized diagnosis code
+///
+/// Local extensions of a code (extra characters not in SKS) get the description of the
+/// nearest SKS code.
+fn get_diagnosis_description(diagnosis: &str) -> Option<String> {
+    Icd10Dictionary::bundled()
+        .nearest(diagnosis)
+        .map(|entry| entry.description.clone())
+}
+
 /// Creates the integrated record batch schema (common for both LPR2 and LPR3)
-fn create_integrated_schema() -> Schema {
+///
+/// The diagnosis filter used is recorded in the schema metadata under
+/// `lpr_diagnosis_filter`.
+pub fn create_integrated_schema(filter: &DiagnosisFilter) -> Schema {
     // Get secondary diagnosis field definition from the secondary diagnosis module
     let secondary_diag_list = create_secondary_diagnoses_field();
 
@@ -248,12 +340,25 @@ fn create_integrated_schema() -> Schema {
         Field::new("primary_diagnosis", DataType::Utf8, true),
         secondary_diag_list,
         Field::new("diagnosis_chapter", DataType::Utf8, true),
+        Field::new("primary_diagnosis_description", DataType::Utf8, true),
+        // Registered ICD-8 code of pre-1994 LPR2 contacts (mapped to ICD-10 above)
+        Field::new("primary_diagnosis_icd8", DataType::Utf8, true),
         Field::new("admission_date", DataType::Date32, true),
         Field::new("discharge_date", DataType::Date32, true),
         Field::new("hospital_code", DataType::Utf8, true),
         Field::new("department_code", DataType::Utf8, true),
         Field::new("admission_type", DataType::Utf8, true),
+        // Contact timing and course, used by the episode builder
+        Field::new("admission_time", DataType::Time32(TimeUnit::Second), true),
+        Field::new("discharge_time", DataType::Time32(TimeUnit::Second), true),
+        Field::new("course_id", DataType::Utf8, true),
+        Field::new("bed_days", DataType::Int32, true),
+        Field::new("emergency", DataType::Boolean, true),
     ])
+    .with_metadata(HashMap::from([(
+        "lpr_diagnosis_filter".to_string(),
+        filter.to_string(),
+    )]))
 }
 
 /// Integrate LPR2 components (`LPR_ADM`, `LPR_DIAG`, and optionally `LPR_BES`)
@@ -261,6 +366,7 @@ pub fn integrate_lpr2_components(
     lpr_adm: &[RecordBatch],
     lpr_diag: &[RecordBatch],
     lpr_bes: Option<&[RecordBatch]>,
+    filter: &DiagnosisFilter,
 ) -> Result<RecordBatch> {
     // First merge all batches
     let lpr_adm = merge_batches(lpr_adm, "LPR_ADM")?;
@@ -296,6 +402,14 @@ pub fn integrate_lpr2_components(
     // Use our flexible date conversion regardless of the column's actual type
     let discharge_date_date32 = date_utils::convert_to_date32_array(discharge_date_array.as_ref())?;
 
+    // Optional contact timing columns (hour and minute of admission, hour of discharge),
+    // bed days and admission mode (1 = acute)
+    let adm_hour_array = get_optional_primitive_column::<Int32Type>(&lpr_adm, "V_INDTIME")?;
+    let adm_minute_array = get_optional_primitive_column::<Int32Type>(&lpr_adm, "V_INDMINUT")?;
+    let disch_hour_array = get_optional_primitive_column::<Int32Type>(&lpr_adm, "V_UDTIME")?;
+    let bed_days_array = get_optional_primitive_column::<Int32Type>(&lpr_adm, "V_SENGDAGE")?;
+    let admission_mode_array = get_optional_string_column(&lpr_adm, "C_INDM")?;
+
     // Get diagnosis data from LPR_DIAG
     let diag_recnum_array = get_string_column(&lpr_diag, "RECNUM")?;
     let diag_array = get_string_column(&lpr_diag, "C_DIAG")?;
@@ -317,8 +431,14 @@ pub fn integrate_lpr2_components(
     };
 
     // Map diagnoses by record number
-    let diagnoses_by_recnum =
-        map_diagnoses_by_recnum(&diag_recnum_array, &diag_array, &diag_type_array);
+    let diagnoses_by_recnum = map_diagnoses_by_recnum(
+        &diag_recnum_array,
+        &diag_array,
+        &diag_type_array,
+        None,
+        None,
+        filter,
+    );
 
     // Create a row index by record number for ADM data
     let _recnum_to_row = build_recnum_index(&recnum_array);
@@ -332,7 +452,10 @@ pub fn integrate_lpr2_components(
     let num_chunks = num_rows.div_ceil(CHUNK_SIZE); // Ceiling division
 
     let mut all_batches = Vec::with_capacity(num_chunks);
-    let integrated_schema = create_integrated_schema();
+    let integrated_schema = create_integrated_schema(filter);
+    // LPR2 primary diagnoses are action diagnoses without LPR3 status flags
+    let keep_primary = filter.accepts("A", false, false);
+    let mut unmapped_icd8 = 0usize;
 
     for chunk_idx in 0..num_chunks {
         let start_idx = chunk_idx * CHUNK_SIZE;
@@ -353,11 +476,17 @@ pub fn integrate_lpr2_components(
         let mut secondary_diagnoses_list: Vec<Option<Vec<SecondaryDiagnosis>>> =
             Vec::with_capacity(chunk_size);
         let mut diagnosis_chapters = Vec::with_capacity(chunk_size);
+        let mut primary_icd8_codes: Vec<Option<String>> = Vec::with_capacity(chunk_size);
         let mut admission_dates = Vec::with_capacity(chunk_size);
         let mut discharge_dates = Vec::with_capacity(chunk_size);
         let mut hospital_codes = Vec::with_capacity(chunk_size);
         let mut department_codes = Vec::with_capacity(chunk_size);
         let mut admission_types = Vec::with_capacity(chunk_size);
+        let mut admission_times: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut discharge_times: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut course_ids: Vec<Option<String>> = Vec::with_capacity(chunk_size);
+        let mut bed_days: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut emergencies: Vec<Option<bool>> = Vec::with_capacity(chunk_size);
 
         for i in start_idx..end_idx {
             // Get record number for this row
@@ -375,28 +504,54 @@ pub fn integrate_lpr2_components(
                 Some(pnr_array.value(i).to_string())
             });
 
-            // Add primary diagnosis (normalize if non-null)
-            primary_diagnoses.push(if primary_diag_array.is_null(i) {
-                None
-            } else {
-                let primary_diag = primary_diag_array.value(i);
-                normalize_diagnosis_code(primary_diag)
-                    .map(|norm| norm.full_code)
-                    .or(Some(primary_diag.to_string()))
-            });
+            // Add primary diagnosis (harmonised to ICD-10 if non-null and kept by the
+            // filter; contacts before 1994 are coded in ICD-8)
+            let contact_date = (!adm_date_date32.is_null(i))
+                .then(|| date_utils::days_since_epoch_to_date(adm_date_date32.value(i)));
+            let primary_diag = (!primary_diag_array.is_null(i) && keep_primary)
+                .then(|| primary_diag_array.value(i));
+            let harmonized =
+                primary_diag.and_then(|code| harmonize_diagnosis_code(code, contact_date));
+            primary_diagnoses.push(primary_diag.and_then(|code| match &harmonized {
+                Some(h) => {
+                    let icd10 = h.icd10_code();
+                    unmapped_icd8 += usize::from(icd10.is_none());
+                    icd10.map(str::to_string)
+                }
+                None => Some(code.to_string()),
+            }));
 
             // Add diagnosis chapter (based on primary diagnosis)
-            diagnosis_chapters.push(if primary_diag_array.is_null(i) {
-                None
-            } else {
-                let primary_diag = primary_diag_array.value(i);
-                get_diagnosis_chapter(primary_diag)
-            });
+            diagnosis_chapters.push(
+                harmonized
+                    .as_ref()
+                    .and_then(HarmonizedDiagnosis::chapter)
+                    .map(|chapter| chapter.description().to_string()),
+            );
+            primary_icd8_codes.push(harmonized.and_then(|h| h.icd8_code));
 
             // Add secondary diagnoses from LPR_DIAG
             if let Some(diagnoses) = diagnoses_by_recnum.get(&recnum) {
-                // Process and convert secondary diagnoses
-                let sec_diagnoses = process_secondary_diagnoses(diagnoses);
+                // Map ICD-8 secondary diagnoses to ICD-10, then process and convert them
+                let diagnoses: Vec<(String, String)> = diagnoses
+                    .iter()
+                    .filter_map(|(code, diag_type)| {
+                        let code = match harmonize_diagnosis_code(code, contact_date)
+                            .filter(|h| h.system == CodeSystem::Icd8)
+                        {
+                            Some(h) => {
+                                let Some(icd10) = h.icd10_code() else {
+                                    unmapped_icd8 += 1;
+                                    return None;
+                                };
+                                icd10.to_string()
+                            }
+                            None => code.clone(),
+                        };
+                        Some((code, diag_type.clone()))
+                    })
+                    .collect();
+                let sec_diagnoses = process_secondary_diagnoses(&diagnoses);
                 if sec_diagnoses.is_empty() {
                     secondary_diagnoses_list.push(None);
                 } else {
@@ -439,18 +594,43 @@ pub fn integrate_lpr2_components(
             } else {
                 Some(pat_type_array.value(i).to_string())
             });
+
+            // Add contact timing, bed days and acute admission flag
+            admission_times.push(primitive_value(adm_hour_array.as_ref(), i).map(|hour| {
+                hour * 3600 + primitive_value(adm_minute_array.as_ref(), i).unwrap_or(0) * 60
+            }));
+            discharge_times
+                .push(primitive_value(disch_hour_array.as_ref(), i).map(|hour| hour * 3600));
+            course_ids.push(None);
+            bed_days.push(primitive_value(bed_days_array.as_ref(), i));
+            emergencies.push(
+                admission_mode_array
+                    .as_ref()
+                    .filter(|array| !array.is_null(i))
+                    .map(|array| array.value(i).trim() == "1"),
+            );
         }
 
         // Create Arrow arrays for this chunk
         let patient_id_array = StringArray::from(patient_ids);
+        let diag_description_array: StringArray = primary_diagnoses
+            .iter()
+            .map(|diagnosis| diagnosis.as_deref().and_then(get_diagnosis_description))
+            .collect();
         let primary_diag_array = StringArray::from(primary_diagnoses);
         let sec_diag_array = create_secondary_diagnoses_array(&secondary_diagnoses_list);
         let diag_chapter_array = StringArray::from(diagnosis_chapters);
+        let primary_icd8_array = StringArray::from(primary_icd8_codes);
         let adm_date_array = Date32Array::from(admission_dates);
         let disch_date_array = Date32Array::from(discharge_dates);
         let hospital_array = StringArray::from(hospital_codes);
         let dept_array = StringArray::from(department_codes);
         let adm_type_array = StringArray::from(admission_types);
+        let adm_time_array = Time32SecondArray::from(admission_times);
+        let disch_time_array = Time32SecondArray::from(discharge_times);
+        let course_id_array = StringArray::from(course_ids);
+        let bed_days_array = Int32Array::from(bed_days);
+        let emergency_array = BooleanArray::from(emergencies);
 
         // Create batch for this chunk
         let chunk_batch = RecordBatch::try_new(
@@ -460,11 +640,18 @@ pub fn integrate_lpr2_components(
                 Arc::new(primary_diag_array),
                 sec_diag_array,
                 Arc::new(diag_chapter_array),
+                Arc::new(diag_description_array),
+                Arc::new(primary_icd8_array),
                 Arc::new(adm_date_array),
                 Arc::new(disch_date_array),
                 Arc::new(hospital_array),
                 Arc::new(dept_array),
                 Arc::new(adm_type_array),
+                Arc::new(adm_time_array),
+                Arc::new(disch_time_array),
+                Arc::new(course_id_array),
+                Arc::new(bed_days_array),
+                Arc::new(emergency_array),
             ],
         )
         .map_err(|e| {
@@ -480,6 +667,13 @@ pub fn integrate_lpr2_components(
         std::mem::drop(secondary_diagnoses_list);
     }
 
+    if unmapped_icd8 > 0 {
+        log::warn!(
+            "{unmapped_icd8} ICD-8 diagnoses have no ICD-10 mapping; primary diagnoses are \
+             kept in primary_diagnosis_icd8 only and secondary diagnoses are left out"
+        );
+    }
+
     // Combine all chunks into a single batch
     if all_batches.is_empty() {
         return Err(IdsError::Data("No valid chunks were created".to_string()));
@@ -500,6 +694,7 @@ pub fn integrate_lpr2_components(
 pub fn integrate_lpr3_components(
     lpr3_kontakter: &[RecordBatch],
     lpr3_diagnoser: &[RecordBatch],
+    filter: &DiagnosisFilter,
 ) -> Result<RecordBatch> {
     // First merge all batches
     let lpr3_kontakter = merge_batches(lpr3_kontakter, "LPR3_KONTAKTER")?;
@@ -538,14 +733,33 @@ pub fn integrate_lpr3_components(
     let afdeling_array = get_string_column(&lpr3_kontakter, "afdeling")?;
     let kontakttype_array = get_string_column(&lpr3_kontakter, "kontakttype")?;
 
+    // Optional contact timing, course and priority (ATA1 = acute) columns
+    let start_time_array =
+        get_optional_primitive_column::<Time32SecondType>(&lpr3_kontakter, "tidspunkt_start")?;
+    let end_time_array =
+        get_optional_primitive_column::<Time32SecondType>(&lpr3_kontakter, "tidspunkt_slut")?;
+    let course_array = get_optional_string_column(&lpr3_kontakter, "DW_EK_FORLOEB")?;
+    let priority_array = get_optional_string_column(&lpr3_kontakter, "prioritet")?;
+
     // Get diagnosis data from LPR3_DIAGNOSER
     let diag_kontakt_id_array = get_string_column(&lpr3_diagnoser, "kontakt_id")?;
     let diag_array = get_string_column(&lpr3_diagnoser, "diagnosekode")?;
-    let diag_type_array = get_string_column(&lpr3_diagnoser, "diagnose_type")?;
+    let diag_type_array = match get_optional_string_column(&lpr3_diagnoser, "diagnose_type")? {
+        Some(array) => array,
+        None => get_string_column(&lpr3_diagnoser, "diagnosetype")?,
+    };
+    let disproven_array = get_optional_string_column(&lpr3_diagnoser, "senere_afkraeftet")?;
+    let parent_array = get_optional_string_column(&lpr3_diagnoser, "diagnosekode_parent")?;
 
     // Map diagnoses by contact ID (equivalent to record number in LPR2)
-    let diagnoses_by_kontakt_id =
-        map_diagnoses_by_recnum(&diag_kontakt_id_array, &diag_array, &diag_type_array);
+    let diagnoses_by_kontakt_id = map_diagnoses_by_recnum(
+        &diag_kontakt_id_array,
+        &diag_array,
+        &diag_type_array,
+        disproven_array.as_deref(),
+        parent_array.as_deref(),
+        filter,
+    );
 
     // Create a row index by contact ID for KONTAKTER data
     let _kontakt_id_to_row = build_recnum_index(&kontakt_id_array);
@@ -559,7 +773,7 @@ pub fn integrate_lpr3_components(
     let num_chunks = num_rows.div_ceil(CHUNK_SIZE); // Ceiling division
 
     let mut all_batches = Vec::with_capacity(num_chunks);
-    let integrated_schema = create_integrated_schema();
+    let integrated_schema = create_integrated_schema(filter);
 
     for chunk_idx in 0..num_chunks {
         let start_idx = chunk_idx * CHUNK_SIZE;
@@ -580,11 +794,17 @@ pub fn integrate_lpr3_components(
         let mut secondary_diagnoses_list: Vec<Option<Vec<SecondaryDiagnosis>>> =
             Vec::with_capacity(chunk_size);
         let mut diagnosis_chapters = Vec::with_capacity(chunk_size);
+        let mut primary_icd8_codes: Vec<Option<String>> = Vec::with_capacity(chunk_size);
         let mut admission_dates = Vec::with_capacity(chunk_size);
         let mut discharge_dates = Vec::with_capacity(chunk_size);
         let mut hospital_codes = Vec::with_capacity(chunk_size);
         let mut department_codes = Vec::with_capacity(chunk_size);
         let mut admission_types = Vec::with_capacity(chunk_size);
+        let mut admission_times: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut discharge_times: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut course_ids: Vec<Option<String>> = Vec::with_capacity(chunk_size);
+        let mut bed_days: Vec<Option<i32>> = Vec::with_capacity(chunk_size);
+        let mut emergencies: Vec<Option<bool>> = Vec::with_capacity(chunk_size);
 
         for i in start_idx..end_idx {
             // Get contact ID for this row
@@ -617,10 +837,12 @@ pub fn integrate_lpr3_components(
 
                     // Add diagnosis chapter based on primary diagnosis
                     diagnosis_chapters.push(get_diagnosis_chapter(diag));
+                    primary_icd8_codes.push(None);
                 } else {
                     // No primary diagnosis found
                     primary_diagnoses.push(None);
                     diagnosis_chapters.push(None);
+                    primary_icd8_codes.push(None);
                 }
 
                 // Process and convert secondary diagnoses
@@ -634,6 +856,7 @@ pub fn integrate_lpr3_components(
                 // No diagnoses for this contact
                 primary_diagnoses.push(None);
                 diagnosis_chapters.push(None);
+                primary_icd8_codes.push(None);
                 secondary_diagnoses_list.push(None);
             }
 
@@ -670,18 +893,45 @@ pub fn integrate_lpr3_components(
             } else {
                 Some(kontakttype_array.value(i).to_string())
             });
+
+            // Add contact timing, course and acute contact flag
+            admission_times.push(primitive_value(start_time_array.as_ref(), i));
+            discharge_times.push(primitive_value(end_time_array.as_ref(), i));
+            course_ids.push(
+                course_array
+                    .as_ref()
+                    .filter(|array| !array.is_null(i))
+                    .map(|array| array.value(i).to_string()),
+            );
+            bed_days.push(None);
+            emergencies.push(
+                priority_array
+                    .as_ref()
+                    .filter(|array| !array.is_null(i))
+                    .map(|array| array.value(i).trim().eq_ignore_ascii_case("ATA1")),
+            );
         }
 
         // Create Arrow arrays for this chunk
         let patient_id_array = StringArray::from(patient_ids);
+        let diag_description_array: StringArray = primary_diagnoses
+            .iter()
+            .map(|diagnosis| diagnosis.as_deref().and_then(get_diagnosis_description))
+            .collect();
         let primary_diag_array = StringArray::from(primary_diagnoses);
         let sec_diag_array = create_secondary_diagnoses_array(&secondary_diagnoses_list);
         let diag_chapter_array = StringArray::from(diagnosis_chapters);
+        let primary_icd8_array = StringArray::from(primary_icd8_codes);
         let adm_date_array = Date32Array::from(admission_dates);
         let disch_date_array = Date32Array::from(discharge_dates);
         let hospital_array = StringArray::from(hospital_codes);
         let dept_array = StringArray::from(department_codes);
         let adm_type_array = StringArray::from(admission_types);
+        let adm_time_array = Time32SecondArray::from(admission_times);
+        let disch_time_array = Time32SecondArray::from(discharge_times);
+        let course_id_array = StringArray::from(course_ids);
+        let bed_days_array = Int32Array::from(bed_days);
+        let emergency_array = BooleanArray::from(emergencies);
 
         // Create batch for this chunk
         let chunk_batch = RecordBatch::try_new(
@@ -691,11 +941,18 @@ pub fn integrate_lpr3_components(
                 Arc::new(primary_diag_array),
                 sec_diag_array,
                 Arc::new(diag_chapter_array),
+                Arc::new(diag_description_array),
+                Arc::new(primary_icd8_array),
                 Arc::new(adm_date_array),
                 Arc::new(disch_date_array),
                 Arc::new(hospital_array),
                 Arc::new(dept_array),
                 Arc::new(adm_type_array),
+                Arc::new(adm_time_array),
+                Arc::new(disch_time_array),
+                Arc::new(course_id_array),
+                Arc::new(bed_days_array),
+                Arc::new(emergency_array),
             ],
         )
         .map_err(|e| {
@@ -732,6 +989,200 @@ pub fn integrate_lpr3_components(
     Ok(integrated_batch)
 }
 
+/// Create the schema for the harmonised procedure table
+///
+/// One row per procedure: LPR2 `LPR_BES` operation codes (`C_OPR`) and LPR3
+/// `procedurer` codes (`procedurekode`) as SKS codes, linked to the patient through
+/// the contact they were registered on.
+#[must_use]
+pub fn create_procedure_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("patient_id", DataType::Utf8, true),
+        Field::new("procedure_code", DataType::Utf8, true),
+        Field::new("procedure_type", DataType::Utf8, true),
+        Field::new("procedure_date", DataType::Date32, true),
+        Field::new("source", DataType::Utf8, false),
+    ])
+}
+
+/// Extracts an optional date column as `Date32`, returning `None` if the column is absent
+fn get_optional_date_column(batch: &RecordBatch, column_name: &str) -> Result<Option<Date32Array>> {
+    match batch.column_by_name(column_name) {
+        Some(column) => date_utils::convert_to_date32_array(column.as_ref()).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Build a procedure table batch from the collected values
+fn build_procedure_batch(
+    patient_ids: Vec<Option<String>>,
+    codes: Vec<Option<String>>,
+    types: Vec<Option<String>>,
+    dates: Vec<Option<i32>>,
+    source: &str,
+) -> Result<RecordBatch> {
+    let sources = vec![source; patient_ids.len()];
+    RecordBatch::try_new(
+        Arc::new(create_procedure_schema()),
+        vec![
+            Arc::new(StringArray::from(patient_ids)),
+            Arc::new(StringArray::from(codes)),
+            Arc::new(StringArray::from(types)),
+            Arc::new(Date32Array::from(dates)),
+            Arc::new(StringArray::from(sources)),
+        ],
+    )
+    .map_err(|e| IdsError::Data(format!("Failed to create {source} procedure batch: {e}")))
+}
+
+/// Integrate LPR2 procedures from `LPR_BES`, linked to patients through `LPR_ADM`
+///
+/// Uses the operation code `C_OPR`, operation type `C_OPRART` and operation date
+/// `D_ODTO`, falling back to the outpatient visit date `D_AMBDTO` and then the admission
+/// date. Returns `None` if the `LPR_BES` data has no `C_OPR` column.
+pub fn integrate_lpr2_procedures(
+    lpr_adm: &[RecordBatch],
+    lpr_bes: &[RecordBatch],
+) -> Result<Option<RecordBatch>> {
+    let lpr_adm = merge_batches(lpr_adm, "LPR_ADM")?;
+    let lpr_bes = merge_batches(lpr_bes, "LPR_BES")?;
+
+    let Some(code_array) = get_optional_string_column(&lpr_bes, "C_OPR")? else {
+        log::info!("LPR_BES has no C_OPR column; no LPR2 procedures available");
+        return Ok(None);
+    };
+    let bes_recnum_array = get_string_column(&lpr_bes, "RECNUM")?;
+    let type_array = get_optional_string_column(&lpr_bes, "C_OPRART")?;
+    let operation_dates = get_optional_date_column(&lpr_bes, "D_ODTO")?;
+    let visit_dates = get_optional_date_column(&lpr_bes, "D_AMBDTO")?;
+
+    // Patient and admission date by record number
+    let recnum_array = get_string_column(&lpr_adm, "RECNUM")?;
+    let pnr_array = get_string_column(&lpr_adm, "PNR")?;
+    let adm_dates = get_optional_date_column(&lpr_adm, "D_INDDTO")?;
+    let recnum_to_row = build_recnum_index(&recnum_array);
+
+    let num_rows = lpr_bes.num_rows();
+    let mut patient_ids = Vec::with_capacity(num_rows);
+    let mut codes = Vec::with_capacity(num_rows);
+    let mut types = Vec::with_capacity(num_rows);
+    let mut dates = Vec::with_capacity(num_rows);
+    let mut unlinked = 0usize;
+
+    for i in 0..num_rows {
+        if bes_recnum_array.is_null(i) || code_array.is_null(i) {
+            continue;
+        }
+        let Some(&adm_row) = recnum_to_row.get(bes_recnum_array.value(i)) else {
+            unlinked += 1;
+            continue;
+        };
+        if pnr_array.is_null(adm_row) {
+            continue;
+        }
+
+        let date_at = |array: &Option<Date32Array>, row: usize| {
+            array
+                .as_ref()
+                .filter(|array| !array.is_null(row))
+                .map(|array| array.value(row))
+        };
+        patient_ids.push(Some(pnr_array.value(adm_row).to_string()));
+        codes.push(Some(code_array.value(i).trim().to_string()));
+        types.push(
+            type_array
+                .as_ref()
+                .filter(|array| !array.is_null(i))
+                .map(|array| array.value(i).to_string()),
+        );
+        dates.push(
+            date_at(&operation_dates, i)
+                .or_else(|| date_at(&visit_dates, i))
+                .or_else(|| date_at(&adm_dates, adm_row)),
+        );
+    }
+
+    if unlinked > 0 {
+        log::warn!("{unlinked} LPR_BES procedures have no matching LPR_ADM record");
+    }
+    log::info!("Harmonised {} LPR2 procedures", patient_ids.len());
+
+    build_procedure_batch(patient_ids, codes, types, dates, "LPR2").map(Some)
+}
+
+/// Integrate LPR3 procedures from `procedurer`, linked to patients through `kontakter`
+///
+/// Uses `procedurekode`, `proceduretype` and `dato_procedure`, falling back to the
+/// contact start date.
+pub fn integrate_lpr3_procedures(
+    lpr3_kontakter: &[RecordBatch],
+    lpr3_procedurer: &[RecordBatch],
+) -> Result<RecordBatch> {
+    let lpr3_kontakter = merge_batches(lpr3_kontakter, "LPR3_KONTAKTER")?;
+    let lpr3_procedurer = merge_batches(lpr3_procedurer, "LPR3_PROCEDURER")?;
+
+    let kontakt_id_array = get_string_column(&lpr3_kontakter, "kontakt_id")?;
+    let pnr_array = get_string_column(&lpr3_kontakter, "cpr")?;
+    let start_dates = get_optional_date_column(&lpr3_kontakter, "starttidspunkt")?;
+    let kontakt_id_to_row = build_recnum_index(&kontakt_id_array);
+
+    let proc_kontakt_id_array = match get_optional_string_column(&lpr3_procedurer, "kontakt_id")? {
+        Some(array) => array,
+        None => get_string_column(&lpr3_procedurer, "DW_EK_KONTAKT")?,
+    };
+    let code_array = get_string_column(&lpr3_procedurer, "procedurekode")?;
+    let type_array = get_optional_string_column(&lpr3_procedurer, "proceduretype")?;
+    let procedure_dates = get_optional_date_column(&lpr3_procedurer, "dato_procedure")?;
+
+    let num_rows = lpr3_procedurer.num_rows();
+    let mut patient_ids = Vec::with_capacity(num_rows);
+    let mut codes = Vec::with_capacity(num_rows);
+    let mut types = Vec::with_capacity(num_rows);
+    let mut dates = Vec::with_capacity(num_rows);
+    let mut unlinked = 0usize;
+
+    for i in 0..num_rows {
+        if proc_kontakt_id_array.is_null(i) || code_array.is_null(i) {
+            continue;
+        }
+        let Some(&contact_row) = kontakt_id_to_row.get(proc_kontakt_id_array.value(i)) else {
+            unlinked += 1;
+            continue;
+        };
+        if pnr_array.is_null(contact_row) {
+            continue;
+        }
+
+        patient_ids.push(Some(pnr_array.value(contact_row).to_string()));
+        codes.push(Some(code_array.value(i).trim().to_string()));
+        types.push(
+            type_array
+                .as_ref()
+                .filter(|array| !array.is_null(i))
+                .map(|array| array.value(i).to_string()),
+        );
+        dates.push(
+            procedure_dates
+                .as_ref()
+                .filter(|array| !array.is_null(i))
+                .map(|array| array.value(i))
+                .or_else(|| {
+                    start_dates
+                        .as_ref()
+                        .filter(|array| !array.is_null(contact_row))
+                        .map(|array| array.value(contact_row))
+                }),
+        );
+    }
+
+    if unlinked > 0 {
+        log::warn!("{unlinked} LPR3 procedures have no matching contact");
+    }
+    log::info!("Harmonised {} LPR3 procedures", patient_ids.len());
+
+    build_procedure_batch(patient_ids, codes, types, dates, "LPR3")
+}
+
 /// Combine harmonized LPR2 and LPR3 data
 pub fn combine_harmonized_data(
     lpr2_data: Option<RecordBatch>,
@@ -760,18 +1211,22 @@ pub fn combine_harmonized_data(
     }
 }
 
-/// Apply date filtering to health data
-fn apply_date_filtering(batch: &RecordBatch, config: &LprConfig) -> Result<RecordBatch> {
+/// Apply date filtering to health data on the given date column
+pub(crate) fn apply_date_filtering(
+    batch: &RecordBatch,
+    config: &LprConfig,
+    date_column: &str,
+) -> Result<RecordBatch> {
     if config.start_date.is_none() && config.end_date.is_none() {
         // No date filtering needed
         return Ok(batch.clone());
     }
 
-    // Get admission date column
+    // Get the date column
     let adm_date_idx = batch
         .schema()
-        .index_of("admission_date")
-        .map_err(|e| IdsError::Data(format!("admission_date column not found: {e}")))?;
+        .index_of(date_column)
+        .map_err(|e| IdsError::Data(format!("{date_column} column not found: {e}")))?;
     let adm_date_array = batch.column(adm_date_idx);
 
     // Create mask for date filtering
@@ -825,6 +1280,8 @@ fn apply_date_filtering(batch: &RecordBatch, config: &LprConfig) -> Result<Recor
 }
 
 /// Process LPR data from LPR2 and/or LPR3 sources
+///
+/// All components and the result are held in memory; see the [module docs](self).
 pub fn process_lpr_data(
     lpr2_adm: Option<&[RecordBatch]>,
     lpr2_diag: Option<&[RecordBatch]>,
@@ -840,6 +1297,7 @@ pub fn process_lpr_data(
             lpr2_adm.unwrap(),
             lpr2_diag.unwrap(),
             lpr2_bes,
+            &config.diagnosis_filter,
         )?)
     } else {
         None
@@ -850,6 +1308,7 @@ pub fn process_lpr_data(
         Some(integrate_lpr3_components(
             lpr3_kontakter.unwrap(),
             lpr3_diagnoser.unwrap(),
+            &config.diagnosis_filter,
         )?)
     } else {
         None
@@ -859,7 +1318,7 @@ pub fn process_lpr_data(
     let combined_data = combine_harmonized_data(lpr2_data, lpr3_data)?;
 
     // Apply date filtering if needed
-    let filtered_data = apply_date_filtering(&combined_data, config)?;
+    let filtered_data = apply_date_filtering(&combined_data, config, "admission_date")?;
 
     Ok(filtered_data)
 }
@@ -884,6 +1343,7 @@ pub fn process_lpr_components(
             lpr2_adm,
             lpr2_diag,
             lpr2_bes,
+            &config.diagnosis_filter,
         )?)
     } else {
         None
@@ -899,6 +1359,7 @@ pub fn process_lpr_components(
         Some(integrate_lpr3_components(
             lpr3_kontakter,
             lpr3_diagnoser,
+            &config.diagnosis_filter,
         )?)
     } else {
         None
@@ -908,11 +1369,59 @@ pub fn process_lpr_components(
     let combined_data = combine_harmonized_data(lpr2_data, lpr3_data)?;
 
     // Apply date filtering if needed
-    let filtered_data = apply_date_filtering(&combined_data, config)?;
+    let filtered_data = apply_date_filtering(&combined_data, config, "admission_date")?;
 
     Ok(filtered_data)
 }
 
+/// Process harmonised procedure records from LPR2 and/or LPR3 sources
+///
+/// LPR2 procedures need `LPR_ADM` and `LPR_BES`, LPR3 procedures need `kontakter` and
+/// `procedurer`. Returns `None` if no procedure data is available.
+pub fn process_lpr_procedure_data(
+    lpr2_adm: Option<&[RecordBatch]>,
+    lpr2_bes: Option<&[RecordBatch]>,
+    lpr3_kontakter: Option<&[RecordBatch]>,
+    lpr3_procedurer: Option<&[RecordBatch]>,
+    config: &LprConfig,
+) -> Result<Option<RecordBatch>> {
+    let mut batches = Vec::with_capacity(2);
+
+    if config.include_lpr2 {
+        if let (Some(lpr2_adm), Some(lpr2_bes)) = (lpr2_adm, lpr2_bes) {
+            batches.extend(integrate_lpr2_procedures(lpr2_adm, lpr2_bes)?);
+        }
+    }
+
+    if config.include_lpr3 {
+        if let (Some(lpr3_kontakter), Some(lpr3_procedurer)) = (lpr3_kontakter, lpr3_procedurer) {
+            batches.push(integrate_lpr3_procedures(lpr3_kontakter, lpr3_procedurer)?);
+        }
+    }
+
+    if batches.is_empty() {
+        return Ok(None);
+    }
+
+    let combined = concat_batches(&Arc::new(create_procedure_schema()), &batches)
+        .map_err(|e| IdsError::Data(format!("Failed to combine LPR procedures: {e}")))?;
+    apply_date_filtering(&combined, config, "procedure_date").map(Some)
+}
+
+/// Process harmonised procedure records from LPR components
+pub fn process_lpr_procedures(
+    components: &crate::data::registry::loaders::lpr::LprComponents,
+    config: &LprConfig,
+) -> Result<Option<RecordBatch>> {
+    process_lpr_procedure_data(
+        components.lpr2_adm.as_deref(),
+        components.lpr2_bes.as_deref(),
+        components.lpr3_kontakter.as_deref(),
+        components.lpr3_procedurer.as_deref(),
+        config,
+    )
+}
+
 /// Load and process LPR data from base path
 /// 
 /// This function loads LPR data from the specified base path
@@ -922,6 +1431,16 @@ pub async fn load_and_process_lpr(
     config: &LprConfig,
     pnr_filter: Option<&crate::data::registry::traits::PnrFilter>,
 ) -> Result<RecordBatch> {
+    let components = load_lpr_components(base_path, config, pnr_filter).await?;
+    process_lpr_components(&components, config)
+}
+
+/// Load the LPR2 and LPR3 components enabled in the config from base path
+pub async fn load_lpr_components(
+    base_path: &str,
+    config: &LprConfig,
+    pnr_filter: Option<&crate::data::registry::traits::PnrFilter>,
+) -> Result<crate::data::registry::loaders::lpr::LprComponents> {
     // Create registry loaders
     let lpr2_loader = crate::data::registry::loaders::lpr::Lpr2Register;
     let lpr3_loader = crate::data::registry::loaders::lpr::Lpr3Register;
@@ -974,6 +1493,123 @@ pub async fn load_and_process_lpr(
         }
     }
     
-    // Process the components
-    process_lpr_components(&components, config)
+    Ok(components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ListArray, StructArray};
+
+    fn strings(values: &[Option<&str>]) -> Arc<StringArray> {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> &'a StringArray {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+    }
+
+    /// Secondary diagnosis codes of a row
+    fn secondary_codes(batch: &RecordBatch, row: usize) -> Vec<String> {
+        let list = batch
+            .column_by_name("secondary_diagnoses")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap();
+        if list.is_null(row) {
+            return Vec::new();
+        }
+        let entries = list.value(row);
+        let codes = entries
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap()
+            .column_by_name("code")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .iter()
+            .flatten()
+            .map(String::from)
+            .collect();
+        codes
+    }
+
+    /// LPR2 contacts in 1985 (ICD-8) and 2000 (ICD-10) with their diagnoses
+    fn lpr2_batches() -> (RecordBatch, RecordBatch) {
+        let days = |y, m, d| {
+            date_utils::date_to_days_since_epoch(NaiveDate::from_ymd_opt(y, m, d).unwrap())
+        };
+        let recnums = [Some("r1"), Some("r2"), Some("r3")];
+        let adm = RecordBatch::try_from_iter(vec![
+            ("RECNUM", strings(&recnums) as _),
+            ("PNR", strings(&[Some("p1"), Some("p2"), Some("p3")]) as _),
+            ("C_ADIAG", strings(&[Some("29509"), Some("79699"), Some("DF200")]) as _),
+            ("C_SGH", strings(&[Some("1301"); 3]) as _),
+            ("C_AFD", strings(&[Some("011"); 3]) as _),
+            ("C_PATTYPE", strings(&[Some("0"); 3]) as _),
+            (
+                "D_INDDTO",
+                Arc::new(Date32Array::from(vec![
+                    days(1985, 3, 1),
+                    days(1985, 3, 1),
+                    days(2000, 3, 1),
+                ])) as _,
+            ),
+            (
+                "D_UDDTO",
+                Arc::new(Date32Array::from(vec![
+                    days(1985, 3, 5),
+                    days(1985, 3, 5),
+                    days(2000, 3, 5),
+                ])) as _,
+            ),
+        ])
+        .unwrap();
+        let diag = RecordBatch::try_from_iter(vec![
+            ("RECNUM", strings(&[Some("r1"), Some("r1"), Some("r3")]) as _),
+            ("C_DIAG", strings(&[Some("29639"), Some("79699"), Some("DJ449")]) as _),
+            ("C_DIAGTYPE", strings(&[Some("B"), Some("B"), Some("B")]) as _),
+        ])
+        .unwrap();
+        (adm, diag)
+    }
+
+    #[test]
+    fn test_lpr2_icd8_diagnoses_are_harmonised() {
+        let (adm, diag) = lpr2_batches();
+        let integrated =
+            integrate_lpr2_components(&[adm], &[diag], None, &DiagnosisFilter::keep_all())
+                .unwrap();
+        assert_eq!(integrated.num_rows(), 3);
+
+        let primary = string_column(&integrated, "primary_diagnosis");
+        let icd8 = string_column(&integrated, "primary_diagnosis_icd8");
+        let chapter = string_column(&integrated, "diagnosis_chapter");
+
+        // Mapped ICD-8 code: ICD-10 in primary_diagnosis, registered code kept
+        assert_eq!(primary.value(0), "F20");
+        assert_eq!(icd8.value(0), "29509");
+        assert_eq!(secondary_codes(&integrated, 0), vec!["F31"]);
+
+        // Unmapped ICD-8 code: only in primary_diagnosis_icd8, chapter from ICD-8
+        assert!(primary.is_null(1));
+        assert_eq!(icd8.value(1), "79699");
+        assert_eq!(
+            chapter.value(1),
+            Icd10Chapter::SymptomsSignsAbnormalities.description()
+        );
+
+        // ICD-10 contact: no ICD-8 code
+        assert_eq!(primary.value(2), "F200");
+        assert!(icd8.is_null(2));
+        assert_eq!(secondary_codes(&integrated, 2), vec!["DJ449"]);
+    }
 }
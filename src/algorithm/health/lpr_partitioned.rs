@@ -0,0 +1,513 @@
+//! Out-of-core harmonisation of LPR data
+//!
+//! LPR2 and LPR3 for the whole population do not fit in memory. [`partition_lpr`]
+//! harmonises them like a grace hash join:
+//!
+//! 1. each component is streamed from Parquet and hash-partitioned on its contact key
+//!    (`RECNUM` for LPR2, `kontakt_id` for LPR3), spilling to disk;
+//! 2. matching contact partitions are integrated one at a time, and the harmonised
+//!    diagnoses and procedures are partitioned again on `patient_id`.
+//!
+//! All records of a patient end up in the same partition of [`PartitionedLpr`], so
+//! per-patient algorithms such as the SCD algorithm can process one partition at a time.
+//! The number of partitions follows from the size of the input and the memory limit of
+//! the [execution configuration](crate::data::io::ExecutionConfig); without a limit
+//! there is a single in-memory partition.
+//!
+//! [`partition_lpr_cached`] keeps the harmonised data in the
+//! [result cache](crate::data::cache), so the SCD, population SCD and phenotype commands
+//! harmonise the same LPR data only once.
+
+use arrow::array::{Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::prelude::ParquetReadOptions;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::algorithm::health::lpr::{
+    apply_date_filtering, create_integrated_schema, create_procedure_schema,
+    integrate_lpr2_components, integrate_lpr2_procedures, integrate_lpr3_components,
+    integrate_lpr3_procedures, LprConfig,
+};
+use crate::data::cache::{read_entry_file, CacheKey, Cacheable, ResultCache};
+use crate::data::filter::pnr::semi_join_values;
+use crate::data::io::execution::{execution_config, session_context, ExecutionConfig};
+use crate::data::io::spill::SpillPartitions;
+use crate::data::io::ParquetBatchWriter;
+use crate::data::pruning::register_indexed_parquet;
+use crate::data::registry::loaders::lpr::{find_lpr_files, find_parquet_files_in_dir};
+use crate::data::registry::traits::PnrFilter;
+use crate::error::{IdsError, Result};
+use crate::model::icd10::Icd10Dictionary;
+use crate::model::icd8::Icd8Crosswalk;
+
+/// Harmonised LPR diagnoses and procedures, partitioned by patient
+pub struct PartitionedLpr {
+    diagnoses: SpillPartitions,
+    procedures: SpillPartitions,
+    has_procedures: bool,
+}
+
+impl PartitionedLpr {
+    /// Number of partitions
+    #[must_use]
+    pub fn partitions(&self) -> usize {
+        self.diagnoses.partitions()
+    }
+
+    /// Partition holding the records of a patient
+    #[must_use]
+    pub fn partition_of(&self, patient_id: &str) -> usize {
+        self.diagnoses.partition_of(patient_id)
+    }
+
+    /// Total number of harmonised diagnosis records
+    #[must_use]
+    pub fn num_diagnoses(&self) -> usize {
+        self.diagnoses.num_rows()
+    }
+
+    /// Total number of harmonised procedure records
+    #[must_use]
+    pub fn num_procedures(&self) -> usize {
+        self.procedures.num_rows()
+    }
+
+    /// Schema of the harmonised diagnoses
+    #[must_use]
+    pub fn diagnosis_schema(&self) -> SchemaRef {
+        self.diagnoses.schema()
+    }
+
+    /// Schema of the harmonised procedures
+    #[must_use]
+    pub fn procedure_schema(&self) -> SchemaRef {
+        self.procedures.schema()
+    }
+
+    /// Whether any procedure data was harmonised
+    #[must_use]
+    pub fn has_procedures(&self) -> bool {
+        self.has_procedures
+    }
+
+    /// Harmonised diagnoses of one partition
+    pub fn diagnoses(&self, partition: usize) -> Result<RecordBatch> {
+        self.diagnoses.read_batch(partition)
+    }
+
+    /// Harmonised procedures of one partition; `None` if no procedure data is available
+    pub fn procedures(&self, partition: usize) -> Result<Option<RecordBatch>> {
+        if !self.has_procedures {
+            return Ok(None);
+        }
+        self.procedures.read_batch(partition).map(Some)
+    }
+}
+
+impl Cacheable for PartitionedLpr {
+    fn write_entry(&self, dir: &Path) -> Result<()> {
+        let mut diagnoses =
+            ParquetBatchWriter::create(dir.join("diagnoses.parquet"), self.diagnosis_schema())?;
+        let mut procedures = if self.has_procedures {
+            Some(ParquetBatchWriter::create(
+                dir.join("procedures.parquet"),
+                self.procedure_schema(),
+            )?)
+        } else {
+            None
+        };
+        for partition in 0..self.partitions() {
+            diagnoses.write(&self.diagnoses(partition)?)?;
+            if let (Some(writer), Some(batch)) = (&mut procedures, self.procedures(partition)?) {
+                writer.write(&batch)?;
+            }
+        }
+        diagnoses.finish()?;
+        procedures.map(ParquetBatchWriter::finish).transpose()?;
+        Ok(())
+    }
+
+    /// Partition the cached data again, for the current memory limit
+    fn read_entry(dir: &Path) -> Result<Self> {
+        let execution = execution_config();
+        let diagnoses_path = dir.join("diagnoses.parquet");
+        let procedures_path = dir.join("procedures.parquet");
+        let has_procedures = procedures_path.is_file();
+        let mut input_bytes = std::fs::metadata(&diagnoses_path)?.len();
+        if has_procedures {
+            input_bytes += std::fs::metadata(&procedures_path)?.len();
+        }
+        let partitions = execution.partitions_for(input_bytes);
+
+        let repartition = |path: &Path| -> Result<SpillPartitions> {
+            let reader = read_entry_file(path)?;
+            let mut output = SpillPartitions::new(
+                arrow::record_batch::RecordBatchReader::schema(&reader),
+                "patient_id",
+                partitions,
+                &execution,
+            )?;
+            for batch in reader {
+                output.push(&batch?)?;
+            }
+            Ok(output)
+        };
+        let diagnoses = repartition(&diagnoses_path)?;
+        let procedures = if has_procedures {
+            repartition(&procedures_path)?
+        } else {
+            SpillPartitions::new(
+                Arc::new(create_procedure_schema()),
+                "patient_id",
+                partitions,
+                &execution,
+            )?
+        };
+        Ok(Self {
+            diagnoses,
+            procedures,
+            has_procedures,
+        })
+    }
+}
+
+/// [`partition_lpr`], reusing harmonised data from the result cache
+pub async fn partition_lpr_cached(
+    base_path: &str,
+    config: &LprConfig,
+    pnr_filter: Option<&PnrFilter>,
+    cache: &ResultCache,
+) -> Result<PartitionedLpr> {
+    if !cache.is_enabled() {
+        return partition_lpr(base_path, config, pnr_filter).await;
+    }
+
+    let key = lpr_cache_key(base_path, config, pnr_filter)?;
+    if let Some(lpr) = cache.get(&key) {
+        return Ok(lpr);
+    }
+    let lpr = partition_lpr(base_path, config, pnr_filter).await?;
+    cache.put(&key, &lpr);
+    Ok(lpr)
+}
+
+/// Cache key of the harmonised LPR data below `base_path`
+///
+/// Harmonisation maps ICD-8 codes with the bundled crosswalk and looks up descriptions
+/// in the bundled SKS dump, so both are part of the key.
+pub fn lpr_cache_key(
+    base_path: &str,
+    config: &LprConfig,
+    pnr_filter: Option<&PnrFilter>,
+) -> Result<CacheKey> {
+    let paths = find_lpr_files(base_path)?;
+    let mut key = CacheKey::new("lpr")
+        .with_value("config", config)
+        .with_content("icd8_crosswalk", Icd8Crosswalk::bundled_source())
+        .with_content("sks", Icd10Dictionary::bundled_source());
+    for (name, path) in [
+        ("LPR_ADM", &paths.admin_path),
+        ("LPR_DIAG", &paths.diag_path),
+        ("LPR_BES", &paths.proc_path),
+        ("LPR3_KONTAKTER", &paths.kontakter_path),
+        ("LPR3_DIAGNOSER", &paths.diagnoser_path),
+        ("LPR3_PROCEDURER", &paths.procedurer_path),
+    ] {
+        if let Some(path) = path {
+            key = key.with_files(name, path)?;
+        }
+    }
+    if let Some(filter) = pnr_filter {
+        key = key.with_set("pnr_filter", filter.pnrs().iter().map(String::as_str));
+    }
+    Ok(key)
+}
+
+/// Harmonise the LPR2 and LPR3 data below `base_path` into patient partitions
+///
+/// Only patients in `pnr_filter` are kept, if given. The same diagnosis integration,
+/// procedure integration and date filtering as [`crate::algorithm::lpr::process_lpr_data`]
+/// and [`crate::algorithm::lpr::process_lpr_procedure_data`] are applied.
+pub async fn partition_lpr(
+    base_path: &str,
+    config: &LprConfig,
+    pnr_filter: Option<&PnrFilter>,
+) -> Result<PartitionedLpr> {
+    let paths = find_lpr_files(base_path)?;
+    let execution = execution_config();
+
+    let lpr2 = config.include_lpr2 && paths.admin_path.is_some() && paths.diag_path.is_some();
+    let lpr3 =
+        config.include_lpr3 && paths.kontakter_path.is_some() && paths.diagnoser_path.is_some();
+    if !lpr2 && !lpr3 {
+        return Err(IdsError::Validation("No LPR data provided".to_string()));
+    }
+
+    let mut inputs = Vec::new();
+    if lpr2 {
+        inputs.extend([&paths.admin_path, &paths.diag_path, &paths.proc_path]);
+    }
+    if lpr3 {
+        inputs.extend([
+            &paths.kontakter_path,
+            &paths.diagnoser_path,
+            &paths.procedurer_path,
+        ]);
+    }
+    let input_bytes = inputs
+        .into_iter()
+        .flatten()
+        .map(|path| parquet_size(path))
+        .sum::<Result<u64>>()?;
+    let partitions = execution.partitions_for(input_bytes);
+    log::info!(
+        "Harmonising {} MiB of LPR data in {partitions} partition(s)",
+        input_bytes >> 20
+    );
+
+    let mut output = PartitionedLpr {
+        diagnoses: SpillPartitions::new(
+            Arc::new(create_integrated_schema(&config.diagnosis_filter)),
+            "patient_id",
+            partitions,
+            &execution,
+        )?,
+        procedures: SpillPartitions::new(
+            Arc::new(create_procedure_schema()),
+            "patient_id",
+            partitions,
+            &execution,
+        )?,
+        has_procedures: false,
+    };
+    let components = Components {
+        partitions,
+        execution: &execution,
+        pnr_filter,
+    };
+
+    if let (true, Some(admin_path), Some(diag_path)) = (lpr2, &paths.admin_path, &paths.diag_path) {
+        let (adm, recnums) = components
+            .partition("LPR_ADM", admin_path, &["RECNUM"], Some("PNR"), None)
+            .await?;
+        let diag = components
+            .partition("LPR_DIAG", diag_path, &["RECNUM"], None, recnums.as_ref())
+            .await?
+            .0;
+        let bes = match &paths.proc_path {
+            Some(path) => Some(
+                components
+                    .partition("LPR_BES", path, &["RECNUM"], None, recnums.as_ref())
+                    .await?
+                    .0,
+            ),
+            None => None,
+        };
+        drop(recnums);
+
+        for partition in 0..partitions {
+            let adm = adm.read(partition)?;
+            if adm.iter().all(|batch| batch.num_rows() == 0) {
+                continue;
+            }
+            let diag = diag.read(partition)?;
+            let bes = bes.as_ref().map(|bes| bes.read(partition)).transpose()?;
+
+            let integrated =
+                integrate_lpr2_components(&adm, &diag, bes.as_deref(), &config.diagnosis_filter)?;
+            output.diagnoses.push(&apply_date_filtering(
+                &integrated,
+                config,
+                "admission_date",
+            )?)?;
+
+            if let Some(bes) = &bes {
+                if let Some(procedures) = integrate_lpr2_procedures(&adm, bes)? {
+                    output.has_procedures = true;
+                    output.procedures.push(&apply_date_filtering(
+                        &procedures,
+                        config,
+                        "procedure_date",
+                    )?)?;
+                }
+            }
+        }
+    }
+
+    if let (true, Some(kontakter_path), Some(diagnoser_path)) =
+        (lpr3, &paths.kontakter_path, &paths.diagnoser_path)
+    {
+        let (kontakter, kontakt_ids) = components
+            .partition(
+                "LPR3_KONTAKTER",
+                kontakter_path,
+                &["kontakt_id"],
+                Some("CPR"),
+                None,
+            )
+            .await?;
+        let diagnoser = components
+            .partition(
+                "LPR3_DIAGNOSER",
+                diagnoser_path,
+                &["kontakt_id"],
+                None,
+                kontakt_ids.as_ref(),
+            )
+            .await?
+            .0;
+        let procedurer = match &paths.procedurer_path {
+            Some(path) => Some(
+                components
+                    .partition(
+                        "LPR3_PROCEDURER",
+                        path,
+                        &["kontakt_id", "DW_EK_KONTAKT"],
+                        None,
+                        kontakt_ids.as_ref(),
+                    )
+                    .await?
+                    .0,
+            ),
+            None => None,
+        };
+        drop(kontakt_ids);
+
+        for partition in 0..partitions {
+            let kontakter = kontakter.read(partition)?;
+            if kontakter.iter().all(|batch| batch.num_rows() == 0) {
+                continue;
+            }
+            let diagnoser = diagnoser.read(partition)?;
+
+            let integrated =
+                integrate_lpr3_components(&kontakter, &diagnoser, &config.diagnosis_filter)?;
+            output.diagnoses.push(&apply_date_filtering(
+                &integrated,
+                config,
+                "admission_date",
+            )?)?;
+
+            if let Some(procedurer) = &procedurer {
+                let procedures =
+                    integrate_lpr3_procedures(&kontakter, &procedurer.read(partition)?)?;
+                output.has_procedures = true;
+                output.procedures.push(&apply_date_filtering(
+                    &procedures,
+                    config,
+                    "procedure_date",
+                )?)?;
+            }
+        }
+    }
+
+    log::info!(
+        "Harmonised {} LPR diagnoses and {} procedures",
+        output.num_diagnoses(),
+        output.num_procedures()
+    );
+    Ok(output)
+}
+
+/// Streams LPR components into contact partitions
+struct Components<'a> {
+    partitions: usize,
+    execution: &'a ExecutionConfig,
+    pnr_filter: Option<&'a PnrFilter>,
+}
+
+impl Components<'_> {
+    /// Partition one component on the first of `key_columns` it has
+    ///
+    /// Contact tables (`pnr_column` given) are filtered on the PNR filter; the tables
+    /// linked to them are semi-joined on `contact_keys` if given. The contact keys of a
+    /// filtered contact table are returned when everything is kept in a single partition;
+    /// with several partitions, unmatched rows of linked tables are spilled and dropped
+    /// when the partitions are integrated instead.
+    async fn partition(
+        &self,
+        name: &str,
+        path: &Path,
+        key_columns: &[&str],
+        pnr_column: Option<&str>,
+        contact_keys: Option<&HashSet<String>>,
+    ) -> Result<(SpillPartitions, Option<HashSet<String>>)> {
+        let ctx = session_context();
+        let table = name.to_lowercase();
+        let pnr_filter = self.pnr_filter.filter(|_| pnr_column.is_some());
+        register_indexed_parquet(
+            &ctx,
+            &table,
+            path,
+            ParquetReadOptions::default(),
+            pnr_filter
+                .zip(pnr_column)
+                .map(|(filter, column)| filter.index_lookup(column)),
+        )
+        .await?;
+
+        let mut df = ctx.table(&table).await?;
+        let key_column = key_columns
+            .iter()
+            .copied()
+            .find(|column| df.schema().has_column_with_unqualified_name(column))
+            .ok_or_else(|| {
+                IdsError::Data(format!("{name} has none of the columns {key_columns:?}"))
+            })?;
+        if let (Some(filter), Some(column)) = (pnr_filter, pnr_column) {
+            df = filter.apply_to_dataframe(df, column)?;
+        }
+        if let Some(keys) = contact_keys {
+            df = semi_join_values(df, keys, key_column)?;
+        }
+
+        let mut stream = df.execute_stream().await?;
+        let mut partitions =
+            SpillPartitions::new(stream.schema(), key_column, self.partitions, self.execution)?;
+        let mut keys = (pnr_filter.is_some() && self.partitions == 1).then(HashSet::new);
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            if let Some(keys) = &mut keys {
+                collect_keys(&batch, key_column, keys)?;
+            }
+            partitions.push(&batch)?;
+        }
+
+        log::info!(
+            "Partitioned {} {name} records from {}",
+            partitions.num_rows(),
+            path.display()
+        );
+        Ok((partitions, keys))
+    }
+}
+
+/// Add the non-null values of a key column to `keys`
+fn collect_keys(batch: &RecordBatch, key_column: &str, keys: &mut HashSet<String>) -> Result<()> {
+    let column = batch
+        .column_by_name(key_column)
+        .ok_or_else(|| IdsError::Data(format!("{key_column} column not found")))?;
+    let column = cast(column, &DataType::Utf8)?;
+    if let Some(values) = column.as_any().downcast_ref::<StringArray>() {
+        keys.extend(values.iter().flatten().map(ToString::to_string));
+    }
+    Ok(())
+}
+
+/// Size in bytes of a Parquet file or of the Parquet files below a directory
+fn parquet_size(path: &Path) -> Result<u64> {
+    let files = if path.is_dir() {
+        find_parquet_files_in_dir(path)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files
+        .iter()
+        .map(|file| Ok(std::fs::metadata(file)?.len()))
+        .sum()
+}
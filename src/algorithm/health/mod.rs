@@ -1,11 +1,20 @@
 //! Health data processing algorithms
 //!
 //! This module implements algorithms for health data processing, including
-//! LPR data harmonization, diagnosis classification, and SCD algorithm.
+//! LPR data harmonization (also out of core), contact-to-episode collapsing, diagnosis classification,
+//! cause-of-death diagnoses, the SCD algorithm and the generic phenotype engine.
 
 pub mod lpr;
+pub mod lpr_partitioned;
+pub mod episodes;
+pub mod death_causes;
 pub mod diagnosis;
+pub mod phenotype;
 
 // Re-export common types
 pub use lpr::LprConfig;
+pub use lpr_partitioned::{partition_lpr, partition_lpr_cached, PartitionedLpr};
+pub use episodes::{Episode, EpisodeConfig, EpisodeType};
+pub use death_causes::{DeathRecord, MortalityOutcome};
 pub use diagnosis::scd::{ScdConfig, ScdResult, ScdDiseaseCodes};
+pub use phenotype::{Phenotype, PhenotypeConfig, PhenotypeResult};
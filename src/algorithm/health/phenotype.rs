@@ -0,0 +1,574 @@
+//! Generic phenotype engine over harmonised LPR data
+//!
+//! A phenotype (ADHD, asthma, depression, ...) is described entirely by a disease
+//! definition file (see [`super::diagnosis::definitions`]): named categories of ICD-10/SKS
+//! diagnosis patterns, optional SKS procedure patterns, the contact types diagnoses must
+//! come from, and confirmation and time-window rules. Running a phenotype over the
+//! output of [`super::lpr::process_lpr_data`] gives, for every person with at least one
+//! matching record, whether the phenotype is confirmed, the first date, the number of
+//! matching contacts and a flag per category.
+
+use arrow::array::{Array, ArrayRef, BooleanArray, Date32Array, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::algorithm::health::death_causes::DEATH_CONTACT_TYPE;
+use crate::algorithm::health::diagnosis::columns::{
+    ContactColumns, DiagnosisColumns, ProcedureColumns,
+};
+use crate::algorithm::health::diagnosis::confirmation::{
+    confirm_categories, CategoryEvents, ConfirmationRules,
+};
+use crate::algorithm::health::diagnosis::definitions::{
+    compile_patterns, DefinitionInfo, DiagnosisSource, DiseaseDefinitions,
+};
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::{
+    normalize_diagnosis_code, DiagnosisPattern, NormalizedDiagnosis,
+};
+use crate::utils::date_utils::{date_to_days_since_epoch, days_since_epoch_to_date};
+
+/// A compiled phenotype category
+struct PhenotypeCategory {
+    name: String,
+    include: Vec<DiagnosisPattern>,
+    exclude: Vec<DiagnosisPattern>,
+    procedures: Vec<DiagnosisPattern>,
+    contact_types: Vec<String>,
+    confirmation: ConfirmationRules,
+}
+
+impl PhenotypeCategory {
+    fn matches_diagnosis(&self, diagnosis: &NormalizedDiagnosis) -> bool {
+        self.include.iter().any(|pattern| pattern.matches(diagnosis))
+            && !self.exclude.iter().any(|pattern| pattern.matches(diagnosis))
+    }
+
+    fn matches_procedure(&self, procedure: &NormalizedDiagnosis) -> bool {
+        self.procedures.iter().any(|pattern| pattern.matches(procedure))
+    }
+
+    /// Cause-of-death records are selected by the definition sources, not contact types
+    fn accepts_contact_type(&self, contact_type: Option<&str>) -> bool {
+        self.contact_types.is_empty()
+            || contact_type == Some(DEATH_CONTACT_TYPE)
+            || contact_type.is_some_and(|value| {
+                self.contact_types
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(value.trim()))
+            })
+    }
+}
+
+/// A phenotype compiled from a disease definition file
+pub struct Phenotype {
+    name: String,
+    categories: Vec<PhenotypeCategory>,
+    sources: Vec<DiagnosisSource>,
+    info: DefinitionInfo,
+}
+
+impl Phenotype {
+    /// Load and compile a phenotype from a JSON or TOML definition file
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_definitions(&DiseaseDefinitions::from_file(path)?)
+    }
+
+    /// Compile a phenotype from parsed disease definitions
+    pub fn from_definitions(definitions: &DiseaseDefinitions) -> Result<Self> {
+        let mut categories = Vec::with_capacity(definitions.categories.len());
+        for category in &definitions.categories {
+            categories.push(PhenotypeCategory {
+                name: category.name.clone(),
+                include: compile_patterns(&category.include)?,
+                exclude: compile_patterns(&category.exclude)?,
+                procedures: compile_patterns(&category.procedures)?,
+                contact_types: definitions.contact_types_for(category),
+                confirmation: definitions.confirmation_for(category),
+            });
+        }
+
+        log::info!(
+            "Loaded phenotype '{}' version {} with {} categories ({})",
+            definitions.name,
+            definitions.version,
+            categories.len(),
+            definitions.source
+        );
+
+        Ok(Self {
+            name: definitions.name.clone(),
+            categories,
+            sources: definitions.sources.clone(),
+            info: definitions.info(),
+        })
+    }
+
+    /// Phenotype name, used as the prefix of output columns
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Identification of the definitions this phenotype was built from
+    #[must_use]
+    pub const fn definition_info(&self) -> &DefinitionInfo {
+        &self.info
+    }
+
+    /// Category names in definition order
+    #[must_use]
+    pub fn category_names(&self) -> Vec<String> {
+        self.categories.iter().map(|category| category.name.clone()).collect()
+    }
+
+    /// Whether any category uses procedure codes
+    #[must_use]
+    pub fn uses_procedures(&self) -> bool {
+        self.categories
+            .iter()
+            .any(|category| !category.procedures.is_empty())
+    }
+
+    /// Whether the definitions take diagnoses from the given register
+    #[must_use]
+    pub fn uses_source(&self, source: DiagnosisSource) -> bool {
+        self.sources.contains(&source)
+    }
+
+    /// Indices of the categories whose diagnosis patterns match a code
+    #[must_use]
+    pub fn diagnosis_categories(&self, diagnosis: &NormalizedDiagnosis) -> Vec<usize> {
+        self.categories
+            .iter()
+            .enumerate()
+            .filter(|(_, category)| category.matches_diagnosis(diagnosis))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Whether any category's confirmation rules need birth dates
+    #[must_use]
+    pub fn requires_birth_dates(&self) -> bool {
+        self.categories
+            .iter()
+            .any(|category| category.confirmation.requires_birth_date())
+    }
+}
+
+/// Configuration for running a phenotype over harmonised LPR data
+#[derive(Debug, Clone)]
+pub struct PhenotypeConfig {
+    /// Diagnosis columns to match against the diagnosis patterns
+    pub diagnosis_columns: Vec<String>,
+    /// Column containing the contact date
+    pub date_column: String,
+    /// Column containing the patient ID
+    pub patient_id_column: String,
+    /// Column containing the contact end date, used to identify inpatient contacts
+    pub discharge_date_column: String,
+    /// Column containing the contact type, used for contact type restrictions
+    pub admission_type_column: String,
+    /// Contact types counted as inpatient; contacts ending on a later day than they
+    /// started are also counted as inpatient
+    pub inpatient_types: Vec<String>,
+    /// Column of the procedure table containing the SKS procedure code
+    pub procedure_code_column: String,
+    /// Column of the procedure table containing the procedure date
+    pub procedure_date_column: String,
+}
+
+impl Default for PhenotypeConfig {
+    fn default() -> Self {
+        Self {
+            diagnosis_columns: vec![
+                "primary_diagnosis".to_string(),
+                "secondary_diagnoses".to_string(),
+            ],
+            date_column: "admission_date".to_string(),
+            patient_id_column: "patient_id".to_string(),
+            discharge_date_column: "discharge_date".to_string(),
+            admission_type_column: "admission_type".to_string(),
+            inpatient_types: vec!["0".to_string()],
+            procedure_code_column: "procedure_code".to_string(),
+            procedure_date_column: "procedure_date".to_string(),
+        }
+    }
+}
+
+/// Result of a phenotype for a single person
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhenotypeResult {
+    /// Patient identifier
+    pub patient_id: String,
+    /// Whether any category is confirmed
+    pub has_phenotype: bool,
+    /// First date of the earliest confirmed category
+    pub first_date: Option<NaiveDate>,
+    /// Number of contacts and procedures with a matching code
+    pub count: usize,
+    /// Confirmed flag per category, in definition order
+    pub categories: Vec<bool>,
+}
+
+/// Run a phenotype over harmonised LPR data and an optional procedure table
+///
+/// The procedure table needs the configured patient ID, procedure code and procedure
+/// date columns; contact type restrictions do not apply to procedures. Birth dates are
+/// only needed for `max_age_years` confirmation rules. Only persons with at least one
+/// matching record are returned, sorted by patient ID.
+pub fn apply_phenotype(
+    health_data: &RecordBatch,
+    procedures: Option<&RecordBatch>,
+    phenotype: &Phenotype,
+    config: &PhenotypeConfig,
+    birth_dates: Option<&HashMap<String, NaiveDate>>,
+) -> Result<Vec<PhenotypeResult>> {
+    let category_count = phenotype.categories.len();
+    let contacts = ContactColumns::new(
+        health_data,
+        &config.patient_id_column,
+        &config.date_column,
+        &config.discharge_date_column,
+        &config.admission_type_column,
+        &config.inpatient_types,
+    )?;
+    let diagnosis_columns = DiagnosisColumns::new(health_data, &config.diagnosis_columns);
+
+    if !contacts.has_dates() {
+        log::warn!(
+            "Date column '{}' not found; phenotype dates and time-window rules are unavailable",
+            config.date_column
+        );
+    }
+
+    let mut persons: HashMap<String, CategoryEvents> = HashMap::new();
+    let mut code_cache: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut hits = vec![false; category_count];
+
+    for row in 0..health_data.num_rows() {
+        let Some(patient_id) = contacts.patient_id(row) else {
+            continue;
+        };
+        let contact_type = contacts.contact_type(row);
+
+        hits.fill(false);
+        for code in diagnosis_columns.codes(row) {
+            let matches = code_cache.entry(code).or_insert_with(|| {
+                normalize_diagnosis_code(code).map_or_else(Vec::new, |normalized| {
+                    phenotype.diagnosis_categories(&normalized)
+                })
+            });
+            for &index in matches.iter() {
+                if phenotype.categories[index].accepts_contact_type(contact_type) {
+                    hits[index] = true;
+                }
+            }
+        }
+        if !hits.contains(&true) {
+            continue;
+        }
+
+        persons
+            .entry(patient_id.to_string())
+            .or_insert_with(|| CategoryEvents::new(category_count))
+            .record(&hits, contacts.date(row), contacts.inpatient(row));
+    }
+
+    if let Some(procedures) = procedures.filter(|_| phenotype.uses_procedures()) {
+        collect_procedure_matches(procedures, phenotype, config, &mut persons)?;
+    }
+
+    if phenotype.requires_birth_dates() && birth_dates.is_none() {
+        log::warn!(
+            "Phenotype '{}' has age rules but no birth dates were given; those categories \
+             cannot be confirmed",
+            phenotype.name
+        );
+    }
+
+    let mut results: Vec<PhenotypeResult> = persons
+        .into_iter()
+        .map(|(patient_id, matches)| {
+            let birth_date = birth_dates.and_then(|dates| dates.get(&patient_id).copied());
+            let (categories, first_date) = confirm_categories(
+                phenotype.categories.iter().enumerate().map(|(index, category)| {
+                    (
+                        &category.confirmation,
+                        matches.matched[index],
+                        matches.events[index].as_slice(),
+                    )
+                }),
+                birth_date,
+            );
+
+            PhenotypeResult {
+                patient_id,
+                has_phenotype: categories.contains(&true),
+                first_date,
+                count: matches.count,
+                categories,
+            }
+        })
+        .collect();
+    results.sort_by(|a, b| a.patient_id.cmp(&b.patient_id));
+
+    log::info!(
+        "Phenotype '{}': {} persons with matching records, {} confirmed",
+        phenotype.name,
+        results.len(),
+        results.iter().filter(|result| result.has_phenotype).count()
+    );
+
+    Ok(results)
+}
+
+/// Add matching procedures to the per-person matches
+fn collect_procedure_matches(
+    procedures: &RecordBatch,
+    phenotype: &Phenotype,
+    config: &PhenotypeConfig,
+    persons: &mut HashMap<String, CategoryEvents>,
+) -> Result<()> {
+    let columns = ProcedureColumns::new(
+        procedures,
+        &config.patient_id_column,
+        &config.procedure_code_column,
+        &config.procedure_date_column,
+    )?;
+
+    let mut hits = vec![false; phenotype.categories.len()];
+    for row in 0..procedures.num_rows() {
+        let Some((patient_id, code, date)) = columns.row(row) else {
+            continue;
+        };
+        for (hit, category) in hits.iter_mut().zip(&phenotype.categories) {
+            *hit = category.matches_procedure(&code);
+        }
+        if !hits.contains(&true) {
+            continue;
+        }
+
+        persons
+            .entry(patient_id.to_string())
+            .or_insert_with(|| CategoryEvents::new(phenotype.categories.len()))
+            .record(&hits, date, false);
+    }
+
+    Ok(())
+}
+
+/// Convert phenotype results to a `RecordBatch`
+///
+/// Columns are prefixed with the phenotype name so that several phenotypes can be joined
+/// on `patient_id`: `<name>`, `<name>_first_date`, `<name>_count` and one
+/// `<name>_<category>` flag per category.
+pub fn phenotype_results_to_record_batch(
+    phenotype: &Phenotype,
+    results: &[PhenotypeResult],
+) -> Result<RecordBatch> {
+    let name = &phenotype.name;
+    let mut fields = vec![
+        Field::new("patient_id", DataType::Utf8, false),
+        Field::new(name, DataType::Boolean, false),
+        Field::new(format!("{name}_first_date"), DataType::Date32, true),
+        Field::new(format!("{name}_count"), DataType::Int32, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            results.iter().map(|result| result.patient_id.as_str()),
+        )),
+        Arc::new(BooleanArray::from_iter(
+            results.iter().map(|result| Some(result.has_phenotype)),
+        )),
+        Arc::new(Date32Array::from_iter(
+            results
+                .iter()
+                .map(|result| result.first_date.map(date_to_days_since_epoch)),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            results
+                .iter()
+                .map(|result| i32::try_from(result.count).unwrap_or(i32::MAX)),
+        )),
+    ];
+
+    for (index, category) in phenotype.categories.iter().enumerate() {
+        fields.push(Field::new(
+            format!("{name}_{}", category.name),
+            DataType::Boolean,
+            false,
+        ));
+        columns.push(Arc::new(BooleanArray::from_iter(
+            results.iter().map(|result| Some(result.categories[index])),
+        )));
+    }
+
+    let schema = Schema::new(fields).with_metadata(phenotype.info.metadata("phenotype"));
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .map_err(|e| IdsError::Data(format!("Failed to create phenotype result batch: {e}")))
+}
+
+/// Read phenotype results from a batch written by [`phenotype_results_to_record_batch`]
+pub fn phenotype_results_from_record_batch(
+    phenotype: &Phenotype,
+    batch: &RecordBatch,
+) -> Result<Vec<PhenotypeResult>> {
+    let name = &phenotype.name;
+    let column = |column_name: &str| {
+        batch.column_by_name(column_name).ok_or_else(|| {
+            IdsError::Data(format!("Phenotype results have no '{column_name}' column"))
+        })
+    };
+    let downcast_error =
+        |column_name: &str| IdsError::Data(format!("Unexpected type of column '{column_name}'"));
+
+    let patient_ids = column("patient_id")?;
+    let patient_ids = patient_ids
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| downcast_error("patient_id"))?;
+    let has_phenotype = column(name)?;
+    let has_phenotype = has_phenotype
+        .as_any()
+        .downcast_ref::<BooleanArray>()
+        .ok_or_else(|| downcast_error(name))?;
+    let first_date_name = format!("{name}_first_date");
+    let first_dates = column(&first_date_name)?;
+    let first_dates = first_dates
+        .as_any()
+        .downcast_ref::<Date32Array>()
+        .ok_or_else(|| downcast_error(&first_date_name))?;
+    let count_name = format!("{name}_count");
+    let counts = column(&count_name)?;
+    let counts = counts
+        .as_any()
+        .downcast_ref::<Int32Array>()
+        .ok_or_else(|| downcast_error(&count_name))?;
+    let categories = phenotype
+        .categories
+        .iter()
+        .map(|category| {
+            let category_name = format!("{name}_{}", category.name);
+            column(&category_name)?
+                .as_any()
+                .downcast_ref::<BooleanArray>()
+                .cloned()
+                .ok_or_else(|| downcast_error(&category_name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((0..batch.num_rows())
+        .map(|row| PhenotypeResult {
+            patient_id: patient_ids.value(row).to_string(),
+            has_phenotype: has_phenotype.value(row),
+            first_date: (!first_dates.is_null(row))
+                .then(|| days_since_epoch_to_date(first_dates.value(row))),
+            count: usize::try_from(counts.value(row)).unwrap_or_default(),
+            categories: categories.iter().map(|flags| flags.value(row)).collect(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phenotype_over_harmonised_lpr() {
+        let definitions = DiseaseDefinitions::from_toml(
+            r#"
+format_version = 1
+name = "adhd"
+version = "1.0"
+contact_types = ["0", "2"]
+
+[[categories]]
+name = "hyperkinetic"
+include = [{ type = "prefix", codes = ["F90"] }]
+
+[[categories]]
+name = "medication"
+procedures = [{ type = "prefix", codes = ["BRXA"] }]
+"#,
+            "adhd.toml",
+        )
+        .unwrap();
+        let phenotype = Phenotype::from_definitions(&definitions).unwrap();
+
+        let date = |s| {
+            date_to_days_since_epoch(NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap())
+        };
+        let health_data = RecordBatch::try_from_iter(vec![
+            (
+                "patient_id",
+                Arc::new(StringArray::from(vec!["p1", "p1", "p2", "p3"])) as ArrayRef,
+            ),
+            (
+                "primary_diagnosis",
+                Arc::new(StringArray::from(vec!["DF900", "DF901", "DF909", "DJ45"])) as ArrayRef,
+            ),
+            (
+                "admission_date",
+                Arc::new(Date32Array::from(vec![
+                    date("2012-05-01"),
+                    date("2011-01-01"),
+                    date("2012-01-01"),
+                    date("2012-01-01"),
+                ])) as ArrayRef,
+            ),
+            (
+                "admission_type",
+                Arc::new(StringArray::from(vec!["2", "0", "1", "0"])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let procedures = RecordBatch::try_from_iter(vec![
+            ("patient_id", Arc::new(StringArray::from(vec!["p4"])) as ArrayRef),
+            ("procedure_code", Arc::new(StringArray::from(vec!["BRXA10"])) as ArrayRef),
+            (
+                "procedure_date",
+                Arc::new(Date32Array::from(vec![date("2013-01-01")])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let results = apply_phenotype(
+            &health_data,
+            Some(&procedures),
+            &phenotype,
+            &PhenotypeConfig::default(),
+            None,
+        )
+        .unwrap();
+
+        // p2's outpatient contact type is excluded and p3 has no matching code
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].patient_id, "p1");
+        assert_eq!(results[0].count, 2);
+        assert_eq!(results[0].categories, vec![true, false]);
+        assert_eq!(
+            results[0].first_date,
+            NaiveDate::from_ymd_opt(2011, 1, 1)
+        );
+        assert_eq!(results[1].categories, vec![false, true]);
+
+        let batch = phenotype_results_to_record_batch(&phenotype, &results).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert!(batch.schema().field_with_name("adhd_medication").is_ok());
+    }
+
+    #[test]
+    fn test_example_phenotypes_parse() {
+        let directory =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas/definitions/phenotypes");
+        for name in ["adhd", "asthma", "depression"] {
+            let phenotype = Phenotype::load(&directory.join(format!("{name}.toml"))).unwrap();
+            assert_eq!(phenotype.name(), name);
+        }
+    }
+}
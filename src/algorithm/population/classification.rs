@@ -8,12 +8,21 @@ use arrow::compute::filter;
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
 use chrono::NaiveDate;
+use rustc_hash::FxHashMap;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::algorithm::health::lpr::{process_lpr_data, LprConfig};
-use crate::algorithm::health::diagnosis::scd::{apply_scd_algorithm, ScdConfig, ScdResult};
+use crate::algorithm::health::lpr::{process_lpr_data, process_lpr_procedure_data, LprConfig};
+use crate::algorithm::health::lpr_partitioned::PartitionedLpr;
+use crate::algorithm::health::diagnosis::definitions::DefinitionInfo;
+use crate::algorithm::health::diagnosis::filter::DiagnosisFilter;
+use crate::algorithm::health::diagnosis::scd::{
+    apply_scd_algorithm_with_procedures, scd_diagnosis_columns, ScdConfig, ScdDiseaseCodes,
+    ScdResult,
+};
 use crate::error::{IdsError, Result};
+use crate::utils::date_utils::extract_date_from_array;
 
 /// Configuration for Population SCD analysis
 pub struct PopulationScdConfig {
@@ -33,6 +42,10 @@ pub struct PopulationScdConfig {
     pub date_column: String,
     /// PNR column in population data
     pub population_pnr_column: String,
+    /// Disease definition file (JSON or TOML); the built-in SCD definitions if `None`
+    pub definitions_path: Option<PathBuf>,
+    /// Diagnosis type and status filter applied when harmonising LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
 }
 
 impl Default for PopulationScdConfig {
@@ -42,13 +55,12 @@ impl Default for PopulationScdConfig {
             include_lpr3: true,
             start_date: None,
             end_date: None,
-            diagnosis_columns: vec![
-                "primary_diagnosis".to_string(),
-                "secondary_diagnosis".to_string(),
-            ],
+            diagnosis_columns: scd_diagnosis_columns(false),
             patient_id_column: "patient_id".to_string(),
             date_column: "admission_date".to_string(),
             population_pnr_column: "PNR".to_string(),
+            definitions_path: None,
+            diagnosis_filter: DiagnosisFilter::default(),
         }
     }
 }
@@ -64,6 +76,45 @@ pub struct PopulationScdResult {
     pub scd_percentage: f64,
     /// Disease category counts
     pub category_counts: HashMap<String, usize>,
+    /// The disease definitions used
+    pub definitions: DefinitionInfo,
+}
+
+/// Collect birth dates by PNR from the population's `FOED_DAG` column, if present
+pub fn collect_birth_dates(
+    population_data: &RecordBatch,
+    pnr_column: &str,
+) -> Option<HashMap<String, NaiveDate>> {
+    let pnr_array = population_data
+        .column_by_name(pnr_column)?
+        .as_any()
+        .downcast_ref::<StringArray>()?;
+    let birth_column = population_data.column_by_name("FOED_DAG")?;
+
+    Some(
+        (0..population_data.num_rows())
+            .filter(|&i| !pnr_array.is_null(i))
+            .filter_map(|i| {
+                extract_date_from_array(birth_column.as_ref(), i)
+                    .map(|birth| (pnr_array.value(i).to_string(), birth))
+            })
+            .collect(),
+    )
+}
+
+/// Collect the distinct PNRs of population batches
+pub fn collect_pnrs(population: &[RecordBatch], pnr_column: &str) -> Result<HashSet<String>> {
+    let mut pnrs = HashSet::new();
+    for batch in population {
+        let column = batch.column_by_name(pnr_column).ok_or_else(|| {
+            IdsError::Data(format!("PNR column {pnr_column} not found in population data"))
+        })?;
+        let column = arrow::compute::cast(column, &DataType::Utf8)?;
+        if let Some(values) = column.as_any().downcast_ref::<StringArray>() {
+            pnrs.extend(values.iter().flatten().map(ToString::to_string));
+        }
+    }
+    Ok(pnrs)
 }
 
 /// Identify children in a population who have SCD
@@ -71,281 +122,330 @@ pub fn identify_scd_in_population(
     population_data: &RecordBatch,
     lpr_data: &RecordBatch,
     config: &PopulationScdConfig,
+) -> Result<(RecordBatch, PopulationScdResult)> {
+    identify_scd_in_population_with_procedures(population_data, lpr_data, None, config)
+}
+
+/// Identify children in a population who have SCD, also using the harmonised procedure
+/// table for categories defined by procedure codes
+pub fn identify_scd_in_population_with_procedures(
+    population_data: &RecordBatch,
+    lpr_data: &RecordBatch,
+    procedures: Option<&RecordBatch>,
+    config: &PopulationScdConfig,
 ) -> Result<(RecordBatch, PopulationScdResult)> {
     // Step 1: Apply SCD algorithm to health data
     let scd_config = ScdConfig {
         diagnosis_columns: config.diagnosis_columns.clone(),
         date_column: config.date_column.clone(),
         patient_id_column: config.patient_id_column.clone(),
+        definitions_path: config.definitions_path.clone(),
+        ..ScdConfig::default()
     };
+    let scd_codes = ScdDiseaseCodes::load(config.definitions_path.as_deref())?;
+    let birth_dates = collect_birth_dates(population_data, &config.population_pnr_column);
 
     log::info!("Applying SCD algorithm to {} health records...", lpr_data.num_rows());
-    let scd_results = apply_scd_algorithm(lpr_data, &scd_config)?;
+    let scd_results = apply_scd_algorithm_with_procedures(
+        lpr_data,
+        procedures,
+        &scd_config,
+        &scd_codes,
+        birth_dates.as_ref(),
+    )?;
     log::info!("SCD analysis complete: {} patient records", scd_results.len());
 
-    // Step 2: Create map of patient ID to SCD result with preallocated capacity
-    // Use a more efficient hash map implementation for string keys
-    log::debug!("Creating index for fast SCD lookup");
-    let estimated_capacity = scd_results.len();
-    
+    // Step 2: Create map of patient ID to SCD result
     // Use rustc_hash::FxHashMap which is faster for string keys than the standard HashMap
-    use rustc_hash::FxHashMap;
-    let mut scd_map: FxHashMap<&str, &ScdResult> = FxHashMap::with_capacity_and_hasher(
-        estimated_capacity, 
-        Default::default()
-    );
-    
-    // Store references to avoid cloning strings
-    for result in &scd_results {
-        scd_map.insert(&result.patient_id, result);
-    }
-    
-    // Step 3: Extract PNR from population data
-    let pnr_col_idx = population_data
-        .schema()
-        .index_of(&config.population_pnr_column)
-        .map_err(|e| {
-            IdsError::Data(format!(
-                "PNR column not found in population data: {e}"
-            ))
-        })?;
+    log::debug!("Creating index for fast SCD lookup");
+    let scd_map: FxHashMap<&str, &ScdResult> = scd_results
+        .iter()
+        .map(|result| (result.patient_id.as_str(), result))
+        .collect();
+    let categories = result_categories(scd_results.iter());
 
-    let pnr_col = population_data.column(pnr_col_idx);
-    
-    // Handle the StringArray conversion
-    let mut converted_array_holder = None;
-    
-    // First, try direct downcast
-    let pnr_array = if let Some(array) = pnr_col.as_any().downcast_ref::<StringArray>() {
-        array
-    } else {
-        // If direct downcast fails, try to convert using Arrow cast
-        log::warn!("Attempting generic conversion to StringArray for column {} with type {:?}", 
-                  config.population_pnr_column, pnr_col.data_type());
-        
-        // Create a cast result that will be owned
-        let cast_result = arrow::compute::cast(pnr_col, &arrow::datatypes::DataType::Utf8)
-            .map_err(|e| IdsError::Data(format!(
-                "Failed to convert PNR column to StringArray: {e}"
-            )))?;
-        
-        log::info!("Successfully converted column {} to StringArray using Arrow cast", 
-                  config.population_pnr_column);
-        
-        // Keep the converted array alive for the duration of this function
-        converted_array_holder = Some(cast_result);
-        
-        // Get a StringArray reference from the cast result
-        converted_array_holder.as_ref().unwrap().as_any().downcast_ref::<StringArray>()
-            .ok_or_else(|| IdsError::Data(format!(
-                "Failed to convert {} to StringArray after casting", 
-                config.population_pnr_column
-            )))?
-    };
+    // Step 3: Match population records with SCD results
+    let annotated = annotate_population(
+        population_data,
+        &scd_map,
+        &categories,
+        &config.population_pnr_column,
+    )?;
 
-    // Step 4: Match population records with SCD results
-    log::debug!("Matching population records with SCD results");
+    // Step 4: Create summary result
     let num_rows = population_data.num_rows();
-    let mut is_scd = Vec::with_capacity(num_rows);
-    let mut first_scd_date = Vec::with_capacity(num_rows);
-    let mut disease_categories = HashMap::new();
+    let result = PopulationScdResult {
+        total_children: num_rows,
+        scd_children: annotated.scd_children,
+        scd_percentage: percentage(annotated.scd_children, num_rows),
+        category_counts: annotated.category_counts,
+        definitions: scd_codes.definition_info().clone(),
+    };
+
+    Ok((annotated.batch, result))
+}
 
-    // Get all unique disease categories from SCD results
-    let all_categories: HashSet<String> = scd_results
+/// Identify children with SCD in a population, using LPR data partitioned by patient
+///
+/// Runs the SCD algorithm one partition at a time and passes the annotated population
+/// batches to `write` as they are produced, so neither the LPR data nor the annotated
+/// population are held in memory as a whole.
+pub fn identify_scd_in_population_partitioned(
+    population: &[RecordBatch],
+    lpr: &PartitionedLpr,
+    config: &PopulationScdConfig,
+    mut write: impl FnMut(&RecordBatch) -> Result<()>,
+) -> Result<PopulationScdResult> {
+    let scd_config = ScdConfig {
+        diagnosis_columns: config.diagnosis_columns.clone(),
+        date_column: config.date_column.clone(),
+        patient_id_column: config.patient_id_column.clone(),
+        definitions_path: config.definitions_path.clone(),
+        ..ScdConfig::default()
+    };
+    let scd_codes = ScdDiseaseCodes::load(config.definitions_path.as_deref())?;
+
+    let mut birth_dates = None;
+    for batch in population {
+        if let Some(dates) = collect_birth_dates(batch, &config.population_pnr_column) {
+            birth_dates.get_or_insert_with(HashMap::new).extend(dates);
+        }
+    }
+    let population_pnrs = collect_pnrs(population, &config.population_pnr_column)?;
+
+    // Keep only the results of the population's children
+    let mut scd_results: FxHashMap<String, ScdResult> = FxHashMap::default();
+    for partition in 0..lpr.partitions() {
+        let results = apply_scd_algorithm_with_procedures(
+            &lpr.diagnoses(partition)?,
+            lpr.procedures(partition)?.as_ref(),
+            &scd_config,
+            &scd_codes,
+            birth_dates.as_ref(),
+        )?;
+        for result in results {
+            if population_pnrs.contains(&result.patient_id) {
+                scd_results.insert(result.patient_id.clone(), result);
+            }
+        }
+        log::debug!(
+            "SCD partition {}/{}: {} population records with results",
+            partition + 1,
+            lpr.partitions(),
+            scd_results.len()
+        );
+    }
+    log::info!("SCD analysis complete: {} patient records", scd_results.len());
+
+    let scd_map: FxHashMap<&str, &ScdResult> = scd_results
         .iter()
-        .flat_map(|r| r.disease_categories.keys().cloned())
+        .map(|(patient_id, result)| (patient_id.as_str(), result))
         .collect();
+    let categories = result_categories(scd_results.values());
 
-    // Initialize category vectors
-    for category in &all_categories {
-        disease_categories.insert(category.clone(), Vec::with_capacity(num_rows));
+    let mut total_children = 0;
+    let mut scd_children = 0;
+    let mut category_counts: HashMap<String, usize> = HashMap::new();
+    for batch in population {
+        let annotated =
+            annotate_population(batch, &scd_map, &categories, &config.population_pnr_column)?;
+        total_children += batch.num_rows();
+        scd_children += annotated.scd_children;
+        for (category, count) in annotated.category_counts {
+            *category_counts.entry(category).or_insert(0) += count;
+        }
+        write(&annotated.batch)?;
     }
 
-    // Counter for children with SCD
-    let mut scd_children_count = 0;
-    let mut category_counts: HashMap<String, usize> = HashMap::with_capacity(all_categories.len());
-    for category in &all_categories {
-        category_counts.insert(category.clone(), 0);
+    Ok(PopulationScdResult {
+        total_children,
+        scd_children,
+        scd_percentage: percentage(scd_children, total_children),
+        category_counts,
+        definitions: scd_codes.definition_info().clone(),
+    })
+}
+
+/// Percentage of `count` in `total`, 0 for an empty total
+fn percentage(count: usize, total: usize) -> f64 {
+    if total > 0 {
+        (count as f64 / total as f64) * 100.0
+    } else {
+        0.0
     }
+}
+
+/// The disease categories of SCD results, sorted
+fn result_categories<'a>(results: impl Iterator<Item = &'a ScdResult>) -> Vec<String> {
+    let categories: HashSet<&String> = results
+        .flat_map(|result| result.disease_categories.keys())
+        .collect();
+    let mut categories: Vec<String> = categories.into_iter().cloned().collect();
+    categories.sort();
+    categories
+}
+
+/// Population records with SCD columns, and the counts of SCD children among them
+struct AnnotatedPopulation {
+    batch: RecordBatch,
+    scd_children: usize,
+    category_counts: HashMap<String, usize>,
+}
+
+/// Add the `is_scd`, `first_scd_date` and `scd_category_` columns to population records
+///
+/// Children without an SCD result get `false`; records without a PNR get nulls.
+fn annotate_population(
+    population_data: &RecordBatch,
+    scd_map: &FxHashMap<&str, &ScdResult>,
+    categories: &[String],
+    pnr_column: &str,
+) -> Result<AnnotatedPopulation> {
+    let pnr_col = population_data.column_by_name(pnr_column).ok_or_else(|| {
+        IdsError::Data(format!("PNR column {pnr_column} not found in population data"))
+    })?;
+
+    // Cast PNRs stored as another type to strings
+    let pnr_col = if pnr_col.data_type() == &DataType::Utf8 {
+        pnr_col.clone()
+    } else {
+        log::warn!(
+            "Attempting generic conversion to StringArray for column {pnr_column} with type {:?}",
+            pnr_col.data_type()
+        );
+        arrow::compute::cast(pnr_col, &DataType::Utf8).map_err(|e| {
+            IdsError::Data(format!("Failed to convert PNR column to StringArray: {e}"))
+        })?
+    };
+    let pnr_array = pnr_col
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| {
+            IdsError::Data(format!("Failed to convert {pnr_column} to StringArray after casting"))
+        })?;
 
     // Process data in parallel using Rayon
     use rayon::prelude::*;
-    
-    // Create intermediate vectors that will be processed in parallel
-    let chunk_size = 10000;
-    let num_chunks = num_rows.div_ceil(chunk_size);
-    
+
     // Define the chunk structure to be processed in parallel
     struct ChunkResult {
         is_scd: Vec<Option<bool>>,
         first_scd_date: Vec<Option<i32>>,
-        category_values: HashMap<String, Vec<Option<bool>>>,
+        category_values: Vec<Vec<Option<bool>>>,
         scd_children_count: usize,
-        category_counts: HashMap<String, usize>,
+        category_counts: Vec<usize>,
     }
-    
+
+    let num_rows = population_data.num_rows();
+    let chunk_size = 10000;
+    let num_chunks = num_rows.div_ceil(chunk_size);
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+
     // Process chunks in parallel
     let chunk_results: Vec<ChunkResult> = (0..num_chunks)
         .into_par_iter()
         .map(|chunk_index| {
             let start_idx = chunk_index * chunk_size;
             let end_idx = (start_idx + chunk_size).min(num_rows);
-            let chunk_size = end_idx - start_idx;
-            
-            // Initialize chunk result data
-            let mut chunk_is_scd = Vec::with_capacity(chunk_size);
-            let mut chunk_first_scd_date = Vec::with_capacity(chunk_size);
-            let mut chunk_category_values: HashMap<String, Vec<Option<bool>>> = HashMap::new();
-            let mut chunk_scd_children_count = 0;
-            let mut chunk_category_counts: HashMap<String, usize> = HashMap::new();
-            
-            // Initialize category vectors and counts
-            for category in &all_categories {
-                chunk_category_values.insert(category.clone(), Vec::with_capacity(chunk_size));
-                chunk_category_counts.insert(category.clone(), 0);
-            }
-            
-            // Process each child in this chunk
+            let mut chunk = ChunkResult {
+                is_scd: Vec::with_capacity(end_idx - start_idx),
+                first_scd_date: Vec::with_capacity(end_idx - start_idx),
+                category_values: vec![Vec::with_capacity(end_idx - start_idx); categories.len()],
+                scd_children_count: 0,
+                category_counts: vec![0; categories.len()],
+            };
+
             for i in start_idx..end_idx {
                 if pnr_array.is_null(i) {
                     // Add nulls for missing PNR
-                    chunk_is_scd.push(None);
-                    chunk_first_scd_date.push(None);
-                    for category_vec in chunk_category_values.values_mut() {
-                        category_vec.push(None);
+                    chunk.is_scd.push(None);
+                    chunk.first_scd_date.push(None);
+                    for values in &mut chunk.category_values {
+                        values.push(None);
                     }
                     continue;
                 }
 
-                let pnr = pnr_array.value(i);
-                
-                // Look up SCD result for this PNR (O(1) lookup with HashMap)
-                if let Some(scd_result) = scd_map.get(pnr) {
-                    chunk_is_scd.push(Some(scd_result.is_scd));
-                    
-                    if scd_result.is_scd {
-                        chunk_scd_children_count += 1;
-                        
-                        // Update first SCD date
-                        if let Some(date) = scd_result.first_scd_date {
-                            let days = (date.signed_duration_since(
-                                NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
-                            ).num_days()) as i32;
-                            chunk_first_scd_date.push(Some(days));
-                        } else {
-                            chunk_first_scd_date.push(None);
-                        }
-                        
-                        // Update disease categories
-                        for (category, has_disease) in &scd_result.disease_categories {
-                            let category_vec = chunk_category_values.get_mut(category).unwrap();
-                            category_vec.push(Some(*has_disease));
-                            
-                            if *has_disease {
-                                *chunk_category_counts.get_mut(category).unwrap() += 1;
-                            }
-                        }
-                    } else {
-                        // Not SCD
-                        chunk_first_scd_date.push(None);
-                        
-                        // Set all categories to false
-                        for category_vec in chunk_category_values.values_mut() {
-                            category_vec.push(Some(false));
-                        }
-                    }
-                } else {
-                    // No SCD result for this PNR
-                    chunk_is_scd.push(Some(false));
-                    chunk_first_scd_date.push(None);
-                    
-                    // Set all categories to false
-                    for category_vec in chunk_category_values.values_mut() {
-                        category_vec.push(Some(false));
+                // Children without an SCD result have no SCD
+                let scd_result = scd_map
+                    .get(pnr_array.value(i))
+                    .filter(|result| result.is_scd);
+                chunk.is_scd.push(Some(scd_result.is_some()));
+                chunk.first_scd_date.push(
+                    scd_result
+                        .and_then(|result| result.first_scd_date)
+                        .map(|date| date.signed_duration_since(epoch).num_days() as i32),
+                );
+                if scd_result.is_some() {
+                    chunk.scd_children_count += 1;
+                }
+                for (index, category) in categories.iter().enumerate() {
+                    let has_disease = scd_result.is_some_and(|result| {
+                        result.disease_categories.get(category).copied().unwrap_or(false)
+                    });
+                    chunk.category_values[index].push(Some(has_disease));
+                    if has_disease {
+                        chunk.category_counts[index] += 1;
                     }
                 }
             }
-            
-            // Log progress
-            log::debug!("Processed chunk {}/{}, found {} SCD children", 
-                       chunk_index + 1, num_chunks, chunk_scd_children_count);
-            
-            // Return this chunk's results
-            ChunkResult {
-                is_scd: chunk_is_scd,
-                first_scd_date: chunk_first_scd_date,
-                category_values: chunk_category_values,
-                scd_children_count: chunk_scd_children_count,
-                category_counts: chunk_category_counts,
-            }
+
+            log::debug!(
+                "Processed chunk {}/{}, found {} SCD children",
+                chunk_index + 1,
+                num_chunks,
+                chunk.scd_children_count
+            );
+            chunk
         })
         .collect();
-    
+
     // Combine results from all chunks
-    for chunk_result in chunk_results {
-        // Extend vectors
-        is_scd.extend(chunk_result.is_scd);
-        first_scd_date.extend(chunk_result.first_scd_date);
-        
-        // Extend category vectors
-        for (category, values) in chunk_result.category_values {
-            disease_categories.get_mut(&category).unwrap().extend(values);
+    let mut is_scd = Vec::with_capacity(num_rows);
+    let mut first_scd_date = Vec::with_capacity(num_rows);
+    let mut category_values = vec![Vec::with_capacity(num_rows); categories.len()];
+    let mut scd_children = 0;
+    let mut category_counts = vec![0; categories.len()];
+    for chunk in chunk_results {
+        is_scd.extend(chunk.is_scd);
+        first_scd_date.extend(chunk.first_scd_date);
+        for (values, chunk_values) in category_values.iter_mut().zip(chunk.category_values) {
+            values.extend(chunk_values);
         }
-        
-        // Update counts
-        scd_children_count += chunk_result.scd_children_count;
-        
-        // Update category counts
-        for (category, count) in chunk_result.category_counts {
-            *category_counts.get_mut(&category).unwrap() += count;
+        scd_children += chunk.scd_children_count;
+        for (count, chunk_count) in category_counts.iter_mut().zip(chunk.category_counts) {
+            *count += chunk_count;
         }
     }
 
-    // Step 5: Create a new RecordBatch with combined data
+    // Create a new RecordBatch with the SCD columns added
     let mut fields = population_data.schema().fields().to_vec();
     let mut columns = population_data.columns().to_vec();
-    
-    // Add SCD fields
     fields.push(Arc::new(Field::new("is_scd", DataType::Boolean, true)));
     fields.push(Arc::new(Field::new("first_scd_date", DataType::Date32, true)));
-    
-    // Add category fields
-    for category in &all_categories {
-        let field_name = format!("scd_category_{category}");
-        fields.push(Arc::new(Field::new(&field_name, DataType::Boolean, true)));
-    }
-    
-    // Create schema
-    let schema = Arc::new(Schema::new(fields));
-    
-    // Add SCD columns
     columns.push(Arc::new(BooleanArray::from(is_scd)));
     columns.push(Arc::new(Date32Array::from(first_scd_date)));
-    
-    // Add category columns
-    for category in &all_categories {
-        columns.push(Arc::new(BooleanArray::from(disease_categories.get(category).unwrap().clone())));
+    for (category, values) in categories.iter().zip(category_values) {
+        fields.push(Arc::new(Field::new(
+            format!("scd_category_{category}"),
+            DataType::Boolean,
+            true,
+        )));
+        columns.push(Arc::new(BooleanArray::from(values)));
     }
-    
-    // Create batch
-    let result_batch = RecordBatch::try_new(schema, columns)
+
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        population_data.schema().metadata().clone(),
+    ));
+    let batch = RecordBatch::try_new(schema, columns)
         .map_err(|e| IdsError::Data(format!("Error creating population SCD batch: {e}")))?;
-    
-    // Step 6: Create summary result
-    let scd_percentage = if num_rows > 0 {
-        (scd_children_count as f64 / num_rows as f64) * 100.0
-    } else {
-        0.0
-    };
-    
-    let result = PopulationScdResult {
-        total_children: num_rows,
-        scd_children: scd_children_count,
-        scd_percentage,
-        category_counts,
-    };
-    
-    Ok((result_batch, result))
+
+    Ok(AnnotatedPopulation {
+        batch,
+        scd_children,
+        category_counts: categories.iter().cloned().zip(category_counts).collect(),
+    })
 }
 
 /// Process LPR data and identify SCD children in a population
@@ -364,6 +464,7 @@ pub fn process_lpr_and_identify_scd(
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
+        diagnosis_filter: config.diagnosis_filter,
     };
     
     let processed_data = process_lpr_data(
@@ -377,9 +478,16 @@ pub fn process_lpr_and_identify_scd(
     )?;
     
     log::info!("Processed LPR data: {} rows", processed_data.num_rows());
+    let procedures =
+        process_lpr_procedure_data(lpr2_adm, lpr2_bes, lpr3_kontakter, None, &lpr_config)?;
     
     // Step 2: Identify SCD in population
-    identify_scd_in_population(population_data, &processed_data, config)
+    identify_scd_in_population_with_procedures(
+        population_data,
+        &processed_data,
+        procedures.as_ref(),
+        config,
+    )
 }
 
 /// Extract only the children with SCD from the population
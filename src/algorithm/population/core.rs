@@ -2,9 +2,18 @@
 
 use arrow::array::{Array, Date32Array, StringArray};
 use arrow::datatypes::{DataType, Field, Schema};
+use arrow::compute::concat_batches;
 use arrow::record_batch::RecordBatch;
 use chrono::NaiveDate;
+use datafusion::execution::SendableRecordBatchStream;
+use futures::StreamExt;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use crate::data::cache::{read_entry_batch, CacheKey, Cacheable, ResultCache};
+use crate::data::io::ParquetBatchWriter;
+use crate::data::registry::traits::{PnrFilter, RegisterLoader};
 use crate::utils::date_utils;
 
 use crate::error::{IdsError, Result};
@@ -13,6 +22,7 @@ use crate::model::population::{FamilyData, Population};
 use datafusion::logical_expr::Expr;
 
 /// Configuration for population generation
+#[derive(Debug, Clone)]
 pub struct PopulationConfig {
     /// Start year for filtering births (inclusive)
     pub birth_inclusion_start_year: i32,
@@ -24,6 +34,8 @@ pub struct PopulationConfig {
     pub include_death_cause_data: bool,
     /// Whether to include migration register data
     pub include_migration_data: bool,
+    /// Which register wins when BEF and MFR disagree on parents or birth date
+    pub parent_precedence: ParentPrecedence,
 }
 
 impl Default for PopulationConfig {
@@ -34,12 +46,79 @@ impl Default for PopulationConfig {
             include_death_data: true,
             include_death_cause_data: true,
             include_migration_data: true,
+            parent_precedence: ParentPrecedence::default(),
+        }
+    }
+}
+
+/// Precedence rule for combining BEF and MFR parent links
+///
+/// The register with precedence supplies `MOR_ID`, `FAR_ID` and `FOED_DAG` whenever it
+/// has a value; the other register only fills in missing values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParentPrecedence {
+    /// BEF values take precedence, using the last BEF record for each child
+    #[default]
+    BefFirst,
+    /// MFR values take precedence
+    MfrFirst,
+    /// BEF values take precedence, using the most recent BEF record for each child
+    /// (latest `VERSION`, then latest `BOP_VFRA`)
+    MostRecentBef,
+}
+
+impl std::str::FromStr for ParentPrecedence {
+    type Err = IdsError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bef-first" => Ok(Self::BefFirst),
+            "mfr-first" => Ok(Self::MfrFirst),
+            "most-recent-bef" => Ok(Self::MostRecentBef),
+            _ => Err(IdsError::Validation(format!(
+                "Unknown parent precedence '{s}'. Expected one of: bef-first, mfr-first, most-recent-bef"
+            ))),
         }
     }
 }
 
+/// Register a value in the combined population was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParentSource {
+    /// Value taken from BEF
+    Bef,
+    /// Value taken from MFR
+    Mfr,
+}
+
+impl ParentSource {
+    /// Get the string representation used in output columns and reports
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bef => "BEF",
+            Self::Mfr => "MFR",
+        }
+    }
+}
+
+/// A disagreement between BEF and MFR for one child
+#[derive(Debug, Clone, Serialize)]
+pub struct ParentConflict {
+    /// The child's PNR
+    pub pnr: String,
+    /// The disagreeing column (`MOR_ID`, `FAR_ID` or `FOED_DAG`)
+    pub field: &'static str,
+    /// Value in BEF
+    pub bef_value: String,
+    /// Value in MFR
+    pub mfr_value: String,
+    /// Register whose value was kept
+    pub chosen: ParentSource,
+}
+
 /// Summary statistics for population data
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PopulationSummary {
     /// Total records from BEF register
     pub total_bef_records: usize,
@@ -63,6 +142,37 @@ pub struct PopulationSummary {
     pub records_only_in_bef: usize,
     /// Number of records only in MFR
     pub records_only_in_mfr: usize,
+    /// Children where BEF and MFR disagree on a parent or the birth date
+    pub parent_conflicts: Vec<ParentConflict>,
+}
+
+/// A [`ParentConflict`] as read from a cached population summary
+#[derive(Deserialize)]
+struct StoredParentConflict {
+    pnr: String,
+    field: String,
+    bef_value: String,
+    mfr_value: String,
+    chosen: ParentSource,
+}
+
+impl<'de> Deserialize<'de> for ParentConflict {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let stored = StoredParentConflict::deserialize(deserializer)?;
+        let field = ["MOR_ID", "FAR_ID", "FOED_DAG"]
+            .into_iter()
+            .find(|field| *field == stored.field)
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("unknown conflict column {}", stored.field))
+            })?;
+        Ok(Self {
+            pnr: stored.pnr,
+            field,
+            bef_value: stored.bef_value,
+            mfr_value: stored.mfr_value,
+            chosen: stored.chosen,
+        })
+    }
 }
 
 /// Creates a filter expression for birth year range
@@ -145,7 +255,7 @@ pub fn extract_mfr_children(
     config: &PopulationConfig,
 ) -> Result<RecordBatch> {
     // Log before filtering
-    log::info!(
+    log::debug!(
         "MFR data before filtering: {} rows, year range: {} to {}", 
         mfr_data.num_rows(),
         config.birth_inclusion_start_year,
@@ -169,7 +279,7 @@ pub fn extract_mfr_children(
     )?;
     
     // Log after filtering
-    log::info!("MFR data after filtering: {} rows", filtered_batch.num_rows());
+    log::debug!("MFR data after filtering: {} rows", filtered_batch.num_rows());
 
     // Need to standardize column names
     let mut columns = Vec::with_capacity(5);
@@ -228,9 +338,15 @@ pub fn extract_mfr_children(
 }
 
 /// Combine children data from BEF and MFR
+///
+/// Values are chosen per column according to `precedence`; disagreements where both
+/// registers have a value are recorded in `PopulationSummary::parent_conflicts`. The
+/// combined batch has a `PARENT_SOURCE` column recording where the parent links came
+/// from: `BEF`, `MFR`, `MIXED` (one parent from each), or null without parents.
 pub fn combine_children_data(
     bef_children: &RecordBatch,
     mfr_children: &RecordBatch,
+    precedence: ParentPrecedence,
 ) -> Result<(RecordBatch, PopulationSummary)> {
     // Log initial data before combining
     log::info!("Combining data: {} BEF records, {} MFR records", 
@@ -249,6 +365,7 @@ pub fn combine_children_data(
         combined_missing_mother: 0,
         records_only_in_bef: 0,
         records_only_in_mfr: 0,
+        parent_conflicts: Vec::new(),
     };
 
     // Extract PNRs from both datasets for matching
@@ -310,7 +427,16 @@ pub fn combine_children_data(
     let mut bef_index: HashMap<String, usize> = HashMap::with_capacity(bef_pnr_array.len());
     for i in 0..bef_pnr_array.len() {
         if !bef_pnr_array.is_null(i) {
-            bef_index.insert(bef_pnr_array.value(i).to_string(), i);
+            let pnr = bef_pnr_array.value(i).to_string();
+            match bef_index.get(&pnr) {
+                Some(&existing)
+                    if precedence == ParentPrecedence::MostRecentBef
+                        && bef_record_recency(bef_children, i)?
+                            < bef_record_recency(bef_children, existing)? => {}
+                _ => {
+                    bef_index.insert(pnr, i);
+                }
+            }
         }
     }
 
@@ -331,44 +457,74 @@ pub fn combine_children_data(
     let mut combined_mother_ids = Vec::with_capacity(total_combined_records);
     let mut combined_family_ids = Vec::with_capacity(total_combined_records);
 
+    let mut combined_parent_sources = Vec::with_capacity(total_combined_records);
+    let mut parent_conflicts = Vec::new();
+
     for pnr in all_pnrs {
-        let mut birth_date = None;
-        let mut father_id = None;
-        let mut mother_id = None;
-        let mut family_id = None;
-
-        // Check if in BEF data using the index (O(1) lookup)
-        if let Some(&i) = bef_index.get(&pnr) {
-            // Get BEF values
-            birth_date = get_date_value(bef_children, "FOED_DAG", i)?;
-            father_id = get_string_value(bef_children, "FAR_ID", i)?;
-            mother_id = get_string_value(bef_children, "MOR_ID", i)?;
-            family_id = get_string_value(bef_children, "FAMILIE_ID", i)?;
+        let bef_row = bef_index.get(&pnr).copied();
+        let mfr_row = mfr_index.get(&pnr).copied();
+
+        let bef_birth_date = bef_row
+            .map(|i| get_date_value(bef_children, "FOED_DAG", i))
+            .transpose()?
+            .flatten();
+        let mfr_birth_date = mfr_row
+            .map(|i| get_date_value(mfr_children, "FOED_DAG", i))
+            .transpose()?
+            .flatten();
+        let family_id = bef_row
+            .map(|i| get_string_value(bef_children, "FAMILIE_ID", i))
+            .transpose()?
+            .flatten();
+
+        let mut parent_ids = Vec::with_capacity(2);
+        for field in ["FAR_ID", "MOR_ID"] {
+            let bef_value = bef_row
+                .map(|i| get_string_value(bef_children, field, i))
+                .transpose()?
+                .flatten();
+            let mfr_value = mfr_row
+                .map(|i| get_string_value(mfr_children, field, i))
+                .transpose()?
+                .flatten();
+            parent_ids.push(resolve_value(
+                &pnr,
+                field,
+                bef_value,
+                mfr_value,
+                precedence,
+                &mut parent_conflicts,
+            ));
         }
 
-        // Check if in MFR data using the index (O(1) lookup)
-        if let Some(&i) = mfr_index.get(&pnr) {
-            // Fill in from MFR if missing in BEF
-            if birth_date.is_none() {
-                birth_date = get_date_value(mfr_children, "FOED_DAG", i)?;
-            }
-            if father_id.is_none() {
-                father_id = get_string_value(mfr_children, "FAR_ID", i)?;
-            }
-            if mother_id.is_none() {
-                mother_id = get_string_value(mfr_children, "MOR_ID", i)?;
-            }
-            // Note: MFR doesn't have FAMILIE_ID, so no need to check
-        }
+        let birth_date = resolve_value(
+            &pnr,
+            "FOED_DAG",
+            bef_birth_date,
+            mfr_birth_date,
+            precedence,
+            &mut parent_conflicts,
+        );
+
+        let mother = parent_ids.pop().flatten();
+        let father = parent_ids.pop().flatten();
+        let parent_source = match (father.as_ref().map(|p| p.1), mother.as_ref().map(|p| p.1)) {
+            (None, None) => None,
+            (Some(a), Some(b)) if a != b => Some("MIXED"),
+            (Some(source), _) | (_, Some(source)) => Some(source.as_str()),
+        };
 
         // Add to combined arrays
         combined_pnrs.push(Some(pnr));
-        combined_birth_dates.push(birth_date);
-        combined_father_ids.push(father_id);
-        combined_mother_ids.push(mother_id);
+        combined_birth_dates.push(birth_date.map(|(date, _)| date));
+        combined_father_ids.push(father.map(|(id, _)| id));
+        combined_mother_ids.push(mother.map(|(id, _)| id));
         combined_family_ids.push(family_id);
+        combined_parent_sources.push(parent_source);
     }
 
+    log::info!("Found {} BEF/MFR disagreements on parents or birth date", parent_conflicts.len());
+
     // Count missing values in combined data
     let combined_missing_father = combined_father_ids.iter().filter(|id| id.is_none()).count();
     let combined_missing_mother = combined_mother_ids.iter().filter(|id| id.is_none()).count();
@@ -380,6 +536,7 @@ pub fn combine_children_data(
         combined_missing_mother,
         records_only_in_bef,
         records_only_in_mfr,
+        parent_conflicts,
         ..summary_before
     };
 
@@ -404,6 +561,7 @@ pub fn combine_children_data(
     let father_id_array = StringArray::from(combined_father_ids);
     let mother_id_array = StringArray::from(combined_mother_ids);
     let family_id_array = StringArray::from(combined_family_ids);
+    let parent_source_array = StringArray::from(combined_parent_sources);
 
     // Create schema
     let schema = Schema::new(vec![
@@ -412,6 +570,7 @@ pub fn combine_children_data(
         Field::new("FAR_ID", DataType::Utf8, true),
         Field::new("MOR_ID", DataType::Utf8, true),
         Field::new("FAMILIE_ID", DataType::Utf8, true),
+        Field::new("PARENT_SOURCE", DataType::Utf8, true),
     ]);
 
     // Create RecordBatch
@@ -423,6 +582,7 @@ pub fn combine_children_data(
             std::sync::Arc::new(father_id_array),
             std::sync::Arc::new(mother_id_array),
             std::sync::Arc::new(family_id_array),
+            std::sync::Arc::new(parent_source_array),
         ],
     )
     .map_err(|e| IdsError::Data(format!("Error creating combined record batch: {e}")))?;
@@ -432,7 +592,7 @@ pub fn combine_children_data(
 
 /// Process parent data from BEF data
 pub fn process_parents(bef_data: &RecordBatch) -> Result<HashMap<Pnr, NaiveDate>> {
-    log::info!("Processing parent data from {} BEF records", bef_data.num_rows());
+    log::debug!("Processing parent data from {} BEF records", bef_data.num_rows());
     
     let pnr_col = bef_data
         .column_by_name("PNR")
@@ -495,9 +655,9 @@ pub fn process_parents(bef_data: &RecordBatch) -> Result<HashMap<Pnr, NaiveDate>
         }
     }
     
-    log::info!("Processed {} parent records: {} with valid dates, {} with missing dates, {} empty PNRs, {} null PNRs", 
+    log::debug!("Processed {} parent records: {} with valid dates, {} with missing dates, {} empty PNRs, {} null PNRs", 
         pnr_array.len(), valid_dates, missing_dates, empty_pnrs, null_pnrs);
-    log::info!("Parent map size: {} unique parents", parent_map.len());
+    log::debug!("Parent map size: {} unique parents", parent_map.len());
 
     Ok(parent_map)
 }
@@ -539,6 +699,62 @@ pub fn create_family_data(
     FamilyData::to_record_batch(&family_data)
 }
 
+/// Choose between a BEF and an MFR value according to the precedence rule
+///
+/// Records a conflict when both registers have a value and they differ.
+fn resolve_value<T: PartialEq + std::fmt::Display>(
+    pnr: &str,
+    field: &'static str,
+    bef_value: Option<T>,
+    mfr_value: Option<T>,
+    precedence: ParentPrecedence,
+    conflicts: &mut Vec<ParentConflict>,
+) -> Option<(T, ParentSource)> {
+    let preferred = match precedence {
+        ParentPrecedence::BefFirst | ParentPrecedence::MostRecentBef => ParentSource::Bef,
+        ParentPrecedence::MfrFirst => ParentSource::Mfr,
+    };
+
+    match (bef_value, mfr_value) {
+        (Some(bef), Some(mfr)) => {
+            if bef != mfr {
+                conflicts.push(ParentConflict {
+                    pnr: pnr.to_string(),
+                    field,
+                    bef_value: bef.to_string(),
+                    mfr_value: mfr.to_string(),
+                    chosen: preferred,
+                });
+            }
+            match preferred {
+                ParentSource::Bef => Some((bef, ParentSource::Bef)),
+                ParentSource::Mfr => Some((mfr, ParentSource::Mfr)),
+            }
+        }
+        (Some(bef), None) => Some((bef, ParentSource::Bef)),
+        (None, Some(mfr)) => Some((mfr, ParentSource::Mfr)),
+        (None, None) => None,
+    }
+}
+
+/// Sort key for the recency of a BEF record: `VERSION`, then `BOP_VFRA`
+fn bef_record_recency(
+    batch: &RecordBatch,
+    row_index: usize,
+) -> Result<(Option<String>, Option<NaiveDate>)> {
+    let version = if batch.column_by_name("VERSION").is_some() {
+        get_string_value(batch, "VERSION", row_index)?
+    } else {
+        None
+    };
+    let valid_from = if batch.column_by_name("BOP_VFRA").is_some() {
+        get_date_value(batch, "BOP_VFRA", row_index)?
+    } else {
+        None
+    };
+    Ok((version, valid_from))
+}
+
 /// Count null values in a column
 fn count_null_values(batch: &RecordBatch, column_name: &str) -> Result<usize> {
     let col = batch
@@ -607,13 +823,412 @@ pub fn generate_population(
     let mfr_children = extract_mfr_children(mfr_data, config)?;
 
     // Combine children data
-    let (combined_children, summary) = combine_children_data(&bef_children, &mfr_children)?;
+    let (combined_children, summary) =
+        combine_children_data(&bef_children, &mfr_children, config.parent_precedence)?;
 
     // Process parent data
     let parent_data = process_parents(bef_data)?;
 
+    let family_data = assemble_family_data(&combined_children, &parent_data)?;
+    Ok((family_data, summary))
+}
+
+/// Population generated by [`generate_population_streaming`]
+pub struct StreamedPopulation {
+    /// Children linked to their parents
+    pub family_data: RecordBatch,
+    /// Summary of combining the BEF and MFR children
+    pub summary: PopulationSummary,
+    /// PNRs of the population's children and parents that are present in BEF
+    pub bef_pnrs: HashSet<String>,
+}
+
+/// Generate population data without loading BEF or MFR in full
+///
+/// Both registers are streamed batch by batch, keeping only the children born in the
+/// inclusion years. BEF is then streamed a second time, filtered to the children and
+/// their parents, for the parents' birth dates. The result is the same as
+/// [`generate_population`] on the complete registers.
+pub async fn generate_population_streaming(
+    bef: &impl RegisterLoader,
+    bef_path: &str,
+    mfr: &impl RegisterLoader,
+    mfr_path: &str,
+    config: &PopulationConfig,
+) -> Result<StreamedPopulation> {
+    let bef_children = collect_children(bef.load_stream(bef_path, None).await?, "BEF", |batch| {
+        filter_by_birth_year(
+            batch,
+            "FOED_DAG",
+            config.birth_inclusion_start_year,
+            config.birth_inclusion_end_year,
+        )
+    })
+    .await?;
+    let mfr_children = collect_children(mfr.load_stream(mfr_path, None).await?, "MFR", |batch| {
+        extract_mfr_children(batch, config)
+    })
+    .await?;
+
+    let (combined_children, summary) =
+        combine_children_data(&bef_children, &mfr_children, config.parent_precedence)?;
+
+    // Parent birth dates from the BEF records of the population's families only
+    let mut family_pnrs = HashSet::new();
+    for column in ["PNR", "FAR_ID", "MOR_ID"] {
+        if let Some(pnrs) = combined_children
+            .column_by_name(column)
+            .and_then(|col| col.as_any().downcast_ref::<StringArray>())
+        {
+            family_pnrs.extend(
+                pnrs.iter()
+                    .flatten()
+                    .filter(|pnr| !pnr.is_empty())
+                    .map(ToString::to_string),
+            );
+        }
+    }
+    log::info!("Reading BEF records of {} children and parents", family_pnrs.len());
+
+    let filter = PnrFilter::new(family_pnrs);
+    let mut stream = bef.load_stream(bef_path, Some(&filter)).await?;
+    let mut parent_data = HashMap::new();
+    let mut bef_pnrs = HashSet::new();
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        for (pnr, birth_date) in process_parents(&batch)? {
+            parent_data.entry(pnr).or_insert(birth_date);
+        }
+        if let Some(pnrs) = batch
+            .column_by_name("PNR")
+            .and_then(|col| col.as_any().downcast_ref::<StringArray>())
+        {
+            bef_pnrs.extend(pnrs.iter().flatten().map(ToString::to_string));
+        }
+    }
+    log::info!("Parent map size: {} unique parents", parent_data.len());
+
+    let family_data = assemble_family_data(&combined_children, &parent_data)?;
+    Ok(StreamedPopulation {
+        family_data,
+        summary,
+        bef_pnrs,
+    })
+}
+
+impl Cacheable for StreamedPopulation {
+    fn write_entry(&self, dir: &Path) -> Result<()> {
+        let mut family =
+            ParquetBatchWriter::create(dir.join("family.parquet"), self.family_data.schema())?;
+        family.write(&self.family_data)?;
+        family.finish()?;
+
+        let bef_pnrs = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("PNR", DataType::Utf8, false)])),
+            vec![Arc::new(StringArray::from_iter_values(&self.bef_pnrs))],
+        )?;
+        let mut writer = ParquetBatchWriter::create(dir.join("bef_pnrs.parquet"), bef_pnrs.schema())?;
+        writer.write(&bef_pnrs)?;
+        writer.finish()?;
+
+        let summary = serde_json::to_string(&self.summary)
+            .map_err(|e| IdsError::Data(format!("Failed to serialize population summary: {e}")))?;
+        std::fs::write(dir.join("summary.json"), summary)?;
+        Ok(())
+    }
+
+    fn read_entry(dir: &Path) -> Result<Self> {
+        let family_data = read_entry_batch(&dir.join("family.parquet"))?;
+        let bef_pnrs = read_entry_batch(&dir.join("bef_pnrs.parquet"))?;
+        let bef_pnrs = bef_pnrs
+            .column_by_name("PNR")
+            .and_then(|col| col.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| IdsError::Data("Cached BEF PNRs have no PNR column".to_string()))?
+            .iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect();
+        let summary = serde_json::from_str(&std::fs::read_to_string(dir.join("summary.json"))?)
+            .map_err(|e| IdsError::Data(format!("Invalid cached population summary: {e}")))?;
+        Ok(Self {
+            family_data,
+            summary,
+            bef_pnrs,
+        })
+    }
+}
+
+/// [`generate_population_streaming`], reusing a population from the result cache
+///
+/// The cached population is keyed on the BEF and MFR files and the configuration.
+pub async fn generate_population_cached(
+    bef: &impl RegisterLoader,
+    bef_path: &str,
+    mfr: &impl RegisterLoader,
+    mfr_path: &str,
+    config: &PopulationConfig,
+    cache: &ResultCache,
+) -> Result<StreamedPopulation> {
+    if !cache.is_enabled() {
+        return generate_population_streaming(bef, bef_path, mfr, mfr_path, config).await;
+    }
+
+    let key = CacheKey::new("population")
+        .with_value("config", config)
+        .with_files("BEF", Path::new(bef_path))?
+        .with_files("MFR", Path::new(mfr_path))?;
+    if let Some(population) = cache.get(&key) {
+        return Ok(population);
+    }
+    let population = generate_population_streaming(bef, bef_path, mfr, mfr_path, config).await?;
+    cache.put(&key, &population);
+    Ok(population)
+}
+
+/// Apply `extract` to every batch of a register stream and combine the extracted children
+///
+/// Only the extracted children are kept, so memory grows with the population rather than
+/// the register.
+async fn collect_children(
+    mut stream: SendableRecordBatchStream,
+    register: &str,
+    extract: impl Fn(&RecordBatch) -> Result<RecordBatch>,
+) -> Result<RecordBatch> {
+    let mut rows = 0;
+    let mut children = Vec::new();
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        rows += batch.num_rows();
+        children.push(extract(&batch)?);
+    }
+    if rows == 0 {
+        return Err(IdsError::Data(format!("No {register} data found")));
+    }
+
+    let schema = children[0].schema();
+    let children = concat_batches(&schema, &children)
+        .map_err(|e| IdsError::Data(format!("Failed to combine {register} children: {e}")))?;
+    log::info!(
+        "{register}: {} of {rows} records born in the inclusion years",
+        children.num_rows()
+    );
+    Ok(children)
+}
+
+/// Link the combined children to their parents and carry over the parent provenance
+fn assemble_family_data(
+    combined_children: &RecordBatch,
+    parent_data: &HashMap<Pnr, NaiveDate>,
+) -> Result<RecordBatch> {
     // Create family data
-    let family_data = create_family_data(&combined_children, &parent_data)?;
+    let family_data = create_family_data(combined_children, parent_data)?;
 
-    Ok((family_data, summary))
-}
\ No newline at end of file
+    // Carry the parent provenance over to the family data
+    attach_parent_source(&family_data, combined_children)
+}
+
+/// Append the `PARENT_SOURCE` column from the combined children to the family data
+fn attach_parent_source(family_data: &RecordBatch, combined_children: &RecordBatch) -> Result<RecordBatch> {
+    let string_column = |batch: &RecordBatch, name: &str| -> Result<StringArray> {
+        batch
+            .column_by_name(name)
+            .and_then(|col| col.as_any().downcast_ref::<StringArray>())
+            .cloned()
+            .ok_or_else(|| IdsError::Data(format!("Missing {name} string column")))
+    };
+
+    let child_pnrs = string_column(combined_children, "PNR")?;
+    let child_sources = string_column(combined_children, "PARENT_SOURCE")?;
+    let sources: HashMap<&str, &str> = (0..child_pnrs.len())
+        .filter(|&i| !child_pnrs.is_null(i) && !child_sources.is_null(i))
+        .map(|i| (child_pnrs.value(i), child_sources.value(i)))
+        .collect();
+
+    let family_pnrs = string_column(family_data, "PNR")?;
+    let parent_source: StringArray = (0..family_pnrs.len())
+        .map(|i| sources.get(family_pnrs.value(i)).copied())
+        .collect();
+
+    let mut fields: Vec<Field> = family_data
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.as_ref().clone())
+        .collect();
+    fields.push(Field::new("PARENT_SOURCE", DataType::Utf8, true));
+
+    let mut columns = family_data.columns().to_vec();
+    columns.push(std::sync::Arc::new(parent_source));
+
+    RecordBatch::try_new(std::sync::Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| IdsError::Data(format!("Error adding PARENT_SOURCE column: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// A child row of (PNR, birth date, father, mother)
+    type ChildRow<'a> = (&'a str, Option<NaiveDate>, Option<&'a str>, Option<&'a str>);
+
+    /// Children batch with an optional `VERSION` column for BEF recency
+    fn children(rows: &[ChildRow], versions: Option<&[&str]>) -> RecordBatch {
+        let mut fields = vec![
+            Field::new("PNR", DataType::Utf8, true),
+            Field::new("FOED_DAG", DataType::Date32, true),
+            Field::new("FAR_ID", DataType::Utf8, true),
+            Field::new("MOR_ID", DataType::Utf8, true),
+            Field::new("FAMILIE_ID", DataType::Utf8, true),
+        ];
+        let mut columns: Vec<arrow::array::ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.0))),
+            Arc::new(Date32Array::from(
+                rows.iter()
+                    .map(|r| r.1.map(date_utils::date_to_days_since_epoch))
+                    .collect::<Vec<_>>(),
+            )),
+            Arc::new(StringArray::from(rows.iter().map(|r| r.2).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(rows.iter().map(|r| r.3).collect::<Vec<_>>())),
+            Arc::new(StringArray::from(vec![None::<&str>; rows.len()])),
+        ];
+        if let Some(versions) = versions {
+            fields.push(Field::new("VERSION", DataType::Utf8, true));
+            columns.push(Arc::new(StringArray::from(versions.to_vec())));
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    /// The combined row for a PNR as (birth date, father, mother, parent source)
+    fn combined_row(
+        batch: &RecordBatch,
+        pnr: &str,
+    ) -> (Option<NaiveDate>, Option<String>, Option<String>, Option<String>) {
+        let pnrs = batch
+            .column_by_name("PNR")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let i = (0..batch.num_rows()).find(|&i| pnrs.value(i) == pnr).unwrap();
+        (
+            get_date_value(batch, "FOED_DAG", i).unwrap(),
+            get_string_value(batch, "FAR_ID", i).unwrap(),
+            get_string_value(batch, "MOR_ID", i).unwrap(),
+            get_string_value(batch, "PARENT_SOURCE", i).unwrap(),
+        )
+    }
+
+    fn owned(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn test_parent_precedence_from_str() {
+        assert_eq!("bef-first".parse::<ParentPrecedence>().unwrap(), ParentPrecedence::BefFirst);
+        assert_eq!("mfr-first".parse::<ParentPrecedence>().unwrap(), ParentPrecedence::MfrFirst);
+        assert_eq!(
+            "most-recent-bef".parse::<ParentPrecedence>().unwrap(),
+            ParentPrecedence::MostRecentBef
+        );
+        assert!("bef".parse::<ParentPrecedence>().is_err());
+    }
+
+    #[test]
+    fn test_bef_first_keeps_bef_values_and_records_conflicts() {
+        let bef = children(&[("C", Some(date(2000, 1, 1)), Some("F1"), Some("M"))], None);
+        let mfr = children(&[("C", Some(date(2000, 1, 2)), Some("F2"), Some("M"))], None);
+
+        let (combined, summary) =
+            combine_children_data(&bef, &mfr, ParentPrecedence::BefFirst).unwrap();
+        assert_eq!(
+            combined_row(&combined, "C"),
+            (Some(date(2000, 1, 1)), owned("F1"), owned("M"), owned("BEF"))
+        );
+
+        let conflicts: Vec<(&str, &str, &str, ParentSource)> = summary
+            .parent_conflicts
+            .iter()
+            .map(|c| (c.field, c.bef_value.as_str(), c.mfr_value.as_str(), c.chosen))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("FAR_ID", "F1", "F2", ParentSource::Bef),
+                ("FOED_DAG", "2000-01-01", "2000-01-02", ParentSource::Bef),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mfr_first_keeps_mfr_values() {
+        let bef = children(&[("C", Some(date(2000, 1, 1)), Some("F1"), Some("M"))], None);
+        let mfr = children(&[("C", Some(date(2000, 1, 2)), Some("F2"), Some("M"))], None);
+
+        let (combined, summary) =
+            combine_children_data(&bef, &mfr, ParentPrecedence::MfrFirst).unwrap();
+        assert_eq!(
+            combined_row(&combined, "C"),
+            (Some(date(2000, 1, 2)), owned("F2"), owned("M"), owned("MFR"))
+        );
+        assert!(summary
+            .parent_conflicts
+            .iter()
+            .all(|c| c.chosen == ParentSource::Mfr));
+    }
+
+    #[test]
+    fn test_missing_values_are_filled_from_other_register() {
+        let bef = children(
+            &[
+                ("C", Some(date(2000, 1, 1)), None, Some("M")),
+                ("B", Some(date(2001, 1, 1)), Some("F"), None),
+            ],
+            None,
+        );
+        let mfr = children(
+            &[
+                ("C", None, Some("F"), None),
+                ("D", Some(date(2002, 1, 1)), None, Some("M")),
+            ],
+            None,
+        );
+
+        let (combined, summary) =
+            combine_children_data(&bef, &mfr, ParentPrecedence::BefFirst).unwrap();
+        assert!(summary.parent_conflicts.is_empty());
+        assert_eq!(combined.num_rows(), 3);
+        assert_eq!((summary.records_only_in_bef, summary.records_only_in_mfr), (1, 1));
+        assert_eq!(
+            combined_row(&combined, "C"),
+            (Some(date(2000, 1, 1)), owned("F"), owned("M"), owned("MIXED"))
+        );
+        assert_eq!(
+            combined_row(&combined, "D"),
+            (Some(date(2002, 1, 1)), None, owned("M"), owned("MFR"))
+        );
+        assert_eq!(summary.combined_missing_mother, 1);
+    }
+
+    #[test]
+    fn test_most_recent_bef_uses_latest_version() {
+        let bef = children(
+            &[
+                ("C", Some(date(2000, 1, 1)), Some("F_NEW"), Some("M")),
+                ("C", Some(date(2000, 1, 1)), Some("F_OLD"), Some("M")),
+            ],
+            Some(&["2020", "2010"]),
+        );
+        let mfr = children(&[], None);
+
+        let (last, _) = combine_children_data(&bef, &mfr, ParentPrecedence::BefFirst).unwrap();
+        assert_eq!(combined_row(&last, "C").1, owned("F_OLD"));
+
+        let (recent, _) =
+            combine_children_data(&bef, &mfr, ParentPrecedence::MostRecentBef).unwrap();
+        assert_eq!(combined_row(&recent, "C").1, owned("F_NEW"));
+    }
+}
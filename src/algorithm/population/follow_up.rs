@@ -0,0 +1,514 @@
+//! Follow-up, censoring and person-time computation
+//!
+//! This module turns an integrated population (see [`integrate_population_data`])
+//! into per-person follow-up: an entry date given by an [`EntryRule`], an exit date
+//! taken as the earliest of outcome, death, emigration, loss to follow-up or end of
+//! study, and the resulting person-time. Periods spent abroad between an emigration
+//! and a later re-immigration are not counted as time at risk.
+//!
+//! [`integrate_population_data`]: crate::algorithm::population::integrate_population_data
+
+use arrow::array::{Array, ArrayRef, Date32Array, Float64Array, Int32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{IdsError, Result};
+use crate::utils::date_utils::{date_to_days_since_epoch, extract_date_from_array};
+
+/// Average number of days in a year, used to convert person-days to person-years
+pub const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Rule determining when a person enters follow-up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryRule {
+    /// Follow-up starts at the date of birth (`FOED_DAG`)
+    Birth,
+    /// Follow-up starts at a per-person index date read from the named column
+    IndexDate(String),
+    /// Follow-up starts at the same calendar date for everyone
+    FixedDate(NaiveDate),
+}
+
+/// Reason a person left follow-up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExitReason {
+    /// The outcome of interest occurred
+    Outcome,
+    /// Death (DOD)
+    Death,
+    /// Emigration (VNDS) without return before the end of follow-up
+    Emigration,
+    /// Lost to follow-up for other reasons
+    LossToFollowUp,
+    /// Administrative censoring at the end of the study
+    EndOfStudy,
+}
+
+impl ExitReason {
+    /// Get the string representation used in output columns
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Outcome => "OUTCOME",
+            Self::Death => "DEATH",
+            Self::Emigration => "EMIGRATION",
+            Self::LossToFollowUp => "LOSS_TO_FOLLOW_UP",
+            Self::EndOfStudy => "END_OF_STUDY",
+        }
+    }
+}
+
+/// Configuration for follow-up computation
+#[derive(Debug, Clone)]
+pub struct FollowUpConfig {
+    /// Rule determining the entry date
+    pub entry_rule: EntryRule,
+    /// Last day of the study period (administrative censoring)
+    pub study_end_date: NaiveDate,
+    /// Column in the population data holding the death date
+    pub death_date_column: String,
+    /// Optional column in the population data holding a loss-to-follow-up date
+    pub loss_to_follow_up_column: Option<String>,
+    /// Whether follow-up ends at the first emigration after entry.
+    ///
+    /// When false, follow-up continues after re-immigration and only the time
+    /// spent abroad is removed from the person-time.
+    pub censor_at_first_emigration: bool,
+}
+
+impl Default for FollowUpConfig {
+    fn default() -> Self {
+        Self {
+            entry_rule: EntryRule::Birth,
+            study_end_date: NaiveDate::from_ymd_opt(2022, 12, 31).unwrap(),
+            death_date_column: "DEATH_DATE".to_string(),
+            loss_to_follow_up_column: None,
+            censor_at_first_emigration: true,
+        }
+    }
+}
+
+/// A single migration event, in days since epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MigrationEvent {
+    pub(crate) date: i32,
+    pub(crate) emigration: bool,
+}
+
+/// Follow-up of a single person, in days since epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FollowUp {
+    entry: i32,
+    exit: i32,
+    reason: ExitReason,
+    days_abroad: i32,
+}
+
+impl FollowUp {
+    const fn person_days(&self) -> i32 {
+        self.exit - self.entry - self.days_abroad
+    }
+}
+
+/// Compute follow-up and person-time for each person in the population
+///
+/// The population batch must contain `PNR` and `FOED_DAG`, and should contain the
+/// columns added by `integrate_population_data` (at least the death date column).
+/// The full migration history is read from the standardized VNDS batches
+/// (`PNR`, `MIGRATION_TYPE`, `MIGRATION_DATE`), since the integrated population only
+/// keeps the most recent migration event. Outcome dates are given per PNR.
+///
+/// The output has one row per person with `PNR`, `ENTRY_DATE`, `EXIT_DATE`,
+/// `EXIT_REASON`, `DAYS_ABROAD`, `PERSON_DAYS` and `PERSON_YEARS`. People whose exit
+/// falls before entry (e.g. died before the index date) get zero person-time.
+pub fn compute_follow_up(
+    population_data: &RecordBatch,
+    migration_data: Option<&[RecordBatch]>,
+    outcome_dates: Option<&HashMap<String, NaiveDate>>,
+    config: &FollowUpConfig,
+) -> Result<RecordBatch> {
+    let pnr_array = population_data
+        .column_by_name("PNR")
+        .ok_or_else(|| IdsError::Data("PNR column not found in population data".to_string()))?
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| IdsError::Data("PNR column is not a StringArray".to_string()))?;
+
+    let entry_column = match &config.entry_rule {
+        EntryRule::Birth => Some(required_column(population_data, "FOED_DAG")?),
+        EntryRule::IndexDate(column) => Some(required_column(population_data, column)?),
+        EntryRule::FixedDate(_) => None,
+    };
+    let death_column = population_data.column_by_name(&config.death_date_column);
+    let loss_column = match &config.loss_to_follow_up_column {
+        Some(column) => Some(required_column(population_data, column)?),
+        None => None,
+    };
+
+    let migrations = match migration_data {
+        Some(batches) => collect_migration_events(batches)?,
+        None => HashMap::new(),
+    };
+
+    let study_end = date_to_days_since_epoch(config.study_end_date);
+    let num_rows = pnr_array.len();
+
+    let mut pnrs = Vec::with_capacity(num_rows);
+    let mut entry_dates = Vec::with_capacity(num_rows);
+    let mut exit_dates = Vec::with_capacity(num_rows);
+    let mut exit_reasons = Vec::with_capacity(num_rows);
+    let mut days_abroad = Vec::with_capacity(num_rows);
+    let mut person_days = Vec::with_capacity(num_rows);
+    let mut person_years = Vec::with_capacity(num_rows);
+
+    for i in 0..num_rows {
+        if pnr_array.is_null(i) {
+            continue;
+        }
+        let pnr = pnr_array.value(i);
+
+        let entry = match (&config.entry_rule, entry_column) {
+            (EntryRule::FixedDate(date), _) => Some(*date),
+            (_, Some(column)) => extract_date_from_array(column.as_ref(), i),
+            (_, None) => None,
+        };
+
+        // People without an entry date never enter follow-up
+        let Some(entry) = entry.map(date_to_days_since_epoch) else {
+            pnrs.push(pnr.to_string());
+            entry_dates.push(None);
+            exit_dates.push(None);
+            exit_reasons.push(None);
+            days_abroad.push(None);
+            person_days.push(None);
+            person_years.push(None);
+            continue;
+        };
+
+        let mut candidates = Vec::with_capacity(4);
+        if let Some(date) = outcome_dates.and_then(|outcomes| outcomes.get(pnr)) {
+            candidates.push((date_to_days_since_epoch(*date), ExitReason::Outcome));
+        }
+        if let Some(date) = death_column.and_then(|column| extract_date_from_array(column.as_ref(), i)) {
+            candidates.push((date_to_days_since_epoch(date), ExitReason::Death));
+        }
+        if let Some(date) = loss_column.and_then(|column| extract_date_from_array(column.as_ref(), i)) {
+            candidates.push((date_to_days_since_epoch(date), ExitReason::LossToFollowUp));
+        }
+        candidates.push((study_end, ExitReason::EndOfStudy));
+
+        let events = migrations.get(pnr).map_or(&[][..], Vec::as_slice);
+        let follow_up = resolve_follow_up(entry, &candidates, events, config.censor_at_first_emigration);
+
+        pnrs.push(pnr.to_string());
+        entry_dates.push(Some(follow_up.entry));
+        exit_dates.push(Some(follow_up.exit));
+        exit_reasons.push(Some(follow_up.reason.as_str()));
+        days_abroad.push(Some(follow_up.days_abroad));
+        person_days.push(Some(follow_up.person_days()));
+        person_years.push(Some(f64::from(follow_up.person_days()) / DAYS_PER_YEAR));
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("PNR", DataType::Utf8, false),
+        Field::new("ENTRY_DATE", DataType::Date32, true),
+        Field::new("EXIT_DATE", DataType::Date32, true),
+        Field::new("EXIT_REASON", DataType::Utf8, true),
+        Field::new("DAYS_ABROAD", DataType::Int32, true),
+        Field::new("PERSON_DAYS", DataType::Int32, true),
+        Field::new("PERSON_YEARS", DataType::Float64, true),
+    ]);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(pnrs)),
+        Arc::new(Date32Array::from(entry_dates)),
+        Arc::new(Date32Array::from(exit_dates)),
+        Arc::new(StringArray::from(exit_reasons)),
+        Arc::new(Int32Array::from(days_abroad)),
+        Arc::new(Int32Array::from(person_days)),
+        Arc::new(Float64Array::from(person_years)),
+    ];
+
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Look up a column that the configuration requires
+fn required_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| IdsError::Data(format!("{name} column not found in population data")))
+}
+
+/// Collect and sort the full migration history per PNR
+pub(crate) fn collect_migration_events(
+    migration_data: &[RecordBatch],
+) -> Result<HashMap<String, Vec<MigrationEvent>>> {
+    let mut events: HashMap<String, Vec<MigrationEvent>> = HashMap::new();
+
+    for batch in migration_data {
+        let pnr_array = batch
+            .column_by_name("PNR")
+            .ok_or_else(|| IdsError::Data("PNR column not found in migration data".to_string()))?
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| IdsError::Data("PNR column is not a StringArray".to_string()))?;
+
+        let type_array = batch
+            .column_by_name("MIGRATION_TYPE")
+            .ok_or_else(|| {
+                IdsError::Data("MIGRATION_TYPE column not found in migration data".to_string())
+            })?
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| {
+                IdsError::Data("MIGRATION_TYPE column is not a StringArray".to_string())
+            })?;
+
+        let date_column = batch.column_by_name("MIGRATION_DATE").ok_or_else(|| {
+            IdsError::Data("MIGRATION_DATE column not found in migration data".to_string())
+        })?;
+
+        for i in 0..batch.num_rows() {
+            if pnr_array.is_null(i) || type_array.is_null(i) {
+                continue;
+            }
+            let emigration = match type_array.value(i) {
+                "OUT" => true,
+                "IN" => false,
+                _ => continue,
+            };
+            let Some(date) = extract_date_from_array(date_column.as_ref(), i) else {
+                continue;
+            };
+
+            events
+                .entry(pnr_array.value(i).to_string())
+                .or_default()
+                .push(MigrationEvent {
+                    date: date_to_days_since_epoch(date),
+                    emigration,
+                });
+        }
+    }
+
+    // Immigration sorts before emigration on the same day so that a same-day
+    // return does not leave the person abroad
+    for person_events in events.values_mut() {
+        person_events.sort_by_key(|event| (event.date, event.emigration));
+    }
+
+    Ok(events)
+}
+
+/// Resolve entry, exit and time abroad for one person
+///
+/// `candidates` are the potential exit events (end of study is always present) and
+/// `events` the person's migrations sorted by date. Dates are days since epoch.
+fn resolve_follow_up(
+    entry: i32,
+    candidates: &[(i32, ExitReason)],
+    events: &[MigrationEvent],
+    censor_at_first_emigration: bool,
+) -> FollowUp {
+    // Earliest exit among the non-migration events; ties keep the first candidate order
+    let (mut exit, mut reason) = candidates
+        .iter()
+        .copied()
+        .min_by_key(|(date, _)| *date)
+        .unwrap_or((entry, ExitReason::EndOfStudy));
+
+    if exit <= entry {
+        return FollowUp { entry, exit: entry, reason, days_abroad: 0 };
+    }
+
+    // Residence status at entry is given by the last migration on or before entry
+    let mut abroad_since = events
+        .iter()
+        .take_while(|event| event.date <= entry)
+        .last()
+        .filter(|event| event.emigration)
+        .map(|_| entry);
+    let mut days_abroad = 0;
+
+    for event in events.iter().filter(|event| event.date > entry) {
+        if event.date >= exit {
+            break;
+        }
+        match (event.emigration, abroad_since) {
+            (true, None) => {
+                if censor_at_first_emigration {
+                    exit = event.date;
+                    reason = ExitReason::Emigration;
+                    break;
+                }
+                abroad_since = Some(event.date);
+            }
+            (false, Some(left)) => {
+                days_abroad += event.date - left;
+                abroad_since = None;
+            }
+            // Repeated events of the same kind carry no new information
+            _ => {}
+        }
+    }
+
+    // Still abroad when follow-up would end: the person is not at risk after leaving
+    if let Some(left) = abroad_since {
+        exit = left;
+        reason = ExitReason::Emigration;
+    }
+
+    FollowUp { entry, exit, reason, days_abroad }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(date: i32, emigration: bool) -> MigrationEvent {
+        MigrationEvent { date, emigration }
+    }
+
+    #[test]
+    fn test_exit_is_earliest_event() {
+        let candidates = [(1000, ExitReason::EndOfStudy), (400, ExitReason::Death), (600, ExitReason::Outcome)];
+        let follow_up = resolve_follow_up(100, &candidates, &[], true);
+        assert_eq!(follow_up.exit, 400);
+        assert_eq!(follow_up.reason, ExitReason::Death);
+        assert_eq!(follow_up.person_days(), 300);
+    }
+
+    #[test]
+    fn test_censor_at_first_emigration() {
+        let candidates = [(1000, ExitReason::EndOfStudy)];
+        let events = [event(300, true), event(500, false)];
+        let follow_up = resolve_follow_up(100, &candidates, &events, true);
+        assert_eq!(follow_up.exit, 300);
+        assert_eq!(follow_up.reason, ExitReason::Emigration);
+        assert_eq!(follow_up.person_days(), 200);
+    }
+
+    #[test]
+    fn test_time_abroad_is_not_at_risk() {
+        let candidates = [(1000, ExitReason::EndOfStudy)];
+        let events = [event(300, true), event(500, false)];
+        let follow_up = resolve_follow_up(100, &candidates, &events, false);
+        assert_eq!(follow_up.exit, 1000);
+        assert_eq!(follow_up.reason, ExitReason::EndOfStudy);
+        assert_eq!(follow_up.days_abroad, 200);
+        assert_eq!(follow_up.person_days(), 700);
+    }
+
+    #[test]
+    fn test_abroad_at_entry_until_return() {
+        let candidates = [(1000, ExitReason::EndOfStudy)];
+        let events = [event(50, true), event(200, false)];
+        let follow_up = resolve_follow_up(100, &candidates, &events, false);
+        assert_eq!(follow_up.days_abroad, 100);
+        assert_eq!(follow_up.person_days(), 800);
+    }
+
+    #[test]
+    fn test_emigration_without_return() {
+        let candidates = [(1000, ExitReason::EndOfStudy)];
+        let events = [event(300, true), event(400, false), event(700, true)];
+        let follow_up = resolve_follow_up(100, &candidates, &events, false);
+        assert_eq!(follow_up.exit, 700);
+        assert_eq!(follow_up.reason, ExitReason::Emigration);
+        assert_eq!(follow_up.person_days(), 500);
+    }
+
+    #[test]
+    fn test_compute_follow_up_from_batches() {
+        let day =
+            |s: &str| date_to_days_since_epoch(NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap());
+        let born = day("2000-01-01");
+        let population = RecordBatch::try_from_iter(vec![
+            (
+                "PNR",
+                Arc::new(StringArray::from(vec![
+                    "dead", "emigrant", "censored", "unborn",
+                ])) as ArrayRef,
+            ),
+            (
+                "FOED_DAG",
+                Arc::new(Date32Array::from(vec![
+                    Some(born),
+                    Some(born),
+                    Some(born),
+                    None,
+                ])) as ArrayRef,
+            ),
+            (
+                "DEATH_DATE",
+                Arc::new(Date32Array::from(vec![
+                    Some(day("2010-06-01")),
+                    None,
+                    None,
+                    None,
+                ])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        // The emigrant leaves in 2015; the censored person is abroad for a year and returns
+        let migrations = RecordBatch::try_from_iter(vec![
+            (
+                "PNR",
+                Arc::new(StringArray::from(vec!["emigrant", "censored", "censored"])) as ArrayRef,
+            ),
+            (
+                "MIGRATION_TYPE",
+                Arc::new(StringArray::from(vec!["OUT", "OUT", "IN"])) as ArrayRef,
+            ),
+            (
+                "MIGRATION_DATE",
+                Arc::new(Date32Array::from(vec![
+                    day("2015-03-01"),
+                    day("2005-01-01"),
+                    day("2006-01-01"),
+                ])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let config = FollowUpConfig {
+            censor_at_first_emigration: false,
+            ..FollowUpConfig::default()
+        };
+        let result = compute_follow_up(&population, Some(&[migrations]), None, &config).unwrap();
+        assert_eq!(result.num_rows(), 4);
+
+        let exit_dates = result
+            .column_by_name("EXIT_DATE")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        let reasons = result
+            .column_by_name("EXIT_REASON")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let abroad = result
+            .column_by_name("DAYS_ABROAD")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+
+        assert_eq!(exit_dates.value(0), day("2010-06-01"));
+        assert_eq!(reasons.value(0), "DEATH");
+        assert_eq!(exit_dates.value(1), day("2015-03-01"));
+        assert_eq!(reasons.value(1), "EMIGRATION");
+        assert_eq!(exit_dates.value(2), day("2022-12-31"));
+        assert_eq!(reasons.value(2), "END_OF_STUDY");
+        assert_eq!(abroad.value(2), 365);
+        // Without a birth date the person never enters follow-up
+        assert!(exit_dates.is_null(3));
+        assert!(reasons.is_null(3));
+    }
+}
@@ -6,8 +6,19 @@
 pub mod core;
 pub mod integration;
 pub mod classification;
+pub mod follow_up;
+pub mod residency;
+pub mod siblings;
 
 // Re-export common types
-pub use core::{PopulationConfig, PopulationSummary, generate_population};
+pub use core::{
+    ParentConflict, ParentPrecedence, ParentSource, PopulationConfig, PopulationSummary,
+    generate_population,
+};
 pub use integration::integrate_population_data;
 pub use classification::{PopulationScdConfig, PopulationScdResult};
+pub use follow_up::{compute_follow_up, EntryRule, ExitReason, FollowUpConfig};
+pub use residency::{
+    apply_residency_rules, ExclusionReason, ResidencyConfig, ResidencyResult, ResidencyRule,
+};
+pub use siblings::{compute_sibling_structure, SiblingConfig};
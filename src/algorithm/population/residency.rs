@@ -0,0 +1,609 @@
+//! Residency eligibility rules based on VNDS migration history
+//!
+//! This module implements population inclusion criteria such as "born in Denmark",
+//! "resident continuously from birth to index" or "resident at least N years before
+//! index". Rules are evaluated in order from the VNDS migration event sequence and
+//! BEF presence, and each excluded person is attributed to the first rule they fail,
+//! which gives a flowchart-style exclusion report. Rows without a PNR cannot be matched
+//! to any register and are excluded in a first step of their own.
+
+use arrow::array::{Array, BooleanArray, StringArray};
+use arrow::compute::filter_record_batch;
+use arrow::record_batch::RecordBatch;
+use chrono::{Months, NaiveDate};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::algorithm::population::follow_up::{collect_migration_events, EntryRule, MigrationEvent};
+use crate::error::{IdsError, Result};
+use crate::utils::date_utils::{date_to_days_since_epoch, extract_date_from_array};
+
+/// A single residency eligibility rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResidencyRule {
+    /// No immigration recorded before the first emigration, i.e. born in Denmark
+    BornInDenmark,
+    /// Born in Denmark and no emigration between birth and the index date
+    ResidentFromBirthToIndex,
+    /// Resident in Denmark without interruption for at least N years before the index date
+    ResidentYearsBeforeIndex(u32),
+    /// Present in the BEF population register
+    PresentInBef,
+}
+
+impl ResidencyRule {
+    /// Get a human-readable description of the rule for reports
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self {
+            Self::BornInDenmark => "Born in Denmark".to_string(),
+            Self::ResidentFromBirthToIndex => {
+                "Resident continuously from birth to index date".to_string()
+            }
+            Self::ResidentYearsBeforeIndex(years) => {
+                format!("Resident at least {years} years before index date")
+            }
+            Self::PresentInBef => "Present in BEF".to_string(),
+        }
+    }
+
+    /// Whether the rule needs an index date
+    #[must_use]
+    pub const fn requires_index_date(&self) -> bool {
+        matches!(
+            self,
+            Self::ResidentFromBirthToIndex | Self::ResidentYearsBeforeIndex(_)
+        )
+    }
+
+    /// Check that the rule's parameters are usable
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::ResidentYearsBeforeIndex(years) if years.checked_mul(12).is_none() => Err(
+                IdsError::Validation(format!("Residency rule {self} has too many years")),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for ResidencyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BornInDenmark => write!(f, "born-in-dk"),
+            Self::ResidentFromBirthToIndex => write!(f, "resident-from-birth"),
+            Self::ResidentYearsBeforeIndex(years) => write!(f, "resident-years={years}"),
+            Self::PresentInBef => write!(f, "in-bef"),
+        }
+    }
+}
+
+impl FromStr for ResidencyRule {
+    type Err = IdsError;
+
+    /// Parse a rule from its command-line form, e.g. `born-in-dk` or `resident-years=5`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "born-in-dk" => Ok(Self::BornInDenmark),
+            "resident-from-birth" => Ok(Self::ResidentFromBirthToIndex),
+            "in-bef" => Ok(Self::PresentInBef),
+            other => {
+                let rule = other
+                    .strip_prefix("resident-years=")
+                    .and_then(|years| years.parse().ok())
+                    .map(Self::ResidentYearsBeforeIndex)
+                    .ok_or_else(|| {
+                        IdsError::Validation(format!(
+                            "Unknown residency rule '{other}'. Expected one of: born-in-dk, \
+                             resident-from-birth, resident-years=N, in-bef"
+                        ))
+                    })?;
+                rule.validate()?;
+                Ok(rule)
+            }
+        }
+    }
+}
+
+/// Configuration for residency eligibility
+#[derive(Debug, Clone)]
+pub struct ResidencyConfig {
+    /// Rules applied in order; a person is excluded by the first rule they fail
+    pub rules: Vec<ResidencyRule>,
+    /// How the index date is determined for rules that need one
+    pub index: EntryRule,
+}
+
+impl Default for ResidencyConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![ResidencyRule::BornInDenmark],
+            index: EntryRule::Birth,
+        }
+    }
+}
+
+/// Why persons were excluded in a flowchart step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// The population row has no PNR
+    MissingPnr,
+    /// The person failed a residency rule
+    Rule(ResidencyRule),
+}
+
+impl ExclusionReason {
+    /// Get a human-readable description of the reason for reports
+    #[must_use]
+    pub fn description(&self) -> String {
+        match self {
+            Self::MissingPnr => "Missing PNR".to_string(),
+            Self::Rule(rule) => rule.description(),
+        }
+    }
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPnr => write!(f, "missing-pnr"),
+            Self::Rule(rule) => write!(f, "{rule}"),
+        }
+    }
+}
+
+/// One step in the exclusion flowchart
+#[derive(Debug, Clone)]
+pub struct ExclusionStep {
+    /// Why persons were excluded in this step
+    pub reason: ExclusionReason,
+    /// Number of persons before the rule was applied
+    pub remaining_before: usize,
+    /// Number of persons excluded by the rule
+    pub excluded: usize,
+}
+
+impl ExclusionStep {
+    /// Number of persons remaining after the rule was applied
+    #[must_use]
+    pub const fn remaining_after(&self) -> usize {
+        self.remaining_before - self.excluded
+    }
+}
+
+/// Result of applying residency rules to a population
+#[derive(Debug, Clone)]
+pub struct ResidencyResult {
+    /// The population rows that passed all rules
+    pub eligible: RecordBatch,
+    /// Flowchart steps: rows without a PNR, then the rules in the order applied
+    pub steps: Vec<ExclusionStep>,
+    /// Excluded persons with the rule that excluded them (rows without a PNR are only
+    /// counted in the first step)
+    pub excluded: Vec<(String, ResidencyRule)>,
+}
+
+/// Apply residency eligibility rules to a population
+///
+/// The population batch must contain `PNR` and `FOED_DAG` (and the index date column
+/// when the index rule is [`EntryRule::IndexDate`]). Migration data is the standardized
+/// VNDS output (`PNR`, `MIGRATION_TYPE`, `MIGRATION_DATE`); `bef_pnrs` is required when
+/// the [`ResidencyRule::PresentInBef`] rule is used.
+pub fn apply_residency_rules(
+    population_data: &RecordBatch,
+    migration_data: &[RecordBatch],
+    bef_pnrs: Option<&HashSet<String>>,
+    config: &ResidencyConfig,
+) -> Result<ResidencyResult> {
+    let pnr_array = population_data
+        .column_by_name("PNR")
+        .ok_or_else(|| IdsError::Data("PNR column not found in population data".to_string()))?
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .ok_or_else(|| IdsError::Data("PNR column is not a StringArray".to_string()))?;
+
+    let birth_column = population_data
+        .column_by_name("FOED_DAG")
+        .ok_or_else(|| IdsError::Data("FOED_DAG column not found in population data".to_string()))?;
+
+    let index_column = match &config.index {
+        EntryRule::IndexDate(column) => Some(population_data.column_by_name(column).ok_or_else(
+            || IdsError::Data(format!("{column} column not found in population data")),
+        )?),
+        EntryRule::Birth | EntryRule::FixedDate(_) => None,
+    };
+
+    for rule in &config.rules {
+        rule.validate()?;
+    }
+    if config.rules.contains(&ResidencyRule::PresentInBef) && bef_pnrs.is_none() {
+        return Err(IdsError::Validation(
+            "The in-bef residency rule requires BEF data".to_string(),
+        ));
+    }
+
+    let migrations = collect_migration_events(migration_data)?;
+
+    let num_rows = population_data.num_rows();
+    let mut failed_rule: Vec<Option<usize>> = vec![None; num_rows];
+
+    for (i, failed) in failed_rule.iter_mut().enumerate() {
+        // Rows without a PNR are excluded before the rules
+        if pnr_array.is_null(i) {
+            continue;
+        }
+        let pnr = pnr_array.value(i);
+        let events = migrations.get(pnr).map_or(&[][..], Vec::as_slice);
+        let birth = extract_date_from_array(birth_column.as_ref(), i);
+        let index = match &config.index {
+            EntryRule::Birth => birth,
+            EntryRule::FixedDate(date) => Some(*date),
+            EntryRule::IndexDate(_) => {
+                index_column.and_then(|column| extract_date_from_array(column.as_ref(), i))
+            }
+        };
+
+        *failed = config.rules.iter().position(|rule| {
+            !passes_rule(*rule, pnr, events, birth, index, bef_pnrs)
+        });
+    }
+
+    // Build the flowchart: rows without a PNR first, then exclusions in rule order
+    let missing_pnr = pnr_array.null_count();
+    let mut steps = Vec::with_capacity(config.rules.len() + 1);
+    steps.push(ExclusionStep {
+        reason: ExclusionReason::MissingPnr,
+        remaining_before: num_rows,
+        excluded: missing_pnr,
+    });
+    let mut remaining = num_rows - missing_pnr;
+    for (rule_index, rule) in config.rules.iter().enumerate() {
+        let excluded = failed_rule
+            .iter()
+            .filter(|failed| **failed == Some(rule_index))
+            .count();
+        steps.push(ExclusionStep {
+            reason: ExclusionReason::Rule(*rule),
+            remaining_before: remaining,
+            excluded,
+        });
+        remaining -= excluded;
+    }
+
+    let excluded = failed_rule
+        .iter()
+        .enumerate()
+        .filter_map(|(i, failed)| failed.map(|rule_index| (i, config.rules[rule_index])))
+        .map(|(i, rule)| (pnr_array.value(i).to_string(), rule))
+        .collect();
+
+    let mask = BooleanArray::from(
+        failed_rule
+            .iter()
+            .enumerate()
+            .map(|(i, failed)| failed.is_none() && !pnr_array.is_null(i))
+            .collect::<Vec<_>>(),
+    );
+    let eligible = filter_record_batch(population_data, &mask)?;
+
+    log::info!(
+        "Residency rules kept {} of {} persons",
+        eligible.num_rows(),
+        num_rows
+    );
+
+    Ok(ResidencyResult {
+        eligible,
+        steps,
+        excluded,
+    })
+}
+
+/// Evaluate a single rule for one person
+fn passes_rule(
+    rule: ResidencyRule,
+    pnr: &str,
+    events: &[MigrationEvent],
+    birth: Option<NaiveDate>,
+    index: Option<NaiveDate>,
+    bef_pnrs: Option<&HashSet<String>>,
+) -> bool {
+    match rule {
+        ResidencyRule::BornInDenmark => born_in_denmark(events),
+        ResidencyRule::PresentInBef => bef_pnrs.is_some_and(|pnrs| pnrs.contains(pnr)),
+        ResidencyRule::ResidentFromBirthToIndex => match (birth, index) {
+            (Some(birth), Some(index)) => {
+                born_in_denmark(events)
+                    && resident_throughout(
+                        events,
+                        date_to_days_since_epoch(birth),
+                        date_to_days_since_epoch(index),
+                    )
+            }
+            _ => false,
+        },
+        ResidencyRule::ResidentYearsBeforeIndex(years) => {
+            let Some(index) = index else {
+                return false;
+            };
+            let Some(start) = years
+                .checked_mul(12)
+                .and_then(|months| index.checked_sub_months(Months::new(months)))
+            else {
+                return false;
+            };
+            // A person cannot have lived in Denmark longer than they have been alive
+            if birth.is_none_or(|birth| birth > start) {
+                return false;
+            }
+            resident_throughout(
+                events,
+                date_to_days_since_epoch(start),
+                date_to_days_since_epoch(index),
+            )
+        }
+    }
+}
+
+/// A person is considered born in Denmark when their first recorded migration is not
+/// an immigration
+fn born_in_denmark(events: &[MigrationEvent]) -> bool {
+    events.first().is_none_or(|event| event.emigration)
+}
+
+/// Whether a person was resident on `start` and stayed resident until `end`
+///
+/// Residence on `start` follows from the last migration on or before that day; without
+/// any earlier migration the person is resident only if born in Denmark.
+fn resident_throughout(events: &[MigrationEvent], start: i32, end: i32) -> bool {
+    let resident_at_start = match events.iter().take_while(|event| event.date <= start).last() {
+        Some(event) => !event.emigration,
+        None => born_in_denmark(events),
+    };
+
+    resident_at_start
+        && !events
+            .iter()
+            .any(|event| event.emigration && event.date > start && event.date <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Date32Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Population of (PNR, birth date)
+    fn population(rows: &[(Option<&str>, NaiveDate)]) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, true),
+            Field::new("FOED_DAG", DataType::Date32, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    rows.iter().map(|(pnr, _)| *pnr).collect::<Vec<_>>(),
+                )),
+                Arc::new(Date32Array::from(
+                    rows.iter()
+                        .map(|(_, birth)| date_to_days_since_epoch(*birth))
+                        .collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    /// VNDS migrations of (PNR, IN/OUT, date)
+    fn migrations(rows: &[(&str, &str, NaiveDate)]) -> Vec<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, false),
+            Field::new("MIGRATION_TYPE", DataType::Utf8, false),
+            Field::new("MIGRATION_DATE", DataType::Date32, false),
+        ]));
+        vec![RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(
+                    rows.iter().map(|(pnr, _, _)| *pnr).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    rows.iter().map(|(_, kind, _)| *kind).collect::<Vec<_>>(),
+                )),
+                Arc::new(Date32Array::from(
+                    rows.iter()
+                        .map(|(_, _, date)| date_to_days_since_epoch(*date))
+                        .collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .unwrap()]
+    }
+
+    fn eligible_pnrs(result: &ResidencyResult) -> Vec<String> {
+        let pnrs = result
+            .eligible
+            .column_by_name("PNR")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        pnrs.iter().flatten().map(String::from).collect()
+    }
+
+    fn apply(
+        population: &RecordBatch,
+        migrations: &[RecordBatch],
+        bef_pnrs: Option<&HashSet<String>>,
+        rules: Vec<ResidencyRule>,
+        index: EntryRule,
+    ) -> ResidencyResult {
+        apply_residency_rules(
+            population,
+            migrations,
+            bef_pnrs,
+            &ResidencyConfig { rules, index },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rules_parse_from_command_line_form() {
+        for rule in [
+            ResidencyRule::BornInDenmark,
+            ResidencyRule::ResidentFromBirthToIndex,
+            ResidencyRule::ResidentYearsBeforeIndex(5),
+            ResidencyRule::PresentInBef,
+        ] {
+            assert_eq!(rule.to_string().parse::<ResidencyRule>().unwrap(), rule);
+        }
+        assert!("resident-years=x".parse::<ResidencyRule>().is_err());
+        assert!(format!("resident-years={}", u32::MAX)
+            .parse::<ResidencyRule>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_born_in_dk() {
+        let population = population(&[
+            (Some("native"), date(2000, 1, 1)),
+            (Some("returned"), date(2000, 1, 1)),
+            (Some("immigrant"), date(2000, 1, 1)),
+        ]);
+        let migrations = migrations(&[
+            ("returned", "OUT", date(2005, 1, 1)),
+            ("returned", "IN", date(2006, 1, 1)),
+            ("immigrant", "IN", date(2003, 1, 1)),
+        ]);
+        let result = apply(
+            &population,
+            &migrations,
+            None,
+            vec![ResidencyRule::BornInDenmark],
+            EntryRule::Birth,
+        );
+        assert_eq!(eligible_pnrs(&result), vec!["native", "returned"]);
+        assert_eq!(
+            result.excluded,
+            vec![("immigrant".to_string(), ResidencyRule::BornInDenmark)]
+        );
+    }
+
+    #[test]
+    fn test_resident_from_birth() {
+        let population = population(&[
+            (Some("stayed"), date(2000, 1, 1)),
+            (Some("away"), date(2000, 1, 1)),
+            (Some("later"), date(2000, 1, 1)),
+        ]);
+        let migrations = migrations(&[
+            ("away", "OUT", date(2004, 1, 1)),
+            ("away", "IN", date(2005, 1, 1)),
+            ("later", "OUT", date(2011, 1, 1)),
+        ]);
+        let result = apply(
+            &population,
+            &migrations,
+            None,
+            vec![ResidencyRule::ResidentFromBirthToIndex],
+            EntryRule::FixedDate(date(2010, 1, 1)),
+        );
+        assert_eq!(eligible_pnrs(&result), vec!["stayed", "later"]);
+    }
+
+    #[test]
+    fn test_resident_years_before_index() {
+        let population = population(&[
+            (Some("settled"), date(1990, 1, 1)),
+            (Some("recent"), date(1990, 1, 1)),
+            (Some("young"), date(2008, 1, 1)),
+        ]);
+        let migrations = migrations(&[
+            ("settled", "IN", date(2000, 1, 1)),
+            ("recent", "IN", date(2008, 1, 1)),
+        ]);
+        let result = apply(
+            &population,
+            &migrations,
+            None,
+            vec![ResidencyRule::ResidentYearsBeforeIndex(5)],
+            EntryRule::FixedDate(date(2010, 1, 1)),
+        );
+        assert_eq!(eligible_pnrs(&result), vec!["settled"]);
+    }
+
+    #[test]
+    fn test_in_bef() {
+        let population = population(&[
+            (Some("listed"), date(2000, 1, 1)),
+            (Some("unlisted"), date(2000, 1, 1)),
+        ]);
+        let bef_pnrs = HashSet::from(["listed".to_string()]);
+        let result = apply(
+            &population,
+            &[],
+            Some(&bef_pnrs),
+            vec![ResidencyRule::PresentInBef],
+            EntryRule::Birth,
+        );
+        assert_eq!(eligible_pnrs(&result), vec!["listed"]);
+
+        let config = ResidencyConfig {
+            rules: vec![ResidencyRule::PresentInBef],
+            index: EntryRule::Birth,
+        };
+        assert!(apply_residency_rules(&population, &[], None, &config).is_err());
+    }
+
+    #[test]
+    fn test_exclusion_counts_follow_rule_order() {
+        let population = population(&[
+            (Some("eligible"), date(2000, 1, 1)),
+            (Some("immigrant"), date(2000, 1, 1)),
+            (Some("unlisted"), date(2000, 1, 1)),
+            (None, date(2000, 1, 1)),
+            (None, date(2001, 1, 1)),
+        ]);
+        let migrations = migrations(&[("immigrant", "IN", date(2002, 1, 1))]);
+        // The immigrant is also missing from BEF, but only counted by the first rule
+        let bef_pnrs = HashSet::from(["eligible".to_string()]);
+        let result = apply(
+            &population,
+            &migrations,
+            Some(&bef_pnrs),
+            vec![ResidencyRule::BornInDenmark, ResidencyRule::PresentInBef],
+            EntryRule::Birth,
+        );
+
+        let steps: Vec<(ExclusionReason, usize, usize, usize)> = result
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    step.reason,
+                    step.remaining_before,
+                    step.excluded,
+                    step.remaining_after(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                (ExclusionReason::MissingPnr, 5, 2, 3),
+                (ExclusionReason::Rule(ResidencyRule::BornInDenmark), 3, 1, 2),
+                (ExclusionReason::Rule(ResidencyRule::PresentInBef), 2, 1, 1),
+            ]
+        );
+        assert_eq!(eligible_pnrs(&result), vec!["eligible"]);
+        assert_eq!(result.eligible.num_rows(), 1);
+        assert_eq!(result.excluded.len(), 2);
+    }
+}
@@ -0,0 +1,451 @@
+//! Sibling structure, birth order and parity derivation
+//!
+//! This module derives family-structure covariates from the parent links produced by
+//! `create_family_data` (`PNR`, `FOED_DAG`, `MOR_ID`, `FAR_ID`): maternal parity, birth
+//! order among full and among all (full and half) siblings, counts of older and younger
+//! siblings, sibling IDs and a twin/multiple-birth flag.
+//!
+//! Siblings are only found among the children in the input, so parity and birth order
+//! are most accurate when the input covers all births to the parents rather than a
+//! restricted birth cohort. Children without a birth date are kept: they count as
+//! siblings, but cannot be ordered, so their own parity and birth order are null and
+//! they are not counted in anyone's birth order, parity or older/younger siblings.
+
+use arrow::array::{
+    Array, ArrayRef, BooleanArray, Int32Array, ListBuilder, StringArray, StringBuilder,
+};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::error::{IdsError, Result};
+use crate::utils::date_utils::{date_to_days_since_epoch, extract_date_from_array};
+
+/// Configuration for sibling structure derivation
+#[derive(Debug, Clone)]
+pub struct SiblingConfig {
+    /// Maximum difference in days between birth dates of children of the same mother
+    /// for them to be considered part of the same multiple birth
+    pub multiple_birth_window_days: i32,
+}
+
+impl Default for SiblingConfig {
+    fn default() -> Self {
+        Self {
+            multiple_birth_window_days: 1,
+        }
+    }
+}
+
+/// A child with its parent links, birth date in days since epoch
+#[derive(Debug, Clone)]
+struct Child {
+    pnr: String,
+    birth: Option<i32>,
+    mother: Option<String>,
+    father: Option<String>,
+}
+
+impl Child {
+    /// Whether two children share both (known) parents
+    fn is_full_sibling_of(&self, other: &Self) -> bool {
+        self.mother.is_some()
+            && self.father.is_some()
+            && self.mother == other.mother
+            && self.father == other.father
+    }
+}
+
+/// Compute sibling structure for each child in the family data
+///
+/// The output has one row per child with:
+/// - `PNR`
+/// - `MATERNAL_PARITY`: number of deliveries by the mother up to and including this
+///   child's; children of the same multiple birth share parity
+/// - `BIRTH_ORDER_FULL`: position among full siblings (same mother and father)
+/// - `BIRTH_ORDER_ALL`: position among all full and half siblings
+/// - `N_FULL_SIBLINGS`, `N_HALF_SIBLINGS`
+/// - `N_OLDER_SIBLINGS`, `N_YOUNGER_SIBLINGS`: siblings born strictly before/after
+/// - `SIBLING_IDS`: PNRs of all full and half siblings ordered by birth date
+/// - `IS_MULTIPLE_BIRTH`: another child of the same mother born within the
+///   configured window
+///
+/// Birth order ties (multiple births) are broken by PNR so each child gets a distinct
+/// position. Parity and birth order are null when the required parent or the child's
+/// birth date is unknown.
+pub fn compute_sibling_structure(
+    family_data: &RecordBatch,
+    config: &SiblingConfig,
+) -> Result<RecordBatch> {
+    let children = extract_children(family_data)?;
+
+    let mut by_mother: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut by_father: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, child) in children.iter().enumerate() {
+        if let Some(mother) = &child.mother {
+            by_mother.entry(mother.as_str()).or_default().push(i);
+        }
+        if let Some(father) = &child.father {
+            by_father.entry(father.as_str()).or_default().push(i);
+        }
+    }
+
+    // Order each parent's children by birth date, then PNR, with undated children last
+    let order = |a: &usize, b: &usize| {
+        let (a, b) = (&children[*a], &children[*b]);
+        a.birth
+            .is_none()
+            .cmp(&b.birth.is_none())
+            .then_with(|| a.birth.cmp(&b.birth))
+            .then_with(|| a.pnr.cmp(&b.pnr))
+    };
+    for group in by_mother.values_mut().chain(by_father.values_mut()) {
+        group.sort_by(order);
+    }
+
+    let num_rows = children.len();
+    let mut parity = Vec::with_capacity(num_rows);
+    let mut birth_order_full = Vec::with_capacity(num_rows);
+    let mut birth_order_all = Vec::with_capacity(num_rows);
+    let mut n_full = Vec::with_capacity(num_rows);
+    let mut n_half = Vec::with_capacity(num_rows);
+    let mut n_older = Vec::with_capacity(num_rows);
+    let mut n_younger = Vec::with_capacity(num_rows);
+    let mut multiple_birth = Vec::with_capacity(num_rows);
+    let mut sibling_ids = ListBuilder::new(StringBuilder::new());
+
+    for (i, child) in children.iter().enumerate() {
+        let maternal = child
+            .mother
+            .as_deref()
+            .and_then(|mother| by_mother.get(mother))
+            .map_or(&[][..], Vec::as_slice);
+        let paternal = child
+            .father
+            .as_deref()
+            .and_then(|father| by_father.get(father))
+            .map_or(&[][..], Vec::as_slice);
+
+        // All siblings share at least one parent; half siblings may appear in both groups
+        let mut siblings: Vec<usize> = maternal
+            .iter()
+            .chain(paternal.iter())
+            .copied()
+            .filter(|&j| j != i)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        siblings.sort_by(order);
+
+        let full_count = siblings
+            .iter()
+            .filter(|&&j| child.is_full_sibling_of(&children[j]))
+            .count();
+
+        let dated_siblings = || siblings.iter().filter_map(|&j| children[j].birth);
+        let older = child
+            .birth
+            .map_or(0, |birth| dated_siblings().filter(|&b| b < birth).count());
+        let younger = child
+            .birth
+            .map_or(0, |birth| dated_siblings().filter(|&b| b > birth).count());
+
+        // Position among siblings using the same (birth date, PNR) ordering, in which
+        // undated siblings come last
+        let born_before = |j: &usize| order(j, &i).is_lt();
+        let dated = child.birth.is_some();
+
+        birth_order_all.push(
+            if dated && (child.mother.is_some() || child.father.is_some()) {
+                Some(siblings.iter().filter(|j| born_before(j)).count() as i32 + 1)
+            } else {
+                None
+            },
+        );
+
+        birth_order_full.push(
+            if dated && child.mother.is_some() && child.father.is_some() {
+                let full_before = siblings
+                    .iter()
+                    .filter(|&&j| child.is_full_sibling_of(&children[j]) && born_before(&j))
+                    .count();
+                Some(full_before as i32 + 1)
+            } else {
+                None
+            },
+        );
+
+        let window = config.multiple_birth_window_days;
+        let is_multiple = child.birth.is_some_and(|birth| {
+            maternal.iter().any(|&j| {
+                j != i
+                    && children[j]
+                        .birth
+                        .is_some_and(|b| (b - birth).abs() <= window)
+            })
+        });
+
+        // Parity counts deliveries, so births within the multiple-birth window of an
+        // earlier sibling's delivery do not start a new one
+        let child_parity = match child.birth {
+            Some(child_birth) if child.mother.is_some() => {
+                let mut deliveries = 0;
+                let mut last_delivery: Option<i32> = None;
+                for &j in maternal {
+                    let Some(birth) = children[j].birth else {
+                        break;
+                    };
+                    if birth > child_birth {
+                        break;
+                    }
+                    if last_delivery.is_none_or(|last| birth - last > window) {
+                        deliveries += 1;
+                        last_delivery = Some(birth);
+                    }
+                }
+                Some(deliveries)
+            }
+            _ => None,
+        };
+        parity.push(child_parity);
+
+        n_full.push(full_count as i32);
+        n_half.push((siblings.len() - full_count) as i32);
+        n_older.push(older as i32);
+        n_younger.push(younger as i32);
+        multiple_birth.push(is_multiple);
+
+        for &j in &siblings {
+            sibling_ids.values().append_value(&children[j].pnr);
+        }
+        sibling_ids.append(true);
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("PNR", DataType::Utf8, false),
+        Field::new("MATERNAL_PARITY", DataType::Int32, true),
+        Field::new("BIRTH_ORDER_FULL", DataType::Int32, true),
+        Field::new("BIRTH_ORDER_ALL", DataType::Int32, true),
+        Field::new("N_FULL_SIBLINGS", DataType::Int32, false),
+        Field::new("N_HALF_SIBLINGS", DataType::Int32, false),
+        Field::new("N_OLDER_SIBLINGS", DataType::Int32, false),
+        Field::new("N_YOUNGER_SIBLINGS", DataType::Int32, false),
+        Field::new(
+            "SIBLING_IDS",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            true,
+        ),
+        Field::new("IS_MULTIPLE_BIRTH", DataType::Boolean, false),
+    ]);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            children.iter().map(|c| c.pnr.as_str()),
+        )),
+        Arc::new(Int32Array::from(parity)),
+        Arc::new(Int32Array::from(birth_order_full)),
+        Arc::new(Int32Array::from(birth_order_all)),
+        Arc::new(Int32Array::from(n_full)),
+        Arc::new(Int32Array::from(n_half)),
+        Arc::new(Int32Array::from(n_older)),
+        Arc::new(Int32Array::from(n_younger)),
+        Arc::new(sibling_ids.finish()),
+        Arc::new(BooleanArray::from(multiple_birth)),
+    ];
+
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+/// Extract children with a PNR from family data
+fn extract_children(family_data: &RecordBatch) -> Result<Vec<Child>> {
+    let string_column = |name: &str| -> Result<&StringArray> {
+        family_data
+            .column_by_name(name)
+            .ok_or_else(|| IdsError::Data(format!("{name} column not found in family data")))?
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| IdsError::Data(format!("{name} column is not a StringArray")))
+    };
+
+    let pnr_array = string_column("PNR")?;
+    let mother_array = string_column("MOR_ID")?;
+    let father_array = string_column("FAR_ID")?;
+    let birth_column = family_data
+        .column_by_name("FOED_DAG")
+        .ok_or_else(|| IdsError::Data("FOED_DAG column not found in family data".to_string()))?;
+
+    let parent = |array: &StringArray, i: usize| {
+        (!array.is_null(i) && !array.value(i).is_empty()).then(|| array.value(i).to_string())
+    };
+
+    let mut children = Vec::with_capacity(family_data.num_rows());
+    for i in 0..family_data.num_rows() {
+        if pnr_array.is_null(i) {
+            continue;
+        }
+        children.push(Child {
+            pnr: pnr_array.value(i).to_string(),
+            birth: extract_date_from_array(birth_column.as_ref(), i).map(date_to_days_since_epoch),
+            mother: parent(mother_array, i),
+            father: parent(father_array, i),
+        });
+    }
+
+    Ok(children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Date32Array;
+    use chrono::NaiveDate;
+
+    fn days(y: i32, m: u32, d: u32) -> i32 {
+        date_to_days_since_epoch(NaiveDate::from_ymd_opt(y, m, d).unwrap())
+    }
+
+    /// Family data of (PNR, birth date, mother, father)
+    fn family(rows: &[(&str, Option<i32>, Option<&str>, Option<&str>)]) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, true),
+            Field::new("FOED_DAG", DataType::Date32, true),
+            Field::new("MOR_ID", DataType::Utf8, true),
+            Field::new("FAR_ID", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.0))),
+                Arc::new(Date32Array::from(
+                    rows.iter().map(|r| r.1).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    rows.iter().map(|r| r.2).collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    rows.iter().map(|r| r.3).collect::<Vec<_>>(),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    /// Values of an Int32 column keyed by PNR
+    fn int_column(result: &RecordBatch, name: &str) -> HashMap<String, Option<i32>> {
+        let pnrs = result
+            .column_by_name("PNR")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let values = result
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        (0..result.num_rows())
+            .map(|i| {
+                let value = (!values.is_null(i)).then(|| values.value(i));
+                (pnrs.value(i).to_string(), value)
+            })
+            .collect()
+    }
+
+    fn bool_column(result: &RecordBatch, name: &str) -> Vec<bool> {
+        let values = result
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        values.iter().map(Option::unwrap).collect()
+    }
+
+    #[test]
+    fn test_twins_share_parity_and_get_distinct_birth_order() {
+        let data = family(&[
+            ("first", Some(days(2000, 1, 1)), Some("M"), Some("F")),
+            ("twin_b", Some(days(2003, 5, 2)), Some("M"), Some("F")),
+            ("twin_a", Some(days(2003, 5, 1)), Some("M"), Some("F")),
+        ]);
+        let result = compute_sibling_structure(&data, &SiblingConfig::default()).unwrap();
+
+        let parity = int_column(&result, "MATERNAL_PARITY");
+        assert_eq!(parity["first"], Some(1));
+        assert_eq!(parity["twin_a"], Some(2));
+        assert_eq!(parity["twin_b"], Some(2));
+
+        let order = int_column(&result, "BIRTH_ORDER_FULL");
+        assert_eq!(order["twin_a"], Some(2));
+        assert_eq!(order["twin_b"], Some(3));
+
+        assert_eq!(
+            bool_column(&result, "IS_MULTIPLE_BIRTH"),
+            vec![false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_half_siblings() {
+        // Two children with father F1, then one with father F2, plus a paternal
+        // half sibling born to another mother
+        let data = family(&[
+            ("a", Some(days(2000, 1, 1)), Some("M"), Some("F1")),
+            ("b", Some(days(2002, 1, 1)), Some("M"), Some("F1")),
+            ("c", Some(days(2005, 1, 1)), Some("M"), Some("F2")),
+            ("d", Some(days(2001, 1, 1)), Some("N"), Some("F1")),
+        ]);
+        let result = compute_sibling_structure(&data, &SiblingConfig::default()).unwrap();
+
+        let full = int_column(&result, "N_FULL_SIBLINGS");
+        let half = int_column(&result, "N_HALF_SIBLINGS");
+        assert_eq!((full["a"], half["a"]), (Some(1), Some(2)));
+        assert_eq!((full["c"], half["c"]), (Some(0), Some(2)));
+        assert_eq!((full["d"], half["d"]), (Some(0), Some(2)));
+
+        let order_full = int_column(&result, "BIRTH_ORDER_FULL");
+        let order_all = int_column(&result, "BIRTH_ORDER_ALL");
+        assert_eq!(order_full["b"], Some(2));
+        assert_eq!(order_all["b"], Some(3));
+        assert_eq!(order_full["c"], Some(1));
+        assert_eq!(order_all["c"], Some(3));
+
+        let parity = int_column(&result, "MATERNAL_PARITY");
+        assert_eq!(parity["c"], Some(3));
+        assert_eq!(parity["d"], Some(1));
+    }
+
+    #[test]
+    fn test_missing_birth_date_is_kept_with_null_order() {
+        let data = family(&[
+            ("a", Some(days(2000, 1, 1)), Some("M"), Some("F")),
+            ("undated", None, Some("M"), Some("F")),
+            ("b", Some(days(2002, 1, 1)), Some("M"), Some("F")),
+        ]);
+        let result = compute_sibling_structure(&data, &SiblingConfig::default()).unwrap();
+        assert_eq!(result.num_rows(), 3);
+
+        let parity = int_column(&result, "MATERNAL_PARITY");
+        let order = int_column(&result, "BIRTH_ORDER_ALL");
+        assert_eq!(parity["undated"], None);
+        assert_eq!(order["undated"], None);
+        assert_eq!(int_column(&result, "BIRTH_ORDER_FULL")["undated"], None);
+
+        // The undated child is a sibling but does not shift anyone's order
+        assert_eq!(int_column(&result, "N_FULL_SIBLINGS")["a"], Some(2));
+        assert_eq!(parity["b"], Some(2));
+        assert_eq!(order["b"], Some(2));
+        assert_eq!(int_column(&result, "N_OLDER_SIBLINGS")["b"], Some(1));
+        assert_eq!(int_column(&result, "N_OLDER_SIBLINGS")["undated"], Some(0));
+    }
+
+    #[test]
+    fn test_unknown_parents_give_null_order() {
+        let data = family(&[("orphan", Some(days(2000, 1, 1)), None, None)]);
+        let result = compute_sibling_structure(&data, &SiblingConfig::default()).unwrap();
+        assert_eq!(int_column(&result, "MATERNAL_PARITY")["orphan"], None);
+        assert_eq!(int_column(&result, "BIRTH_ORDER_ALL")["orphan"], None);
+    }
+}
@@ -1,11 +1,11 @@
+use crate::algorithm::health::diagnosis::DiagnosisFilter;
+use crate::algorithm::population::{ParentPrecedence, ResidencyRule};
 use crate::cli::console::Console;
 use crate::error::Result;
 use crate::utils::runtime::get_runtime;
+use chrono::NaiveDate;
 use clap::{Args, Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
-use datafusion::common::DataFusionError;
-use datafusion::common::config::TableParquetOptions;
-use datafusion::dataframe::DataFrameWriteOptions;
 use std::path::PathBuf;
 
 /// Command handler trait
@@ -24,6 +24,9 @@ pub struct SampleCommand {
 
     /// Number of samples
     pub sample_count: usize,
+
+    /// Writer settings and partitioning of the output
+    pub options: crate::data::io::ParquetWriteOptions,
 }
 
 impl CommandHandler for SampleCommand {
@@ -48,44 +51,203 @@ impl CommandHandler for SampleCommand {
         Console::print_info(&format!("Sampling {} records...", self.sample_count));
         let sampled = crate::algorithm::sampler::sample_records(&records, self.sample_count, None)?;
 
-        // Save to the output path using DataFusion
         Console::print_info(&format!("Writing sampled data to {}", self.output_path));
+        let summary = runtime.block_on(crate::data::io::write_batches(
+            &sampled,
+            &self.output_path,
+            &self.options,
+        ))?;
+        Console::print_key_value("Files written", &summary.files.len().to_string());
+        Console::print_key_value("Row groups", &summary.row_groups.to_string());
 
-        // We can use DataFusion to write Parquet files efficiently
-        runtime.block_on(async {
-            use datafusion::prelude::*;
-
-            // Create a session context
-            let ctx = SessionContext::new();
-
-            // Create a memory table from the record batch
-            let table_name = "sampled_data";
-            
-            // If sampled is a Vec<RecordBatch>, we need to combine them into a single batch
-            // or register them via a different method
-            if sampled.len() == 1 {
-                // If there's only one batch, use register_batch
-                ctx.register_batch(table_name, sampled[0].clone())?;
-            } else {
-                // If there are multiple batches, use register_batches
-                let provider = crate::data::io::parquet::batches_to_table(&sampled)
-                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
-                ctx.register_table(table_name, provider)?;
-            }
+        Console::print_success("Sampling completed");
+        Ok(())
+    }
+}
 
-            // Create a DataFrame and write to Parquet
-            let df = ctx.table(table_name).await?;
+/// Rewrite command handler
+pub struct RewriteCommand {
+    /// Input file or directory
+    pub input_path: PathBuf,
 
-            // Write to Parquet using DataFusion with optimal settings
-            df.write_parquet(
-                &self.output_path,
-                DataFrameWriteOptions::default(),
-                Some(TableParquetOptions::new()),
-            )
-            .await
+    /// Output file or directory
+    pub output_path: PathBuf,
+
+    /// Rewrite options
+    pub options: crate::data::io::RewriteOptions,
+}
+
+impl CommandHandler for RewriteCommand {
+    fn execute(&self) -> Result<()> {
+        Console::print_header("Rewriting Registers for PNR Lookups");
+        Console::print_key_value("Input", &self.input_path.display().to_string());
+        Console::print_key_value("Output", &self.output_path.display().to_string());
+        Console::print_key_value(
+            "PNR column",
+            self.options.pnr_column.as_deref().unwrap_or("PNR/CPR"),
+        );
+        Console::print_key_value("Row group size", &self.options.row_group_size.to_string());
+        Console::print_key_value("Page row limit", &self.options.page_row_limit.to_string());
+        Console::print_key_value("Bloom filter FPP", &self.options.bloom_filter_fpp.to_string());
+
+        let summary = get_runtime()?.block_on(crate::data::io::rewrite_parquet(
+            &self.input_path,
+            &self.output_path,
+            &self.options,
+        ))?;
+
+        Console::print_success(&format!(
+            "Rewrote {} files ({} rows in {} row groups)",
+            summary.files, summary.rows, summary.row_groups
+        ));
+        Ok(())
+    }
+}
+
+/// Convert command handler
+pub struct ConvertCommand {
+    /// Input file or directory
+    pub input_path: PathBuf,
+
+    /// Output directory
+    pub output_path: PathBuf,
+
+    /// Convert options
+    pub options: crate::data::io::ConvertOptions,
+}
+
+impl CommandHandler for ConvertCommand {
+    fn execute(&self) -> Result<()> {
+        Console::print_header("Converting Register Files to Parquet");
+        Console::print_key_value("Input", &self.input_path.display().to_string());
+        Console::print_key_value("Output", &self.output_path.display().to_string());
+        Console::print_key_value(
+            "Registry",
+            self.options.registry.as_deref().unwrap_or("inferred from path"),
+        );
+        match (&self.options.year_column, self.options.year) {
+            (Some(column), _) => Console::print_key_value("Year column", column),
+            (None, Some(year)) => Console::print_key_value("Year", &year.to_string()),
+            (None, None) => Console::print_key_value("Year", "from file names"),
+        }
+
+        let summary = crate::data::io::convert_registry(
+            &self.input_path,
+            &self.output_path,
+            &self.options,
+        )?;
+
+        for output in &summary.outputs {
+            Console::print_info(&format!("Wrote {}", output.display()));
+        }
+        Console::print_success(&format!(
+            "Converted {} {} files ({} rows) into {} Parquet files",
+            summary.files,
+            summary.registry,
+            summary.rows,
+            summary.outputs.len()
+        ));
+        Ok(())
+    }
+}
+
+/// Cache command handler
+pub struct CacheCommand {
+    /// Maintenance action to run on the result cache
+    pub action: CacheAction,
+}
+
+/// Maintenance actions on the result cache
+pub enum CacheAction {
+    /// List the cached results
+    List,
+    /// Remove old or least recently used results
+    Prune(crate::data::cache::PruneOptions),
+    /// Remove all cached results
+    Clear,
+}
+
+impl CommandHandler for CacheCommand {
+    fn execute(&self) -> Result<()> {
+        let dir = crate::data::cache::cache_config().dir.ok_or_else(|| {
+            crate::error::IdsError::Validation("The cache commands need --cache-dir".to_string())
         })?;
+        let cache = crate::data::cache::ResultCache::new(dir);
+        Console::print_header("Result Cache");
+        Console::print_key_value("Directory", &cache.root().display().to_string());
+
+        let summary = match &self.action {
+            CacheAction::List => {
+                let entries = cache.entries()?;
+                for entry in &entries {
+                    Console::print_subheader(&format!("{} {}", entry.stage, &entry.digest[..12]));
+                    Console::print_key_value("Size", &format!("{} MiB", entry.size >> 20));
+                    let last_used = i64::try_from(entry.last_used)
+                        .ok()
+                        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                        .map_or_else(String::new, |time| time.format("%Y-%m-%d %H:%M").to_string());
+                    Console::print_key_value("Last used", &last_used);
+                    for input in &entry.inputs {
+                        Console::print_info(input);
+                    }
+                }
+                let total: u64 = entries.iter().map(|entry| entry.size).sum();
+                Console::print_success(&format!(
+                    "{} cached results ({} MiB)",
+                    entries.len(),
+                    total >> 20
+                ));
+                return Ok(());
+            }
+            CacheAction::Prune(options) => cache.prune(options)?,
+            CacheAction::Clear => cache.clear()?,
+        };
+        Console::print_success(&format!(
+            "Removed {} cached results ({} MiB), kept {}",
+            summary.removed,
+            summary.freed >> 20,
+            summary.kept
+        ));
+        Ok(())
+    }
+}
 
-        Console::print_success("Sampling completed");
+/// Validate command handler
+pub struct ValidateCommand {
+    /// Validate command configuration
+    pub config: crate::commands::validate::ValidateCommandConfig,
+}
+
+impl CommandHandler for ValidateCommand {
+    fn execute(&self) -> Result<()> {
+        // Without a report file the JSON report goes to stdout on its own
+        let Some(report_path) = &self.config.report_path else {
+            crate::commands::handle_validate_command(&self.config)?;
+            return Ok(());
+        };
+
+        Console::print_header("Validating Registry Files");
+        Console::print_key_value("Input", &self.config.input_path.display().to_string());
+        Console::print_key_value(
+            "Registry",
+            self.config.registry.as_deref().unwrap_or("inferred from path"),
+        );
+        Console::print_key_value("Report", &report_path.display().to_string());
+        Console::print_key_value("PNR format", &self.config.validation.pnr_format.to_string());
+        Console::print_key_value(
+            "Date range",
+            &format!(
+                "{} to {}",
+                self.config.validation.min_date, self.config.validation.max_date
+            ),
+        );
+
+        let report = crate::commands::handle_validate_command(&self.config)?;
+
+        Console::print_success(&format!(
+            "All {} {} files passed validation",
+            report.files_checked, report.registry
+        ));
         Ok(())
     }
 }
@@ -176,6 +338,18 @@ pub struct PopulationCommand {
 
     /// End year for birth inclusion
     pub birth_end_year: i32,
+
+    /// Precedence between BEF and MFR parent links
+    pub parent_precedence: ParentPrecedence,
+
+    /// VNDS migration data path
+    pub vnds_path: Option<PathBuf>,
+
+    /// Residency eligibility rules
+    pub residency_rules: Vec<ResidencyRule>,
+
+    /// Index date for residency rules
+    pub index_date: Option<NaiveDate>,
 }
 
 impl CommandHandler for PopulationCommand {
@@ -189,6 +363,11 @@ impl CommandHandler for PopulationCommand {
             &format!("{} - {}", self.birth_start_year, self.birth_end_year),
         );
 
+        if !self.residency_rules.is_empty() {
+            let rules: Vec<String> = self.residency_rules.iter().map(ToString::to_string).collect();
+            Console::print_key_value("Residency Rules", &rules.join(", "));
+        }
+
         // Create config from CLI arguments
         let config = crate::commands::population::PopulationCommandConfig {
             bef_path: self.bef_path.clone(),
@@ -196,6 +375,10 @@ impl CommandHandler for PopulationCommand {
             output_dir: self.output_dir.clone(),
             birth_inclusion_start_year: self.birth_start_year,
             birth_inclusion_end_year: self.birth_end_year,
+            parent_precedence: self.parent_precedence,
+            vnds_path: self.vnds_path.clone(),
+            residency_rules: self.residency_rules.clone(),
+            index_date: self.index_date,
         };
 
         // Execute the population generation
@@ -225,6 +408,21 @@ pub struct ScdCommand {
 
     /// End date for filtering
     pub end_date: Option<chrono::NaiveDate>,
+
+    /// SCD definition file (built-in definitions if not given)
+    pub definitions: Option<PathBuf>,
+
+    /// Diagnosis type and status filter applied to LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+
+    /// Also count secondary diagnoses
+    pub secondary_diagnoses: bool,
+
+    /// Cause-of-death (DODSAARSAG) data path
+    pub death_causes_path: Option<PathBuf>,
+
+    /// Death register (DOD) data path, for the dates of causes of death
+    pub deaths_path: Option<PathBuf>,
 }
 
 impl CommandHandler for ScdCommand {
@@ -243,6 +441,24 @@ impl CommandHandler for ScdCommand {
             Console::print_key_value("End Date", &date.to_string());
         }
 
+        if let Some(path) = &self.definitions {
+            Console::print_key_value("Definitions", &path.display().to_string());
+        }
+
+        Console::print_key_value("Diagnosis Filter", &self.diagnosis_filter.to_string());
+        Console::print_key_value(
+            "Secondary Diagnoses",
+            if self.secondary_diagnoses { "counted" } else { "not counted" },
+        );
+
+        if let Some(path) = &self.death_causes_path {
+            Console::print_key_value("Causes of Death", &path.display().to_string());
+        }
+
+        if let Some(path) = &self.deaths_path {
+            Console::print_key_value("Deaths", &path.display().to_string());
+        }
+
         // Create config from CLI arguments
         let config = crate::commands::scd::ScdCommandConfig {
             lpr_data_path: self.lpr_path.clone(),
@@ -251,12 +467,15 @@ impl CommandHandler for ScdCommand {
             include_lpr3: self.include_lpr3,
             start_date: self.start_date,
             end_date: self.end_date,
-            diagnosis_columns: vec![
-                "primary_diagnosis".to_string(),
-                "secondary_diagnosis".to_string(),
-            ],
+            diagnosis_columns: crate::algorithm::health::diagnosis::scd_diagnosis_columns(
+                self.secondary_diagnoses,
+            ),
             patient_id_column: "patient_id".to_string(),
             date_column: "admission_date".to_string(),
+            definitions_path: self.definitions.clone(),
+            diagnosis_filter: self.diagnosis_filter,
+            death_causes_path: self.death_causes_path.clone(),
+            deaths_path: self.deaths_path.clone(),
         };
 
         // Execute the SCD analysis
@@ -289,6 +508,15 @@ pub struct PopulationScdCommand {
 
     /// End date for filtering
     pub end_date: Option<chrono::NaiveDate>,
+
+    /// SCD definition file (built-in definitions if not given)
+    pub definitions: Option<PathBuf>,
+
+    /// Diagnosis type and status filter applied to LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+
+    /// Also count secondary diagnoses
+    pub secondary_diagnoses: bool,
 }
 
 impl CommandHandler for PopulationScdCommand {
@@ -311,6 +539,16 @@ impl CommandHandler for PopulationScdCommand {
             Console::print_key_value("End Date", &date.to_string());
         }
 
+        if let Some(path) = &self.definitions {
+            Console::print_key_value("Definitions", &path.display().to_string());
+        }
+
+        Console::print_key_value("Diagnosis Filter", &self.diagnosis_filter.to_string());
+        Console::print_key_value(
+            "Secondary Diagnoses",
+            if self.secondary_diagnoses { "counted" } else { "not counted" },
+        );
+
         // Create config from CLI arguments
         let config = crate::commands::population_scd::PopulationScdCommandConfig {
             population_path: self.population_path.clone(),
@@ -320,6 +558,9 @@ impl CommandHandler for PopulationScdCommand {
             include_lpr3: self.include_lpr3,
             start_date: self.start_date,
             end_date: self.end_date,
+            definitions_path: self.definitions.clone(),
+            diagnosis_filter: self.diagnosis_filter,
+            secondary_diagnoses: self.secondary_diagnoses,
         };
 
         // Execute the Population SCD analysis
@@ -330,6 +571,93 @@ impl CommandHandler for PopulationScdCommand {
     }
 }
 
+/// Phenotype command handler
+pub struct PhenotypeCommand {
+    /// Phenotype definition file
+    pub definitions: PathBuf,
+
+    /// LPR data path
+    pub lpr_path: PathBuf,
+
+    /// Output directory
+    pub output_dir: PathBuf,
+
+    /// Population data path, for birth dates used by age rules
+    pub population_path: Option<PathBuf>,
+
+    /// Include LPR2 data
+    pub include_lpr2: bool,
+
+    /// Include LPR3 data
+    pub include_lpr3: bool,
+
+    /// Start date for filtering
+    pub start_date: Option<chrono::NaiveDate>,
+
+    /// End date for filtering
+    pub end_date: Option<chrono::NaiveDate>,
+
+    /// Diagnosis type and status filter applied to LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+
+    /// Cause-of-death (DODSAARSAG) data path
+    pub death_causes_path: Option<PathBuf>,
+
+    /// Death register (DOD) data path, for the dates of causes of death
+    pub deaths_path: Option<PathBuf>,
+}
+
+impl CommandHandler for PhenotypeCommand {
+    fn execute(&self) -> Result<()> {
+        Console::print_header("Running Phenotype Definition");
+        Console::print_key_value("Definitions", &self.definitions.display().to_string());
+        Console::print_key_value("LPR Data", &self.lpr_path.display().to_string());
+        Console::print_key_value("Output Directory", &self.output_dir.display().to_string());
+        if let Some(path) = &self.population_path {
+            Console::print_key_value("Population Data", &path.display().to_string());
+        }
+        Console::print_key_value("Include LPR2", &self.include_lpr2.to_string());
+        Console::print_key_value("Include LPR3", &self.include_lpr3.to_string());
+
+        if let Some(date) = self.start_date {
+            Console::print_key_value("Start Date", &date.to_string());
+        }
+
+        if let Some(date) = self.end_date {
+            Console::print_key_value("End Date", &date.to_string());
+        }
+
+        Console::print_key_value("Diagnosis Filter", &self.diagnosis_filter.to_string());
+
+        if let Some(path) = &self.death_causes_path {
+            Console::print_key_value("Causes of Death", &path.display().to_string());
+        }
+
+        if let Some(path) = &self.deaths_path {
+            Console::print_key_value("Deaths", &path.display().to_string());
+        }
+
+        let config = crate::commands::phenotype::PhenotypeCommandConfig {
+            definitions_path: self.definitions.clone(),
+            lpr_data_path: self.lpr_path.clone(),
+            output_dir: self.output_dir.clone(),
+            population_path: self.population_path.clone(),
+            include_lpr2: self.include_lpr2,
+            include_lpr3: self.include_lpr3,
+            start_date: self.start_date,
+            end_date: self.end_date,
+            diagnosis_filter: self.diagnosis_filter,
+            death_causes_path: self.death_causes_path.clone(),
+            deaths_path: self.deaths_path.clone(),
+        };
+
+        crate::commands::phenotype::handle_phenotype_command(&config)?;
+
+        Console::print_success("Phenotype analysis completed");
+        Ok(())
+    }
+}
+
 /// Study Design command handler
 pub struct StudyDesignCommand {
     /// BEF data path
@@ -454,16 +782,62 @@ pub struct Cli {
     #[clap(flatten)]
     pub verbose: Verbosity,
 
+    /// Memory limit and spill directory for query execution
+    #[clap(flatten)]
+    pub execution: ExecutionArgs,
+
+    /// Location and use of the result cache
+    #[clap(flatten)]
+    pub cache: CacheArgs,
+
     #[clap(subcommand)]
     command: Commands,
 }
 
+/// Global options bounding the memory used by query execution
+#[derive(Args)]
+pub struct ExecutionArgs {
+    /// Memory limit for query execution, e.g. 16G; data beyond it is spilled to disk
+    #[clap(long, global = true, value_parser = crate::data::io::parse_memory_size)]
+    pub memory_limit: Option<usize>,
+
+    /// Directory for spill files (defaults to the system temporary directory)
+    #[clap(long, global = true)]
+    pub spill_dir: Option<PathBuf>,
+}
+
+/// Global options for the cache of intermediate results
+#[derive(Args)]
+pub struct CacheArgs {
+    /// Recompute intermediate results without reading or writing the result cache, even
+    /// with --cache-dir
+    #[clap(long, global = true)]
+    pub no_cache: bool,
+
+    /// Cache intermediate results in this directory; nothing is cached without it.
+    /// Cached results contain register data, so use a directory only you can read
+    #[clap(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+}
+
 /// Available commands
 #[derive(Subcommand)]
 enum Commands {
     /// Sample data from a registry
     Sample(SampleArgs),
 
+    /// Rewrite registers sorted by PNR with bloom filters and page indexes
+    Rewrite(RewriteArgs),
+
+    /// Validate registry files against their schema and write a JSON report
+    Validate(ValidateArgs),
+
+    /// Convert SAS7BDAT or CSV register files to Parquet partitioned by year
+    Convert(ConvertArgs),
+
+    /// List, prune or clear cached intermediate results
+    Cache(CacheCommandArgs),
+
     /// Check balance between case and control groups
     Balance(BalanceArgs),
 
@@ -476,6 +850,9 @@ enum Commands {
     /// Identify children in a population with Severe Chronic Disease (SCD)
     PopulationScd(PopulationScdArgs),
 
+    /// Run a phenotype definition file (JSON or TOML) over LPR data
+    Phenotype(PhenotypeArgs),
+
     /// Run the full study design pipeline (population, SCD, matching, balance)
     StudyDesign(StudyDesignArgs),
 }
@@ -494,6 +871,189 @@ struct SampleArgs {
     /// Number of samples
     #[clap(short, long)]
     count: usize,
+
+    #[clap(flatten)]
+    parquet: ParquetOutputArgs,
+}
+
+/// Parquet writer settings for commands that write registry data
+#[derive(Args)]
+struct ParquetOutputArgs {
+    /// Split the output into Hive-style directories: none, year:COLUMN or
+    /// cohort:COLUMN[:YEARS] for birth cohorts
+    #[clap(long, default_value = "none")]
+    partition_by: crate::data::io::Partitioning,
+
+    /// Sort the rows of each output file by PNR
+    #[clap(long)]
+    sort_by_pnr: bool,
+
+    /// Compression codec, e.g. zstd(3), snappy, lz4_raw or uncompressed
+    #[clap(long, default_value = "zstd(1)")]
+    compression: datafusion::parquet::basic::Compression,
+
+    /// Maximum rows per row group
+    #[clap(long, default_value = "131072")]
+    row_group_size: usize,
+
+    /// Write columns without dictionary encoding
+    #[clap(long)]
+    no_dictionary: bool,
+
+    /// Column statistics: none, chunk or page (page also writes the page index)
+    #[clap(long, default_value = "page")]
+    statistics: datafusion::parquet::file::properties::EnabledStatistics,
+
+    /// Comma-separated columns with bloom filters (the PNR column if not given)
+    #[clap(long, value_delimiter = ',')]
+    bloom_filter_columns: Option<Vec<String>>,
+}
+
+impl From<ParquetOutputArgs> for crate::data::io::ParquetWriteOptions {
+    fn from(args: ParquetOutputArgs) -> Self {
+        Self {
+            compression: args.compression,
+            row_group_size: args.row_group_size,
+            dictionary: !args.no_dictionary,
+            statistics: args.statistics,
+            bloom_filter_columns: args.bloom_filter_columns,
+            sort_by_pnr: args.sort_by_pnr,
+            partitioning: args.partition_by,
+            ..Self::default()
+        }
+    }
+}
+
+/// Arguments for the rewrite command
+#[derive(Args)]
+struct RewriteArgs {
+    /// Input Parquet file or directory
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Output Parquet file or directory
+    #[clap(short, long)]
+    output: PathBuf,
+
+    /// PNR column to sort by (PNR or CPR if not given)
+    #[clap(long)]
+    pnr_column: Option<String>,
+
+    /// Maximum rows per row group
+    #[clap(long, default_value = "131072")]
+    row_group_size: usize,
+
+    /// Maximum rows per data page
+    #[clap(long, default_value = "8192")]
+    page_row_limit: usize,
+
+    /// False positive probability of the PNR bloom filters
+    #[clap(long, default_value = "0.01")]
+    bloom_filter_fpp: f64,
+}
+
+/// Arguments for the validate command
+#[derive(Args)]
+struct ValidateArgs {
+    /// Input Parquet file or registry directory
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Registry name (inferred from the input path if not given)
+    #[clap(short = 'g', long)]
+    registry: Option<String>,
+
+    /// JSON report file (printed to stdout if not given)
+    #[clap(short, long)]
+    report: Option<PathBuf>,
+
+    /// Accepted PNR format: any, digits or cpr
+    #[clap(long, default_value = "digits")]
+    pnr_format: String,
+
+    /// Earliest plausible date (format: YYYY-MM-DD)
+    #[clap(long, default_value = "1900-01-01")]
+    min_date: String,
+
+    /// Latest plausible date (format: YYYY-MM-DD, today if not given)
+    #[clap(long)]
+    max_date: Option<String>,
+
+    /// Fail on warnings (renamed, missing, extra or coerced columns)
+    #[clap(long)]
+    strict: bool,
+}
+
+/// Arguments for the cache command
+#[derive(Args)]
+struct CacheCommandArgs {
+    #[clap(subcommand)]
+    action: CacheSubcommand,
+}
+
+/// Cache maintenance subcommands
+#[derive(Subcommand)]
+enum CacheSubcommand {
+    /// List cached results with their inputs, size and last use
+    List,
+
+    /// Remove cached results that are old or beyond a size limit
+    Prune(CachePruneArgs),
+
+    /// Remove all cached results
+    Clear,
+}
+
+/// Arguments for the cache prune command
+#[derive(Args)]
+struct CachePruneArgs {
+    /// Remove results not used in this many days
+    #[clap(long)]
+    older_than_days: Option<u64>,
+
+    /// Remove the least recently used results until the cache is at most this size, e.g. 100G
+    #[clap(long, value_parser = crate::data::io::parse_memory_size)]
+    max_size: Option<usize>,
+
+    /// Only prune results of this stage (lpr, population or phenotype)
+    #[clap(long)]
+    stage: Option<String>,
+}
+
+/// Arguments for the convert command
+#[derive(Args)]
+struct ConvertArgs {
+    /// Input SAS7BDAT or CSV file, or a directory of them
+    #[clap(short, long)]
+    input: PathBuf,
+
+    /// Output directory for the Parquet files
+    #[clap(short, long)]
+    output: PathBuf,
+
+    /// Registry name (inferred from the input path if not given)
+    #[clap(short = 'g', long)]
+    registry: Option<String>,
+
+    /// Year of all rows (taken from the file names if not given)
+    #[clap(long)]
+    year: Option<i32>,
+
+    /// Date or year column to partition rows by
+    #[clap(long)]
+    year_column: Option<String>,
+
+    /// CSV field separator
+    #[clap(long, default_value = ";")]
+    delimiter: char,
+
+    /// CSV encoding: utf8 or latin1
+    #[clap(long, default_value = "utf8")]
+    encoding: String,
+
+    /// CSV numbers use a decimal point instead of a decimal comma
+    #[clap(long)]
+    decimal_point: bool,
 }
 
 /// Arguments for the balance command
@@ -534,6 +1094,54 @@ struct PopulationArgs {
     /// End year for filtering births (inclusive)
     #[clap(long, default_value = "2018")]
     end_year: i32,
+
+    /// Precedence when BEF and MFR disagree on parents (bef-first, mfr-first, most-recent-bef)
+    #[clap(long, default_value = "bef-first")]
+    parent_precedence: String,
+
+    /// VNDS migration data path (required for residency rules)
+    #[clap(long)]
+    vnds: Option<PathBuf>,
+
+    /// Residency eligibility rule, applied in the order given
+    /// (born-in-dk, resident-from-birth, resident-years=N, in-bef)
+    #[clap(long = "residency-rule")]
+    residency_rules: Vec<String>,
+
+    /// Index date for residency rules (format: YYYY-MM-DD)
+    #[clap(long)]
+    index_date: Option<String>,
+}
+
+/// Diagnosis type and status filter arguments shared by the SCD and phenotype commands
+#[derive(Args)]
+struct DiagnosisFilterArgs {
+    /// Diagnosis types to include: action, action-secondary or all
+    #[clap(long, default_value = "all")]
+    diagnosis_types: String,
+
+    /// Drop LPR3 diagnoses that were later disproven (senere_afkraeftet)
+    #[clap(long)]
+    drop_disproven: bool,
+
+    /// Ignore referral diagnoses (type H)
+    #[clap(long)]
+    ignore_referral: bool,
+
+    /// Ignore supplementary codes (types + and M, and LPR3 codes with a parent code)
+    #[clap(long)]
+    ignore_supplementary: bool,
+}
+
+impl DiagnosisFilterArgs {
+    fn to_filter(&self) -> Result<DiagnosisFilter> {
+        Ok(DiagnosisFilter {
+            types: self.diagnosis_types.parse()?,
+            drop_disproven: self.drop_disproven,
+            ignore_referral: self.ignore_referral,
+            ignore_supplementary: self.ignore_supplementary,
+        })
+    }
 }
 
 /// Arguments for the SCD command
@@ -562,6 +1170,28 @@ struct ScdArgs {
     /// End date for filtering LPR data (format: YYYY-MM-DD)
     #[clap(long)]
     end_date: Option<String>,
+
+    /// SCD definition file in JSON or TOML format, or `scdalgo-r` for the bundled
+    /// definitions matching the reference R algorithm (defaults to the built-in definitions)
+    #[clap(long)]
+    definitions: Option<PathBuf>,
+
+    /// Also count secondary diagnoses (type B, and H and +/M unless excluded with the
+    /// diagnosis filter options). Off by default: like the reference algorithm, only the
+    /// primary diagnosis of each contact is counted
+    #[clap(long)]
+    secondary_diagnoses: bool,
+
+    #[clap(flatten)]
+    diagnosis_filter: DiagnosisFilterArgs,
+
+    /// Cause-of-death data (DODSAARSAG), used by definitions with the `death_cause` source
+    #[clap(long)]
+    death_causes: Option<PathBuf>,
+
+    /// Death register data (DOD), for the dates of causes of death
+    #[clap(long)]
+    deaths: Option<PathBuf>,
 }
 
 /// Arguments for the Population SCD command
@@ -594,6 +1224,67 @@ struct PopulationScdArgs {
     /// End date for filtering LPR data (format: YYYY-MM-DD)
     #[clap(long)]
     end_date: Option<String>,
+
+    /// SCD definition file in JSON or TOML format, or `scdalgo-r` for the bundled
+    /// definitions matching the reference R algorithm (defaults to the built-in definitions)
+    #[clap(long)]
+    definitions: Option<PathBuf>,
+
+    /// Also count secondary diagnoses (type B, and H and +/M unless excluded with the
+    /// diagnosis filter options). Off by default: like the reference algorithm, only the
+    /// primary diagnosis of each contact is counted
+    #[clap(long)]
+    secondary_diagnoses: bool,
+
+    #[clap(flatten)]
+    diagnosis_filter: DiagnosisFilterArgs,
+}
+
+/// Arguments for the Phenotype command
+#[derive(Args)]
+struct PhenotypeArgs {
+    /// Phenotype definition file in JSON or TOML format
+    #[clap(short, long)]
+    definitions: PathBuf,
+
+    /// LPR data directory (should contain LPR2 and/or LPR3 data)
+    #[clap(short, long)]
+    lpr: PathBuf,
+
+    /// Output directory for phenotype results and reports
+    #[clap(short, long)]
+    output: PathBuf,
+
+    /// Population data file with birth dates, needed for age rules
+    #[clap(short, long)]
+    population: Option<PathBuf>,
+
+    /// Include LPR2 data
+    #[clap(long, default_value = "true")]
+    include_lpr2: bool,
+
+    /// Include LPR3 data
+    #[clap(long, default_value = "true")]
+    include_lpr3: bool,
+
+    /// Start date for filtering LPR data (format: YYYY-MM-DD)
+    #[clap(long)]
+    start_date: Option<String>,
+
+    /// End date for filtering LPR data (format: YYYY-MM-DD)
+    #[clap(long)]
+    end_date: Option<String>,
+
+    #[clap(flatten)]
+    diagnosis_filter: DiagnosisFilterArgs,
+
+    /// Cause-of-death data (DODSAARSAG), used by definitions with the `death_cause` source
+    #[clap(long)]
+    death_causes: Option<PathBuf>,
+
+    /// Death register data (DOD), for the dates of causes of death
+    #[clap(long)]
+    deaths: Option<PathBuf>,
 }
 
 /// Arguments for the Study Design command
@@ -680,15 +1371,113 @@ impl Cli {
         
         crate::debug_log!(crate::utils::logging::Component::Cli, "init", "Log level set to: {}", log_level);
 
+        crate::data::io::set_execution_config(crate::data::io::ExecutionConfig {
+            memory_limit: cli.execution.memory_limit,
+            spill_dir: cli.execution.spill_dir,
+        })?;
+        crate::data::cache::set_cache_config(crate::data::cache::CacheConfig {
+            dir: cli.cache.cache_dir.filter(|_| !cli.cache.no_cache),
+        })?;
+
         match cli.command {
             Commands::Sample(args) => {
                 let command = SampleCommand {
                     input_path: args.input,
                     output_path: args.output,
                     sample_count: args.count,
+                    options: args.parquet.into(),
                 };
                 command.execute()
             }
+            Commands::Rewrite(args) => {
+                let command = RewriteCommand {
+                    input_path: args.input,
+                    output_path: args.output,
+                    options: crate::data::io::RewriteOptions {
+                        pnr_column: args.pnr_column,
+                        row_group_size: args.row_group_size,
+                        page_row_limit: args.page_row_limit,
+                        bloom_filter_fpp: args.bloom_filter_fpp,
+                    },
+                };
+                command.execute()
+            }
+            Commands::Validate(args) => {
+                let parse_date = |date_str: &str| {
+                    NaiveDate::parse_from_str(date_str, "%Y-%m-%d").map_err(|_| {
+                        crate::error::IdsError::Validation(format!(
+                            "Invalid date format. Expected YYYY-MM-DD, got {date_str}"
+                        ))
+                    })
+                };
+                let defaults = crate::data::schema::ValidationConfig::default();
+                let validation = crate::data::schema::ValidationConfig {
+                    pnr_format: args.pnr_format.parse()?,
+                    min_date: parse_date(&args.min_date)?,
+                    max_date: match args.max_date {
+                        Some(date_str) => parse_date(&date_str)?,
+                        None => defaults.max_date,
+                    },
+                    strict: args.strict,
+                    ..defaults
+                };
+                let command = ValidateCommand {
+                    config: crate::commands::validate::ValidateCommandConfig {
+                        input_path: args.input,
+                        registry: args.registry,
+                        report_path: args.report,
+                        validation,
+                    },
+                };
+                command.execute()
+            }
+            Commands::Convert(args) => {
+                let delimiter = u8::try_from(args.delimiter).map_err(|_| {
+                    crate::error::IdsError::Validation(format!(
+                        "CSV delimiter must be an ASCII character, got {}",
+                        args.delimiter
+                    ))
+                })?;
+                let command = ConvertCommand {
+                    input_path: args.input,
+                    output_path: args.output,
+                    options: crate::data::io::ConvertOptions {
+                        registry: args.registry,
+                        year: args.year,
+                        year_column: args.year_column,
+                        csv: crate::data::io::convert::CsvOptions {
+                            delimiter,
+                            encoding: args.encoding.parse()?,
+                            decimal_comma: !args.decimal_point,
+                            ..crate::data::io::convert::CsvOptions::default()
+                        },
+                        ..crate::data::io::ConvertOptions::default()
+                    },
+                };
+                command.execute()
+            }
+            Commands::Cache(args) => {
+                let action = match args.action {
+                    CacheSubcommand::List => CacheAction::List,
+                    CacheSubcommand::Prune(prune) => {
+                        if prune.older_than_days.is_none() && prune.max_size.is_none() {
+                            return Err(crate::error::IdsError::Validation(
+                                "Nothing to prune: give --older-than-days or --max-size"
+                                    .to_string(),
+                            ));
+                        }
+                        CacheAction::Prune(crate::data::cache::PruneOptions {
+                            older_than: prune
+                                .older_than_days
+                                .map(|days| std::time::Duration::from_secs(days * 24 * 60 * 60)),
+                            max_size: prune.max_size.map(|size| size as u64),
+                            stage: prune.stage,
+                        })
+                    }
+                    CacheSubcommand::Clear => CacheAction::Clear,
+                };
+                CacheCommand { action }.execute()
+            }
             Commands::Balance(args) => {
                 let command = BalanceCommand {
                     case_path: args.cases,
@@ -698,12 +1487,35 @@ impl Cli {
                 command.execute()
             }
             Commands::Population(args) => {
+                let residency_rules = args
+                    .residency_rules
+                    .iter()
+                    .map(|rule| rule.parse())
+                    .collect::<Result<Vec<ResidencyRule>>>()?;
+
+                let index_date = args
+                    .index_date
+                    .map(|date_str| {
+                        NaiveDate::parse_from_str(&date_str, "%Y-%m-%d").map_err(|_| {
+                            crate::error::IdsError::Validation(format!(
+                                "Invalid index date format. Expected YYYY-MM-DD, got {date_str}"
+                            ))
+                        })
+                    })
+                    .transpose()?;
+
+                let parent_precedence: ParentPrecedence = args.parent_precedence.parse()?;
+
                 let command = PopulationCommand {
                     bef_path: args.bef,
                     mfr_path: args.mfr,
                     output_dir: args.output,
                     birth_start_year: args.start_year,
                     birth_end_year: args.end_year,
+                    parent_precedence,
+                    vnds_path: args.vnds,
+                    residency_rules,
+                    index_date,
                 };
                 command.execute()
             }
@@ -728,6 +1540,11 @@ impl Cli {
                     include_lpr3: args.include_lpr3,
                     start_date,
                     end_date,
+                    definitions: args.definitions,
+                    diagnosis_filter: args.diagnosis_filter.to_filter()?,
+                    secondary_diagnoses: args.secondary_diagnoses,
+                    death_causes_path: args.death_causes,
+                    deaths_path: args.deaths,
                 };
                 command.execute()
             }
@@ -753,6 +1570,38 @@ impl Cli {
                     include_lpr3: args.include_lpr3,
                     start_date,
                     end_date,
+                    definitions: args.definitions,
+                    diagnosis_filter: args.diagnosis_filter.to_filter()?,
+                    secondary_diagnoses: args.secondary_diagnoses,
+                };
+                command.execute()
+            }
+            Commands::Phenotype(args) => {
+                // Parse start and end dates if provided
+                let start_date = args.start_date.map(|date_str| {
+                    chrono::NaiveDate::parse_from_str(&date_str, "%Y-%m-%d").unwrap_or_else(|_| {
+                        panic!("Invalid start date format. Expected YYYY-MM-DD, got {date_str}")
+                    })
+                });
+
+                let end_date = args.end_date.map(|date_str| {
+                    chrono::NaiveDate::parse_from_str(&date_str, "%Y-%m-%d").unwrap_or_else(|_| {
+                        panic!("Invalid end date format. Expected YYYY-MM-DD, got {date_str}")
+                    })
+                });
+
+                let command = PhenotypeCommand {
+                    definitions: args.definitions,
+                    lpr_path: args.lpr,
+                    output_dir: args.output,
+                    population_path: args.population,
+                    include_lpr2: args.include_lpr2,
+                    include_lpr3: args.include_lpr3,
+                    start_date,
+                    end_date,
+                    diagnosis_filter: args.diagnosis_filter.to_filter()?,
+                    death_causes_path: args.death_causes,
+                    deaths_path: args.deaths,
                 };
                 command.execute()
             }
@@ -2,13 +2,17 @@
 //!
 //! This module contains handlers for various CLI commands
 
+pub mod phenotype;
 pub mod population;
 pub mod scd;
 pub mod population_scd;
 pub mod study_design;
+pub mod validate;
 
 // Re-export common command handlers
+pub use phenotype::handle_phenotype_command;
 pub use population::handle_population_command;
 pub use scd::handle_scd_command;
 pub use population_scd::handle_population_scd_command;
-pub use study_design::handle_study_design_command;
\ No newline at end of file
+pub use study_design::handle_study_design_command;
+pub use validate::handle_validate_command;
\ No newline at end of file
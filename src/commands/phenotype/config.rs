@@ -0,0 +1,52 @@
+//! Configuration for the Phenotype command
+//!
+//! This module defines the configuration options for the Phenotype command.
+
+use chrono::NaiveDate;
+
+use crate::algorithm::health::diagnosis::DiagnosisFilter;
+use std::path::PathBuf;
+
+/// Configuration for the Phenotype command
+pub struct PhenotypeCommandConfig {
+    /// Phenotype definition file (JSON or TOML)
+    pub definitions_path: PathBuf,
+    /// Base directory for LPR data
+    pub lpr_data_path: PathBuf,
+    /// Output directory for the phenotype results
+    pub output_dir: PathBuf,
+    /// Population data with `PNR` and `FOED_DAG`, needed for age rules
+    pub population_path: Option<PathBuf>,
+    /// Whether to include LPR2 data
+    pub include_lpr2: bool,
+    /// Whether to include LPR3 data
+    pub include_lpr3: bool,
+    /// Start date for filtering health data (inclusive)
+    pub start_date: Option<NaiveDate>,
+    /// End date for filtering health data (inclusive)
+    pub end_date: Option<NaiveDate>,
+    /// Diagnosis type and status filter applied when harmonising LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+    /// Cause-of-death (DODSAARSAG) data, for definitions with the `death_cause` source
+    pub death_causes_path: Option<PathBuf>,
+    /// Death register (DOD) data, for the dates of causes of death
+    pub deaths_path: Option<PathBuf>,
+}
+
+impl Default for PhenotypeCommandConfig {
+    fn default() -> Self {
+        Self {
+            definitions_path: PathBuf::from("./phenotype.toml"),
+            lpr_data_path: PathBuf::from("./data/lpr"),
+            output_dir: PathBuf::from("./output/phenotype"),
+            population_path: None,
+            include_lpr2: true,
+            include_lpr3: true,
+            start_date: None,
+            end_date: None,
+            diagnosis_filter: DiagnosisFilter::default(),
+            death_causes_path: None,
+            deaths_path: None,
+        }
+    }
+}
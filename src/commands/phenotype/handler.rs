@@ -0,0 +1,253 @@
+//! Handler for the Phenotype command
+//!
+//! This module provides the implementation for handling the Phenotype command.
+
+use arrow::record_batch::RecordBatch;
+use log::info;
+use std::collections::HashSet;
+use std::fs;
+
+use crate::algorithm::health::death_causes::{
+    append_death_causes, cause_specific_mortality, load_death_records, mortality_to_record_batch,
+};
+use crate::algorithm::health::diagnosis::DiagnosisSource;
+use crate::algorithm::health::lpr::LprConfig;
+use crate::algorithm::health::lpr_partitioned::{lpr_cache_key, partition_lpr_cached};
+use crate::algorithm::health::phenotype::{
+    apply_phenotype, phenotype_results_from_record_batch, phenotype_results_to_record_batch,
+    Phenotype, PhenotypeConfig, PhenotypeResult,
+};
+use crate::algorithm::population::classification::collect_birth_dates;
+use crate::data::cache::{CacheKey, ResultCache};
+use crate::error::{IdsError, Result};
+use crate::utils::reports::{write_csv_report, DiagnosisFrequencies};
+use crate::utils::runtime::get_runtime;
+
+use super::config::PhenotypeCommandConfig;
+
+/// Handle the Phenotype command
+pub fn handle_phenotype_command(config: &PhenotypeCommandConfig) -> Result<()> {
+    // Create output directory if it doesn't exist
+    if !config.output_dir.exists() {
+        fs::create_dir_all(&config.output_dir).map_err(IdsError::Io)?;
+    }
+
+    // Step 1: Load the phenotype definitions
+    let phenotype = Phenotype::load(&config.definitions_path)?;
+    let info = phenotype.definition_info().clone();
+    if phenotype.uses_source(DiagnosisSource::DeathCause) && config.death_causes_path.is_none() {
+        return Err(IdsError::Validation(format!(
+            "Phenotype '{}' uses causes of death (death_cause source), but no cause-of-death \
+             data was given (--death-causes)",
+            phenotype.name()
+        )));
+    }
+
+    // Get the shared Tokio runtime
+    let runtime = get_runtime()?;
+
+    // Step 2: Harmonise LPR data, partitioned by patient so it need not fit in memory
+    info!("Loading LPR data from: {}", config.lpr_data_path.display());
+    let lpr_config = LprConfig {
+        include_lpr2: config.include_lpr2,
+        include_lpr3: config.include_lpr3,
+        start_date: config.start_date,
+        end_date: config.end_date,
+        diagnosis_filter: config.diagnosis_filter,
+    };
+    let lpr_path = config.lpr_data_path.to_str().unwrap();
+    let cache = ResultCache::global();
+    let lpr = runtime.block_on(partition_lpr_cached(lpr_path, &lpr_config, None, &cache))?;
+    info!(
+        "Harmonised {} LPR diagnoses in {} partition(s)",
+        lpr.num_diagnoses(),
+        lpr.partitions()
+    );
+    if phenotype.uses_procedures() && !lpr.has_procedures() {
+        log::warn!(
+            "Phenotype '{}' has procedure patterns, but no LPR procedure data was found",
+            phenotype.name()
+        );
+    }
+
+    // Causes of death, as a diagnosis source and for cause-specific mortality
+    let death_records = match &config.death_causes_path {
+        Some(path) => {
+            info!("Loading causes of death from: {}", path.display());
+            Some(runtime.block_on(load_death_records(path, config.deaths_path.as_deref()))?)
+        }
+        None => None,
+    };
+    // Split like the LPR data, so causes of death are added to their patient's partition
+    let partitioned_deaths = death_records
+        .as_ref()
+        .filter(|_| phenotype.uses_source(DiagnosisSource::DeathCause))
+        .map(|records| {
+            let mut partitions = vec![Vec::new(); lpr.partitions()];
+            for record in records {
+                partitions[lpr.partition_of(&record.patient_id)].push(record.clone());
+            }
+            partitions
+        });
+
+    // Step 3: Collect birth dates for age rules
+    let birth_dates = match &config.population_path {
+        Some(path) => {
+            info!("Loading population data from: {}", path.display());
+            let batches = runtime.block_on(async {
+                crate::data::io::parquet::load_parquet_directory(path, None, None).await
+            })?;
+            let mut birth_dates = std::collections::HashMap::new();
+            for batch in &batches {
+                birth_dates.extend(collect_birth_dates(batch, "PNR").unwrap_or_default());
+            }
+            info!("Collected {} birth dates", birth_dates.len());
+            Some(birth_dates)
+        }
+        None => None,
+    };
+
+    // Step 4: Run the phenotype one partition at a time, unless its results are cached
+    let phenotype_config = PhenotypeConfig::default();
+    let mut results_key = CacheKey::new("phenotype")
+        .with_value("lpr", &lpr_cache_key(lpr_path, &lpr_config, None)?.digest())
+        .with_value("definitions", &(&info.name, &info.version, &info.sha256))
+        .with_value("config", &phenotype_config);
+    for (name, path) in [
+        ("population", &config.population_path),
+        ("death_causes", &config.death_causes_path),
+        ("deaths", &config.deaths_path),
+    ] {
+        if let Some(path) = path {
+            results_key = results_key.with_files(name, path)?;
+        }
+    }
+    let cached = cache.get::<RecordBatch>(&results_key);
+    let mut results = match &cached {
+        Some(batch) => phenotype_results_from_record_batch(&phenotype, batch)?,
+        None => Vec::new(),
+    };
+    let cached_patients = cached.is_some().then(|| confirmed_patients(&results));
+
+    let mut diagnosis_frequencies = DiagnosisFrequencies::default();
+    for partition in 0..lpr.partitions() {
+        let lpr_data = lpr.diagnoses(partition)?;
+        let lpr_data = match &partitioned_deaths {
+            Some(records) => append_death_causes(
+                &lpr_data,
+                &records[partition],
+                phenotype.uses_source(DiagnosisSource::Lpr),
+            )?,
+            None => lpr_data,
+        };
+
+        let mut partition_patients = HashSet::new();
+        if cached_patients.is_none() {
+            let procedures = if phenotype.uses_procedures() {
+                lpr.procedures(partition)?
+            } else {
+                None
+            };
+            let partition_results = apply_phenotype(
+                &lpr_data,
+                procedures.as_ref(),
+                &phenotype,
+                &phenotype_config,
+                birth_dates.as_ref(),
+            )?;
+            partition_patients = confirmed_patients(&partition_results);
+            results.extend(partition_results);
+        }
+
+        // Most frequent primary diagnoses among persons with the phenotype
+        diagnosis_frequencies.add(
+            &lpr_data,
+            "patient_id",
+            "primary_diagnosis",
+            Some(cached_patients.as_ref().unwrap_or(&partition_patients)),
+        )?;
+    }
+
+    let batch = match cached {
+        Some(batch) => batch,
+        None => {
+            results.sort_by(|a, b| a.patient_id.cmp(&b.patient_id));
+            let batch = phenotype_results_to_record_batch(&phenotype, &results)?;
+            cache.put(&results_key, &batch);
+            batch
+        }
+    };
+
+    // Step 5: Save results
+    let results_path = config
+        .output_dir
+        .join(format!("phenotype_{}.parquet", phenotype.name()));
+    runtime.block_on(async {
+        crate::data::io::parquet::save_batch_to_parquet(&batch, &results_path).await
+    })?;
+    info!("Saved phenotype results to: {}", results_path.display());
+
+    if let Some(records) = &death_records {
+        let outcomes = cause_specific_mortality(records, &phenotype);
+        let mortality = mortality_to_record_batch(&phenotype, &outcomes)?;
+        let mortality_path = config
+            .output_dir
+            .join(format!("phenotype_{}_mortality.parquet", phenotype.name()));
+        runtime.block_on(async {
+            crate::data::io::parquet::save_batch_to_parquet(&mortality, &mortality_path).await
+        })?;
+        info!("Saved cause-specific mortality to: {}", mortality_path.display());
+    }
+
+    let confirmed = results.iter().filter(|result| result.has_phenotype).count();
+    let summary_path = config
+        .output_dir
+        .join(format!("phenotype_{}_summary.csv", phenotype.name()));
+    let mut summary_rows = vec![
+        vec!["Metric".to_string(), "Value".to_string()],
+        vec!["Phenotype".to_string(), info.name.clone()],
+        vec!["Definitions Version".to_string(), info.version.clone()],
+        vec!["Definitions Source".to_string(), info.source.clone()],
+        vec!["Definitions SHA-256".to_string(), info.sha256.clone()],
+        vec![
+            "Diagnosis Filter".to_string(),
+            config.diagnosis_filter.to_string(),
+        ],
+        vec![
+            "Persons with Matching Records".to_string(),
+            results.len().to_string(),
+        ],
+        vec!["Persons with Phenotype".to_string(), confirmed.to_string()],
+    ];
+    for (index, category) in phenotype.category_names().iter().enumerate() {
+        let count = results
+            .iter()
+            .filter(|result| result.categories[index])
+            .count();
+        summary_rows.push(vec![format!("Category: {category}"), count.to_string()]);
+    }
+    write_csv_report(&summary_path, &summary_rows)?;
+    info!("Saved phenotype summary to: {}", summary_path.display());
+
+    let diagnoses_path = config
+        .output_dir
+        .join(format!("phenotype_{}_diagnoses.csv", phenotype.name()));
+    diagnosis_frequencies.save(&diagnoses_path, 100)?;
+    info!("Saved phenotype diagnosis report to: {}", diagnoses_path.display());
+
+    info!(
+        "Phenotype '{}' complete: {confirmed} of {} persons with matching records confirmed",
+        phenotype.name(),
+        results.len()
+    );
+    Ok(())
+}
+
+/// Patients with the phenotype
+fn confirmed_patients(results: &[PhenotypeResult]) -> HashSet<String> {
+    results
+        .iter()
+        .filter(|result| result.has_phenotype)
+        .map(|result| result.patient_id.clone())
+        .collect()
+}
@@ -0,0 +1,10 @@
+//! Phenotype command implementation
+//!
+//! This module provides the implementation for the Phenotype command, which runs a
+//! phenotype definition file over harmonised LPR data.
+
+pub mod config;
+pub mod handler;
+
+pub use config::PhenotypeCommandConfig;
+pub use handler::handle_phenotype_command;
@@ -1,7 +1,10 @@
 //! Configuration for the population generation command
 
+use chrono::NaiveDate;
 use std::path::PathBuf;
 
+use crate::algorithm::population::{ParentPrecedence, ResidencyRule};
+
 /// Configuration for the population generation command
 #[derive(Debug, Clone)]
 pub struct PopulationCommandConfig {
@@ -19,6 +22,18 @@ pub struct PopulationCommandConfig {
 
     /// End year for filtering births (inclusive)
     pub birth_inclusion_end_year: i32,
+
+    /// Which register wins when BEF and MFR disagree on parent links
+    pub parent_precedence: ParentPrecedence,
+
+    /// Path to the VNDS migration data, required when residency rules are given
+    pub vnds_path: Option<PathBuf>,
+
+    /// Residency eligibility rules applied after population generation, in order
+    pub residency_rules: Vec<ResidencyRule>,
+
+    /// Index date for residency rules (defaults to each person's birth date)
+    pub index_date: Option<NaiveDate>,
 }
 
 impl Default for PopulationCommandConfig {
@@ -29,6 +44,10 @@ impl Default for PopulationCommandConfig {
             output_dir: PathBuf::from("data/population"),
             birth_inclusion_start_year: 1995,
             birth_inclusion_end_year: 2018,
+            parent_precedence: ParentPrecedence::default(),
+            vnds_path: None,
+            residency_rules: Vec::new(),
+            index_date: None,
         }
     }
 }
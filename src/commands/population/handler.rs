@@ -3,22 +3,24 @@
 use log::info;
 use std::fs;
 
-use crate::algorithm::population::core::{generate_population, PopulationConfig};
+use crate::algorithm::population::core::{
+    generate_population_cached, PopulationConfig, StreamedPopulation,
+};
+use crate::algorithm::population::{
+    apply_residency_rules, EntryRule, ResidencyConfig, ResidencyRule,
+};
 use crate::commands::population::config::PopulationCommandConfig;
 use crate::error::{IdsError, Result};
-use crate::utils::date_utils;
-use crate::utils::reports::save_population_summary;
-use arrow::array::{Array, StringArray};
+use crate::data::cache::ResultCache;
+use crate::data::io::ParquetBatchWriter;
+use crate::utils::reports::{save_exclusion_report, save_population_summary};
 use arrow::record_batch::RecordBatch;
-use chrono::Datelike;
-use datafusion::common::config::TableParquetOptions;
-use datafusion::dataframe::DataFrameWriteOptions;
-use datafusion::prelude::*;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use tokio::runtime::Runtime;
 
-// Import the new DataFusion-based registry registry factory
 use crate::data::registry::factory::RegistryFactory;
+use crate::data::registry::loaders::bef::BefRegister;
+use crate::data::registry::loaders::mfr::MfrRegister;
 use crate::data::registry::traits::RegisterLoader;
 
 /// Handle the population generation command
@@ -32,70 +34,35 @@ pub fn handle_population_command(config: &PopulationCommandConfig) -> Result<()>
         include_death_data: true,
         include_death_cause_data: true,
         include_migration_data: true,
+        parent_precedence: config.parent_precedence,
     };
 
     // Create a tokio runtime for async operations
     let runtime = Runtime::new()
         .map_err(|e| IdsError::Data(format!("Failed to create async runtime: {e}")))?;
 
-    // Load BEF data using the new DataFusion-based registry loader
+    // Stream BEF and MFR, keeping only the children and parents of the population
     info!("Reading BEF data from: {:?}", config.bef_path);
-    let bef_data_vec = runtime.block_on(async {
-        // Create a BEF registry loader from the factory
-        let bef_registry = RegistryFactory::from_name("bef")?;
-
-        // Downcast to the correct type and use async load method
-        let loader = bef_registry
-            .downcast_ref::<crate::data::registry::loaders::bef::BefRegister>()
-            .ok_or_else(|| IdsError::Data("Failed to downcast BEF register".to_string()))?;
-            
-        // Use async load method from the RegisterLoader trait
-        let base_path = config.bef_path.to_str().unwrap_or("");
-        loader.load(base_path, None).await
-    })?;
-
-    if bef_data_vec.is_empty() {
-        return Err(IdsError::Data("No BEF data found".to_string()));
-    }
-
-    // Combine all batches into a single RecordBatch
-    info!("Found {} BEF batches", bef_data_vec.len());
-    let bef_data = combine_record_batches(&bef_data_vec)?;
-    info!("Loaded {} rows from BEF data", bef_data.num_rows());
-
-    // Load MFR data using the new DataFusion-based registry loader
     info!("Reading MFR data from: {:?}", config.mfr_path);
-    let mfr_data_vec = runtime.block_on(async {
-        // Create an MFR registry loader from the factory
-        let mfr_registry = RegistryFactory::from_name("mfr")?;
-
-        // Downcast to the correct type and use async load method
-        let loader = mfr_registry
-            .downcast_ref::<crate::data::registry::loaders::mfr::MfrRegister>()
-            .ok_or_else(|| IdsError::Data("Failed to downcast MFR register".to_string()))?;
-            
-        // Use async load method
-        let base_path = config.mfr_path.to_str().unwrap_or("");
-        loader.load(base_path, None).await
-    })?;
-
-    if mfr_data_vec.is_empty() {
-        return Err(IdsError::Data("No MFR data found".to_string()));
-    }
-
-    // Combine all batches into a single RecordBatch
-    info!("Found {} MFR batches", mfr_data_vec.len());
-    let mfr_data = combine_record_batches(&mfr_data_vec)?;
-    info!("Loaded {} rows from MFR data", mfr_data.num_rows());
-
-    // Generate population data
     info!("Generating population data");
-
-    // Add debug info about birth years
-    debug_birth_years(&bef_data, "BEF")?;
-    debug_birth_years(&mfr_data, "MFR")?;
-
-    let (family_data, summary) = generate_population(&bef_data, &mfr_data, &algo_config)?;
+    let population = runtime.block_on(async {
+        let bef_path = config.bef_path.to_str().unwrap_or("");
+        let mfr_path = config.mfr_path.to_str().unwrap_or("");
+        generate_population_cached(
+            &BefRegister,
+            bef_path,
+            &MfrRegister,
+            mfr_path,
+            &algo_config,
+            &ResultCache::global(),
+        )
+        .await
+    })?;
+    let StreamedPopulation {
+        family_data,
+        summary,
+        bef_pnrs,
+    } = population;
 
     // Print detailed summary
     info!("Population Summary:");
@@ -107,6 +74,11 @@ pub fn handle_population_command(config: &PopulationCommandConfig) -> Result<()>
     info!(" - MFR missing mother: {}", summary.mfr_missing_mother);
     info!(" - Records only in BEF: {}", summary.records_only_in_bef);
     info!(" - Records only in MFR: {}", summary.records_only_in_mfr);
+    info!(
+        " - BEF/MFR disagreements (resolved {:?}): {}",
+        config.parent_precedence,
+        summary.parent_conflicts.len()
+    );
     info!(
         " - Total combined records: {}",
         summary.total_combined_records
@@ -128,30 +100,19 @@ pub fn handle_population_command(config: &PopulationCommandConfig) -> Result<()>
     // Create output directory if it doesn't exist
     fs::create_dir_all(&config.output_dir)?;
 
-    // Save population data using DataFusion's write_parquet functionality
+    // Apply residency eligibility rules if requested
+    let family_data = if config.residency_rules.is_empty() {
+        family_data
+    } else {
+        apply_residency_eligibility(config, &runtime, &bef_pnrs, &family_data)?
+    };
+
+    // Save population data
     let population_file = config.output_dir.join("population.parquet");
     info!("Saving population data to: {population_file:?}");
-
-    // Use DataFusion to write the parquet file
-    runtime.block_on(async {
-        // Create a session context
-        let ctx = SessionContext::new();
-
-        // Create a memory table from the record batch
-        let table_name = "population_data";
-        ctx.register_batch(table_name, family_data.clone())?;
-
-        // Create a DataFrame and write to Parquet
-        let df = ctx.table(table_name).await?;
-
-        // Write to Parquet using DataFusion (with optimal settings)
-        df.write_parquet(
-            population_file.to_str().unwrap(),
-            DataFrameWriteOptions::default(),
-            Some(TableParquetOptions::new()),
-        )
-        .await
-    })?;
+    let mut writer = ParquetBatchWriter::create(&population_file, family_data.schema())?;
+    writer.write(&family_data)?;
+    writer.finish()?;
 
     // Save summary reports
     let reports_dir = config.output_dir.join("reports");
@@ -162,115 +123,55 @@ pub fn handle_population_command(config: &PopulationCommandConfig) -> Result<()>
     Ok(())
 }
 
-/// Combines multiple record batches into a single batch
-///
-/// This function takes a vector of Arrow `RecordBatches` with the same schema
-/// and combines them into a single `RecordBatch` containing all rows.
-fn combine_record_batches(batches: &[RecordBatch]) -> Result<RecordBatch> {
-    // If empty, return an error
-    if batches.is_empty() {
-        return Err(IdsError::Data("No record batches to combine".to_string()));
-    }
+/// Apply residency eligibility rules and write the exclusion flowchart
+fn apply_residency_eligibility(
+    config: &PopulationCommandConfig,
+    runtime: &Runtime,
+    bef_pnrs: &HashSet<String>,
+    family_data: &RecordBatch,
+) -> Result<RecordBatch> {
+    let vnds_path = config.vnds_path.as_ref().ok_or_else(|| {
+        IdsError::Validation("Residency rules require a VNDS data path".to_string())
+    })?;
 
-    // If only one batch, return a clone of it
-    if batches.len() == 1 {
-        return Ok(batches[0].clone());
+    if config.index_date.is_none()
+        && config
+            .residency_rules
+            .iter()
+            .any(ResidencyRule::requires_index_date)
+    {
+        return Err(IdsError::Validation(
+            "Residency rules relative to an index date require --index-date".to_string(),
+        ));
     }
 
-    // Get schema from first batch
-    let schema = batches[0].schema();
-
-    // For each column, concatenate all arrays
-    let mut combined_columns = Vec::with_capacity(schema.fields().len());
-
-    for i in 0..schema.fields().len() {
-        // Extract arrays in proper format for arrow::compute::concat
-        let mut arrays = Vec::with_capacity(batches.len());
-        for batch in batches {
-            let col = batch.column(i);
-            arrays.push(col.as_ref());
-        }
-
-        // Use arrow's concat to combine the arrays
-        let concatenated = arrow::compute::kernels::concat::concat(&arrays)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate arrays: {e}")))?;
-
-        combined_columns.push(concatenated);
-    }
+    info!("Reading VNDS data from: {vnds_path:?}");
+    let migration_data = runtime.block_on(async {
+        let vnds_registry = RegistryFactory::from_name("vnds")?;
+        let loader = vnds_registry
+            .downcast_ref::<crate::data::registry::loaders::vnds::VndsRegister>()
+            .ok_or_else(|| IdsError::Data("Failed to downcast VNDS register".to_string()))?;
 
-    // Create a new RecordBatch with the combined columns
-    RecordBatch::try_new(schema, combined_columns)
-        .map_err(|e| IdsError::Data(format!("Failed to create combined record batch: {e}")))
-}
+        let base_path = vnds_path.to_str().unwrap_or("");
+        loader.load(base_path, None).await
+    })?;
 
-/// Debug function to analyze birth years in a dataset
-fn debug_birth_years(batch: &RecordBatch, dataset_name: &str) -> Result<()> {
-    // Determine the date column name based on dataset
-    let date_column = if dataset_name == "MFR" {
-        "FOEDSELSDATO"
-    } else {
-        "FOED_DAG"
+    let residency_config = ResidencyConfig {
+        rules: config.residency_rules.clone(),
+        index: config.index_date.map_or(EntryRule::Birth, EntryRule::FixedDate),
     };
 
-    // Get the date column
-    let date_col = batch
-        .column_by_name(date_column)
-        .ok_or_else(|| IdsError::Data(format!("Missing {date_column} column in {dataset_name}")))?;
-
-    // Count occurrences by year
-    let mut year_counts: HashMap<i32, usize> = HashMap::new();
-    let mut null_count = 0;
-    let mut invalid_count = 0;
-
-    for i in 0..date_col.len() {
-        if date_col.is_null(i) {
-            null_count += 1;
-            continue;
-        }
-
-        if let Some(date) = date_utils::extract_date_from_array(date_col.as_ref(), i) {
-            let year = date.year();
-            *year_counts.entry(year).or_insert(0) += 1;
-        } else {
-            invalid_count += 1;
-        }
-    }
-
-    // Sort years
-    let mut years: Vec<_> = year_counts.keys().collect();
-    years.sort();
-
-    // Log summary
-    info!("{dataset_name} birth year distribution:");
-    for year in years {
-        let count = year_counts[year];
-        info!(" - {year}: {count} records");
+    let result = apply_residency_rules(family_data, &migration_data, Some(bef_pnrs), &residency_config)?;
+    for step in &result.steps {
+        info!(
+            " - {}: excluded {} of {}",
+            step.reason.description(),
+            step.excluded,
+            step.remaining_before
+        );
     }
-    info!("{dataset_name} null date values: {null_count}");
-    info!("{dataset_name} invalid date values: {invalid_count}");
 
-    // Check if PNR column exists
-    if let Some(pnr_col) = batch.column_by_name("PNR").or_else(|| {
-        // For MFR, try CPR_BARN instead
-        if dataset_name == "MFR" {
-            batch.column_by_name("CPR_BARN")
-        } else {
-            None
-        }
-    }) {
-        // Count non-null PNRs
-        if let Some(pnr_array) = pnr_col.as_any().downcast_ref::<StringArray>() {
-            let valid_pnr_count = (0..pnr_array.len())
-                .filter(|&i| !pnr_array.is_null(i) && !pnr_array.value(i).is_empty())
-                .count();
-            info!(
-                "{} valid PNR count: {}/{}",
-                dataset_name,
-                valid_pnr_count,
-                pnr_array.len()
-            );
-        }
-    }
+    save_exclusion_report(&result, &config.output_dir.join("reports"))?;
 
-    Ok(())
+    Ok(result.eligible)
 }
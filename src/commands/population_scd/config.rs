@@ -3,6 +3,8 @@
 //! This module defines the configuration options for the Population SCD command.
 
 use chrono::NaiveDate;
+
+use crate::algorithm::health::diagnosis::DiagnosisFilter;
 use std::path::PathBuf;
 
 /// Configuration for the Population SCD command
@@ -21,6 +23,13 @@ pub struct PopulationScdCommandConfig {
     pub start_date: Option<NaiveDate>,
     /// End date for filtering health data (inclusive)
     pub end_date: Option<NaiveDate>,
+    /// SCD definition file (JSON or TOML); the built-in definitions if `None`
+    pub definitions_path: Option<PathBuf>,
+    /// Diagnosis type and status filter applied when harmonising LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+    /// Also count secondary diagnoses, see
+    /// [`scd_diagnosis_columns`](crate::algorithm::health::diagnosis::scd_diagnosis_columns)
+    pub secondary_diagnoses: bool,
 }
 
 impl Default for PopulationScdCommandConfig {
@@ -33,6 +42,9 @@ impl Default for PopulationScdCommandConfig {
             include_lpr3: true,
             start_date: None,
             end_date: None,
+            definitions_path: None,
+            diagnosis_filter: DiagnosisFilter::default(),
+            secondary_diagnoses: false,
         }
     }
 }
\ No newline at end of file
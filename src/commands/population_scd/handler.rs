@@ -5,10 +5,18 @@
 use log::info;
 use std::fs;
 
+use arrow::record_batch::RecordBatch;
+
+use crate::algorithm::health::diagnosis::{scd_diagnosis_columns, DiagnosisSource, ScdDiseaseCodes};
+use crate::algorithm::health::lpr_partitioned::partition_lpr_cached;
 use crate::algorithm::population::classification::{
-    extract_scd_children, identify_scd_in_population, PopulationScdConfig,
+    collect_pnrs, extract_scd_children, identify_scd_in_population_partitioned,
+    PopulationScdConfig,
 };
+use crate::data::cache::ResultCache;
+use crate::data::io::ParquetBatchWriter;
 use crate::data::registry::loaders::lpr::find_lpr_files;
+use crate::data::registry::traits::PnrFilter;
 use crate::error::{IdsError, Result};
 use crate::utils::reports::write_csv_report;
 use crate::utils::runtime::get_runtime;
@@ -17,6 +25,17 @@ use super::config::PopulationScdCommandConfig;
 
 /// Handle the Population SCD command
 pub fn handle_population_scd_command(config: &PopulationScdCommandConfig) -> Result<()> {
+    // Causes of death are only read by the scd command
+    if ScdDiseaseCodes::load(config.definitions_path.as_deref())?
+        .uses_source(DiagnosisSource::DeathCause)
+    {
+        return Err(IdsError::Validation(
+            "The SCD definitions use causes of death (death_cause source), which the \
+             population SCD command does not read; use the scd command with --death-causes"
+                .to_string(),
+        ));
+    }
+
     // Create output directory if it doesn't exist
     if !config.output_dir.exists() {
         fs::create_dir_all(&config.output_dir).map_err(IdsError::Io)?;
@@ -40,16 +59,8 @@ pub fn handle_population_scd_command(config: &PopulationScdCommandConfig) -> Res
         return Err(IdsError::Data("No population data found".to_string()));
     }
     
-    // Combine batches if necessary
-    let population_data = if population_batches.len() == 1 {
-        population_batches[0].clone()
-    } else {
-        let schema = population_batches[0].schema();
-        arrow::compute::concat_batches(&schema, &population_batches)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate population batches: {e}")))?
-    };
-    
-    info!("Loaded {} population records", population_data.num_rows());
+    let population_rows: usize = population_batches.iter().map(RecordBatch::num_rows).sum();
+    info!("Loaded {population_rows} population records");
 
     // Step 2: Find LPR files
     info!(
@@ -75,53 +86,66 @@ pub fn handle_population_scd_command(config: &PopulationScdCommandConfig) -> Res
         info!("  LPR3_DIAGNOSER: {}", path.display());
     }
 
-    // Step 3: Load LPR data using the new optimized approach
-    info!("Loading LPR data...");
-
-    // Create LPR config
+    // Step 3: Harmonise the LPR data of the population, partitioned by patient so it
+    // need not fit in memory
+    info!("Harmonising LPR data...");
     let lpr_config = crate::algorithm::health::lpr::LprConfig {
         include_lpr2: config.include_lpr2,
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
+        diagnosis_filter: config.diagnosis_filter,
     };
-    
-    // Load and process LPR data in a single call
-    // This avoids loading the same component (like lpr_adm) multiple times
-    let lpr_data = runtime.block_on(async {
-        crate::algorithm::health::lpr::load_and_process_lpr(
-            config.lpr_data_path.to_str().unwrap(),
-            &lpr_config,
-            None, // No PNR filter for now
-        ).await
-    })?;
-    
-    info!("Loaded and processed LPR data with {} records", lpr_data.num_rows());
-    
-    // Now we can skip the step for calling process_lpr_and_identify_scd with individual components
-    // and use the processed LPR data directly
-    
-    // Step 4: Process LPR data and identify SCD in population
+    let pnr_filter = PnrFilter::new(collect_pnrs(&population_batches, "PNR")?);
+    let lpr = runtime.block_on(partition_lpr_cached(
+        config.lpr_data_path.to_str().unwrap(),
+        &lpr_config,
+        Some(&pnr_filter),
+        &ResultCache::global(),
+    ))?;
+    info!(
+        "Harmonised {} LPR diagnoses and {} procedures in {} partition(s)",
+        lpr.num_diagnoses(),
+        lpr.num_procedures(),
+        lpr.partitions()
+    );
+
+    // Step 4: Identify SCD in the population, writing the full population with SCD
+    // indicators and the SCD children as the annotated batches are produced
     info!("Identifying SCD in population...");
     let scd_config = PopulationScdConfig {
         include_lpr2: config.include_lpr2,
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
-        diagnosis_columns: vec![
-            "primary_diagnosis".to_string(),
-            "secondary_diagnosis".to_string(),
-        ],
+        diagnosis_columns: scd_diagnosis_columns(config.secondary_diagnoses),
         patient_id_column: "patient_id".to_string(),
         date_column: "admission_date".to_string(),
         population_pnr_column: "PNR".to_string(),
+        definitions_path: config.definitions_path.clone(),
+        diagnosis_filter: config.diagnosis_filter,
     };
-    
-    // Use the pre-processed LPR data directly
-    let (population_scd_data, scd_summary) = identify_scd_in_population(
-        &population_data,
-        &lpr_data,
+
+    let population_scd_path = config.output_dir.join("population_scd.parquet");
+    let scd_children_path = config.output_dir.join("scd_children.parquet");
+    let mut writers: Option<(ParquetBatchWriter, ParquetBatchWriter)> = None;
+    let scd_summary = identify_scd_in_population_partitioned(
+        &population_batches,
+        &lpr,
         &scd_config,
+        |batch| {
+            if writers.is_none() {
+                writers = Some((
+                    ParquetBatchWriter::create(&population_scd_path, batch.schema())?,
+                    ParquetBatchWriter::create(&scd_children_path, batch.schema())?,
+                ));
+            }
+            if let Some((population_writer, children_writer)) = &mut writers {
+                population_writer.write(batch)?;
+                children_writer.write(&extract_scd_children(batch)?)?;
+            }
+            Ok(())
+        },
     )?;
 
     // Step 5: Log summary statistics
@@ -145,32 +169,17 @@ pub fn handle_population_scd_command(config: &PopulationScdCommandConfig) -> Res
         info!("  {category}: {count} ({percentage:.2}%)");
     }
 
-    // Step 6: Extract SCD children
-    info!("Extracting children with SCD...");
-    let scd_children_data = extract_scd_children(&population_scd_data)?;
-    info!(
-        "Extracted {} children with SCD",
-        scd_children_data.num_rows()
-    );
-
-    // Step 7: Save results
-
-    // Save full population with SCD indicators
-    let population_scd_path = config.output_dir.join("population_scd.parquet");
-    runtime.block_on(async {
-        crate::data::io::parquet::save_batch_to_parquet(&population_scd_data, &population_scd_path).await
-    })?;
-    info!(
-        "Saved population with SCD indicators to: {}",
-        population_scd_path.display()
-    );
-
-    // Save only SCD children
-    let scd_children_path = config.output_dir.join("scd_children.parquet");
-    runtime.block_on(async {
-        crate::data::io::parquet::save_batch_to_parquet(&scd_children_data, &scd_children_path).await
-    })?;
-    info!("Saved SCD children to: {}", scd_children_path.display());
+    // Step 6: Close the output files
+    if let Some((population_writer, children_writer)) = writers {
+        population_writer.finish()?;
+        info!(
+            "Saved population with SCD indicators to: {}",
+            population_scd_path.display()
+        );
+        info!("Saved {} children with SCD", children_writer.rows());
+        children_writer.finish()?;
+        info!("Saved SCD children to: {}", scd_children_path.display());
+    }
 
     // Save summary as CSV
     let summary_path = config.output_dir.join("population_scd_summary.csv");
@@ -188,6 +197,23 @@ pub fn handle_population_scd_command(config: &PopulationScdCommandConfig) -> Res
             "SCD Percentage".to_string(),
             format!("{:.2}%", scd_summary.scd_percentage),
         ],
+        vec!["Definitions".to_string(), scd_summary.definitions.name.clone()],
+        vec![
+            "Definitions Version".to_string(),
+            scd_summary.definitions.version.clone(),
+        ],
+        vec![
+            "Definitions Source".to_string(),
+            scd_summary.definitions.source.clone(),
+        ],
+        vec![
+            "Definitions SHA-256".to_string(),
+            scd_summary.definitions.sha256.clone(),
+        ],
+        vec![
+            "Diagnosis Filter".to_string(),
+            config.diagnosis_filter.to_string(),
+        ],
     ];
 
     // Add category breakdowns
@@ -3,6 +3,8 @@
 //! This module defines the configuration options for the SCD command.
 
 use chrono::NaiveDate;
+
+use crate::algorithm::health::diagnosis::{scd_diagnosis_columns, DiagnosisFilter};
 use std::path::PathBuf;
 
 /// Configuration for the SCD command
@@ -25,6 +27,14 @@ pub struct ScdCommandConfig {
     pub patient_id_column: String,
     /// Date column
     pub date_column: String,
+    /// SCD definition file (JSON or TOML); the built-in definitions if `None`
+    pub definitions_path: Option<PathBuf>,
+    /// Diagnosis type and status filter applied when harmonising LPR diagnoses
+    pub diagnosis_filter: DiagnosisFilter,
+    /// Cause-of-death (DODSAARSAG) data, for definitions with the `death_cause` source
+    pub death_causes_path: Option<PathBuf>,
+    /// Death register (DOD) data, for the dates of causes of death
+    pub deaths_path: Option<PathBuf>,
 }
 
 impl Default for ScdCommandConfig {
@@ -36,12 +46,13 @@ impl Default for ScdCommandConfig {
             include_lpr3: true,
             start_date: None,
             end_date: None,
-            diagnosis_columns: vec![
-                "primary_diagnosis".to_string(),
-                "secondary_diagnosis".to_string(),
-            ],
+            diagnosis_columns: scd_diagnosis_columns(false),
             patient_id_column: "patient_id".to_string(),
             date_column: "admission_date".to_string(),
+            definitions_path: None,
+            diagnosis_filter: DiagnosisFilter::default(),
+            death_causes_path: None,
+            deaths_path: None,
         }
     }
 }
\ No newline at end of file
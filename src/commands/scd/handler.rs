@@ -2,21 +2,23 @@
 //!
 //! This module provides the implementation for handling the SCD command.
 
-use datafusion::common::config::TableParquetOptions;
-use datafusion::dataframe::DataFrameWriteOptions;
-use datafusion::prelude::*;
+use std::collections::HashSet;
 use std::fs;
 use tokio::runtime::Runtime;
 
-use crate::algorithm::lpr::{process_lpr_data, LprConfig};
-use crate::algorithm::scd::{apply_scd_algorithm, scd_results_to_record_batch, ScdConfig};
-use crate::data::registry::traits::RegisterLoader;
-use crate::error::{IdsError, Result};
-use crate::utils::reports::write_csv_report;
-
-// Import the new DataFusion-based registry loaders
-use crate::data::registry::factory::RegistryFactory;
+use crate::algorithm::health::death_causes::{append_death_causes, load_death_records};
+use crate::algorithm::health::diagnosis::DiagnosisSource;
+use crate::algorithm::health::lpr_partitioned::partition_lpr_cached;
+use crate::algorithm::lpr::LprConfig;
+use crate::algorithm::scd::{
+    apply_scd_algorithm_with_procedures, scd_results_to_record_batch_with_categories, ScdConfig,
+    ScdDiseaseCodes,
+};
+use crate::data::cache::ResultCache;
+use crate::data::io::ParquetBatchWriter;
 use crate::data::registry::loaders::lpr::find_lpr_files;
+use crate::error::{IdsError, Result};
+use crate::utils::reports::{write_csv_report, DiagnosisFrequencies};
 
 use super::config::ScdCommandConfig;
 
@@ -27,6 +29,17 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
         fs::create_dir_all(&config.output_path).map_err(IdsError::Io)?;
     }
 
+    // Definitions with causes of death as their only source would otherwise silently
+    // run on LPR data alone
+    let scd_codes = ScdDiseaseCodes::load(config.definitions_path.as_deref())?;
+    if scd_codes.uses_source(DiagnosisSource::DeathCause) && config.death_causes_path.is_none() {
+        return Err(IdsError::Validation(
+            "The SCD definitions use causes of death (death_cause source), but no \
+             cause-of-death data was given (--death-causes)"
+                .to_string(),
+        ));
+    }
+
     // Create a tokio runtime for async operations
     let runtime = Runtime::new()
         .map_err(|e| IdsError::Data(format!("Failed to create async runtime: {e}")))?;
@@ -54,154 +67,158 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
     if let Some(path) = &lpr_paths.diagnoser_path {
         log::info!("  LPR3_DIAGNOSER: {}", path.display());
     }
-
-    // Step 2: Load LPR data using the new DataFusion-based registry loaders
-    log::info!("Loading LPR data...");
-
-    // LPR2 data
-    let mut lpr2_adm = None;
-    let mut lpr2_diag = None;
-    let mut lpr2_bes = None;
-
-    if config.include_lpr2 {
-        if let Some(path) = &lpr_paths.admin_path {
-            log::info!("Loading LPR_ADM data...");
-            let adm_data = runtime.block_on(async {
-                let adm_loader = RegistryFactory::from_name("lpr_adm")?;
-                
-                // Downcast to the actual type
-                let loader = adm_loader
-                    .downcast_ref::<crate::data::registry::loaders::lpr::lpr2_loader::Lpr2Register>()
-                    .ok_or_else(|| IdsError::Data("Failed to downcast LPR ADM register".to_string()))?;
-                    
-                loader.load(path.to_str().unwrap(), None).await
-            })?;
-            let adm_batch_count = adm_data.len();
-            lpr2_adm = Some(adm_data);
-            log::info!("Loaded {adm_batch_count} LPR_ADM batches");
-        }
-
-        if let Some(path) = &lpr_paths.diag_path {
-            log::info!("Loading LPR_DIAG data...");
-            let diag_data = runtime.block_on(async {
-                let diag_loader = RegistryFactory::from_name("lpr_diag")?;
-                
-                // Downcast to the actual type
-                let loader = diag_loader
-                    .downcast_ref::<crate::data::registry::loaders::lpr::lpr2_loader::Lpr2Register>()
-                    .ok_or_else(|| IdsError::Data("Failed to downcast LPR DIAG register".to_string()))?;
-                    
-                loader.load(path.to_str().unwrap(), None).await
-            })?;
-            let diag_batch_count = diag_data.len();
-            lpr2_diag = Some(diag_data);
-            log::info!("Loaded {diag_batch_count} LPR_DIAG batches");
-        }
-
-        if let Some(path) = &lpr_paths.proc_path {
-            log::info!("Loading LPR_BES data...");
-            let bes_data = runtime.block_on(async {
-                let bes_loader = RegistryFactory::from_name("lpr_bes")?;
-                
-                // Downcast to the actual type
-                let loader = bes_loader
-                    .downcast_ref::<crate::data::registry::loaders::lpr::lpr2_loader::Lpr2Register>()
-                    .ok_or_else(|| IdsError::Data("Failed to downcast LPR BES register".to_string()))?;
-                    
-                loader.load(path.to_str().unwrap(), None).await
-            })?;
-            let bes_batch_count = bes_data.len();
-            lpr2_bes = Some(bes_data);
-            log::info!("Loaded {bes_batch_count} LPR_BES batches");
-        }
-    }
-
-    // LPR3 data
-    let mut lpr3_kontakter = None;
-    let mut lpr3_diagnoser = None;
-
-    if config.include_lpr3 {
-        if let Some(path) = &lpr_paths.kontakter_path {
-            log::info!("Loading LPR3_KONTAKTER data...");
-            let kontakter_data = runtime.block_on(async {
-                let kontakter_loader = RegistryFactory::from_name("lpr3_kontakter")?;
-                
-                // Downcast to the actual type
-                let loader = kontakter_loader
-                    .downcast_ref::<crate::data::registry::loaders::lpr::lpr3_loader::Lpr3Register>()
-                    .ok_or_else(|| IdsError::Data("Failed to downcast LPR3 KONTAKTER register".to_string()))?;
-                    
-                loader.load(path.to_str().unwrap(), None).await
-            })?;
-            let kontakter_batch_count = kontakter_data.len();
-            lpr3_kontakter = Some(kontakter_data);
-            log::info!("Loaded {kontakter_batch_count} LPR3_KONTAKTER batches");
-        }
-
-        if let Some(path) = &lpr_paths.diagnoser_path {
-            log::info!("Loading LPR3_DIAGNOSER data...");
-            let diagnoser_data = runtime.block_on(async {
-                let diagnoser_loader = RegistryFactory::from_name("lpr3_diagnoser")?;
-                
-                // Downcast to the actual type
-                let loader = diagnoser_loader
-                    .downcast_ref::<crate::data::registry::loaders::lpr::lpr3_loader::Lpr3Register>()
-                    .ok_or_else(|| IdsError::Data("Failed to downcast LPR3 DIAGNOSER register".to_string()))?;
-                    
-                loader.load(path.to_str().unwrap(), None).await
-            })?;
-            let diagnoser_batch_count = diagnoser_data.len();
-            lpr3_diagnoser = Some(diagnoser_data);
-            log::info!("Loaded {diagnoser_batch_count} LPR3_DIAGNOSER batches");
-        }
+    if let Some(path) = &lpr_paths.procedurer_path {
+        log::info!("  LPR3_PROCEDURER: {}", path.display());
     }
 
-    // Step 3: Process LPR data
-    log::info!("Processing LPR data...");
+    // Step 2: Harmonise LPR data, partitioned by patient so it need not fit in memory
+    log::info!("Harmonising LPR data...");
     let lpr_config = LprConfig {
         include_lpr2: config.include_lpr2,
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
+        diagnosis_filter: config.diagnosis_filter,
     };
-
-    let processed_data = process_lpr_data(
-        lpr2_adm.as_deref(),
-        lpr2_diag.as_deref(),
-        lpr2_bes.as_deref(),
-        lpr3_kontakter.as_deref(),
-        lpr3_diagnoser.as_deref(),
-        None, // lpr3_procedurer
+    let lpr = runtime.block_on(partition_lpr_cached(
+        config.lpr_data_path.to_str().unwrap(),
         &lpr_config,
-    )?;
-
-    log::info!("Processed data: {} rows", processed_data.num_rows());
-
-    // Save the processed data using our utility function
-    let processed_path = config.output_path.join("processed_lpr_data.parquet");
-    runtime.block_on(crate::data::io::parquet::save_batch_to_parquet(
-        &processed_data,
-        &processed_path,
+        None,
+        &ResultCache::global(),
     ))?;
-    log::info!("Saved processed data to: {}", processed_path.display());
+    log::info!(
+        "Harmonised {} diagnosis records in {} partition(s)",
+        lpr.num_diagnoses(),
+        lpr.partitions()
+    );
 
-    // Step 4: Apply SCD algorithm
-    log::info!("Applying SCD algorithm...");
+    // Step 3: Prepare the SCD algorithm
     let scd_config = ScdConfig {
         diagnosis_columns: config.diagnosis_columns.clone(),
         date_column: config.date_column.clone(),
         patient_id_column: config.patient_id_column.clone(),
+        definitions_path: config.definitions_path.clone(),
+        ..ScdConfig::default()
+    };
+    let definitions = scd_codes.definition_info();
+    let categories = scd_codes.get_all_categories();
+
+    // Causes of death for definitions that use them as a diagnosis source, split like
+    // the LPR data so they are added to the partition of their patient
+    let death_records = match &config.death_causes_path {
+        Some(path) if scd_codes.uses_source(DiagnosisSource::DeathCause) => {
+            log::info!("Loading causes of death from: {}", path.display());
+            let records =
+                runtime.block_on(load_death_records(path, config.deaths_path.as_deref()))?;
+            let mut partitions = vec![Vec::new(); lpr.partitions()];
+            for record in records {
+                partitions[lpr.partition_of(&record.patient_id)].push(record);
+            }
+            Some(partitions)
+        }
+        _ => None,
     };
 
-    let scd_results = apply_scd_algorithm(&processed_data, &scd_config)?;
-    log::info!(
-        "SCD analysis complete: {} patient records",
-        scd_results.len()
-    );
+    // Step 4: Apply the SCD algorithm one partition at a time, writing as we go
+    log::info!("Applying SCD algorithm...");
+    let processed_path = config.output_path.join("processed_lpr_data.parquet");
+    let mut processed_writer = ParquetBatchWriter::create(&processed_path, lpr.diagnosis_schema())?;
+    let procedures_path = config.output_path.join("processed_lpr_procedures.parquet");
+    let mut procedures_writer = if lpr.has_procedures() {
+        Some(ParquetBatchWriter::create(&procedures_path, lpr.procedure_schema())?)
+    } else {
+        None
+    };
+
+    // Record the definitions in the Parquet schema metadata
+    let scd_schema = scd_results_to_record_batch_with_categories(&[], &categories)?
+        .schema()
+        .as_ref()
+        .clone()
+        .with_metadata(definitions.metadata("scd"));
+    let scd_schema = std::sync::Arc::new(scd_schema);
+    let scd_parquet_path = config.output_path.join("scd_results.parquet");
+    let mut scd_writer = ParquetBatchWriter::create(&scd_parquet_path, scd_schema.clone())?;
+
+    let mut total_patients = 0;
+    let mut scd_patients = 0;
+    let mut category_counts: std::collections::HashMap<String, u32> =
+        std::collections::HashMap::new();
+    let mut diagnosis_frequencies = DiagnosisFrequencies::default();
+
+    for partition in 0..lpr.partitions() {
+        let processed_data = lpr.diagnoses(partition)?;
+        let processed_procedures = lpr.procedures(partition)?;
+        processed_writer.write(&processed_data)?;
+        if let (Some(writer), Some(procedures)) = (&mut procedures_writer, &processed_procedures) {
+            writer.write(procedures)?;
+        }
+
+        let processed_data = match &death_records {
+            Some(records) => append_death_causes(
+                &processed_data,
+                &records[partition],
+                scd_codes.uses_source(DiagnosisSource::Lpr),
+            )?,
+            None => processed_data,
+        };
+
+        let scd_results = apply_scd_algorithm_with_procedures(
+            &processed_data,
+            processed_procedures.as_ref(),
+            &scd_config,
+            &scd_codes,
+            None,
+        )?;
+
+        total_patients += scd_results.len();
+        let mut scd_patient_ids = HashSet::new();
+        for result in &scd_results {
+            if result.is_scd {
+                scd_patient_ids.insert(result.patient_id.clone());
+            }
+            for (category, has_disease) in &result.disease_categories {
+                if *has_disease {
+                    *category_counts.entry(category.clone()).or_insert(0) += 1;
+                }
+            }
+        }
+        scd_patients += scd_patient_ids.len();
+
+        // Most frequent primary diagnoses among SCD patients, with SKS descriptions
+        diagnosis_frequencies.add(
+            &processed_data,
+            "patient_id",
+            "primary_diagnosis",
+            Some(&scd_patient_ids),
+        )?;
+
+        let scd_batch = scd_results_to_record_batch_with_categories(&scd_results, &categories)?
+            .with_schema(scd_schema.clone())?;
+        scd_writer.write(&scd_batch)?;
+        log::debug!(
+            "SCD partition {}/{}: {} patients",
+            partition + 1,
+            lpr.partitions(),
+            scd_results.len()
+        );
+    }
+
+    processed_writer.finish()?;
+    log::info!("Saved processed data to: {}", processed_path.display());
+    if let Some(writer) = procedures_writer {
+        log::info!(
+            "Saved {} processed procedures to: {}",
+            writer.rows(),
+            procedures_path.display()
+        );
+        writer.finish()?;
+    }
+    scd_writer.finish()?;
+    log::info!("Saved SCD results to: {}", scd_parquet_path.display());
+    log::info!("SCD analysis complete: {total_patients} patient records");
 
     // Calculate summary statistics
-    let total_patients = scd_results.len();
-    let scd_patients = scd_results.iter().filter(|r| r.is_scd).count();
     let scd_percentage = if total_patients > 0 {
         (scd_patients as f64 / total_patients as f64) * 100.0
     } else {
@@ -212,16 +229,6 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
     log::info!("  Total patients: {total_patients}");
     log::info!("  Patients with SCD: {scd_patients} ({scd_percentage:.2}%)");
 
-    // Count by disease category
-    let mut category_counts = std::collections::HashMap::new();
-    for result in &scd_results {
-        for (category, has_disease) in &result.disease_categories {
-            if *has_disease {
-                *category_counts.entry(category.clone()).or_insert(0) += 1;
-            }
-        }
-    }
-
     log::info!("Disease categories:");
     for (category, count) in &category_counts {
         let percentage = if total_patients > 0 {
@@ -232,35 +239,6 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
         log::info!("  {category}: {count} ({percentage:.2}%)");
     }
 
-    // Step 5: Save results
-    log::info!("Converting SCD results to RecordBatch...");
-    let scd_batch = scd_results_to_record_batch(&scd_results)?;
-
-    // Save as Parquet using DataFusion
-    let scd_parquet_path = config.output_path.join("scd_results.parquet");
-    log::info!("Saving SCD results to: {}", scd_parquet_path.display());
-
-    // Use DataFusion to write the Parquet file
-    runtime.block_on(async {
-        // Create a session context
-        let ctx = SessionContext::new();
-
-        // Create a memory table from the record batch
-        let table_name = "scd_results";
-        ctx.register_batch(table_name, scd_batch.clone())?;
-
-        // Create a DataFrame and write to Parquet
-        let df = ctx.table(table_name).await?;
-
-        // Write to Parquet using DataFusion (with optimal settings)
-        df.write_parquet(
-            scd_parquet_path.to_str().unwrap(),
-            DataFrameWriteOptions::default(),
-            Some(TableParquetOptions::new()),
-        )
-        .await
-    })?;
-
     // Save summary as CSV
     let summary_path = config.output_path.join("scd_summary.csv");
     let mut summary_rows = vec![
@@ -271,6 +249,14 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
             "SCD Percentage".to_string(),
             format!("{:.2}%", scd_percentage),
         ],
+        vec!["Definitions".to_string(), definitions.name.clone()],
+        vec!["Definitions Version".to_string(), definitions.version.clone()],
+        vec!["Definitions Source".to_string(), definitions.source.clone()],
+        vec!["Definitions SHA-256".to_string(), definitions.sha256.clone()],
+        vec![
+            "Diagnosis Filter".to_string(),
+            config.diagnosis_filter.to_string(),
+        ],
     ];
 
     // Add category breakdowns
@@ -289,6 +275,10 @@ pub fn handle_scd_command(config: &ScdCommandConfig) -> Result<()> {
     write_csv_report(&summary_path, &summary_rows)?;
     log::info!("Saved SCD summary to: {}", summary_path.display());
 
+    let diagnoses_path = config.output_path.join("scd_diagnoses.csv");
+    diagnosis_frequencies.save(&diagnoses_path, 100)?;
+    log::info!("Saved SCD diagnosis report to: {}", diagnoses_path.display());
+
     log::info!("SCD command completed successfully");
     Ok(())
 }
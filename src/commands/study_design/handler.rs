@@ -8,6 +8,7 @@ use arrow::array::{Array, BooleanArray, StringArray};
 use arrow::compute;
 use arrow::record_batch::RecordBatch;
 use chrono::{Datelike, NaiveDate};
+use datafusion::prelude::{col, lit, ParquetReadOptions};
 use log::info;
 use rand::seq::IteratorRandom;
 use std::path::Path;
@@ -18,6 +19,7 @@ use crate::commands::population::config::PopulationCommandConfig;
 use crate::commands::population::handler::handle_population_command;
 use crate::commands::population_scd::config::PopulationScdCommandConfig;
 use crate::commands::population_scd::handler::handle_population_scd_command;
+use crate::data::io::execution::session_context;
 use crate::error::{IdsError, Result};
 use crate::model::pnr::Pnr;
 use crate::utils::date_utils;
@@ -42,6 +44,7 @@ pub fn handle_study_design_command(config: &StudyDesignCommandConfig) -> Result<
         output_dir: config.output_dir.join("01_population"),
         birth_inclusion_start_year: config.birth_inclusion_start_year,
         birth_inclusion_end_year: config.birth_inclusion_end_year,
+        ..PopulationCommandConfig::default()
     };
 
     // Create population output directory
@@ -63,6 +66,7 @@ pub fn handle_study_design_command(config: &StudyDesignCommandConfig) -> Result<
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
+        ..PopulationScdCommandConfig::default()
     };
 
     // Create SCD output directory
@@ -79,50 +83,18 @@ pub fn handle_study_design_command(config: &StudyDesignCommandConfig) -> Result<
     // Step 3: Sample Controls and Match with Cases
     info!("Step 3: Matching Cases with Controls");
 
-    // Load SCD children (cases) using the DataFusion-based loader
+    // Load SCD children (cases) and the non-SCD children of the population (controls),
+    // reading only the rows of each group
     let runtime = crate::utils::runtime::get_runtime()?;
-    let scd_children_batches = runtime.block_on(async {
-        crate::data::io::parquet::load_parquet_directory(&scd_children_path, None, None).await
-    })?;
-    
-    if scd_children_batches.is_empty() {
-        return Err(IdsError::Data("No SCD children data found".to_string()));
-    }
-    
-    // Combine batches if necessary
-    let scd_children = if scd_children_batches.len() == 1 {
-        scd_children_batches[0].clone()
-    } else {
-        let schema = scd_children_batches[0].schema();
-        arrow::compute::concat_batches(&schema, &scd_children_batches)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate SCD children batches: {e}")))?
-    };
-
-    // Load full population data
     let population_scd_data_path = population_scd_config
         .output_dir
         .join("population_scd.parquet");
-    
-    // Load population SCD data using the DataFusion-based loader
-    let population_scd_batches = runtime.block_on(async {
-        crate::data::io::parquet::load_parquet_directory(&population_scd_data_path, None, None).await
+    let (scd_children, controls) = runtime.block_on(async {
+        Ok::<_, IdsError>((
+            load_children(&scd_children_path, true).await?,
+            load_children(&population_scd_data_path, false).await?,
+        ))
     })?;
-    
-    if population_scd_batches.is_empty() {
-        return Err(IdsError::Data("No population SCD data found".to_string()));
-    }
-    
-    // Combine batches if necessary
-    let population_scd_data = if population_scd_batches.len() == 1 {
-        population_scd_batches[0].clone()
-    } else {
-        let schema = population_scd_batches[0].schema();
-        arrow::compute::concat_batches(&schema, &population_scd_batches)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate population SCD batches: {e}")))?
-    };
-
-    // Extract controls (non-SCD children) from population
-    let controls = extract_controls(&population_scd_data)?;
 
     // Create matching criteria
     let criteria = MatchingCriteria {
@@ -194,46 +166,28 @@ pub fn handle_study_design_command(config: &StudyDesignCommandConfig) -> Result<
 }
 
 
-/// Extract controls (non-SCD children) from the population data
-fn extract_controls(population_data: &RecordBatch) -> Result<RecordBatch> {
-    // Get the is_scd column
-    let is_scd_idx = population_data
-        .schema()
-        .index_of("is_scd")
-        .map_err(|e| IdsError::Data(format!("is_scd column not found: {e}")))?;
-
-    let is_scd_col = population_data.column(is_scd_idx);
-    let is_scd_array = is_scd_col
-        .as_any()
-        .downcast_ref::<BooleanArray>()
-        .ok_or_else(|| IdsError::Data("is_scd column is not a boolean array".to_string()))?;
-
-    // Create a mask for rows where is_scd is false
-    let mask = BooleanArray::from(
-        (0..is_scd_array.len())
-            .map(|i| {
-                if is_scd_array.is_null(i) {
-                    None
-                } else {
-                    Some(!is_scd_array.value(i)) // Note the negation here
-                }
-            })
-            .collect::<Vec<Option<bool>>>(),
-    );
-
-    // Apply the mask to all columns
-    let mut filtered_columns = Vec::with_capacity(population_data.num_columns());
-    for col in population_data.columns() {
-        let filtered_col = compute::filter(col, &mask)
-            .map_err(|e| IdsError::Data(format!("Failed to filter column: {e}")))?;
-        filtered_columns.push(filtered_col);
-    }
-
-    // Create filtered batch
-    let filtered_batch = RecordBatch::try_new(population_data.schema(), filtered_columns)
-        .map_err(|e| IdsError::Data(format!("Failed to create filtered batch: {e}")))?;
-
-    Ok(filtered_batch)
+/// Load the children of a population SCD file with the given `is_scd` value
+///
+/// The filter is pushed down into the Parquet scan, so only the rows of the requested
+/// group are read into memory. Matching compares every case with every control, so both
+/// groups are held in memory.
+async fn load_children(path: &Path, is_scd: bool) -> Result<RecordBatch> {
+    let ctx = session_context();
+    let df = ctx
+        .read_parquet(
+            path.to_string_lossy().to_string(),
+            ParquetReadOptions::default(),
+        )
+        .await?
+        .filter(col("is_scd").eq(lit(is_scd)))?;
+    let schema = df.schema().inner().clone();
+    let batches = df.collect().await?;
+    compute::concat_batches(&schema, &batches).map_err(|e| {
+        IdsError::Data(format!(
+            "Failed to concatenate children from {}: {e}",
+            path.display()
+        ))
+    })
 }
 
 /// Extract PNR and birth date pairs from a `RecordBatch`
@@ -624,28 +578,6 @@ fn perform_matching(
     Ok((matched_cases_batch, matched_controls_batch))
 }
 
-/// Combines multiple record batches into a single batch
-#[allow(dead_code)]
-fn combine_record_batches(batches: &[RecordBatch]) -> Result<RecordBatch> {
-    // If empty, return an error
-    if batches.is_empty() {
-        return Err(IdsError::Data("No record batches to combine".to_string()));
-    }
-
-    // If only one batch, return a clone of it
-    if batches.len() == 1 {
-        return Ok(batches[0].clone());
-    }
-
-    // Get schema from first batch
-    let schema = batches[0].schema();
-
-    // Use arrow's concat_batches function
-    arrow::compute::concat_batches(&schema, batches)
-        .map_err(|e| IdsError::Data(format!("Failed to concatenate batches: {e}")))
-}
-
-
 /// Handle the study design command using async I/O
 ///
 /// This version uses the async Parquet reader/writer for better performance
@@ -660,6 +592,7 @@ pub async fn handle_study_design_command_async(config: &StudyDesignCommandConfig
         output_dir: config.output_dir.join("01_population"),
         birth_inclusion_start_year: config.birth_inclusion_start_year,
         birth_inclusion_end_year: config.birth_inclusion_end_year,
+        ..PopulationCommandConfig::default()
     };
 
     // Create population output directory
@@ -683,6 +616,7 @@ pub async fn handle_study_design_command_async(config: &StudyDesignCommandConfig
         include_lpr3: config.include_lpr3,
         start_date: config.start_date,
         end_date: config.end_date,
+        ..PopulationScdCommandConfig::default()
     };
 
     // Create SCD output directory
@@ -701,44 +635,13 @@ pub async fn handle_study_design_command_async(config: &StudyDesignCommandConfig
     // Step 3: Sample Controls and Match with Cases (async loading)
     info!("Step 3: Matching Cases with Controls (async)");
 
-    // Load SCD children (cases) with async reader
-    let scd_children_batches = crate::data::io::parquet::load_parquet_directory(&scd_children_path, None, None).await?;
-    
-    if scd_children_batches.is_empty() {
-        return Err(IdsError::Data("No SCD children data found".to_string()));
-    }
-    
-    // Combine batches if necessary
-    let scd_children = if scd_children_batches.len() == 1 {
-        scd_children_batches[0].clone()
-    } else {
-        let schema = scd_children_batches[0].schema();
-        arrow::compute::concat_batches(&schema, &scd_children_batches)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate SCD children batches: {e}")))?
-    };
-
-    // Load full population data with async reader
+    // Load SCD children (cases) and the non-SCD children of the population (controls),
+    // reading only the rows of each group
+    let scd_children = load_children(&scd_children_path, true).await?;
     let population_scd_data_path = population_scd_config
         .output_dir
         .join("population_scd.parquet");
-    
-    let population_scd_batches = crate::data::io::parquet::load_parquet_directory(&population_scd_data_path, None, None).await?;
-    
-    if population_scd_batches.is_empty() {
-        return Err(IdsError::Data("No population SCD data found".to_string()));
-    }
-    
-    // Combine batches if necessary
-    let population_scd_data = if population_scd_batches.len() == 1 {
-        population_scd_batches[0].clone()
-    } else {
-        let schema = population_scd_batches[0].schema();
-        arrow::compute::concat_batches(&schema, &population_scd_batches)
-            .map_err(|e| IdsError::Data(format!("Failed to concatenate population SCD batches: {e}")))?
-    };
-
-    // Extract controls (non-SCD children) from population
-    let controls = extract_controls(&population_scd_data)?;
+    let controls = load_children(&population_scd_data_path, false).await?;
 
     // Create matching criteria
     let criteria = MatchingCriteria {
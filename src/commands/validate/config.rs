@@ -0,0 +1,30 @@
+//! Configuration for the Validate command
+//!
+//! This module defines the configuration options for the Validate command.
+
+use std::path::PathBuf;
+
+use crate::data::schema::ValidationConfig;
+
+/// Configuration for the Validate command
+pub struct ValidateCommandConfig {
+    /// Parquet file or registry directory to validate
+    pub input_path: PathBuf,
+    /// Registry name; inferred from the input path if `None`
+    pub registry: Option<String>,
+    /// File to write the JSON report to; printed to stdout if `None`
+    pub report_path: Option<PathBuf>,
+    /// Value checks and strictness
+    pub validation: ValidationConfig,
+}
+
+impl Default for ValidateCommandConfig {
+    fn default() -> Self {
+        Self {
+            input_path: PathBuf::from("./data"),
+            registry: None,
+            report_path: None,
+            validation: ValidationConfig::default(),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Handler for the Validate command
+//!
+//! This module provides the implementation for handling the Validate command.
+
+use log::{info, warn};
+use std::fs;
+
+use crate::data::registry::factory::RegistryFactory;
+use crate::data::schema::{RegistryValidator, ValidationReport};
+use crate::error::{IdsError, Result};
+
+use super::config::ValidateCommandConfig;
+
+/// Handle the Validate command
+///
+/// Writes the JSON report and returns an error if any file fails validation.
+pub fn handle_validate_command(config: &ValidateCommandConfig) -> Result<ValidationReport> {
+    if !config.input_path.exists() {
+        return Err(IdsError::Validation(format!(
+            "Input path does not exist: {}",
+            config.input_path.display()
+        )));
+    }
+
+    let info = match &config.registry {
+        Some(name) => RegistryFactory::schema_info_from_name(name)?,
+        None => RegistryFactory::schema_info_from_path(&config.input_path)?,
+    };
+    info!(
+        "Validating {} against the {} schema",
+        config.input_path.display(),
+        info.name
+    );
+
+    let validator = RegistryValidator::new(info, config.validation.clone());
+    let report = validator.validate_path(&config.input_path)?;
+    let json = report.to_json()?;
+    match &config.report_path {
+        Some(path) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, json)?;
+            info!("Wrote validation report to {}", path.display());
+        }
+        None => println!("{json}"),
+    }
+
+    for file in report.files.iter().filter(|file| !file.passed) {
+        warn!("{} failed validation", file.path.display());
+    }
+    if report.files_checked == 0 {
+        return Err(IdsError::Validation(format!(
+            "No Parquet files found in {}",
+            config.input_path.display()
+        )));
+    }
+    if !report.passed {
+        return Err(IdsError::Validation(format!(
+            "{} of {} files failed validation",
+            report.files_failed, report.files_checked
+        )));
+    }
+    Ok(report)
+}
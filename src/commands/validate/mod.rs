@@ -0,0 +1,10 @@
+//! Validate command implementation
+//!
+//! This module provides the implementation for the Validate command, which checks a
+//! registry file or directory against its registry schema before it is loaded.
+
+pub mod config;
+pub mod handler;
+
+pub use config::ValidateCommandConfig;
+pub use handler::handle_validate_command;
@@ -0,0 +1,745 @@
+//! Disk-backed cache of intermediate results
+//!
+//! Harmonising LPR, generating populations and running phenotypes over the full registers
+//! takes hours, and the same inputs are processed by several commands and runs.
+//! [`ResultCache`] stores such results on disk as Parquet, one entry per [`CacheKey`]. A
+//! key is a SHA-256 digest of everything the result depends on:
+//!
+//! - the fingerprints (relative path, size, modification time) of the input files;
+//! - the configuration and filters of the stage;
+//! - the versions and hashes of code definition files and bundled classifications;
+//! - the crate version and the cache format version.
+//!
+//! Changing any input gives a new key, so entries are never updated in place; unused
+//! entries are removed with `ids cache prune`. An entry is a directory
+//! `<cache dir>/<stage>/<digest>/` with the files written by the [`Cacheable`] result and
+//! an `entry.json` manifest. Entries are written to a temporary directory and renamed
+//! into place, so a partially written entry is never read. The cache directory itself is
+//! marked with a `.ids_cache` file when the cache creates it, and `ids cache clear` only
+//! removes entries from a marked directory.
+//!
+//! Entries hold register microdata, so caching is opt-in: results are only cached in a
+//! directory given explicitly, and the directories the cache creates are readable by
+//! their owner only (mode 0700 on Unix).
+//!
+//! The configuration is process-wide, like the
+//! [execution configuration](crate::data::io::ExecutionConfig): the CLI sets it with
+//! [`set_cache_config`] (`--cache-dir`, `--no-cache`) and [`ResultCache::global`] uses it.
+
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
+use datafusion::parquet::arrow::arrow_reader::{
+    ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::data::io::ParquetBatchWriter;
+use crate::data::pruning::file_stamp;
+use crate::error::{IdsError, Result};
+
+/// File name of the manifest in an entry directory
+pub const MANIFEST_FILE_NAME: &str = "entry.json";
+
+/// File name of the marker in a cache directory created by [`ResultCache`]
+pub const ROOT_MARKER_FILE_NAME: &str = ".ids_cache";
+
+/// Prefix of the temporary directories entries are written to
+const TMP_PREFIX: &str = ".tmp-";
+
+/// Cache format version; part of every key, so entries of other versions are never read
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// A result that can be stored in and restored from a cache entry
+pub trait Cacheable: Sized {
+    /// Write the result into an empty entry directory
+    fn write_entry(&self, dir: &Path) -> Result<()>;
+
+    /// Read a result written by [`Cacheable::write_entry`]
+    fn read_entry(dir: &Path) -> Result<Self>;
+}
+
+impl Cacheable for RecordBatch {
+    fn write_entry(&self, dir: &Path) -> Result<()> {
+        let mut writer = ParquetBatchWriter::create(dir.join("data.parquet"), self.schema())?;
+        writer.write(self)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn read_entry(dir: &Path) -> Result<Self> {
+        read_entry_batch(&dir.join("data.parquet"))
+    }
+}
+
+/// Open a Parquet file of a cache entry for reading batch by batch
+pub fn read_entry_file(path: &Path) -> Result<ParquetRecordBatchReader> {
+    Ok(ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?)
+}
+
+/// Read a Parquet file of a cache entry into a single batch
+///
+/// For results held in memory as one batch; larger results such as the partitioned LPR
+/// data are read batch by batch with [`read_entry_file`].
+pub fn read_entry_batch(path: &Path) -> Result<RecordBatch> {
+    let reader = read_entry_file(path)?;
+    let schema = reader.schema();
+    let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(arrow::compute::concat_batches(&schema, &batches)?)
+}
+
+/// Digest of the inputs of a cached result
+#[derive(Clone)]
+pub struct CacheKey {
+    stage: String,
+    hasher: Sha256,
+    inputs: Vec<String>,
+}
+
+impl CacheKey {
+    /// Start a key for a stage
+    #[must_use]
+    pub fn new(stage: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(format!(
+            "ids-rs {} cache {CACHE_FORMAT_VERSION} {stage}\n",
+            env!("CARGO_PKG_VERSION")
+        ));
+        Self {
+            stage: stage.to_string(),
+            hasher,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Add the fingerprints of a file, or of all files below a directory
+    ///
+    /// Hidden files, such as the registry statistics index, are ignored. A missing path
+    /// is recorded as missing.
+    pub fn with_files(mut self, name: &str, path: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+        if path.is_dir() {
+            collect_files(path, &mut files)?;
+            files.sort();
+        } else if path.exists() {
+            files.push(path.to_path_buf());
+        }
+
+        self.hasher
+            .update(format!("{name} files {}\n", files.len()));
+        for file in &files {
+            let (size, secs, nanos) = file_stamp(file)?;
+            let relative = file.strip_prefix(path).unwrap_or(file);
+            self.hasher
+                .update(format!("{} {size} {secs}.{nanos}\n", relative.display()));
+        }
+        self.inputs.push(format!(
+            "{name}: {} ({} files)",
+            path.display(),
+            files.len()
+        ));
+        Ok(self)
+    }
+
+    /// Add a configuration value by its `Debug` representation
+    #[must_use]
+    pub fn with_value(mut self, name: &str, value: &impl Debug) -> Self {
+        let value = format!("{value:?}");
+        self.hasher.update(format!("{name} = {value}\n"));
+        self.inputs.push(format!("{name}: {value}"));
+        self
+    }
+
+    /// Add the content of a bundled file, such as a classification, by its hash
+    #[must_use]
+    pub fn with_content(mut self, name: &str, content: &str) -> Self {
+        let digest = format!("{:x}", Sha256::digest(content.as_bytes()));
+        self.hasher.update(format!("{name} content {digest}\n"));
+        self.inputs.push(format!("{name}: sha256 {}", &digest[..12]));
+        self
+    }
+
+    /// Add a set of values, such as a PNR filter, independent of their order
+    #[must_use]
+    pub fn with_set<'a>(mut self, name: &str, values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut values: Vec<&str> = values.into_iter().collect();
+        values.sort_unstable();
+        values.dedup();
+        self.hasher.update(format!("{name} set {}\n", values.len()));
+        for value in &values {
+            self.hasher.update(value.as_bytes());
+            self.hasher.update(b"\n");
+        }
+        self.inputs.push(format!("{name}: {} values", values.len()));
+        self
+    }
+
+    /// Stage the key belongs to
+    #[must_use]
+    pub fn stage(&self) -> &str {
+        &self.stage
+    }
+
+    /// Hexadecimal SHA-256 digest of the key
+    #[must_use]
+    pub fn digest(&self) -> String {
+        format!("{:x}", self.hasher.clone().finalize())
+    }
+}
+
+/// Manifest of a cache entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntryInfo {
+    /// Stage that produced the entry
+    pub stage: String,
+    /// Digest of the entry's key
+    pub digest: String,
+    /// Description of the inputs in the key
+    pub inputs: Vec<String>,
+    /// Creation time, in seconds since the Unix epoch
+    pub created: u64,
+    /// Last time the entry was read or written, in seconds since the Unix epoch
+    pub last_used: u64,
+    /// Entry directory
+    #[serde(skip)]
+    pub path: PathBuf,
+    /// Total size of the entry's files in bytes
+    #[serde(skip)]
+    pub size: u64,
+}
+
+/// Entries to remove from a cache
+#[derive(Debug, Clone, Default)]
+pub struct PruneOptions {
+    /// Remove entries not used for longer than this
+    pub older_than: Option<Duration>,
+    /// Remove the least recently used entries until the cache is at most this many bytes
+    pub max_size: Option<u64>,
+    /// Only consider entries of this stage
+    pub stage: Option<String>,
+}
+
+/// Outcome of pruning a cache
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruneSummary {
+    /// Number of entries removed
+    pub removed: usize,
+    /// Bytes freed
+    pub freed: u64,
+    /// Number of entries kept
+    pub kept: usize,
+}
+
+/// Process-wide cache settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Cache directory; results are only cached when one is set
+    pub dir: Option<PathBuf>,
+}
+
+static CACHE_CONFIG: Lazy<RwLock<CacheConfig>> = Lazy::new(|| RwLock::new(CacheConfig::default()));
+
+/// Set the process-wide cache configuration
+pub fn set_cache_config(config: CacheConfig) -> Result<()> {
+    match &config.dir {
+        Some(dir) => log::info!("Caching intermediate results in {}", dir.display()),
+        None => log::debug!("Result cache disabled"),
+    }
+    *CACHE_CONFIG
+        .write()
+        .map_err(|_| IdsError::Data("Cache configuration lock poisoned".to_string()))? = config;
+    Ok(())
+}
+
+/// The process-wide cache configuration
+#[must_use]
+pub fn cache_config() -> CacheConfig {
+    CACHE_CONFIG
+        .read()
+        .map(|config| config.clone())
+        .unwrap_or_default()
+}
+
+/// A directory of cached results
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    root: PathBuf,
+    enabled: bool,
+}
+
+impl ResultCache {
+    /// Cache in `root`
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            enabled: true,
+        }
+    }
+
+    /// Cache with the process-wide configuration, disabled without a cache directory
+    #[must_use]
+    pub fn global() -> Self {
+        match cache_config().dir {
+            Some(root) => Self::new(root),
+            None => Self {
+                root: PathBuf::new(),
+                enabled: false,
+            },
+        }
+    }
+
+    /// Cache directory
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether results are read from and written to the cache
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Read the result for a key, if it is cached
+    ///
+    /// An entry that cannot be read is removed and treated as missing.
+    #[must_use]
+    pub fn get<T: Cacheable>(&self, key: &CacheKey) -> Option<T> {
+        if !self.enabled {
+            return None;
+        }
+        let dir = self.entry_dir(key);
+        let manifest = dir.join(MANIFEST_FILE_NAME);
+        if !manifest.is_file() {
+            return None;
+        }
+
+        match T::read_entry(&dir) {
+            Ok(value) => {
+                log::info!(
+                    "Using cached {} result {}",
+                    key.stage(),
+                    &key.digest()[..12]
+                );
+                if let Err(e) = touch(&manifest) {
+                    log::debug!("Failed to update {}: {e}", manifest.display());
+                }
+                Some(value)
+            }
+            Err(e) => {
+                log::warn!("Discarding unreadable cache entry {}: {e}", dir.display());
+                if let Err(e) = fs::remove_dir_all(&dir) {
+                    log::warn!("Failed to remove {}: {e}", dir.display());
+                }
+                None
+            }
+        }
+    }
+
+    /// Store the result for a key
+    ///
+    /// The result is valid whether or not it can be cached, so failures are logged and
+    /// otherwise ignored.
+    pub fn put<T: Cacheable>(&self, key: &CacheKey, value: &T) {
+        if !self.enabled {
+            return;
+        }
+        if let Err(e) = self.try_put(key, value) {
+            log::warn!("Failed to cache {} result: {e}", key.stage());
+        }
+    }
+
+    fn try_put<T: Cacheable>(&self, key: &CacheKey, value: &T) -> Result<()> {
+        create_private_dir(&self.root)?;
+        let marker = self.root.join(ROOT_MARKER_FILE_NAME);
+        if !marker.exists() {
+            fs::write(&marker, format!("ids-rs result cache, format {CACHE_FORMAT_VERSION}\n"))?;
+        }
+        let stage_dir = self.root.join(key.stage());
+        let tmp = stage_dir.join(format!("{TMP_PREFIX}{}", uuid::Uuid::new_v4()));
+        create_private_dir(&tmp)?;
+
+        let written = value.write_entry(&tmp).and_then(|()| {
+            let now = unix_now();
+            let info = CacheEntryInfo {
+                stage: key.stage().to_string(),
+                digest: key.digest(),
+                inputs: key.inputs.clone(),
+                created: now,
+                last_used: now,
+                path: PathBuf::new(),
+                size: 0,
+            };
+            write_manifest(&tmp.join(MANIFEST_FILE_NAME), &info)
+        });
+        let dir = self.entry_dir(key);
+        let result = written.and_then(|()| {
+            if dir.exists() {
+                // Written concurrently by another run with the same inputs
+                return Ok(());
+            }
+            fs::rename(&tmp, &dir).map_err(IdsError::from)
+        });
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp)?;
+        }
+        result?;
+
+        log::info!("Cached {} result in {}", key.stage(), dir.display());
+        Ok(())
+    }
+
+    /// All entries, least recently used first
+    pub fn entries(&self) -> Result<Vec<CacheEntryInfo>> {
+        let mut entries = Vec::new();
+        if !self.root.is_dir() {
+            return Ok(entries);
+        }
+        for stage in fs::read_dir(&self.root)? {
+            let stage = stage?.path();
+            if !stage.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&stage)? {
+                let path = entry?.path();
+                if is_hidden(&path) || !path.is_dir() {
+                    continue;
+                }
+                let manifest = path.join(MANIFEST_FILE_NAME);
+                let mut info = match read_manifest(&manifest) {
+                    Ok(info) => info,
+                    Err(e) => {
+                        log::warn!("Skipping cache entry {}: {e}", path.display());
+                        continue;
+                    }
+                };
+                info.size = directory_size(&path)?;
+                info.path = path;
+                entries.push(info);
+            }
+        }
+        entries.sort_by_key(|entry| entry.last_used);
+        Ok(entries)
+    }
+
+    /// Remove entries that are too old, or the least recently used ones beyond a size
+    pub fn prune(&self, options: &PruneOptions) -> Result<PruneSummary> {
+        let entries: Vec<_> = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| {
+                options
+                    .stage
+                    .as_ref()
+                    .is_none_or(|stage| &entry.stage == stage)
+            })
+            .collect();
+        let cutoff = options
+            .older_than
+            .map(|age| unix_now().saturating_sub(age.as_secs()));
+        let mut remaining: u64 = entries.iter().map(|entry| entry.size).sum();
+
+        let mut summary = PruneSummary::default();
+        for entry in entries {
+            let too_old = cutoff.is_some_and(|cutoff| entry.last_used < cutoff);
+            let too_large = options.max_size.is_some_and(|max| remaining > max);
+            if too_old || too_large {
+                fs::remove_dir_all(&entry.path)?;
+                remaining -= entry.size;
+                summary.removed += 1;
+                summary.freed += entry.size;
+            } else {
+                summary.kept += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Remove all entries, including interrupted writes
+    ///
+    /// Only directories the cache wrote are removed: entries with a manifest, temporary
+    /// entry directories, and stage directories left empty by their removal. A directory
+    /// without the cache marker is refused, so a mistyped `--cache-dir` is left alone.
+    pub fn clear(&self) -> Result<PruneSummary> {
+        let mut summary = PruneSummary::default();
+        if !self.root.is_dir() {
+            return Ok(summary);
+        }
+        if !self.root.join(ROOT_MARKER_FILE_NAME).is_file() {
+            return Err(IdsError::Validation(format!(
+                "{} is not a result cache directory (no {ROOT_MARKER_FILE_NAME} file); \
+                 refusing to clear it",
+                self.root.display()
+            )));
+        }
+
+        let mut stages = Vec::new();
+        for entry in self.entries()? {
+            summary.freed += entry.size;
+            summary.removed += 1;
+            fs::remove_dir_all(&entry.path)?;
+            if let Some(stage) = entry.path.parent() {
+                stages.push(stage.to_path_buf());
+            }
+        }
+        for stage in fs::read_dir(&self.root)? {
+            let stage = stage?.path();
+            if is_hidden(&stage) || !stage.is_dir() {
+                continue;
+            }
+            for tmp in fs::read_dir(&stage)? {
+                let tmp = tmp?;
+                let is_tmp = tmp
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(TMP_PREFIX));
+                if is_tmp && tmp.file_type()?.is_dir() {
+                    summary.freed += directory_size(&tmp.path())?;
+                    fs::remove_dir_all(tmp.path())?;
+                    stages.push(stage.clone());
+                }
+            }
+        }
+
+        stages.sort();
+        stages.dedup();
+        for stage in stages {
+            if fs::read_dir(&stage)?.next().is_none() {
+                fs::remove_dir(&stage)?;
+            }
+        }
+        Ok(summary)
+    }
+
+    fn entry_dir(&self, key: &CacheKey) -> PathBuf {
+        self.root.join(key.stage()).join(key.digest())
+    }
+}
+
+/// Create a directory and any missing parents readable by the owner only
+pub(crate) fn create_private_dir(path: &Path) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)?;
+    Ok(())
+}
+
+/// Add the non-hidden files below a directory to `files`
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Total size of the files below a directory
+fn directory_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn read_manifest(path: &Path) -> Result<CacheEntryInfo> {
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| IdsError::Data(format!("Invalid cache manifest {}: {e}", path.display())))
+}
+
+fn write_manifest(path: &Path, info: &CacheEntryInfo) -> Result<()> {
+    let content = serde_json::to_string_pretty(info)
+        .map_err(|e| IdsError::Data(format!("Failed to serialize cache manifest: {e}")))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Record that an entry was used, for least-recently-used pruning
+fn touch(manifest: &Path) -> Result<()> {
+    let mut info = read_manifest(manifest)?;
+    info.last_used = unix_now();
+    write_manifest(manifest, &info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use arrow::array::Int32Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn test_batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("x", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap()
+    }
+
+    fn key(inputs: &Path, value: i32) -> CacheKey {
+        CacheKey::new("test")
+            .with_files("inputs", inputs)
+            .unwrap()
+            .with_value("value", &value)
+    }
+
+    /// A temporary directory with an `inputs` directory holding one file
+    fn with_inputs() -> (tempfile::TempDir, PathBuf) {
+        let dir = temp_dir();
+        let inputs = dir.path().join("inputs");
+        fs::create_dir_all(&inputs).unwrap();
+        fs::write(inputs.join("a.parquet"), b"a").unwrap();
+        (dir, inputs)
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let (dir, inputs) = with_inputs();
+        assert_eq!(key(&inputs, 1).digest(), key(&inputs, 1).digest());
+        assert_ne!(key(&inputs, 1).digest(), key(&inputs, 2).digest());
+
+        let cache = ResultCache::new(dir.path().join("cache"));
+        assert!(cache.get::<RecordBatch>(&key(&inputs, 1)).is_none());
+        cache.put(&key(&inputs, 1), &test_batch());
+        assert_eq!(
+            cache.get::<RecordBatch>(&key(&inputs, 1)).unwrap(),
+            test_batch()
+        );
+    }
+
+    #[test]
+    fn test_changed_inputs_change_key() {
+        let (dir, inputs) = with_inputs();
+        let cache = ResultCache::new(dir.path().join("cache"));
+        cache.put(&key(&inputs, 1), &test_batch());
+
+        fs::write(inputs.join("b.parquet"), b"b").unwrap();
+        assert!(cache.get::<RecordBatch>(&key(&inputs, 1)).is_none());
+    }
+
+    #[test]
+    fn test_prune_by_size() {
+        let (dir, inputs) = with_inputs();
+        let cache = ResultCache::new(dir.path().join("cache"));
+        cache.put(&key(&inputs, 1), &test_batch());
+        cache.put(&key(&inputs, 2), &test_batch());
+        assert_eq!(cache.entries().unwrap().len(), 2);
+
+        let summary = cache
+            .prune(&PruneOptions {
+                max_size: Some(0),
+                ..PruneOptions::default()
+            })
+            .unwrap();
+        assert_eq!((summary.removed, summary.kept), (2, 0));
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    /// A corrupt entry is a cache miss and is removed, so the result is recomputed
+    #[test]
+    fn test_corrupt_entry_is_discarded() {
+        let (dir, inputs) = with_inputs();
+        let cache = ResultCache::new(dir.path().join("cache"));
+        let key = key(&inputs, 1);
+        cache.put(&key, &test_batch());
+
+        let entry = cache.entry_dir(&key);
+        fs::write(entry.join("data.parquet"), b"truncated").unwrap();
+        assert!(cache.get::<RecordBatch>(&key).is_none());
+        assert!(!entry.exists());
+
+        cache.put(&key, &test_batch());
+        assert_eq!(cache.get::<RecordBatch>(&key).unwrap(), test_batch());
+    }
+
+    #[test]
+    fn test_cache_is_opt_in() {
+        assert_eq!(CacheConfig::default().dir, None);
+    }
+
+    #[test]
+    fn test_bundled_content_changes_key() {
+        let key = |content: &str| CacheKey::new("test").with_content("sks", content).digest();
+        assert_eq!(key("a"), key("a"));
+        assert_ne!(key("a"), key("b"));
+    }
+
+    /// Clearing removes cache entries only, and refuses a directory the cache did not create
+    #[test]
+    fn test_clear_keeps_foreign_directories() {
+        let (dir, inputs) = with_inputs();
+        let root = dir.path().join("cache");
+        let cache = ResultCache::new(&root);
+        cache.put(&key(&inputs, 1), &test_batch());
+        cache.put(&key(&inputs, 2), &test_batch());
+        fs::create_dir_all(root.join("test").join(".tmp-interrupted")).unwrap();
+        fs::create_dir_all(root.join("results")).unwrap();
+        fs::write(root.join("results").join("cohort.parquet"), b"data").unwrap();
+        fs::create_dir_all(root.join("test").join("notes")).unwrap();
+
+        let summary = cache.clear().unwrap();
+        assert_eq!(summary.removed, 2);
+        assert!(cache.entries().unwrap().is_empty());
+        assert!(root.join("results").join("cohort.parquet").is_file());
+        assert!(root.join("test").join("notes").is_dir());
+        assert!(!root.join("test").join(".tmp-interrupted").exists());
+
+        let unmarked = dir.path().join("data");
+        fs::create_dir_all(unmarked.join("stage")).unwrap();
+        assert!(ResultCache::new(&unmarked).clear().is_err());
+        assert!(unmarked.join("stage").is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_directories_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir();
+        let root = dir.path().join("cache");
+        ResultCache::new(&root).put(&CacheKey::new("test"), &test_batch());
+
+        for path in [root.clone(), root.join("test")] {
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700, "{}", path.display());
+        }
+    }
+}
@@ -1,6 +1,91 @@
 use crate::error::Result;
+use arrow::array::StringArray;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use datafusion::catalog::MemTable;
+use datafusion::common::{Column, JoinType};
+use datafusion::dataframe::DataFrame;
+use datafusion::datasource::provider_as_source;
+use datafusion::logical_expr::LogicalPlanBuilder;
 use datafusion::prelude::*;
 use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Column name of the in-memory PNR table joined against registry data
+pub const PNR_FILTER_COLUMN: &str = "__pnr_filter";
+
+/// Table name of the in-memory PNR table
+const PNR_FILTER_TABLE: &str = "__pnr_filter_values";
+
+/// Smallest and largest PNR of a set
+#[must_use]
+pub fn pnr_range(pnrs: &HashSet<String>) -> Option<(&str, &str)> {
+    let min = pnrs.iter().min()?;
+    let max = pnrs.iter().max()?;
+    Some((min.as_str(), max.as_str()))
+}
+
+/// Range predicate `column >= min AND column <= max` over a PNR set
+///
+/// The range is implied by the semi-join, but unlike the join it can be evaluated
+/// against Parquet row group statistics and [`crate::data::pruning::RegistryPruningStatistics`],
+/// so files and row groups outside the range of the cohort are skipped.
+#[must_use]
+pub fn pnr_range_expr(pnrs: &HashSet<String>, column: &str) -> Option<Expr> {
+    let (min, max) = pnr_range(pnrs)?;
+    let column = Expr::Column(Column::from_name(column));
+    Some(
+        column
+            .clone()
+            .gt_eq(lit(min.to_string()))
+            .and(column.lt_eq(lit(max.to_string()))),
+    )
+}
+
+/// A single-column batch of PNRs (column [`PNR_FILTER_COLUMN`])
+pub fn pnr_filter_batch(pnrs: &HashSet<String>) -> Result<RecordBatch> {
+    let schema = Schema::new(vec![Field::new(PNR_FILTER_COLUMN, DataType::Utf8, false)]);
+    let values = StringArray::from_iter_values(pnrs.iter().map(String::as_str));
+    Ok(RecordBatch::try_new(Arc::new(schema), vec![Arc::new(values)])?)
+}
+
+/// Keep the rows of a `DataFrame` whose `column` is one of the values
+///
+/// Used for PNR sets and for the record keys derived from them (`RECNUM`, `kontakt_id`).
+/// The values are registered as an in-memory table and applied as a left semi-join, so
+/// the plan size does not grow with the cohort and no value is ever formatted into SQL.
+/// The column name is used verbatim, without SQL parsing. A range predicate over the
+/// values is applied first to keep file and row group pruning.
+pub fn semi_join_values(
+    df: DataFrame,
+    pnrs: &HashSet<String>,
+    column: &str,
+) -> Result<DataFrame> {
+    let Some(range) = pnr_range_expr(pnrs, column) else {
+        return Ok(df);
+    };
+
+    let batch = pnr_filter_batch(pnrs)?;
+    let table = MemTable::try_new(batch.schema(), vec![vec![batch]])?;
+    let pnr_table =
+        LogicalPlanBuilder::scan(PNR_FILTER_TABLE, provider_as_source(Arc::new(table)), None)?
+            .build()?;
+
+    let (state, plan) = df.into_parts();
+    let plan = LogicalPlanBuilder::from(plan)
+        .filter(range)?
+        .join(
+            pnr_table,
+            JoinType::LeftSemi,
+            (
+                vec![Column::from_name(column)],
+                vec![Column::from_name(PNR_FILTER_COLUMN)],
+            ),
+            None,
+        )?
+        .build()?;
+    Ok(DataFrame::new(state, plan))
+}
 
 /// A structure for PNR filtering
 pub struct PnrFilter {
@@ -78,15 +163,30 @@ impl PnrFilter {
         }
     }
 
-    /// Apply filter to a `DataFrame`
-    pub fn apply_to_dataframe(&self, df: DataFrame) -> Result<DataFrame> {
-        if let Some(expr) = self.to_expr() {
-            Ok(df.filter(expr)?)
+    /// The column this filter applies to
+    #[must_use]
+    pub fn filter_column(&self) -> Option<&str> {
+        if self.direct_filter {
+            Some(&self.pnr_column)
         } else {
-            Ok(df)
+            self.relation_column.as_deref()
         }
     }
 
+    /// Apply filter to a `DataFrame` as a semi-join against the PNR set
+    pub fn apply_to_dataframe(&self, df: DataFrame) -> Result<DataFrame> {
+        match self.filter_column() {
+            Some(column) => semi_join_values(df, &self.pnrs, column),
+            None => Ok(df),
+        }
+    }
+
+    /// Range predicate over the PNRs for file and row group pruning
+    #[must_use]
+    pub fn to_pruning_expr(&self) -> Option<Expr> {
+        pnr_range_expr(&self.pnrs, self.filter_column()?)
+    }
+
     /// Create a predicate for `DataFusion` execution
     #[must_use] pub fn to_predicate(&self) -> Option<Expr> {
         self.to_expr()
@@ -101,4 +201,51 @@ impl PnrFilter {
     #[must_use] pub fn len(&self) -> usize {
         self.pnrs.len()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, ArrayRef};
+
+    #[test]
+    fn test_semi_join_values() {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "MOR PNR",
+                Arc::new(StringArray::from(vec!["0101", "o'brien", "0303", "0404"])) as ArrayRef,
+            ),
+            (
+                "VALUE",
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let ctx = SessionContext::new();
+        let pnrs: HashSet<String> = ["o'brien", "0303", "9999"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        let filter = PnrFilter::with_relation(pnrs, "MOR PNR");
+        let df = filter
+            .apply_to_dataframe(ctx.read_batch(batch).unwrap())
+            .unwrap();
+        let batches = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(df.collect())
+            .unwrap();
+
+        let mut values: Vec<String> = Vec::new();
+        for batch in &batches {
+            assert_eq!(batch.num_columns(), 2);
+            let column = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            values.extend((0..column.len()).map(|i| column.value(i).to_string()));
+        }
+        values.sort();
+        assert_eq!(values, vec!["b", "c"]);
+    }
+}
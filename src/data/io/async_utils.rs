@@ -13,7 +13,7 @@ pub async fn load_parquet_files_async(
     _batch_size: usize,
 ) -> Result<Vec<RecordBatch>> {
     // Create session context
-    let ctx = SessionContext::new();
+    let ctx = crate::data::io::execution::session_context();
 
     // Note: batch_size configuration is handled differently in DataFusion 47.0.0
     // Configure ctx with batch size if needed
@@ -41,7 +41,7 @@ pub async fn load_parquet_files_async_with_filter(
     _batch_size: usize,
 ) -> Result<Vec<RecordBatch>> {
     // Create session context
-    let ctx = SessionContext::new();
+    let ctx = crate::data::io::execution::session_context();
 
     // Note: batch_size configuration is handled differently in DataFusion 47.0.0
     // Configure ctx with batch size if needed
@@ -58,9 +58,7 @@ pub async fn load_parquet_files_async_with_filter(
         .await?;
 
     // Apply PNR filter
-    if let Some(expr) = pnr_filter.to_expr() {
-        df = df.filter(expr)?;
-    }
+    df = pnr_filter.apply_to_dataframe(df)?;
 
     // Collect and return
     Ok(df.collect().await?)
@@ -74,7 +72,7 @@ pub async fn load_parquet_directory_async(
     _batch_size: usize,
 ) -> Result<Vec<RecordBatch>> {
     // Create session context
-    let ctx = SessionContext::new();
+    let ctx = crate::data::io::execution::session_context();
 
     // Note: batch_size configuration is handled differently in DataFusion 47.0.0
     // Configure ctx with batch size if needed
@@ -92,9 +90,7 @@ pub async fn load_parquet_directory_async(
 
     // Apply PNR filter if provided
     if let Some(filter) = pnr_filter {
-        if let Some(expr) = filter.to_expr() {
-            df = df.filter(expr)?;
-        }
+        df = filter.apply_to_dataframe(df)?;
     }
 
     // Collect and return
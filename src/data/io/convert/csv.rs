@@ -0,0 +1,158 @@
+//! CSV register exports
+//!
+//! Statistics Denmark exports CSV with semicolons as separators, decimal commas, Danish
+//! dates (`31-12-2020`, `31.12.2020`) and often Latin-1 text. All columns are read as
+//! strings; [`super::convert_registry`] then parses them into the registry types.
+
+use arrow::csv::reader::Format;
+use arrow::csv::ReaderBuilder;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::{IdsError, Result};
+
+/// Character encoding of a CSV file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, as written by Windows SAS and Excel
+    Latin1,
+}
+
+impl FromStr for CsvEncoding {
+    type Err = IdsError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "latin1" | "iso88591" => Ok(Self::Latin1),
+            _ => Err(IdsError::Validation(format!(
+                "Unknown CSV encoding: {s} (expected utf8 or latin1)"
+            ))),
+        }
+    }
+}
+
+/// Options for reading CSV files
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub has_header: bool,
+    pub encoding: CsvEncoding,
+    /// Numbers use a decimal comma (`12,5`)
+    pub decimal_comma: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b';',
+            has_header: true,
+            encoding: CsvEncoding::default(),
+            decimal_comma: true,
+        }
+    }
+}
+
+/// Transcodes Latin-1 bytes to UTF-8
+struct Latin1Reader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    /// Second byte of a character that did not fit the last read
+    pending: Option<u8>,
+}
+
+impl<R: Read> Read for Latin1Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut written = 0;
+        if let Some(byte) = self.pending.take() {
+            buf[0] = byte;
+            written = 1;
+        }
+        let space = buf.len() - written;
+        if space == 0 {
+            return Ok(written);
+        }
+
+        // Each Latin-1 byte takes at most two UTF-8 bytes
+        self.buffer.resize((space / 2).max(1), 0);
+        let read = self.inner.read(&mut self.buffer)?;
+        for &byte in &self.buffer[..read] {
+            if byte < 0x80 {
+                buf[written] = byte;
+                written += 1;
+            } else {
+                let (first, second) = (0xC0 | (byte >> 6), 0x80 | (byte & 0x3F));
+                buf[written] = first;
+                written += 1;
+                if written < buf.len() {
+                    buf[written] = second;
+                    written += 1;
+                } else {
+                    self.pending = Some(second);
+                }
+            }
+        }
+        Ok(written)
+    }
+}
+
+fn open(path: &Path, encoding: CsvEncoding) -> Result<Box<dyn Read + Send>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(match encoding {
+        CsvEncoding::Utf8 => Box::new(file),
+        CsvEncoding::Latin1 => Box::new(Latin1Reader {
+            inner: file,
+            buffer: Vec::new(),
+            pending: None,
+        }),
+    })
+}
+
+/// Record batches read from a file
+pub type RecordBatches = Box<dyn Iterator<Item = Result<RecordBatch>> + Send>;
+
+/// Read a CSV file as string columns; blank fields are null
+pub fn read_csv(
+    path: &Path,
+    options: &CsvOptions,
+    batch_size: usize,
+) -> Result<(SchemaRef, RecordBatches)> {
+    let blank = Regex::new(r"^\s*$")
+        .map_err(|e| IdsError::Validation(format!("Invalid null pattern: {e}")))?;
+    let format = Format::default()
+        .with_delimiter(options.delimiter)
+        .with_header(options.has_header)
+        .with_null_regex(blank);
+
+    // Only the column names of the inferred schema are used
+    let (inferred, _) = format.infer_schema(open(path, options.encoding)?, Some(1))?;
+    let fields: Vec<Field> = inferred
+        .fields()
+        .iter()
+        .map(|field| {
+            let name = field.name().trim_start_matches('\u{feff}').trim();
+            Field::new(name, DataType::Utf8, true)
+        })
+        .collect();
+    let schema = Arc::new(Schema::new(fields));
+
+    let reader = ReaderBuilder::new(schema.clone())
+        .with_format(format)
+        .with_batch_size(batch_size.max(1))
+        .build(open(path, options.encoding)?)?;
+    Ok((
+        schema,
+        Box::new(reader.map(|batch| batch.map_err(IdsError::from))),
+    ))
+}
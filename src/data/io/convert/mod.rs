@@ -0,0 +1,534 @@
+//! Conversion of SAS and CSV register deliveries to registry Parquet
+//!
+//! Registers arrive as SAS datasets or semicolon-separated CSV, while the loaders read
+//! Parquet. [`convert_registry`] reads each delivered file batch by batch and:
+//!
+//! - parses text columns whose registry type is a date with [`parse_danish_date`] and
+//!   numbers with thousands separators and, for CSV, decimal commas, counting values
+//!   that do not parse in the drift report;
+//! - reconciles the result with the registry schema (see [`SchemaReconciler`]);
+//! - writes one Parquet file per year, e.g. `bef2020.parquet`, taking the year from a
+//!   date or year column, the options, or the input file name.
+
+pub mod csv;
+pub mod sas7bdat;
+
+use arrow::array::{Array, ArrayRef, Date32Array, Float64Array, Int64Array, StringArray};
+use arrow::compute::{cast, filter_record_batch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use chrono::Datelike;
+use datafusion::parquet::arrow::ArrowWriter;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub use self::csv::{read_csv, CsvEncoding, CsvOptions, RecordBatches};
+pub use self::sas7bdat::{Sas7bdatReader, SasColumn, SasColumnKind};
+
+use super::partitioned::ParquetWriteOptions;
+use crate::data::pruning::year_from_file_name;
+use crate::data::registry::factory::RegistryFactory;
+use crate::data::schema::drift::{DriftReport, SchemaReconciler};
+use crate::error::{IdsError, Result};
+use crate::utils::date_utils::{date_to_days_since_epoch, parse_danish_date, parse_flexible};
+
+/// Format of a delivered register file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Sas7bdat,
+    Csv,
+}
+
+impl InputFormat {
+    /// The format of a file from its extension
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "sas7bdat" => Some(Self::Sas7bdat),
+            "csv" | "txt" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = IdsError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sas" | "sas7bdat" => Ok(Self::Sas7bdat),
+            "csv" => Ok(Self::Csv),
+            _ => Err(IdsError::Validation(format!(
+                "Unknown input format: {s} (expected sas7bdat or csv)"
+            ))),
+        }
+    }
+}
+
+/// Options for converting register files
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Registry name; inferred from the input path if `None`
+    pub registry: Option<String>,
+    /// Year of all rows; from the year column or file names if `None`
+    pub year: Option<i32>,
+    /// Date or integer year column to partition rows by
+    pub year_column: Option<String>,
+    pub csv: CsvOptions,
+    /// Rows per batch read
+    pub batch_size: usize,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            registry: None,
+            year: None,
+            year_column: None,
+            csv: CsvOptions::default(),
+            batch_size: 64 * 1024,
+        }
+    }
+}
+
+/// Files and rows converted
+#[derive(Debug, Clone, Default)]
+pub struct ConvertSummary {
+    pub registry: String,
+    /// Input files converted
+    pub files: usize,
+    /// Rows written
+    pub rows: usize,
+    /// Parquet files written, one per year
+    pub outputs: Vec<PathBuf>,
+    /// Schema reconciliations of the input files
+    pub drift: DriftReport,
+}
+
+/// One Parquet writer per year
+struct YearPartitions {
+    dir: PathBuf,
+    prefix: String,
+    schema: SchemaRef,
+    writers: BTreeMap<Option<i32>, (PathBuf, ArrowWriter<File>)>,
+}
+
+impl YearPartitions {
+    fn path(&self, year: Option<i32>) -> PathBuf {
+        match year {
+            Some(year) => self.dir.join(format!("{}{year}.parquet", self.prefix)),
+            None => self
+                .dir
+                .join(format!("{}_unknown_year.parquet", self.prefix)),
+        }
+    }
+
+    fn write(&mut self, year: Option<i32>, batch: &RecordBatch) -> Result<()> {
+        if !self.writers.contains_key(&year) {
+            let path = self.path(year);
+            let properties = ParquetWriteOptions::default().writer_properties(&self.schema)?;
+            let writer =
+                ArrowWriter::try_new(File::create(&path)?, self.schema.clone(), Some(properties))?;
+            self.writers.insert(year, (path, writer));
+        }
+        if let Some((_, writer)) = self.writers.get_mut(&year) {
+            writer.write(batch)?;
+        }
+        Ok(())
+    }
+
+    fn close(self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::with_capacity(self.writers.len());
+        for (_, (path, writer)) in self.writers {
+            writer.close()?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+/// The year of each row from a date or integer year column
+pub(crate) fn row_years(column: &ArrayRef) -> Result<Vec<Option<i32>>> {
+    if let Some(dates) = column.as_any().downcast_ref::<Date32Array>() {
+        return Ok((0..dates.len())
+            .map(|i| {
+                dates
+                    .value_as_date(i)
+                    .filter(|_| dates.is_valid(i))
+                    .map(|d| d.year())
+            })
+            .collect());
+    }
+    if column.data_type().is_integer() {
+        let years = cast(column, &DataType::Int64)?;
+        let years = years
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .ok_or_else(|| IdsError::Data("Failed to cast year column".to_string()))?;
+        return Ok(years
+            .iter()
+            .map(|year| year.and_then(|year| i32::try_from(year).ok()))
+            .collect());
+    }
+    Err(IdsError::Validation(format!(
+        "Year column must be a date or integer column, not {}",
+        column.data_type()
+    )))
+}
+
+/// The schema of a source after [`prepare_batch`]
+fn prepared_schema(schema: &Schema, reconciler: &SchemaReconciler) -> Schema {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(
+            |field| match (field.data_type(), reconciler.target_field(field.name())) {
+                (DataType::Utf8, Some(target)) if target.data_type() == &DataType::Date32 => {
+                    Field::new(field.name(), DataType::Date32, true)
+                }
+                (DataType::Utf8, Some(target)) if target.data_type().is_floating() => {
+                    Field::new(field.name(), DataType::Float64, true)
+                }
+                _ => field.as_ref().clone(),
+            },
+        )
+        .collect();
+    Schema::new(fields)
+}
+
+/// Parse a number with an optional sign, thousands separators and decimal separator,
+/// e.g. `-1.234,5` with a decimal comma or `1,234.5` without
+///
+/// Thousands separators must group the integer part by three digits, so `1.5` is not
+/// read as `15` in a decimal-comma file.
+pub(crate) fn parse_number(value: &str, decimal_comma: bool) -> Option<f64> {
+    let (thousands, decimal) = if decimal_comma {
+        ('.', ',')
+    } else {
+        (',', '.')
+    };
+    let (integer, fraction) = match value.split_once(decimal) {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (value, None),
+    };
+    let unsigned = integer.trim_start_matches(['-', '+']);
+    let mut groups = unsigned.split(thousands);
+    let first = groups.next()?;
+    let grouped = groups.all(|group| group.len() == 3);
+    if unsigned.contains(thousands) && !(grouped && (1..=3).contains(&first.len())) {
+        return None;
+    }
+    let mut number = integer.replace(thousands, "");
+    if let Some(fraction) = fraction {
+        number.push('.');
+        number.push_str(fraction);
+    }
+    number.parse().ok()
+}
+
+/// Parse text columns into dates and numbers where the registry expects them, counting
+/// the non-empty values that do not parse per column in `unparsed`
+fn prepare_batch(
+    batch: &RecordBatch,
+    schema: &SchemaRef,
+    decimal_comma: bool,
+    unparsed: &mut BTreeMap<String, usize>,
+) -> Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(batch.num_columns());
+    for (column, field) in batch.columns().iter().zip(schema.fields()) {
+        let strings = column.as_any().downcast_ref::<StringArray>();
+        let prepared: ArrayRef = match (strings, field.data_type()) {
+            (Some(strings), DataType::Date32) => Arc::new(
+                strings
+                    .iter()
+                    .map(|value| {
+                        let value = value?.trim();
+                        parse_danish_date(value)
+                            .or_else(|| parse_flexible(value).ok())
+                            .map(date_to_days_since_epoch)
+                    })
+                    .collect::<Date32Array>(),
+            ),
+            (Some(strings), DataType::Float64) => Arc::new(
+                strings
+                    .iter()
+                    .map(|value| parse_number(value?.trim(), decimal_comma))
+                    .collect::<Float64Array>(),
+            ),
+            _ => column.clone(),
+        };
+        if let Some(strings) = strings.filter(|_| field.data_type() != &DataType::Utf8) {
+            let empty = strings.iter().flatten().filter(|v| v.trim().is_empty());
+            let failed = prepared.null_count() - strings.null_count() - empty.count();
+            if failed > 0 {
+                *unparsed.entry(field.name().clone()).or_default() += failed;
+            }
+        }
+        columns.push(prepared);
+    }
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Delivered files of a file or directory, sorted by path
+fn input_files(input: &Path) -> Result<Vec<(PathBuf, InputFormat)>> {
+    let candidates = if input.is_dir() {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(input)? {
+            files.push(entry?.path());
+        }
+        files.sort();
+        files
+    } else {
+        vec![input.to_path_buf()]
+    };
+    Ok(candidates
+        .into_iter()
+        .filter_map(|path| InputFormat::from_path(&path).map(|format| (path, format)))
+        .collect())
+}
+
+/// Convert SAS7BDAT and CSV files of a registry to Parquet partitioned by year
+///
+/// `input` is a file or a directory of `.sas7bdat`, `.csv` and `.txt` files; the Parquet
+/// files are written to the `output` directory.
+pub fn convert_registry(
+    input: &Path,
+    output: &Path,
+    options: &ConvertOptions,
+) -> Result<ConvertSummary> {
+    let info = match &options.registry {
+        Some(name) => RegistryFactory::schema_info_from_name(name)?,
+        None => RegistryFactory::schema_info_from_path(input)?,
+    };
+    let reconciler = SchemaReconciler::new(info.schema.clone(), info.drift_rules.clone());
+    let year_idx = match &options.year_column {
+        Some(column) => Some(info.schema.index_of(column).map_err(|_| {
+            IdsError::Validation(format!(
+                "Year column {column} is not in the {} schema",
+                info.name
+            ))
+        })?),
+        None => None,
+    };
+
+    let files = input_files(input)?;
+    if files.is_empty() {
+        return Err(IdsError::Validation(format!(
+            "No SAS7BDAT or CSV files found in {}",
+            input.display()
+        )));
+    }
+    std::fs::create_dir_all(output)?;
+
+    let mut partitions = YearPartitions {
+        dir: output.to_path_buf(),
+        prefix: info.name.to_lowercase(),
+        schema: info.schema.clone(),
+        writers: BTreeMap::new(),
+    };
+    let mut summary = ConvertSummary {
+        registry: info.name.to_string(),
+        ..ConvertSummary::default()
+    };
+
+    for (path, format) in &files {
+        let (schema, batches): (SchemaRef, RecordBatches) = match format {
+            InputFormat::Sas7bdat => {
+                let reader = Sas7bdatReader::open(path, options.batch_size)?;
+                (reader.schema(), Box::new(reader))
+            }
+            InputFormat::Csv => {
+                let (schema, batches) = read_csv(path, &options.csv, options.batch_size)?;
+                (schema, batches)
+            }
+        };
+        let prepared = Arc::new(prepared_schema(&schema, &reconciler));
+        let decimal_comma = *format == InputFormat::Csv && options.csv.decimal_comma;
+        let mut unparsed = BTreeMap::new();
+        let batches = batches.map(|batch| {
+            batch.and_then(|batch| prepare_batch(&batch, &prepared, decimal_comma, &mut unparsed))
+        });
+
+        let file_year = options.year.or_else(|| year_from_file_name(path));
+        let mut rows = 0;
+        let mut drift = reconciler.reconcile_batches(path, &prepared, batches, |batch| {
+            rows += batch.num_rows();
+            let Some(idx) = year_idx else {
+                return partitions.write(file_year, &batch);
+            };
+            let years = row_years(batch.column(idx))?;
+            let distinct: BTreeSet<Option<i32>> = years.iter().copied().collect();
+            if distinct.len() == 1 {
+                return partitions.write(years.first().copied().flatten(), &batch);
+            }
+            for year in distinct {
+                let mask = years.iter().map(|y| Some(*y == year)).collect();
+                partitions.write(year, &filter_record_batch(&batch, &mask)?)?;
+            }
+            Ok(())
+        })?;
+        drift.unparsed = unparsed;
+
+        log::info!("Converted {} ({rows} rows)", path.display());
+        if !drift.is_clean() {
+            summary.drift.files.push(drift);
+        }
+        summary.drift.files_checked += 1;
+        summary.files += 1;
+        summary.rows += rows;
+    }
+
+    summary.outputs = partitions.close()?;
+    for path in &summary.outputs {
+        if year_from_file_name(path).is_none() {
+            log::warn!(
+                "{}: rows without a year written to {}",
+                info.name,
+                path.display()
+            );
+        }
+    }
+    summary.drift.log(info.name);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    /// Options converting BEF input read as Latin-1
+    fn bef_options() -> ConvertOptions {
+        ConvertOptions {
+            registry: Some("bef".to_string()),
+            csv: CsvOptions {
+                encoding: CsvEncoding::Latin1,
+                ..CsvOptions::default()
+            },
+            ..ConvertOptions::default()
+        }
+    }
+
+    /// Write a Latin-1 CSV file with Danish dates for 2019 and a SAS dataset for 2020
+    fn write_bef_inputs(input: &Path) {
+        std::fs::create_dir_all(input).unwrap();
+        let csv = b"PNR;FOED_DAG;KOM;CIVST\n0101801234;15-01-1980; 101;G\n0202811234;02.02.1981;147;\xd8\n";
+        std::fs::write(input.join("bef2019.csv"), csv).unwrap();
+        sas7bdat::tests::write_test_dataset(&input.join("bef2020.sas7bdat"));
+    }
+
+    #[test]
+    fn test_convert_csv_and_sas_by_year() {
+        let dir = crate::test_utils::temp_dir();
+        let input = dir.path().join("input");
+        let output = dir.path().join("output");
+        write_bef_inputs(&input);
+
+        let summary = convert_registry(&input, &output, &bef_options()).unwrap();
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.rows, 4);
+        assert_eq!(
+            summary.outputs,
+            vec![
+                output.join("bef2019.parquet"),
+                output.join("bef2020.parquet")
+            ]
+        );
+
+        let file = File::open(output.join("bef2019.parquet")).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let dates = batch
+            .column_by_name("FOED_DAG")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(
+            dates.value_as_date(1),
+            chrono::NaiveDate::from_ymd_opt(1981, 2, 2)
+        );
+        let civst = batch
+            .column_by_name("CIVST")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(civst.value(1), "Ø");
+        assert_eq!(batch.column_by_name("KOM").unwrap().null_count(), 0);
+    }
+
+    #[test]
+    fn test_convert_by_year_column() {
+        let dir = crate::test_utils::temp_dir();
+        let input = dir.path().join("input");
+        write_bef_inputs(&input);
+
+        let by_year = ConvertOptions {
+            year_column: Some("FOED_DAG".to_string()),
+            ..bef_options()
+        };
+        let summary = convert_registry(&input, &dir.path().join("by_year"), &by_year).unwrap();
+        assert_eq!(summary.outputs.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_number_with_separators() {
+        assert_eq!(parse_number("1.234,5", true), Some(1234.5));
+        assert_eq!(parse_number("-1.234.567,25", true), Some(-1_234_567.25));
+        assert_eq!(parse_number("12,5", true), Some(12.5));
+        assert_eq!(parse_number("1234", true), Some(1234.0));
+        assert_eq!(parse_number("1,234.5", false), Some(1234.5));
+        assert_eq!(parse_number("12.5", false), Some(12.5));
+
+        // A decimal point in a decimal-comma file is not a thousands separator
+        assert_eq!(parse_number("1.5", true), None);
+        assert_eq!(parse_number("12.34.567,8", true), None);
+        assert_eq!(parse_number("abc", true), None);
+    }
+
+    #[test]
+    fn test_unparsed_values_are_reported() {
+        let dir = crate::test_utils::temp_dir();
+        let input = dir.path().join("ind2020.csv");
+        std::fs::write(
+            &input,
+            "PNR;LOENMV;PERINDKIALT\n0101801234;1.234,5;12,5\n0202811234;1.5;\n",
+        )
+        .unwrap();
+
+        let options = ConvertOptions {
+            registry: Some("ind".to_string()),
+            ..ConvertOptions::default()
+        };
+        let summary = convert_registry(&input, &dir.path().join("output"), &options).unwrap();
+        let drift = &summary.drift.files[0];
+        assert_eq!(drift.unparsed, BTreeMap::from([("LOENMV".to_string(), 1)]));
+        assert!(drift.summary().contains("unparsed LOENMV (1)"));
+
+        let file = File::open(&summary.outputs[0]).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let income = batch
+            .column_by_name("LOENMV_13")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(income.iter().collect::<Vec<_>>(), vec![Some(1234.5), None]);
+    }
+}
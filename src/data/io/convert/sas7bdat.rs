@@ -0,0 +1,1057 @@
+//! SAS7BDAT reader
+//!
+//! Reads SAS datasets as Arrow record batches without SAS or Python. The format is not
+//! documented by SAS; the layout follows the reverse-engineered description used by the
+//! `sas7bdat` R package, Parso and pandas:
+//!
+//! - a header with alignment, endianness, encoding and page geometry;
+//! - metadata pages whose subheaders describe row size, column names, attributes and
+//!   formats;
+//! - rows on data and mixed pages, or (for compressed datasets) as RLE (`SASYZCRL`) or
+//!   RDC (`SASYZCR2`) compressed subheaders.
+//!
+//! Numeric columns with a SAS date format become `Date32`, datetime formats become
+//! millisecond timestamps and other numbers `Float64`; SAS missing values become null.
+//! Character columns are decoded as UTF-8, Latin-1 or Windows-1252 from the dataset
+//! encoding.
+
+use arrow::array::{
+    ArrayRef, Date32Builder, Float64Builder, StringBuilder, TimestampMillisecondBuilder,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{IdsError, Result};
+
+const MAGIC: [u8; 32] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc2, 0xea, 0x81, 0x60,
+    0xb3, 0x14, 0x11, 0xcf, 0xbd, 0x92, 0x08, 0x00, 0x09, 0xc7, 0x31, 0x8c, 0x18, 0x1f, 0x10, 0x11,
+];
+
+/// Days from the SAS epoch (1960-01-01) to the Unix epoch
+const SAS_EPOCH_DAYS: i64 = 3653;
+/// Seconds from the SAS epoch to the Unix epoch
+const SAS_EPOCH_SECONDS: f64 = 315_619_200.0;
+
+const RLE_LITERAL: &[u8] = b"SASYZCRL";
+const RDC_LITERAL: &[u8] = b"SASYZCR2";
+
+// Subheader signatures, read as i32 in the file's byte order
+const ROW_SIZE_SIGNATURE: i32 = -134_744_073; // F7F7F7F7
+const COLUMN_SIZE_SIGNATURE: i32 = -151_587_082; // F6F6F6F6
+const COLUMN_TEXT_SIGNATURE: i32 = -3;
+const COLUMN_NAME_SIGNATURE: i32 = -1;
+const COLUMN_ATTRIBUTES_SIGNATURE: i32 = -4;
+const FORMAT_SIGNATURE: i32 = -1026;
+const COUNTS_SIGNATURE: i32 = -1024;
+const COLUMN_LIST_SIGNATURE: i32 = -2;
+
+// Page types after masking
+const PAGE_TYPE_MASK: i16 = 0x4700;
+const PAGE_DATA: i16 = 0x0100;
+const PAGE_MIX: i16 = 0x0200;
+const PAGE_COMP: i16 = -28672; // 0x9000
+
+/// Subheader pointer compression flags
+const SUBHEADER_TRUNCATED: u8 = 1;
+const SUBHEADER_COMPRESSED: u8 = 4;
+
+/// Numeric formats holding a datetime (seconds since 1960)
+const DATETIME_FORMATS: [&str; 12] = [
+    "DATETIME", "DATEAMPM", "DTDATE", "DTMONYY", "DTWKDATX", "DTYEAR", "DTYYQC", "E8601DT",
+    "B8601DT", "IS8601DT", "NLDATM", "MDYAMPM",
+];
+
+/// Numeric format prefixes holding a date (days since 1960)
+const DATE_FORMATS: [&str; 28] = [
+    "DATE", "DAY", "DDMMYY", "DOWNAME", "E8601DA", "B8601DA", "IS8601DA", "EURDF", "JULDAY",
+    "JULIAN", "MMDDYY", "MMYY", "MONNAME", "MONTH", "MONYY", "NLDATE", "QTR", "WEEKDATE",
+    "WEEKDATX", "WEEKDAY", "WORDDATE", "WORDDATX", "YEAR", "YYMM", "YYMMDD", "YYMON", "YYQ",
+    "YYQR",
+];
+
+/// Windows-1252 characters for bytes 0x80-0x9F (undefined bytes map to C1 controls)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// How a SAS column is represented in Arrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SasColumnKind {
+    /// Number without a date format, as `Float64`
+    Number,
+    /// Number with a date format, as `Date32`
+    Date,
+    /// Number with a datetime format, as millisecond `Timestamp`
+    DateTime,
+    /// Character column, as `Utf8`
+    Text,
+}
+
+/// A column of a SAS dataset
+#[derive(Debug, Clone)]
+pub struct SasColumn {
+    pub name: String,
+    pub label: String,
+    /// SAS format name without width, e.g. `DDMMYY`
+    pub format: String,
+    pub kind: SasColumnKind,
+    offset: usize,
+    length: usize,
+}
+
+impl SasColumn {
+    fn data_type(&self) -> DataType {
+        match self.kind {
+            SasColumnKind::Number => DataType::Float64,
+            SasColumnKind::Date => DataType::Date32,
+            SasColumnKind::DateTime => DataType::Timestamp(TimeUnit::Millisecond, None),
+            SasColumnKind::Text => DataType::Utf8,
+        }
+    }
+}
+
+/// Classify a column by type and format
+fn column_kind(numeric: bool, format: &str) -> SasColumnKind {
+    if !numeric {
+        return SasColumnKind::Text;
+    }
+    let name = format
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_ascii_uppercase();
+    if DATETIME_FORMATS.iter().any(|f| name.starts_with(f)) {
+        SasColumnKind::DateTime
+    } else if DATE_FORMATS.iter().any(|f| name.starts_with(f)) {
+        SasColumnKind::Date
+    } else {
+        SasColumnKind::Number
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Rle,
+    Rdc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextEncoding {
+    Utf8,
+    Latin1,
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// From the encoding byte of the header; unspecified encodings are read as Windows-1252
+    fn from_code(code: u8) -> Self {
+        match code {
+            20 => Self::Utf8,
+            29 => Self::Latin1,
+            _ => Self::Windows1252,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> String {
+        let end = bytes
+            .iter()
+            .rposition(|b| *b != b' ' && *b != 0)
+            .map_or(0, |i| i + 1);
+        let bytes = &bytes[..end];
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Latin1 => bytes.iter().map(|b| char::from(*b)).collect(),
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(b - 0x80)],
+                    _ => char::from(*b),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Word size and byte order of a dataset
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    u64: bool,
+    little_endian: bool,
+}
+
+impl Layout {
+    fn int_len(self) -> usize {
+        if self.u64 {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn page_bit_offset(self) -> usize {
+        if self.u64 {
+            32
+        } else {
+            16
+        }
+    }
+
+    fn pointer_len(self) -> usize {
+        if self.u64 {
+            24
+        } else {
+            12
+        }
+    }
+
+    fn uint(self, buf: &[u8], offset: usize, len: usize) -> Result<usize> {
+        let bytes = slice(buf, offset, len)?;
+        let mut value: u64 = 0;
+        for i in 0..len {
+            let byte = if self.little_endian {
+                bytes[len - 1 - i]
+            } else {
+                bytes[i]
+            };
+            value = (value << 8) | u64::from(byte);
+        }
+        usize::try_from(value).map_err(|_| corrupt("integer out of range"))
+    }
+
+    fn int(self, buf: &[u8], offset: usize) -> Result<usize> {
+        self.uint(buf, offset, self.int_len())
+    }
+
+    fn i16(self, buf: &[u8], offset: usize) -> Result<i16> {
+        let bytes: [u8; 2] = slice(buf, offset, 2)?
+            .try_into()
+            .map_err(|_| corrupt("short read"))?;
+        Ok(if self.little_endian {
+            i16::from_le_bytes(bytes)
+        } else {
+            i16::from_be_bytes(bytes)
+        })
+    }
+
+    /// A subheader signature; 64-bit signatures are sign-extended 32-bit values
+    fn signature(self, buf: &[u8], offset: usize) -> Result<i32> {
+        let start = if self.u64 && !self.little_endian {
+            offset + 4
+        } else {
+            offset
+        };
+        let bytes: [u8; 4] = slice(buf, start, 4)?
+            .try_into()
+            .map_err(|_| corrupt("short read"))?;
+        Ok(if self.little_endian {
+            i32::from_le_bytes(bytes)
+        } else {
+            i32::from_be_bytes(bytes)
+        })
+    }
+
+    /// A SAS number of 2 to 8 bytes (shorter numbers are truncated doubles)
+    fn number(self, bytes: &[u8]) -> Option<f64> {
+        let len = bytes.len().min(8);
+        let mut buf = [0u8; 8];
+        let value = if self.little_endian {
+            buf[8 - len..].copy_from_slice(&bytes[..len]);
+            f64::from_le_bytes(buf)
+        } else {
+            buf[..len].copy_from_slice(&bytes[..len]);
+            f64::from_be_bytes(buf)
+        };
+        // SAS missing values (., .A-.Z, ._) are NaNs
+        (!value.is_nan()).then_some(value)
+    }
+}
+
+fn corrupt(message: &str) -> IdsError {
+    IdsError::Data(format!("Invalid SAS7BDAT file: {message}"))
+}
+
+fn slice(buf: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| buf.get(offset..end))
+        .ok_or_else(|| corrupt("offset beyond the end of a page"))
+}
+
+/// Decompress a SASYZCRL (run-length encoded) row
+fn rle_decompress(input: &[u8], output_len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(output_len);
+    let mut pos = 0;
+    let byte = |pos: usize| {
+        input
+            .get(pos)
+            .copied()
+            .ok_or_else(|| corrupt("truncated RLE data"))
+    };
+    let literal = |output: &mut Vec<u8>, pos: usize, len: usize| -> Result<()> {
+        output.extend_from_slice(slice(input, pos, len)?);
+        Ok(())
+    };
+    while pos < input.len() {
+        let control = input[pos] & 0xF0;
+        let low = usize::from(input[pos] & 0x0F);
+        pos += 1;
+        match control {
+            0x00 => {
+                let len = usize::from(byte(pos)?) + 64 + low * 256;
+                literal(&mut output, pos + 1, len)?;
+                pos += 1 + len;
+            }
+            0x40 => {
+                let len = usize::from(byte(pos)?) + 18 + low * 256;
+                output.extend(std::iter::repeat_n(byte(pos + 1)?, len));
+                pos += 2;
+            }
+            0x60 => {
+                let len = low * 256 + usize::from(byte(pos)?) + 17;
+                output.extend(std::iter::repeat_n(b' ', len));
+                pos += 1;
+            }
+            0x70 => {
+                let len = low * 256 + usize::from(byte(pos)?) + 17;
+                output.extend(std::iter::repeat_n(0, len));
+                pos += 1;
+            }
+            0x80 | 0x90 | 0xA0 | 0xB0 => {
+                let len = low + 1 + usize::from((control - 0x80) >> 4) * 16;
+                literal(&mut output, pos, len)?;
+                pos += len;
+            }
+            0xC0 => {
+                output.extend(std::iter::repeat_n(byte(pos)?, low + 3));
+                pos += 1;
+            }
+            0xD0 => output.extend(std::iter::repeat_n(b'@', low + 2)),
+            0xE0 => output.extend(std::iter::repeat_n(b' ', low + 2)),
+            0xF0 => output.extend(std::iter::repeat_n(0, low + 2)),
+            _ => return Err(corrupt("unknown RLE control byte")),
+        }
+    }
+    check_length(output, output_len)
+}
+
+/// Decompress a SASYZCR2 (Ross data compression) row
+fn rdc_decompress(input: &[u8], output_len: usize) -> Result<Vec<u8>> {
+    let mut output: Vec<u8> = Vec::with_capacity(output_len);
+    let mut pos = 0;
+    let mut control_bits: u16 = 0;
+    let mut control_mask: u16 = 0;
+    let byte = |pos: usize| {
+        input
+            .get(pos)
+            .copied()
+            .ok_or_else(|| corrupt("truncated RDC data"))
+    };
+    let copy_back = |output: &mut Vec<u8>, offset: usize, len: usize| -> Result<()> {
+        let start = output
+            .len()
+            .checked_sub(offset)
+            .ok_or_else(|| corrupt("RDC pattern before the start of the row"))?;
+        for i in 0..len {
+            output.push(output[start + i]);
+        }
+        Ok(())
+    };
+    while pos < input.len() {
+        control_mask >>= 1;
+        if control_mask == 0 {
+            control_bits = u16::from(byte(pos)?) << 8 | u16::from(byte(pos + 1)?);
+            pos += 2;
+            control_mask = 0x8000;
+        }
+        if control_bits & control_mask == 0 {
+            output.push(byte(pos)?);
+            pos += 1;
+            continue;
+        }
+
+        let command = usize::from(byte(pos)? >> 4);
+        let count = usize::from(byte(pos)? & 0x0F);
+        pos += 1;
+        match command {
+            // Short run
+            0 => {
+                output.extend(std::iter::repeat_n(byte(pos)?, count + 3));
+                pos += 1;
+            }
+            // Long run
+            1 => {
+                let len = count + (usize::from(byte(pos)?) << 4) + 19;
+                output.extend(std::iter::repeat_n(byte(pos + 1)?, len));
+                pos += 2;
+            }
+            // Long pattern
+            2 => {
+                let offset = count + 3 + (usize::from(byte(pos)?) << 4);
+                let len = usize::from(byte(pos + 1)?) + 16;
+                pos += 2;
+                copy_back(&mut output, offset, len)?;
+            }
+            // Short pattern of `command` bytes
+            _ => {
+                let offset = count + 3 + (usize::from(byte(pos)?) << 4);
+                pos += 1;
+                copy_back(&mut output, offset, command)?;
+            }
+        }
+    }
+    check_length(output, output_len)
+}
+
+fn check_length(output: Vec<u8>, expected: usize) -> Result<Vec<u8>> {
+    if output.len() == expected {
+        Ok(output)
+    } else {
+        Err(corrupt(&format!(
+            "decompressed row has {} bytes, expected {expected}",
+            output.len()
+        )))
+    }
+}
+
+/// Where the rows of the current page are
+#[derive(Debug, Clone, Copy)]
+struct RowLocation {
+    offset: usize,
+    length: usize,
+    compressed: bool,
+}
+
+/// Column metadata collected from the subheaders
+#[derive(Debug, Default)]
+struct Metadata {
+    text_blocks: Vec<Vec<u8>>,
+    names: Vec<String>,
+    /// Offset in the row, length and whether the column is numeric
+    attributes: Vec<(usize, usize, bool)>,
+    /// Format and label per column
+    formats: Vec<(String, String)>,
+    row_length: usize,
+    row_count: usize,
+    mix_page_row_count: usize,
+    compression: Option<Compression>,
+}
+
+impl Metadata {
+    /// A string from a column text block
+    fn text(&self, encoding: TextEncoding, block: usize, offset: usize, len: usize) -> String {
+        let block = block.min(self.text_blocks.len().saturating_sub(1));
+        self.text_blocks
+            .get(block)
+            .and_then(|text| text.get(offset..offset + len))
+            .map(|bytes| encoding.decode(bytes))
+            .unwrap_or_default()
+    }
+}
+
+/// Builds the Arrow column of a SAS column
+enum ColumnBuilder {
+    Number(Float64Builder),
+    Date(Date32Builder),
+    DateTime(TimestampMillisecondBuilder),
+    Text(StringBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(kind: SasColumnKind, capacity: usize) -> Self {
+        match kind {
+            SasColumnKind::Number => Self::Number(Float64Builder::with_capacity(capacity)),
+            SasColumnKind::Date => Self::Date(Date32Builder::with_capacity(capacity)),
+            SasColumnKind::DateTime => {
+                Self::DateTime(TimestampMillisecondBuilder::with_capacity(capacity))
+            }
+            SasColumnKind::Text => Self::Text(StringBuilder::with_capacity(capacity, capacity * 8)),
+        }
+    }
+
+    fn append(&mut self, bytes: &[u8], layout: Layout, encoding: TextEncoding) {
+        match self {
+            Self::Number(builder) => builder.append_option(layout.number(bytes)),
+            Self::Date(builder) => builder.append_option(
+                layout
+                    .number(bytes)
+                    .and_then(|days| i32::try_from(days.floor() as i64 - SAS_EPOCH_DAYS).ok()),
+            ),
+            Self::DateTime(builder) => builder.append_option(
+                layout
+                    .number(bytes)
+                    .map(|seconds| ((seconds - SAS_EPOCH_SECONDS) * 1000.0).round() as i64),
+            ),
+            Self::Text(builder) => {
+                let text = encoding.decode(bytes);
+                if text.is_empty() {
+                    builder.append_null();
+                } else {
+                    builder.append_value(text);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            Self::Number(mut builder) => Arc::new(builder.finish()),
+            Self::Date(mut builder) => Arc::new(builder.finish()),
+            Self::DateTime(mut builder) => Arc::new(builder.finish()),
+            Self::Text(mut builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Reads a SAS7BDAT file as record batches
+pub struct Sas7bdatReader {
+    file: File,
+    path: PathBuf,
+    layout: Layout,
+    encoding: TextEncoding,
+    header_length: u64,
+    page_length: usize,
+    page_count: usize,
+    meta: Metadata,
+    columns: Vec<SasColumn>,
+    schema: SchemaRef,
+    batch_size: usize,
+    next_page: usize,
+    page: Vec<u8>,
+    page_rows: Vec<RowLocation>,
+    page_row: usize,
+    rows_read: usize,
+}
+
+impl Sas7bdatReader {
+    /// Open a dataset and read its column metadata
+    pub fn open(path: &Path, batch_size: usize) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut header = vec![0u8; 288];
+        file.read_exact(&mut header)
+            .map_err(|_| corrupt("file shorter than the header"))?;
+        if header[..MAGIC.len()] != MAGIC {
+            return Err(IdsError::Validation(format!(
+                "{} is not a SAS7BDAT file",
+                path.display()
+            )));
+        }
+
+        let layout = Layout {
+            u64: header[32] == b'3',
+            little_endian: header[37] == 0x01,
+        };
+        let align = if header[35] == b'3' { 4 } else { 0 };
+        let encoding = TextEncoding::from_code(header[70]);
+        let header_length = layout.uint(&header, 196 + align, 4)?;
+        let page_length = layout.uint(&header, 200 + align, 4)?;
+        let page_count = layout.uint(&header, 204 + align, 4)?;
+        if page_length == 0 {
+            return Err(corrupt("page length is zero"));
+        }
+
+        let mut reader = Self {
+            file,
+            path: path.to_path_buf(),
+            layout,
+            encoding,
+            header_length: header_length as u64,
+            page_length,
+            page_count,
+            meta: Metadata::default(),
+            columns: Vec::new(),
+            schema: Arc::new(Schema::empty()),
+            batch_size: batch_size.max(1),
+            next_page: 0,
+            page: vec![0u8; page_length],
+            page_rows: Vec::new(),
+            page_row: 0,
+            rows_read: 0,
+        };
+
+        // Metadata precedes the first rows
+        while reader.columns.is_empty() {
+            if !reader.load_page()? {
+                break;
+            }
+            if !reader.page_rows.is_empty() || reader.next_page >= reader.page_count {
+                reader.build_columns()?;
+            }
+        }
+        if reader.columns.is_empty() {
+            reader.build_columns()?;
+        }
+        Ok(reader)
+    }
+
+    /// The Arrow schema of the batches
+    #[must_use]
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// The dataset's columns with formats and labels
+    #[must_use]
+    pub fn columns(&self) -> &[SasColumn] {
+        &self.columns
+    }
+
+    /// Rows in the dataset
+    #[must_use]
+    pub fn row_count(&self) -> usize {
+        self.meta.row_count
+    }
+
+    fn build_columns(&mut self) -> Result<()> {
+        if self.meta.attributes.len() != self.meta.names.len() {
+            return Err(corrupt(&format!(
+                "{} column names but {} column attributes in {}",
+                self.meta.names.len(),
+                self.meta.attributes.len(),
+                self.path.display()
+            )));
+        }
+        self.columns = self
+            .meta
+            .names
+            .iter()
+            .zip(&self.meta.attributes)
+            .enumerate()
+            .map(|(i, (name, &(offset, length, numeric)))| {
+                let (format, label) = self.meta.formats.get(i).cloned().unwrap_or_default();
+                SasColumn {
+                    name: name.clone(),
+                    kind: column_kind(numeric, &format),
+                    label,
+                    format,
+                    offset,
+                    length,
+                }
+            })
+            .collect();
+        for column in &self.columns {
+            if column.offset + column.length > self.meta.row_length {
+                return Err(corrupt(&format!(
+                    "column {} lies outside the row",
+                    column.name
+                )));
+            }
+        }
+        self.schema = Arc::new(Schema::new(
+            self.columns
+                .iter()
+                .map(|column| Field::new(&column.name, column.data_type(), true))
+                .collect::<Vec<_>>(),
+        ));
+        Ok(())
+    }
+
+    /// Read the next page and locate its rows; false after the last page
+    fn load_page(&mut self) -> Result<bool> {
+        if self.next_page >= self.page_count {
+            return Ok(false);
+        }
+        let position = self.header_length + (self.next_page * self.page_length) as u64;
+        self.file.seek(SeekFrom::Start(position))?;
+        self.file
+            .read_exact(&mut self.page)
+            .map_err(|_| corrupt("truncated page"))?;
+        self.next_page += 1;
+        self.page_row = 0;
+        self.page_rows.clear();
+
+        let layout = self.layout;
+        let bit_offset = layout.page_bit_offset();
+        let page_type = layout.i16(&self.page, bit_offset)?;
+        if page_type == PAGE_COMP {
+            return Ok(true);
+        }
+        let block_count = layout.uint(&self.page, bit_offset + 2, 2)?;
+        let subheader_count = layout.uint(&self.page, bit_offset + 4, 2)?;
+
+        let page_type = page_type & PAGE_TYPE_MASK;
+        if page_type != PAGE_DATA {
+            self.process_subheaders(subheader_count)?;
+        }
+
+        let row_length = self.meta.row_length;
+        let first_row = bit_offset + 8;
+        match page_type {
+            PAGE_DATA => {
+                for row in 0..block_count {
+                    self.page_rows.push(RowLocation {
+                        offset: first_row + row * row_length,
+                        length: row_length,
+                        compressed: false,
+                    });
+                }
+            }
+            PAGE_MIX => {
+                let pointers_end = first_row + subheader_count * layout.pointer_len();
+                let start = pointers_end + pointers_end % 8;
+                let rows = self.meta.row_count.min(self.meta.mix_page_row_count);
+                for row in 0..rows {
+                    let offset = start + row * row_length;
+                    if offset + row_length > self.page.len() {
+                        break;
+                    }
+                    self.page_rows.push(RowLocation {
+                        offset,
+                        length: row_length,
+                        compressed: false,
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Process the subheaders of a metadata or mixed page
+    fn process_subheaders(&mut self, count: usize) -> Result<()> {
+        let layout = self.layout;
+        let int_len = layout.int_len();
+        for i in 0..count {
+            let pointer = layout.page_bit_offset() + 8 + i * layout.pointer_len();
+            let offset = layout.int(&self.page, pointer)?;
+            let length = layout.int(&self.page, pointer + int_len)?;
+            let compression = *slice(&self.page, pointer + 2 * int_len, 1)?
+                .first()
+                .unwrap_or(&0);
+            let subheader_type = *slice(&self.page, pointer + 2 * int_len + 1, 1)?
+                .first()
+                .unwrap_or(&0);
+            if length == 0 || compression == SUBHEADER_TRUNCATED {
+                continue;
+            }
+            slice(&self.page, offset, length)?;
+
+            let signature = layout.signature(&self.page, offset)?;
+            match signature {
+                ROW_SIZE_SIGNATURE => {
+                    self.meta.row_length = layout.int(&self.page, offset + 5 * int_len)?;
+                    self.meta.row_count = layout.int(&self.page, offset + 6 * int_len)?;
+                    self.meta.mix_page_row_count = layout.int(&self.page, offset + 15 * int_len)?;
+                }
+                COLUMN_TEXT_SIGNATURE => {
+                    let start = offset + int_len;
+                    let size = layout.uint(&self.page, start, 2)?;
+                    let block = slice(&self.page, start, size)?.to_vec();
+                    if self.meta.compression.is_none() {
+                        let contains = |literal: &[u8]| {
+                            block.windows(literal.len()).any(|window| window == literal)
+                        };
+                        self.meta.compression = Some(if contains(RLE_LITERAL) {
+                            Compression::Rle
+                        } else if contains(RDC_LITERAL) {
+                            Compression::Rdc
+                        } else {
+                            Compression::None
+                        });
+                    }
+                    self.meta.text_blocks.push(block);
+                }
+                COLUMN_NAME_SIGNATURE => {
+                    let start = offset + int_len;
+                    let pointers = length.saturating_sub(2 * int_len + 12) / 8;
+                    for i in 0..pointers {
+                        let base = start + 8 * (i + 1);
+                        let block = layout.uint(&self.page, base, 2)?;
+                        let name_offset = layout.uint(&self.page, base + 2, 2)?;
+                        let name_length = layout.uint(&self.page, base + 4, 2)?;
+                        let name = self
+                            .meta
+                            .text(self.encoding, block, name_offset, name_length);
+                        self.meta.names.push(name);
+                    }
+                }
+                COLUMN_ATTRIBUTES_SIGNATURE => {
+                    let vectors = length.saturating_sub(2 * int_len + 12) / (int_len + 8);
+                    for i in 0..vectors {
+                        let base = offset + i * (int_len + 8);
+                        let data_offset = layout.int(&self.page, base + int_len + 8)?;
+                        let data_length = layout.uint(&self.page, base + 2 * int_len + 8, 4)?;
+                        let numeric = slice(&self.page, base + 2 * int_len + 14, 1)?[0] == 1;
+                        self.meta
+                            .attributes
+                            .push((data_offset, data_length, numeric));
+                    }
+                }
+                FORMAT_SIGNATURE => {
+                    let base = offset + 3 * int_len;
+                    let text = |at: usize| -> Result<String> {
+                        Ok(self.meta.text(
+                            self.encoding,
+                            layout.uint(&self.page, base + at, 2)?,
+                            layout.uint(&self.page, base + at + 2, 2)?,
+                            layout.uint(&self.page, base + at + 4, 2)?,
+                        ))
+                    };
+                    let format = text(22)?;
+                    let label = text(28)?;
+                    self.meta.formats.push((format, label));
+                }
+                COLUMN_SIZE_SIGNATURE | COUNTS_SIGNATURE | COLUMN_LIST_SIGNATURE => {}
+                _ => {
+                    // Rows of compressed datasets are stored as subheaders
+                    let compressed_file =
+                        !matches!(self.meta.compression, None | Some(Compression::None));
+                    if compressed_file
+                        && subheader_type == 1
+                        && (compression == SUBHEADER_COMPRESSED || compression == 0)
+                    {
+                        self.page_rows.push(RowLocation {
+                            offset,
+                            length,
+                            compressed: compression == SUBHEADER_COMPRESSED
+                                && length != self.meta.row_length,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the next batch of rows
+    fn read_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let remaining = self.meta.row_count.saturating_sub(self.rows_read);
+        if remaining == 0 || self.columns.is_empty() {
+            return Ok(None);
+        }
+        let capacity = remaining.min(self.batch_size);
+        let mut builders: Vec<ColumnBuilder> = self
+            .columns
+            .iter()
+            .map(|column| ColumnBuilder::new(column.kind, capacity))
+            .collect();
+
+        let mut rows = 0;
+        while rows < capacity {
+            if self.page_row >= self.page_rows.len() {
+                if !self.load_page()? {
+                    break;
+                }
+                continue;
+            }
+            let location = self.page_rows[self.page_row];
+            self.page_row += 1;
+
+            let raw = slice(&self.page, location.offset, location.length)?;
+            let decompressed;
+            let row = if location.compressed {
+                decompressed = match self.meta.compression {
+                    Some(Compression::Rle) => rle_decompress(raw, self.meta.row_length)?,
+                    Some(Compression::Rdc) => rdc_decompress(raw, self.meta.row_length)?,
+                    _ => return Err(corrupt("compressed row in an uncompressed dataset")),
+                };
+                &decompressed[..]
+            } else {
+                raw
+            };
+            for (column, builder) in self.columns.iter().zip(builders.iter_mut()) {
+                let bytes = slice(row, column.offset, column.length)?;
+                builder.append(bytes, self.layout, self.encoding);
+            }
+            rows += 1;
+        }
+
+        if rows == 0 {
+            if self.rows_read < self.meta.row_count {
+                log::warn!(
+                    "{}: read {} of {} rows",
+                    self.path.display(),
+                    self.rows_read,
+                    self.meta.row_count
+                );
+            }
+            return Ok(None);
+        }
+        self.rows_read += rows;
+        let columns = builders.into_iter().map(ColumnBuilder::finish).collect();
+        Ok(Some(RecordBatch::try_new(self.schema.clone(), columns)?))
+    }
+}
+
+impl Iterator for Sas7bdatReader {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_batch().transpose()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use arrow::array::{Array, Date32Array, Float64Array, StringArray};
+
+    const PAGE: usize = 1024;
+    const HEADER: usize = 1024;
+
+    fn put(buf: &mut [u8], offset: usize, bytes: &[u8]) {
+        buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn put64(buf: &mut [u8], offset: usize, value: u64) {
+        put(buf, offset, &value.to_le_bytes());
+    }
+
+    fn put16(buf: &mut [u8], offset: usize, value: u16) {
+        put(buf, offset, &value.to_le_bytes());
+    }
+
+    /// A 64-bit little-endian dataset with a PNR, a DDMMYY date and an RLE compressed row
+    pub(crate) fn write_test_dataset(path: &Path) {
+        let mut header = vec![0u8; HEADER];
+        put(&mut header, 0, &MAGIC);
+        header[32] = b'3';
+        header[35] = b'3';
+        header[37] = 0x01;
+        header[70] = 20;
+        put(&mut header, 200, &(HEADER as u32).to_le_bytes());
+        put(&mut header, 204, &(PAGE as u32).to_le_bytes());
+        put(&mut header, 208, &1u32.to_le_bytes());
+
+        // Row: PNR (10 bytes), FOED_DAG (8 bytes), VALUE (8 bytes)
+        let row_length = 26;
+        let mut page = vec![0u8; PAGE];
+        let mut subheaders: Vec<(Vec<u8>, u8, u8)> = Vec::new();
+
+        let mut row_size = vec![0u8; 128];
+        put(&mut row_size, 0, &[0xF7; 4]);
+        put64(&mut row_size, 40, row_length);
+        put64(&mut row_size, 48, 2);
+        put64(&mut row_size, 120, 0);
+        subheaders.push((row_size, 0, 0));
+
+        let text = b"\0\0\0\0SASYZCRL    PNRFOED_DAGVALUEDDMMYY";
+        let mut column_text = vec![0u8; 8 + text.len()];
+        put(&mut column_text, 0, &(-3i64).to_le_bytes());
+        put(&mut column_text, 8, text);
+        put16(&mut column_text, 8, text.len() as u16);
+        subheaders.push((column_text, 0, 0));
+
+        let names = [(16u16, 3u16), (19, 8), (27, 5)];
+        let mut column_name = vec![0u8; 8 + 8 + names.len() * 8 + 12];
+        put(&mut column_name, 0, &(-1i64).to_le_bytes());
+        for (i, (offset, len)) in names.iter().enumerate() {
+            let base = 8 + 8 * (i + 1);
+            put16(&mut column_name, base + 2, *offset);
+            put16(&mut column_name, base + 4, *len);
+        }
+        subheaders.push((column_name, 0, 0));
+
+        let attributes = [(0u64, 10u32, 2u8), (10, 8, 1), (18, 8, 1)];
+        let mut column_attributes = vec![0u8; 16 + attributes.len() * 16 + 12];
+        put(&mut column_attributes, 0, &(-4i64).to_le_bytes());
+        for (i, (offset, len, kind)) in attributes.iter().enumerate() {
+            let base = i * 16;
+            put64(&mut column_attributes, base + 16, *offset);
+            put(&mut column_attributes, base + 24, &len.to_le_bytes());
+            column_attributes[base + 30] = *kind;
+        }
+        subheaders.push((column_attributes, 0, 0));
+
+        for format in [(0u16, 0u16), (32, 6), (0, 0)] {
+            let mut format_subheader = vec![0u8; 64];
+            put(&mut format_subheader, 0, &(-1026i64).to_le_bytes());
+            put16(&mut format_subheader, 24 + 22 + 2, format.0);
+            put16(&mut format_subheader, 24 + 22 + 4, format.1);
+            subheaders.push((format_subheader, 0, 0));
+        }
+
+        let row = |pnr: &[u8], days: f64, value: f64| {
+            let mut row = pnr.to_vec();
+            row.extend_from_slice(&days.to_le_bytes());
+            row.extend_from_slice(&value.to_le_bytes());
+            row
+        };
+        // 2020-01-15 is 21929 days after 1960-01-01
+        subheaders.push((row(b"0101801234", 21929.0, 1.5), 0, 1));
+        // The second row RLE compressed: 10 literal bytes, then 16 literal bytes
+        let second = row(b"0202801234", f64::NAN, 2.0);
+        let mut compressed = vec![0x89];
+        compressed.extend_from_slice(&second[..10]);
+        compressed.push(0x8F);
+        compressed.extend_from_slice(&second[10..]);
+        subheaders.push((compressed, SUBHEADER_COMPRESSED, 1));
+
+        let bit_offset = 32;
+        put(&mut page, bit_offset, &0i16.to_le_bytes());
+        put16(&mut page, bit_offset + 4, subheaders.len() as u16);
+        let mut end = PAGE;
+        for (i, (data, compression, kind)) in subheaders.iter().enumerate() {
+            end -= data.len();
+            put(&mut page, end, data);
+            let pointer = bit_offset + 8 + i * 24;
+            put64(&mut page, pointer, end as u64);
+            put64(&mut page, pointer + 8, data.len() as u64);
+            page[pointer + 16] = *compression;
+            page[pointer + 17] = *kind;
+        }
+
+        let mut file = header;
+        file.extend_from_slice(&page);
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_read_compressed_dataset() {
+        let dir = crate::test_utils::temp_dir();
+        let path = dir.path().join("test.sas7bdat");
+        write_test_dataset(&path);
+
+        let reader = Sas7bdatReader::open(&path, 1024).unwrap();
+        assert_eq!(reader.row_count(), 2);
+        let kinds: Vec<SasColumnKind> = reader.columns().iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SasColumnKind::Text,
+                SasColumnKind::Date,
+                SasColumnKind::Number
+            ]
+        );
+
+        let batches: Vec<RecordBatch> = reader.collect::<Result<_>>().unwrap();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        let pnrs = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(pnrs.value(1), "0202801234");
+        let dates = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(
+            dates.value_as_date(0),
+            chrono::NaiveDate::from_ymd_opt(2020, 1, 15)
+        );
+        assert!(dates.is_null(1));
+        let values = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(values.value(1), 2.0);
+    }
+
+    #[test]
+    fn test_rdc_decompress() {
+        assert_eq!(
+            rdc_decompress(&[0x40, 0x00, b'a', 0x00, b'b'], 4).unwrap(),
+            b"abbb"
+        );
+    }
+}
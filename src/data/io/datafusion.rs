@@ -14,7 +14,7 @@ use std::sync::Arc;
     let config = SessionConfig::new()
         .with_target_partitions(4) // Reasonable default without depending on num_cpus
         .with_batch_size(8192);
-    SessionContext::new_with_config(config)
+    super::execution::session_context_with_config(config)
 }
 
 /// Register a parquet file or directory with the session context
@@ -44,35 +44,9 @@ pub async fn register_parquet(
     Ok(())
 }
 
-/// Apply a PNR filter to a `DataFrame`
+/// Apply a PNR filter to a `DataFrame` as a semi-join against the PNR set
 pub fn apply_pnr_filter(df: DataFrame, pnr_filter: &PnrFilter) -> Result<DataFrame> {
-    if pnr_filter.is_direct_filter() {
-        // Convert HashSet to a list of literals for IN expression
-        let pnr_list: Vec<Expr> = pnr_filter
-            .pnrs()
-            .iter()
-            .map(|pnr| lit(pnr.clone()))
-            .collect();
-
-        if !pnr_list.is_empty() {
-            // Create filter: PNR IN (pnr1, pnr2, ...)
-            return Ok(df.filter(col("PNR").in_list(pnr_list, false))?);
-        }
-    } else if let Some(relation_col) = pnr_filter.relation_column() {
-        // Similar approach for relation filtering
-        let pnr_list: Vec<Expr> = pnr_filter
-            .pnrs()
-            .iter()
-            .map(|pnr| lit(pnr.clone()))
-            .collect();
-
-        if !pnr_list.is_empty() {
-            // Create filter: relation_col IN (pnr1, pnr2, ...)
-            return Ok(df.filter(col(relation_col).in_list(pnr_list, false))?);
-        }
-    }
-
-    Ok(df)
+    pnr_filter.apply_to_dataframe(df, "PNR")
 }
 
 /// Convert `RecordBatches` to `DataFrame`
@@ -17,7 +17,7 @@ use std::sync::Arc;
     let config = SessionConfig::new()
         .with_target_partitions(4)
         .with_batch_size(8192);
-    SessionContext::new_with_config(config)
+    super::execution::session_context_with_config(config)
 }
 
 /// Create a `ListingTable` from a directory of parquet files
@@ -0,0 +1,186 @@
+//! Memory-bounded query execution
+//!
+//! The full registers do not fit in memory on the analysis servers. [`ExecutionConfig`]
+//! sets a memory limit for `DataFusion` plans, which spill sorts, aggregations and joins
+//! to the spill directory when they reach it, and determines how many partitions the
+//! out-of-core pipelines split their input into (see [`super::spill::SpillPartitions`]).
+//!
+//! The configuration is process-wide: the CLI sets it once with [`set_execution_config`]
+//! and every context created with [`session_context`] uses it.
+
+use datafusion::execution::memory_pool::FairSpillPool;
+use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+use datafusion::prelude::{SessionConfig, SessionContext};
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::error::{IdsError, Result};
+
+/// Approximate size of Arrow data in memory relative to its Parquet file size
+const IN_MEMORY_EXPANSION: u64 = 4;
+
+/// Upper bound on the number of partitions of an out-of-core pipeline
+const MAX_PARTITIONS: usize = 1024;
+
+/// Memory limit and spill location for query execution
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionConfig {
+    /// Memory limit in bytes; unlimited if `None`
+    pub memory_limit: Option<usize>,
+    /// Directory for spill files; the system temporary directory if `None`
+    pub spill_dir: Option<PathBuf>,
+}
+
+impl ExecutionConfig {
+    /// Directory spill files are written to
+    #[must_use]
+    pub fn spill_dir(&self) -> PathBuf {
+        self.spill_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// Number of partitions for an input of `input_bytes` on disk
+    ///
+    /// Chosen so that one partition takes at most half the memory limit once loaded;
+    /// always 1 without a limit.
+    #[must_use]
+    pub fn partitions_for(&self, input_bytes: u64) -> usize {
+        let Some(limit) = self.memory_limit else {
+            return 1;
+        };
+        let budget = (limit as u64 / 2).max(1);
+        let partitions = input_bytes
+            .saturating_mul(IN_MEMORY_EXPANSION)
+            .div_ceil(budget);
+        usize::try_from(partitions)
+            .unwrap_or(MAX_PARTITIONS)
+            .clamp(1, MAX_PARTITIONS)
+    }
+
+    /// Bytes of rows a spilling operator may buffer per partition before writing them out
+    #[must_use]
+    pub fn buffer_per_partition(&self, partitions: usize) -> usize {
+        const MIN_BUFFER: usize = 1 << 20;
+        const MAX_BUFFER: usize = 64 << 20;
+        self.memory_limit.map_or(MAX_BUFFER, |limit| {
+            (limit / 4 / partitions.max(1)).clamp(MIN_BUFFER, MAX_BUFFER)
+        })
+    }
+}
+
+static EXECUTION_CONFIG: Lazy<RwLock<ExecutionConfig>> =
+    Lazy::new(|| RwLock::new(ExecutionConfig::default()));
+
+/// Set the process-wide execution configuration, creating the spill directory
+pub fn set_execution_config(config: ExecutionConfig) -> Result<()> {
+    if let Some(dir) = &config.spill_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    if let Some(limit) = config.memory_limit {
+        log::info!(
+            "Query execution limited to {limit} bytes, spilling to {}",
+            config.spill_dir().display()
+        );
+    }
+    *EXECUTION_CONFIG
+        .write()
+        .map_err(|_| IdsError::Data("Execution configuration lock poisoned".to_string()))? = config;
+    Ok(())
+}
+
+/// The process-wide execution configuration
+#[must_use]
+pub fn execution_config() -> ExecutionConfig {
+    EXECUTION_CONFIG
+        .read()
+        .map(|config| config.clone())
+        .unwrap_or_default()
+}
+
+/// Create a session context with the process-wide memory limit and spill directory
+#[must_use]
+pub fn session_context() -> SessionContext {
+    session_context_with_config(SessionConfig::new())
+}
+
+/// Create a session context with `config` and the process-wide memory limit
+#[must_use]
+pub fn session_context_with_config(config: SessionConfig) -> SessionContext {
+    let execution = execution_config();
+    if execution == ExecutionConfig::default() {
+        return SessionContext::new_with_config(config);
+    }
+
+    let mut runtime = RuntimeEnvBuilder::new();
+    if let Some(limit) = execution.memory_limit {
+        runtime = runtime.with_memory_pool(Arc::new(FairSpillPool::new(limit)));
+    }
+    if let Some(dir) = &execution.spill_dir {
+        runtime = runtime.with_temp_file_path(dir);
+    }
+    match runtime.build_arc() {
+        Ok(runtime) => SessionContext::new_with_config_rt(config, runtime),
+        Err(e) => {
+            log::warn!("Failed to apply the execution memory limit: {e}");
+            SessionContext::new_with_config(config)
+        }
+    }
+}
+
+/// Parse a memory size such as `512M`, `16G`, `1.5GB` or `1048576` into bytes
+///
+/// Units are binary: `K` is 1024 bytes.
+pub fn parse_memory_size(value: &str) -> Result<usize> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = || IdsError::Validation(format!("Invalid memory size: {value}"));
+
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let shift = match unit
+        .trim()
+        .to_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(invalid()),
+    };
+    let bytes = number * (1u64 << shift) as f64;
+    if !bytes.is_finite() || bytes < 1.0 || bytes > usize::MAX as f64 {
+        return Err(invalid());
+    }
+    Ok(bytes as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_size_and_partitions() {
+        assert_eq!(parse_memory_size("1048576").unwrap(), 1 << 20);
+        assert_eq!(parse_memory_size("512M").unwrap(), 512 << 20);
+        assert_eq!(parse_memory_size("16gb").unwrap(), 16 << 30);
+        assert_eq!(parse_memory_size("1.5GiB").unwrap(), 3 << 29);
+        assert!(parse_memory_size("lots").is_err());
+        assert!(parse_memory_size("0").is_err());
+
+        let unlimited = ExecutionConfig::default();
+        assert_eq!(unlimited.partitions_for(u64::MAX), 1);
+
+        let limited = ExecutionConfig {
+            memory_limit: Some(1 << 30),
+            spill_dir: None,
+        };
+        assert_eq!(limited.partitions_for(0), 1);
+        assert_eq!(limited.partitions_for(1 << 30), 8);
+        assert_eq!(limited.partitions_for(u64::MAX), MAX_PARTITIONS);
+    }
+}
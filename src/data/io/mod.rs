@@ -5,9 +5,14 @@
 
 pub mod async_utils;
 pub mod base_provider;
+pub mod convert;
 pub mod datafusion;
 pub mod datafusion_utils;
+pub mod execution;
 pub mod parquet;
+pub mod partitioned;
+pub mod rewrite;
+pub mod spill;
 
 // // Legacy modules - these are now deprecated
 // // New code should use the modules in src/data/filter and src/data/pruning
@@ -34,6 +39,16 @@ pub use datafusion_utils::filter_by_pnrs;
 pub use datafusion_utils::register_listing_table;
 pub use datafusion_utils::register_tables_from_directories;
 pub use parquet::*;
+pub use execution::{
+    execution_config, parse_memory_size, session_context, set_execution_config, ExecutionConfig,
+};
+pub use spill::SpillPartitions;
+pub use convert::{convert_registry, ConvertOptions, ConvertSummary, InputFormat};
+pub use partitioned::{
+    hive_partition_columns, read_partitioned_files, write_batches, write_parquet,
+    ParquetWriteOptions, ParquetWriteSummary, Partitioning,
+};
+pub use rewrite::{rewrite_parquet, RewriteOptions, RewriteSummary};
 
 // Re-export from the new modules to maintain backward compatibility
 pub use crate::data::filter::PnrFilter;
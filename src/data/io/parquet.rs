@@ -10,13 +10,14 @@ use datafusion::execution::context::{SessionConfig, SessionContext};
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::*;
 
-use rand::random;
-
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use super::datafusion::create_optimized_context;
+use super::partitioned::{write_batches, ParquetWriteOptions};
+use crate::data::filter::pnr::semi_join_values;
+use crate::data::pruning::{read_parquet_for_pnrs, PnrPruningReport};
 use crate::data::PnrFilter;
 use crate::utils::path_utils::resolve_path;
 
@@ -41,6 +42,8 @@ pub struct ParquetReadConfig {
     pub partitions: usize,
     /// Optional filter expression to push down
     pub filter: Option<Expr>,
+    /// Optional PNR filter (column and PNRs), applied as a semi-join
+    pub pnr_filter: Option<(String, HashSet<String>)>,
     /// Optional projection (list of column indices)
     pub projection: Option<Vec<usize>>,
     /// Optional limit on number of records
@@ -59,6 +62,7 @@ impl Default for ParquetReadConfig {
             pruning_column: "PNR".to_string(),
             partitions: 4,
             filter: None,
+            pnr_filter: None,
             projection: None,
             limit: None,
         }
@@ -141,6 +145,29 @@ impl ParquetReader {
         self
     }
 
+    /// Keep only rows of the PNRs in a filter
+    ///
+    /// The PNRs are applied as a semi-join; their range is set as the filter expression.
+    #[must_use]
+    pub fn pnr_filter(mut self, pnr_filter: &PnrFilter) -> Self {
+        if let Some(column) = pnr_filter.filter_column() {
+            self.config.filter = pnr_filter.to_pruning_expr();
+            self.config.pnr_filter = Some((column.to_string(), pnr_filter.pnrs().clone()));
+        }
+        self
+    }
+
+    /// Apply the filter expression and the PNR filter to a `DataFrame`
+    fn apply_filters(&self, mut df: DataFrame) -> Result<DataFrame> {
+        if let Some(filter) = &self.config.filter {
+            df = df.filter(filter.clone())?;
+        }
+        if let Some((column, pnrs)) = &self.config.pnr_filter {
+            df = semi_join_values(df, pnrs, column)?;
+        }
+        Ok(df)
+    }
+
     /// Set projection (list of column indices)
     #[must_use]
     pub fn projection(mut self, projection: Vec<usize>) -> Self {
@@ -166,7 +193,7 @@ impl ParquetReader {
                 config = config.with_target_partitions(1);
             }
 
-            let ctx = SessionContext::new_with_config(config);
+            let ctx = crate::data::io::execution::session_context_with_config(config);
             self.session_context = Some(ctx);
         }
 
@@ -350,6 +377,20 @@ impl ParquetReader {
 
         log::debug!("Reading parquet from absolute path: {}", abs_path.display());
 
+        // PNR lookups without other options are read with bloom filter and page pruning
+        if let Some((column, pnrs)) = &self.config.pnr_filter {
+            if self.config.enable_pruning
+                && self.config.schema.is_none()
+                && self.config.projection.is_none()
+                && self.config.limit.is_none()
+            {
+                match read_pnr_pruned(&abs_path, column, pnrs) {
+                    Ok(batches) => return Ok(batches),
+                    Err(e) => log::debug!("PNR pruning not possible, scanning instead: {e}"),
+                }
+            }
+        }
+
         // If filter is set, we'll use the execution plan approach
         if self.config.filter.is_some()
             || self.config.enable_pruning
@@ -366,12 +407,11 @@ impl ParquetReader {
 
             // Apply filter if provided after collecting results - this is inefficient
             // but a proper implementation would incorporate the filter into the logical plan
-            if let Some(filter) = &self.config.filter {
+            if self.config.filter.is_some() || self.config.pnr_filter.is_some() {
                 // This is a simplified approach - in a real implementation,
                 // you would incorporate the filter into the logical plan before creating the physical plan
                 let df = ctx.read_batches(results.clone())?;
-                let filtered_df = df.filter(filter.clone())?;
-                Ok(filtered_df.collect().await?)
+                Ok(self.apply_filters(df)?.collect().await?)
             } else {
                 Ok(results)
             }
@@ -402,9 +442,10 @@ impl ParquetReader {
         &mut self,
         pnr_filter: &PnrFilter,
     ) -> Result<Vec<RecordBatch>> {
-        // Clone the PnrFilter to an Expr and set it in config
-        if let Some(expr) = pnr_filter.to_expr() {
-            self.config.filter = Some(expr);
+        // Set the PNR range as the filter expression and the PNRs as a semi-join
+        if let Some(column) = pnr_filter.filter_column() {
+            self.config.filter = pnr_filter.to_pruning_expr();
+            self.config.pnr_filter = Some((column.to_string(), pnr_filter.pnrs().clone()));
         }
 
         // Use read_async with the filter
@@ -426,12 +467,8 @@ impl ParquetReader {
         let results = datafusion::physical_plan::collect(physical_plan.clone(), task_ctx).await?;
         let df = ctx.read_batches(results)?;
 
-        // Apply filter if provided
-        if let Some(filter) = &self.config.filter {
-            Ok(df.filter(filter.clone())?)
-        } else {
-            Ok(df)
-        }
+        // Apply filters if provided
+        self.apply_filters(df)
     }
 
     /// List all parquet files in a directory with enhanced logging
@@ -535,6 +572,37 @@ impl ParquetReader {
     }
 }
 
+/// Read the rows of a PNR set from a file or directory with bloom filter and page pruning
+fn read_pnr_pruned(path: &Path, column: &str, pnrs: &HashSet<String>) -> Result<Vec<RecordBatch>> {
+    let files = if path.is_dir() {
+        ParquetReader::list_parquet_files(path)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut batches = Vec::new();
+    let mut report = PnrPruningReport::default();
+    for file in &files {
+        let (file_batches, file_report) = read_parquet_for_pnrs(file, column, pnrs, None)?;
+        batches.extend(file_batches);
+        report.row_groups += file_report.row_groups;
+        report.row_groups_read += file_report.row_groups_read;
+        report.rows += file_report.rows;
+        report.rows_read += file_report.rows_read;
+    }
+    log::info!(
+        "Read {} of {} row groups and {} of {} rows from {} files for {} PNRs",
+        report.row_groups_read,
+        report.row_groups,
+        report.rows_read,
+        report.rows,
+        files.len(),
+        pnrs.len()
+    );
+
+    Ok(batches)
+}
+
 /// Load parquet files from a directory using `DataFusion` with pruning optimization
 pub async fn load_parquet_directory(
     dir_path: impl AsRef<Path>,
@@ -556,9 +624,7 @@ pub async fn load_parquet_directory(
 
     // Set filter if provided
     if let Some(filter) = pnr_filter {
-        if let Some(expr) = filter.to_expr() {
-            reader = reader.filter(expr);
-        }
+        reader = reader.pnr_filter(filter);
     }
 
     // Enable pruning for better performance
@@ -606,13 +672,12 @@ pub async fn register_parquet_as_table(
     let mut df = ctx.table(table_name).await?;
 
     // Apply filter if provided
-    if let Some(filter) = pnr_filter {
-        if let Some(expr) = filter.to_expr() {
-            df = df.filter(expr)?;
+    if let Some(filter) = pnr_filter.filter(|filter| !filter.is_empty()) {
+        df = filter.apply_to_dataframe(df)?;
 
-            // Update the table with the filtered data
-            ctx.register_table(table_name, df.clone().into_view())?;
-        }
+        // Update the table with the filtered data
+        ctx.deregister_table(table_name)?;
+        ctx.register_table(table_name, df.clone().into_view())?;
     }
 
     Ok(df)
@@ -647,74 +712,86 @@ pub fn register_batches(
     Ok(())
 }
 
-/// Save record batches to parquet file with optimized settings
+/// Save record batches to a parquet file with the default [`ParquetWriteOptions`]
+///
+/// Use [`super::partitioned::write_batches`] for partitioned or PNR-sorted output.
 pub async fn save_batches_to_parquet(
     batches: &[RecordBatch],
     output_path: impl AsRef<Path>,
 ) -> Result<()> {
-    if batches.is_empty() {
-        return Err(IdsError::Validation(
-            "Cannot save empty batches".to_string(),
-        ));
-    }
-
-    // Create a session context
-    let ctx = create_optimized_context();
-
-    // Create a random table name
-    let table_name = format!("temp_table_{}", random::<u64>());
-
-    // Register the batches
-    register_batches(&ctx, &table_name, batches)?;
-
-    // Get the table as a DataFrame
-    let df = ctx.table(&table_name).await?;
-
-    // Get absolute path for the output
-    let abs_path = crate::utils::path_utils::resolve_path(&output_path)?;
-    let path_str = abs_path.to_string_lossy().to_string();
-    log::debug!("Writing parquet batches to absolute path: {}", path_str);
-
-    // Write to parquet with optimized settings
-    df.write_parquet(
-        &path_str,
-        datafusion::dataframe::DataFrameWriteOptions::default(),
-        Some(datafusion::common::config::TableParquetOptions::new()),
-    )
-    .await?;
-
+    write_batches(batches, output_path, &ParquetWriteOptions::default()).await?;
     Ok(())
 }
 
-/// Save a single record batch to parquet file with optimized settings
+/// Save a single record batch to a parquet file with the default [`ParquetWriteOptions`]
 pub async fn save_batch_to_parquet(
     batch: &RecordBatch,
     output_path: impl AsRef<Path>,
 ) -> Result<()> {
-    // Create a session context
-    let ctx = create_optimized_context();
+    save_batches_to_parquet(std::slice::from_ref(batch), output_path).await
+}
 
-    // Create a random table name
-    let table_name = format!("temp_table_{}", random::<u64>());
+/// Parquet file written incrementally, one record batch at a time
+///
+/// Unlike [`save_batches_to_parquet`], which needs all batches up front, this keeps only
+/// the current row group in memory.
+pub struct ParquetBatchWriter {
+    path: PathBuf,
+    writer: datafusion::parquet::arrow::ArrowWriter<fs::File>,
+    rows: usize,
+}
 
-    // Register the batch
-    ctx.register_batch(&table_name, batch.clone())?;
+impl ParquetBatchWriter {
+    /// Create the output file with the default [`ParquetWriteOptions`], replacing any
+    /// existing file
+    pub fn create(output_path: impl AsRef<Path>, schema: SchemaRef) -> Result<Self> {
+        Self::create_with_options(output_path, schema, &ParquetWriteOptions::default())
+    }
 
-    // Get the table as a DataFrame
-    let df = ctx.table(&table_name).await?;
+    /// Create the output file with the writer settings of `options`
+    ///
+    /// Batches are written in the order given; sorting and partitioning are not applied.
+    pub fn create_with_options(
+        output_path: impl AsRef<Path>,
+        schema: SchemaRef,
+        options: &ParquetWriteOptions,
+    ) -> Result<Self> {
+        let path = resolve_path(&output_path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let properties = options.writer_properties(&schema)?;
+        let writer = datafusion::parquet::arrow::ArrowWriter::try_new(
+            fs::File::create(&path)?,
+            schema,
+            Some(properties),
+        )?;
+        Ok(Self {
+            path,
+            writer,
+            rows: 0,
+        })
+    }
 
-    // Get absolute path for the output
-    let abs_path = crate::utils::path_utils::resolve_path(&output_path)?;
-    let path_str = abs_path.to_string_lossy().to_string();
-    log::debug!("Writing parquet to absolute path: {}", path_str);
+    /// Append a batch
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() > 0 {
+            self.writer.write(batch)?;
+            self.rows += batch.num_rows();
+        }
+        Ok(())
+    }
 
-    // Write to parquet with optimized settings
-    df.write_parquet(
-        &path_str,
-        datafusion::dataframe::DataFrameWriteOptions::default(),
-        Some(datafusion::common::config::TableParquetOptions::new()),
-    )
-    .await?;
+    /// Number of rows written so far
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
 
-    Ok(())
+    /// Close the file and return its path
+    pub fn finish(self) -> Result<PathBuf> {
+        self.writer.close()?;
+        log::debug!("Wrote {} rows to {}", self.rows, self.path.display());
+        Ok(self.path)
+    }
 }
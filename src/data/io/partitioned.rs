@@ -0,0 +1,879 @@
+//! Hive-partitioned Parquet output with tuned writer settings
+//!
+//! [`write_parquet`] writes a `DataFrame` as a single file or as a Hive-style partitioned
+//! directory with one subdirectory per year or birth cohort of a date column:
+//!
+//! ```text
+//! output/year=2017/part-0.parquet
+//! output/year=2018/part-0.parquet
+//! output/year=__HIVE_DEFAULT_PARTITION__/part-0.parquet
+//! ```
+//!
+//! [`ParquetWriteOptions`] sets the compression, row group size, dictionary encoding,
+//! statistics and bloom filters, and can sort each file by PNR so that
+//! [`crate::data::pruning::read_parquet_for_pnrs`] skips most row groups.
+//!
+//! Registry loaders read partitioned directories as `DataFusion` listing tables with the
+//! partition keys as partition columns ([`hive_partition_columns`]), so a filter on a
+//! partition column prunes whole partitions before their files are opened.
+
+use arrow::array::{RecordBatch, UInt32Array};
+use arrow::compute::take_record_batch;
+use arrow::datatypes::{DataType, Schema};
+use datafusion::common::{Column, ScalarValue};
+use datafusion::parquet::arrow::ArrowWriter;
+use datafusion::parquet::basic::{Compression, ZstdLevel};
+use datafusion::parquet::file::properties::{EnabledStatistics, WriterProperties};
+use datafusion::parquet::format::SortingColumn;
+use datafusion::parquet::schema::types::ColumnPath;
+use datafusion::prelude::*;
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::convert::row_years;
+use super::execution::{execution_config, session_context};
+use super::parquet::{batches_to_table, ParquetBatchWriter};
+use crate::error::{IdsError, Result};
+use crate::utils::path_utils::resolve_path;
+
+/// PNR column names tried when no column is configured
+const PNR_COLUMNS: [&str; 2] = ["PNR", "CPR"];
+
+/// Directory value of rows without a partition value
+pub const DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// How to split output into Hive-style directories
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Partitioning {
+    /// A single file
+    #[default]
+    None,
+    /// One directory per calendar year of a date column (`year=2018`)
+    Year {
+        /// Date or integer year column
+        column: String,
+    },
+    /// One directory per birth cohort of `width` years, named by its first year
+    /// (`birth_cohort=2005`)
+    BirthCohort {
+        /// Birth date column
+        column: String,
+        /// Years per cohort
+        width: u32,
+    },
+}
+
+impl Partitioning {
+    /// Directory key of the partitions, `None` if not partitioned
+    #[must_use]
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Year { .. } => Some("year"),
+            Self::BirthCohort { .. } => Some("birth_cohort"),
+        }
+    }
+
+    /// Partition of each row of a batch, `None` for rows without a date
+    fn row_values(&self, batch: &RecordBatch) -> Result<Vec<Option<i32>>> {
+        let (column, width) = match self {
+            Self::None => return Ok(vec![None; batch.num_rows()]),
+            Self::Year { column } => (column, 1),
+            Self::BirthCohort { column, width } => (column, i32::try_from(*width).unwrap_or(1)),
+        };
+        let array = batch
+            .column_by_name(column)
+            .ok_or_else(|| IdsError::Validation(format!("Partition column {column} not found")))?;
+        Ok(row_years(array)?
+            .into_iter()
+            .map(|year| year.map(|year| year - year.rem_euclid(width.max(1))))
+            .collect())
+    }
+}
+
+impl FromStr for Partitioning {
+    type Err = IdsError;
+
+    /// Parse `none`, `year:COLUMN` or `cohort:COLUMN[:YEARS]`
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            ["none"] => Ok(Self::None),
+            ["year", column] => Ok(Self::Year {
+                column: (*column).to_string(),
+            }),
+            ["cohort", column] => Ok(Self::BirthCohort {
+                column: (*column).to_string(),
+                width: 1,
+            }),
+            ["cohort", column, width] => match width.parse::<u32>() {
+                Ok(width) if width > 0 => Ok(Self::BirthCohort {
+                    column: (*column).to_string(),
+                    width,
+                }),
+                _ => Err(IdsError::Validation(format!(
+                    "Invalid cohort width: {width}"
+                ))),
+            },
+            _ => Err(IdsError::Validation(format!(
+                "Invalid partitioning {s}: expected none, year:COLUMN or cohort:COLUMN[:YEARS]"
+            ))),
+        }
+    }
+}
+
+/// Options for writing Parquet output
+#[derive(Debug, Clone)]
+pub struct ParquetWriteOptions {
+    /// Compression codec
+    pub compression: Compression,
+    /// Maximum rows per row group
+    pub row_group_size: usize,
+    /// Maximum rows per data page (the writer default if not set)
+    pub page_row_limit: Option<usize>,
+    /// Whether to dictionary-encode columns
+    pub dictionary: bool,
+    /// Level of min/max statistics; `Page` also writes the page index
+    pub statistics: EnabledStatistics,
+    /// Columns with bloom filters (the PNR column if not set)
+    pub bloom_filter_columns: Option<Vec<String>>,
+    /// False positive probability of the bloom filters
+    pub bloom_filter_fpp: f64,
+    /// Whether to sort the rows of each file by PNR
+    pub sort_by_pnr: bool,
+    /// PNR column (PNR or CPR if not set)
+    pub pnr_column: Option<String>,
+    /// How to split the output into directories
+    pub partitioning: Partitioning,
+}
+
+impl Default for ParquetWriteOptions {
+    fn default() -> Self {
+        Self {
+            compression: Compression::ZSTD(ZstdLevel::default()),
+            row_group_size: 128 * 1024,
+            page_row_limit: None,
+            dictionary: true,
+            statistics: EnabledStatistics::Page,
+            bloom_filter_columns: None,
+            bloom_filter_fpp: 0.01,
+            sort_by_pnr: false,
+            pnr_column: None,
+            partitioning: Partitioning::None,
+        }
+    }
+}
+
+impl ParquetWriteOptions {
+    /// The PNR column of a schema: the configured column, or PNR or CPR
+    #[must_use]
+    pub fn pnr_column(&self, schema: &Schema) -> Option<String> {
+        match &self.pnr_column {
+            Some(column) => schema.index_of(column).is_ok().then(|| column.clone()),
+            None => PNR_COLUMNS
+                .into_iter()
+                .find(|column| schema.index_of(column).is_ok())
+                .map(String::from),
+        }
+    }
+
+    /// Writer properties for files with `schema`
+    pub fn writer_properties(&self, schema: &Schema) -> Result<WriterProperties> {
+        let mut builder = WriterProperties::builder()
+            .set_compression(self.compression)
+            .set_max_row_group_size(self.row_group_size.max(1))
+            .set_dictionary_enabled(self.dictionary)
+            .set_statistics_enabled(self.statistics);
+        if let Some(limit) = self.page_row_limit {
+            builder = builder
+                .set_data_page_row_count_limit(limit)
+                // The page row limit is only checked between write batches
+                .set_write_batch_size(limit.clamp(1, 1024));
+        }
+
+        let pnr_column = self.pnr_column(schema);
+        let bloom_columns = match &self.bloom_filter_columns {
+            Some(columns) => columns.clone(),
+            None => pnr_column.iter().cloned().collect(),
+        };
+        for column in bloom_columns {
+            if schema.index_of(&column).is_err() {
+                log::warn!("No column {column} for a bloom filter");
+                continue;
+            }
+            let path = ColumnPath::from(column.as_str());
+            builder = builder
+                .set_column_bloom_filter_enabled(path.clone(), true)
+                .set_column_bloom_filter_fpp(path.clone(), self.bloom_filter_fpp)
+                .set_column_bloom_filter_ndv(path, self.row_group_size as u64);
+        }
+
+        if let Some(pnr_column) = pnr_column.filter(|_| self.sort_by_pnr) {
+            builder = builder.set_sorting_columns(Some(vec![SortingColumn {
+                column_idx: i32::try_from(schema.index_of(&pnr_column)?)
+                    .map_err(|_| IdsError::Validation("Too many columns".to_string()))?,
+                descending: false,
+                nulls_first: false,
+            }]));
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Files and rows written by [`write_parquet`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParquetWriteSummary {
+    /// Files written
+    pub files: Vec<PathBuf>,
+    /// Rows written
+    pub rows: usize,
+    /// Row groups written
+    pub row_groups: usize,
+}
+
+/// Directory name of a partition, e.g. `year=2018`
+fn partition_dir(key: &str, value: Option<i32>) -> String {
+    match value {
+        Some(value) => format!("{key}={value}"),
+        None => format!("{key}={DEFAULT_PARTITION}"),
+    }
+}
+
+/// A staging directory, removed with its files when dropped
+struct StagingDir(PathBuf);
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if self.0.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&self.0) {
+                log::warn!("Could not remove {}: {e}", self.0.display());
+            }
+        }
+    }
+}
+
+/// The partition directories (`key=value`) of `key` directly below `dir`
+fn partition_dirs(dir: &Path, key: &str) -> Result<Vec<PathBuf>> {
+    let prefix = format!("{key}=");
+    let mut dirs = Vec::new();
+    if !dir.is_dir() {
+        return Ok(dirs);
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let is_partition = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(&prefix));
+        if is_partition && entry.file_type()?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Move every directory of `dirs` into `target`, recording the moved names in `moved`
+fn move_dirs(dirs: &[PathBuf], target: &Path, moved: &mut Vec<PathBuf>) -> Result<()> {
+    for dir in dirs {
+        let name = dir
+            .file_name()
+            .ok_or_else(|| IdsError::Validation(format!("Invalid partition {}", dir.display())))?;
+        std::fs::rename(dir, target.join(name))?;
+        moved.push(PathBuf::from(name));
+    }
+    Ok(())
+}
+
+/// Replace the `key` partitions below `output` with those below `staged`
+///
+/// The previous partitions are moved aside, the new ones renamed into place and the
+/// previous ones removed only then. If a rename fails, the previous partitions are moved
+/// back. Directories of other keys are left alone.
+fn replace_partition_dirs(output: &Path, staged: &Path, key: &str) -> Result<()> {
+    let replaced = StagingDir(output.join(format!(".ids_replaced_{}", uuid::Uuid::new_v4())));
+    std::fs::create_dir(&replaced.0)?;
+
+    let mut moved = Vec::new();
+    let mut installed = Vec::new();
+    let result = move_dirs(&partition_dirs(output, key)?, &replaced.0, &mut moved)
+        .and_then(|()| move_dirs(&partition_dirs(staged, key)?, output, &mut installed));
+    if let Err(e) = result {
+        for name in installed {
+            std::fs::remove_dir_all(output.join(name))?;
+        }
+        for name in moved {
+            std::fs::rename(replaced.0.join(&name), output.join(&name))?;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Write a `DataFrame` to one file, sorted by PNR if configured, returning the rows and
+/// row groups written
+pub(crate) async fn write_file(
+    df: DataFrame,
+    output: &Path,
+    options: &ParquetWriteOptions,
+) -> Result<(usize, usize)> {
+    let schema = df.schema().inner().clone();
+    let properties = options.writer_properties(&schema)?;
+    let df = match options.pnr_column(&schema).filter(|_| options.sort_by_pnr) {
+        Some(pnr_column) => df.sort(vec![
+            Expr::Column(Column::from_name(pnr_column)).sort(true, false)
+        ])?,
+        None => df,
+    };
+    let mut stream = df.execute_stream().await?;
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = ArrowWriter::try_new(File::create(output)?, schema, Some(properties))?;
+    let mut rows = 0;
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        rows += batch.num_rows();
+        writer.write(&batch)?;
+    }
+    let metadata = writer.close()?;
+
+    Ok((rows, metadata.row_groups.len()))
+}
+
+/// Write a `DataFrame` as Parquet
+///
+/// Without partitioning `output` is the output file. Otherwise it is the root directory of
+/// the partitions: rows are first split into one staging file per partition in the spill
+/// directory, then each partition is sorted (if configured) and written to a hidden staging
+/// directory below `output`. Only when every partition is written do they replace the
+/// `<key>=<value>` directories of a previous write, as `output/<key>=<value>/part-0.parquet`;
+/// other directories below `output` are kept. The staging files are removed also when
+/// writing fails, leaving the previous output as it was.
+pub async fn write_parquet(
+    df: DataFrame,
+    output: impl AsRef<Path>,
+    options: &ParquetWriteOptions,
+) -> Result<ParquetWriteSummary> {
+    let output = resolve_path(&output)?;
+    let Some(key) = options.partitioning.key() else {
+        let (rows, row_groups) = write_file(df, &output, options).await?;
+        log::debug!("Wrote {rows} rows to {}", output.display());
+        return Ok(ParquetWriteSummary {
+            files: vec![output],
+            rows,
+            row_groups,
+        });
+    };
+
+    let schema = df.schema().inner().clone();
+    let staging = StagingDir(
+        execution_config()
+            .spill_dir()
+            .join(format!("ids_partitions_{}", uuid::Uuid::new_v4())),
+    );
+    let mut partitions: BTreeMap<Option<i32>, ParquetBatchWriter> = BTreeMap::new();
+    let mut stream = df.execute_stream().await?;
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        let mut rows: BTreeMap<Option<i32>, Vec<u32>> = BTreeMap::new();
+        for (row, value) in options
+            .partitioning
+            .row_values(&batch)?
+            .into_iter()
+            .enumerate()
+        {
+            let row = u32::try_from(row)
+                .map_err(|_| IdsError::Validation("Batch too large".to_string()))?;
+            rows.entry(value).or_default().push(row);
+        }
+        for (value, indices) in rows {
+            let part = take_record_batch(&batch, &UInt32Array::from(indices))?;
+            let writer = match partitions.entry(value) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    let path = staging
+                        .0
+                        .join(format!("{}.parquet", partition_dir(key, value)));
+                    entry.insert(ParquetBatchWriter::create(path, schema.clone())?)
+                }
+            };
+            writer.write(&part)?;
+        }
+    }
+
+    std::fs::create_dir_all(&output)?;
+    let finished = StagingDir(output.join(format!(".ids_staging_{}", uuid::Uuid::new_v4())));
+    std::fs::create_dir(&finished.0)?;
+    let ctx = session_context();
+    let mut summary = ParquetWriteSummary::default();
+    for (value, writer) in partitions {
+        let staged = writer.finish()?;
+        let dir = partition_dir(key, value);
+        let path = finished.0.join(&dir).join("part-0.parquet");
+        let df = ctx
+            .read_parquet(
+                staged.to_string_lossy().to_string(),
+                ParquetReadOptions::default(),
+            )
+            .await?;
+        let (rows, row_groups) = write_file(df, &path, options).await?;
+        std::fs::remove_file(&staged)?;
+        summary.files.push(output.join(dir).join("part-0.parquet"));
+        summary.rows += rows;
+        summary.row_groups += row_groups;
+    }
+    replace_partition_dirs(&output, &finished.0, key)?;
+    log::debug!(
+        "Wrote {} rows in {} partitions to {}",
+        summary.rows,
+        summary.files.len(),
+        output.display()
+    );
+    Ok(summary)
+}
+
+/// Write record batches as Parquet, see [`write_parquet`]
+pub async fn write_batches(
+    batches: &[RecordBatch],
+    output: impl AsRef<Path>,
+    options: &ParquetWriteOptions,
+) -> Result<ParquetWriteSummary> {
+    if batches.is_empty() {
+        return Err(IdsError::Validation(
+            "Cannot save empty batches".to_string(),
+        ));
+    }
+    let df = session_context().read_table(batches_to_table(batches)?)?;
+    write_parquet(df, output, options).await
+}
+
+/// The `key=value` directories between `dir` and a file below it, outermost first
+#[must_use]
+pub fn hive_partition_values(dir: &Path, file: &Path) -> Vec<(String, String)> {
+    let Some(parent) = file.strip_prefix(dir).ok().and_then(Path::parent) else {
+        return Vec::new();
+    };
+    parent
+        .components()
+        .filter_map(|component| {
+            let (key, value) = component.as_os_str().to_str()?.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The files of `all_files` that are in the same partitions as `files`
+#[must_use]
+pub fn partition_files(dir: &Path, all_files: &[PathBuf], files: &[PathBuf]) -> Vec<PathBuf> {
+    let partitions: HashSet<Vec<(String, String)>> = files
+        .iter()
+        .map(|file| hive_partition_values(dir, file))
+        .collect();
+    all_files
+        .iter()
+        .filter(|file| partitions.contains(&hive_partition_values(dir, file)))
+        .cloned()
+        .collect()
+}
+
+/// The partition columns of Hive-partitioned files below `dir`
+///
+/// Empty if the files are not partitioned or their keys differ. A column is `Int32` if
+/// every value is an integer and `Utf8` otherwise.
+#[must_use]
+pub fn hive_partition_columns(dir: &Path, files: &[PathBuf]) -> Vec<(String, DataType)> {
+    let values: Vec<Vec<(String, String)>> = files
+        .iter()
+        .map(|file| hive_partition_values(dir, file))
+        .collect();
+    let Some(first) = values.first() else {
+        return Vec::new();
+    };
+    let keys: Vec<&String> = first.iter().map(|(key, _)| key).collect();
+    if values
+        .iter()
+        .any(|file| !file.iter().map(|(key, _)| key).eq(keys.iter().copied()))
+    {
+        log::debug!("Inconsistent partition keys below {}", dir.display());
+        return Vec::new();
+    }
+
+    keys.iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let integer = values.iter().all(|file| {
+                let value = &file[i].1;
+                value == DEFAULT_PARTITION || value.parse::<i32>().is_ok()
+            });
+            let data_type = if integer {
+                DataType::Int32
+            } else {
+                DataType::Utf8
+            };
+            ((*key).clone(), data_type)
+        })
+        .collect()
+}
+
+/// Add partition columns to a `DataFrame` with the partition values of its rows as literals
+///
+/// Columns without a value (or in the default partition) are null; columns already in the
+/// data are kept as they are.
+pub fn with_partition_values(
+    mut df: DataFrame,
+    values: &[(String, String)],
+    columns: &[(String, DataType)],
+) -> Result<DataFrame> {
+    for (name, data_type) in columns {
+        if df.schema().has_column_with_unqualified_name(name) {
+            continue;
+        }
+        let value = values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .filter(|value| *value != DEFAULT_PARTITION);
+        let value = match value {
+            Some(value) => ScalarValue::try_from_string(value.to_string(), data_type)?,
+            None => ScalarValue::try_from(data_type)?,
+        };
+        df = df.with_column(name, lit(value))?;
+    }
+    Ok(df)
+}
+
+/// Read the partitions of a Hive-partitioned directory that contain `files`, with the
+/// partition values as columns
+///
+/// The directory is read as one listing table with `columns` as its partition columns,
+/// filtered to the partitions of `files`, so `DataFusion` lists and scans only those
+/// partitions, and a filter on a partition column prunes them further. Partitions are
+/// read whole: every file in a partition of `files` is read. Without a schema in
+/// `options`, the schema of `files` is merged first so that the partitions agree.
+///
+/// `DataFusion` does not read the default partition (`key=__HIVE_DEFAULT_PARTITION__`)
+/// as null, so its files are scanned separately with null partition values.
+pub async fn read_partitioned_files(
+    ctx: &SessionContext,
+    dir: &Path,
+    files: &[PathBuf],
+    options: ParquetReadOptions<'_>,
+    columns: &[(String, DataType)],
+) -> Result<DataFrame> {
+    let merged;
+    let options = if options.schema.is_none() {
+        let paths: Vec<String> = files
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+        merged = ctx
+            .read_parquet(paths, options.clone())
+            .await?
+            .schema()
+            .inner()
+            .clone();
+        options.schema(merged.as_ref())
+    } else {
+        options
+    };
+
+    let mut partitions: BTreeMap<Vec<(String, String)>, Vec<String>> = BTreeMap::new();
+    for file in files {
+        partitions
+            .entry(hive_partition_values(dir, file))
+            .or_default()
+            .push(file.to_string_lossy().to_string());
+    }
+    let (defaults, valued): (Vec<_>, Vec<_>) = partitions
+        .into_iter()
+        .partition(|(values, _)| values.iter().any(|(_, value)| value == DEFAULT_PARTITION));
+
+    let mut parts = Vec::new();
+    let selection = valued
+        .iter()
+        .map(|(values, _)| {
+            values
+                .iter()
+                .zip(columns)
+                .map(|((_, value), (name, data_type))| {
+                    let value = ScalarValue::try_from_string(value.clone(), data_type)?;
+                    Ok(Expr::Column(Column::from_name(name)).eq(lit(value)))
+                })
+                .reduce(|a: Result<Expr>, b| Ok(a?.and(b?)))
+                .unwrap_or_else(|| Ok(lit(true)))
+        })
+        .reduce(|a, b| Ok(a?.or(b?)));
+    if let Some(selection) = selection {
+        let listing = options.clone().table_partition_cols(columns.to_vec());
+        let df = ctx
+            .read_parquet(dir.to_string_lossy().to_string(), listing)
+            .await?;
+        parts.push(df.filter(selection?)?);
+    }
+    for (values, paths) in defaults {
+        parts.push(with_partition_values(
+            ctx.read_parquet(paths, options.clone()).await?,
+            &values,
+            columns,
+        )?);
+    }
+
+    let mut df: Option<DataFrame> = None;
+    for part in parts {
+        df = Some(match df {
+            Some(df) => df.union(part)?,
+            None => part,
+        });
+    }
+    df.ok_or_else(|| IdsError::Validation(format!("No Parquet files to read in {}", dir.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::pruning::register_indexed_parquet;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::array::{Date32Array, StringArray};
+    use arrow::compute::cast;
+    use arrow::datatypes::Field;
+    use datafusion::physical_plan::displayable;
+    use std::sync::Arc;
+
+    fn bef_batch(pnrs: Vec<&str>, birth_dates: Vec<Option<i32>>) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("PNR", DataType::Utf8, false),
+                Field::new("FOED_DAG", DataType::Date32, true),
+            ])),
+            vec![
+                Arc::new(StringArray::from(pnrs)),
+                Arc::new(Date32Array::from(birth_dates)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn by_birth_year() -> ParquetWriteOptions {
+        ParquetWriteOptions {
+            sort_by_pnr: true,
+            partitioning: "year:FOED_DAG".parse().unwrap(),
+            ..ParquetWriteOptions::default()
+        }
+    }
+
+    /// Write births on 2017-06-01, 2018-01-01, 2018-12-31 and an unknown date
+    fn write_births(dir: &Path) -> ParquetWriteSummary {
+        let batch = bef_batch(
+            vec!["4", "3", "2", "1"],
+            vec![Some(17318), Some(17532), Some(17896), None],
+        );
+        crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(write_batches(&[batch], dir, &by_birth_year()))
+            .unwrap()
+    }
+
+    /// The PNRs of a query on the registry directory registered as `bef`
+    fn query_pnrs(dir: &Path, sql: &str) -> Vec<String> {
+        let runtime = crate::utils::runtime::get_runtime().unwrap();
+        let ctx = SessionContext::new();
+        runtime
+            .block_on(register_indexed_parquet(
+                &ctx,
+                "bef",
+                dir,
+                ParquetReadOptions::default(),
+                None,
+            ))
+            .unwrap();
+        // Scans read strings as views
+        runtime
+            .block_on(async { ctx.sql(sql).await?.collect().await })
+            .unwrap()
+            .iter()
+            .flat_map(|batch| {
+                let pnrs = cast(batch.column(0), &DataType::Utf8).unwrap();
+                let pnrs = pnrs.as_any().downcast_ref::<StringArray>().unwrap();
+                pnrs.iter().flatten().map(String::from).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_partitioned_write() {
+        let dir = temp_dir();
+        let summary = write_births(dir.path());
+        assert_eq!(summary.rows, 4);
+        assert_eq!(summary.files.len(), 3);
+        assert!(dir.path().join("year=2018").join("part-0.parquet").exists());
+        assert!(dir
+            .path()
+            .join(format!("year={DEFAULT_PARTITION}"))
+            .exists());
+    }
+
+    #[test]
+    fn test_partition_filter_prunes_read() {
+        let dir = temp_dir();
+        write_births(dir.path());
+
+        let runtime = crate::utils::runtime::get_runtime().unwrap();
+        let ctx = SessionContext::new();
+        runtime
+            .block_on(register_indexed_parquet(
+                &ctx,
+                "bef",
+                dir.path(),
+                ParquetReadOptions::default(),
+                None,
+            ))
+            .unwrap();
+        let df = runtime
+            .block_on(ctx.sql("SELECT \"PNR\" FROM bef WHERE year = 2018"))
+            .unwrap();
+        let plan = runtime.block_on(df.create_physical_plan()).unwrap();
+        let plan = displayable(plan.as_ref()).indent(true).to_string();
+        assert!(plan.contains("year=2018"));
+        assert!(!plan.contains("year=2017"));
+
+        assert_eq!(
+            query_pnrs(dir.path(), "SELECT \"PNR\" FROM bef WHERE year = 2018"),
+            vec!["2", "3"]
+        );
+    }
+
+    #[test]
+    fn test_default_partition_reads_as_null() {
+        let dir = temp_dir();
+        write_births(dir.path());
+        assert_eq!(
+            query_pnrs(dir.path(), "SELECT \"PNR\" FROM bef WHERE year IS NULL"),
+            vec!["1"]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_replaces_partitions() {
+        let dir = temp_dir();
+        write_births(dir.path());
+
+        let batch = bef_batch(vec!["5"], vec![Some(18000)]);
+        let summary = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(write_batches(&[batch], dir.path(), &by_birth_year()))
+            .unwrap();
+        assert_eq!(
+            summary.files,
+            vec![dir.path().join("year=2019").join("part-0.parquet")]
+        );
+        assert!(!dir.path().join("year=2018").exists());
+        assert!(!dir
+            .path()
+            .join(format!("year={DEFAULT_PARTITION}"))
+            .exists());
+    }
+
+    /// Only the directories of the partition key are replaced
+    #[test]
+    fn test_rewrite_keeps_other_directories() {
+        let dir = temp_dir();
+        write_births(dir.path());
+        std::fs::create_dir_all(dir.path().join("birth_cohort=2018")).unwrap();
+        std::fs::create_dir_all(dir.path().join("notes")).unwrap();
+
+        let batch = bef_batch(vec!["5"], vec![Some(18000)]);
+        crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(write_batches(&[batch], dir.path(), &by_birth_year()))
+            .unwrap();
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["birth_cohort=2018", "notes", "year=2019"]);
+    }
+
+    /// A write that fails leaves the partitions of the previous write in place
+    #[test]
+    fn test_failed_write_keeps_previous_output() {
+        let dir = temp_dir();
+        write_births(dir.path());
+        // A file where the new partition directory goes makes replacing it fail
+        std::fs::write(dir.path().join("year=2019"), b"").unwrap();
+
+        let batch = bef_batch(vec!["5", "6"], vec![Some(17532), Some(18000)]);
+        let result = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(write_batches(&[batch], dir.path(), &by_birth_year()));
+        assert!(result.is_err());
+        assert_eq!(
+            query_pnrs(
+                dir.path().join("year=2018").as_path(),
+                "SELECT \"PNR\" FROM bef"
+            ),
+            vec!["2", "3"]
+        );
+        assert!(dir.path().join("year=2017").join("part-0.parquet").exists());
+        let hidden = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with('.')
+            })
+            .count();
+        assert_eq!(hidden, 0);
+    }
+
+    /// Writing no rows writes no partitions and removes those of a previous write
+    #[test]
+    fn test_empty_write_leaves_no_partitions() {
+        let dir = temp_dir();
+        write_births(dir.path());
+
+        let batch = bef_batch(Vec::new(), Vec::new());
+        let summary = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(write_batches(&[batch], dir.path(), &by_birth_year()))
+            .unwrap();
+        assert_eq!(summary.rows, 0);
+        assert!(summary.files.is_empty());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    /// A partition whose file has no rows is read without error
+    #[test]
+    fn test_read_empty_partition() {
+        let dir = temp_dir();
+        write_parquet(
+            &dir.path().join("year=2018").join("part-0.parquet"),
+            &bef_batch(vec!["1"], vec![Some(17532)]),
+        );
+        write_parquet(
+            &dir.path().join("year=2019").join("part-0.parquet"),
+            &bef_batch(Vec::new(), Vec::new()),
+        );
+
+        assert_eq!(query_pnrs(dir.path(), "SELECT \"PNR\" FROM bef"), vec!["1"]);
+        assert!(query_pnrs(dir.path(), "SELECT \"PNR\" FROM bef WHERE year = 2019").is_empty());
+    }
+
+    #[test]
+    fn test_staging_dir_removed_on_drop() {
+        let dir = temp_dir();
+        let path = dir.path().join("ids_partitions");
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("year=2018.parquet"), b"partial").unwrap();
+        drop(StagingDir(path.clone()));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,207 @@
+//! Rewrite registers for PNR point lookups
+//!
+//! Registers are usually delivered in arbitrary row order, so every row group spans
+//! almost the whole PNR range and a cohort extraction has to read all of them. The
+//! rewrite sorts each file by PNR and writes it with:
+//!
+//! - a split-block bloom filter on the PNR column;
+//! - page-level statistics, which make the writer emit the column and offset index;
+//! - small data pages and the PNR as declared sorting column.
+//!
+//! [`crate::data::pruning::read_parquet_for_pnrs`] then skips row groups by statistics
+//! and bloom filters and pages by the page index.
+
+use datafusion::prelude::*;
+use std::path::{Path, PathBuf};
+
+use super::parquet::ParquetReader;
+use super::partitioned::{write_file, ParquetWriteOptions};
+use crate::error::{IdsError, Result};
+
+/// Options for rewriting registers
+#[derive(Debug, Clone)]
+pub struct RewriteOptions {
+    /// PNR column to sort by (PNR or CPR if not set)
+    pub pnr_column: Option<String>,
+    /// Maximum rows per row group
+    pub row_group_size: usize,
+    /// Maximum rows per data page
+    pub page_row_limit: usize,
+    /// False positive probability of the bloom filters
+    pub bloom_filter_fpp: f64,
+}
+
+impl Default for RewriteOptions {
+    fn default() -> Self {
+        Self {
+            pnr_column: None,
+            row_group_size: 128 * 1024,
+            page_row_limit: 8192,
+            bloom_filter_fpp: 0.01,
+        }
+    }
+}
+
+/// Files and rows written by a rewrite
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewriteSummary {
+    /// Files rewritten
+    pub files: usize,
+    /// Rows written
+    pub rows: usize,
+    /// Row groups written
+    pub row_groups: usize,
+}
+
+/// Rewrite one file sorted by PNR, returning the rows and row groups written
+async fn rewrite_file(
+    ctx: &SessionContext,
+    input: &Path,
+    output: &Path,
+    options: &RewriteOptions,
+) -> Result<(usize, usize)> {
+    let df = ctx
+        .read_parquet(
+            input.to_string_lossy().to_string(),
+            ParquetReadOptions::default(),
+        )
+        .await?;
+
+    let mut write_options = ParquetWriteOptions {
+        row_group_size: options.row_group_size,
+        page_row_limit: Some(options.page_row_limit),
+        bloom_filter_fpp: options.bloom_filter_fpp,
+        sort_by_pnr: true,
+        pnr_column: options.pnr_column.clone(),
+        ..ParquetWriteOptions::default()
+    };
+    let pnr_column = write_options
+        .pnr_column(df.schema().as_arrow())
+        .ok_or_else(|| {
+            IdsError::Validation(format!("No PNR column found in {}", input.display()))
+        })?;
+    write_options.bloom_filter_columns = Some(vec![pnr_column]);
+
+    write_file(df, output, &write_options).await
+}
+
+/// Rewrite a Parquet file or directory sorted by PNR with bloom filters and page indexes
+///
+/// A directory is rewritten file by file into `output`, keeping the relative paths. For a
+/// single file, `output` is the output file, or a directory to write it into.
+pub async fn rewrite_parquet(
+    input: &Path,
+    output: &Path,
+    options: &RewriteOptions,
+) -> Result<RewriteSummary> {
+    let files: Vec<(PathBuf, PathBuf)> = if input.is_dir() {
+        ParquetReader::list_parquet_files(input)?
+            .into_iter()
+            .map(|file| {
+                let relative = file.strip_prefix(input).unwrap_or(&file).to_path_buf();
+                (file, output.join(relative))
+            })
+            .collect()
+    } else if output.is_dir() {
+        let name = input.file_name().unwrap_or_default();
+        vec![(input.to_path_buf(), output.join(name))]
+    } else {
+        vec![(input.to_path_buf(), output.to_path_buf())]
+    };
+
+    let ctx = crate::data::io::execution::session_context();
+    let mut summary = RewriteSummary::default();
+    for (input_file, output_file) in &files {
+        if input_file == output_file {
+            return Err(IdsError::Validation(format!(
+                "Refusing to rewrite {} in place",
+                input_file.display()
+            )));
+        }
+        let (rows, row_groups) = rewrite_file(&ctx, input_file, output_file, options).await?;
+        log::info!(
+            "Rewrote {} to {} ({rows} rows, {row_groups} row groups)",
+            input_file.display(),
+            output_file.display()
+        );
+        summary.files += 1;
+        summary.rows += rows;
+        summary.row_groups += row_groups;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::pruning::read_parquet_for_pnrs;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::array::{Int32Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    /// Rewrite 2000 PNRs in scrambled order to `sorted.parquet` in `dir`
+    fn rewrite_scrambled(dir: &Path) -> PathBuf {
+        let input = dir.join("input.parquet");
+        let output = dir.join("sorted.parquet");
+        let pnrs: Vec<String> = (0..2000)
+            .map(|i| format!("{:010}", (i * 7919) % 2000))
+            .collect();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, false),
+            Field::new("VALUE", DataType::Int32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(pnrs)),
+                Arc::new(Int32Array::from_iter_values(0..2000)),
+            ],
+        )
+        .unwrap();
+        write_parquet(&input, &batch);
+
+        let options = RewriteOptions {
+            row_group_size: 200,
+            page_row_limit: 50,
+            ..RewriteOptions::default()
+        };
+        let summary = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(rewrite_parquet(&input, &output, &options))
+            .unwrap();
+        assert_eq!(summary.rows, 2000);
+        assert_eq!(summary.row_groups, 10);
+        output
+    }
+
+    #[test]
+    fn test_rewrite_prunes_pnr_lookups() {
+        let dir = temp_dir();
+        let output = rewrite_scrambled(dir.path());
+
+        let wanted: HashSet<String> = ["0000000042", "0000000043", "0000001999"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let (batches, report) = read_parquet_for_pnrs(&output, "PNR", &wanted, None).unwrap();
+        assert_eq!(report.rows_matched, 3);
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 3);
+        assert_eq!(report.row_groups_read, 2);
+        assert!(report.rows_read <= 100);
+    }
+
+    #[test]
+    fn test_missing_pnrs_read_no_row_groups() {
+        let dir = temp_dir();
+        let output = rewrite_scrambled(dir.path());
+
+        let missing: HashSet<String> = HashSet::from(["9999999999".to_string()]);
+        let (batches, report) = read_parquet_for_pnrs(&output, "PNR", &missing, None).unwrap();
+        assert!(batches.is_empty());
+        assert_eq!(report.row_groups_read, 0);
+    }
+}
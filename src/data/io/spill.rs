@@ -0,0 +1,295 @@
+//! Hash-partitioned spilling of record batches
+//!
+//! [`SpillPartitions`] routes the rows of incoming batches to a fixed number of
+//! partitions by the hash of a key column, so rows with the same key end up in the same
+//! partition. Out-of-core pipelines use it like the partitioning phase of a grace hash
+//! join: every input is partitioned on the join key, and the partitions are then
+//! processed one at a time.
+//!
+//! With a single partition the rows stay in memory. Otherwise rows are buffered per
+//! partition and written as Arrow IPC files to a temporary directory below the spill
+//! directory, which is removed when the partitions are dropped.
+
+use arrow::array::{Array, StringArray, UInt32Array};
+use arrow::compute::{cast, take_record_batch};
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+use super::execution::ExecutionConfig;
+use crate::error::{IdsError, Result};
+
+/// Partition of a key among `partitions` partitions
+#[must_use]
+pub fn partition_of(key: &str, partitions: usize) -> usize {
+    if partitions <= 1 {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % partitions as u64) as usize
+}
+
+/// Rows of one partition
+#[derive(Default)]
+struct Partition {
+    /// Batches not yet written to disk
+    buffered: Vec<RecordBatch>,
+    buffered_bytes: usize,
+    /// Spill files, in write order
+    files: Vec<PathBuf>,
+    rows: usize,
+}
+
+/// Record batches partitioned by the hash of a key column
+pub struct SpillPartitions {
+    schema: SchemaRef,
+    key_column: String,
+    partitions: Vec<Partition>,
+    /// Spill directory of these partitions; `None` if they are kept in memory
+    dir: Option<PathBuf>,
+    buffer_bytes: usize,
+}
+
+impl SpillPartitions {
+    /// Create `partitions` empty partitions keyed on `key_column`
+    pub fn new(
+        schema: SchemaRef,
+        key_column: &str,
+        partitions: usize,
+        execution: &ExecutionConfig,
+    ) -> Result<Self> {
+        let partitions = partitions.max(1);
+        let dir = if partitions > 1 {
+            let dir = execution
+                .spill_dir()
+                .join(format!("ids_spill_{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir)?;
+            Some(dir)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            schema,
+            key_column: key_column.to_string(),
+            partitions: (0..partitions).map(|_| Partition::default()).collect(),
+            dir,
+            buffer_bytes: execution.buffer_per_partition(partitions),
+        })
+    }
+
+    /// Schema of the partitioned batches
+    #[must_use]
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Number of partitions
+    #[must_use]
+    pub fn partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// Total number of rows in all partitions
+    #[must_use]
+    pub fn num_rows(&self) -> usize {
+        self.partitions.iter().map(|partition| partition.rows).sum()
+    }
+
+    /// Partition of a key
+    #[must_use]
+    pub fn partition_of(&self, key: &str) -> usize {
+        partition_of(key, self.partitions.len())
+    }
+
+    /// Route the rows of a batch to their partitions; rows with a null key go to the first
+    pub fn push(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let batch = if batch.schema() == self.schema {
+            batch.clone()
+        } else {
+            RecordBatch::try_new(self.schema.clone(), batch.columns().to_vec()).map_err(|e| {
+                IdsError::Data(format!("Batch does not match the partition schema: {e}"))
+            })?
+        };
+
+        if self.partitions.len() == 1 {
+            return self.append(0, batch);
+        }
+
+        let key = batch.column_by_name(&self.key_column).ok_or_else(|| {
+            IdsError::Data(format!(
+                "Partition key column {} not found",
+                self.key_column
+            ))
+        })?;
+        let key = cast(key, &DataType::Utf8)?;
+        let key = key
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| IdsError::Data(format!("Cannot read {} as strings", self.key_column)))?;
+
+        let mut indices = vec![Vec::new(); self.partitions.len()];
+        for row in 0..key.len() {
+            let partition = if key.is_null(row) {
+                0
+            } else {
+                self.partition_of(key.value(row))
+            };
+            indices[partition].push(row as u32);
+        }
+
+        for (partition, rows) in indices.into_iter().enumerate() {
+            if !rows.is_empty() {
+                let rows = take_record_batch(&batch, &UInt32Array::from(rows))?;
+                self.append(partition, rows)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, index: usize, batch: RecordBatch) -> Result<()> {
+        let partition = &mut self.partitions[index];
+        partition.rows += batch.num_rows();
+        partition.buffered_bytes += batch.get_array_memory_size();
+        partition.buffered.push(batch);
+        if self.dir.is_some() && partition.buffered_bytes >= self.buffer_bytes {
+            self.flush(index)?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows of a partition to a new spill file
+    fn flush(&mut self, index: usize) -> Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let partition = &mut self.partitions[index];
+        if partition.buffered.is_empty() {
+            return Ok(());
+        }
+
+        let path = dir.join(format!("{index}_{}.arrow", partition.files.len()));
+        let mut writer = FileWriter::try_new(BufWriter::new(File::create(&path)?), &self.schema)?;
+        for batch in partition.buffered.drain(..) {
+            writer.write(&batch)?;
+        }
+        writer.finish()?;
+        partition.buffered_bytes = 0;
+        partition.files.push(path);
+        Ok(())
+    }
+
+    /// Read the rows of one partition
+    ///
+    /// Always returns at least one batch, which is empty if the partition has no rows.
+    pub fn read(&self, index: usize) -> Result<Vec<RecordBatch>> {
+        let partition = self.partitions.get(index).ok_or_else(|| {
+            IdsError::Validation(format!(
+                "Partition {index} out of range ({} partitions)",
+                self.partitions.len()
+            ))
+        })?;
+
+        let mut batches = Vec::new();
+        for path in &partition.files {
+            let reader = FileReader::try_new(BufReader::new(File::open(path)?), None)?;
+            for batch in reader {
+                batches.push(batch?);
+            }
+        }
+        batches.extend(partition.buffered.iter().cloned());
+        if batches.is_empty() {
+            batches.push(RecordBatch::new_empty(self.schema.clone()));
+        }
+        Ok(batches)
+    }
+
+    /// Read the rows of one partition as a single batch
+    pub fn read_batch(&self, index: usize) -> Result<RecordBatch> {
+        let batches = self.read(index)?;
+        arrow::compute::concat_batches(&self.schema, &batches)
+            .map_err(|e| IdsError::Data(format!("Failed to combine partition {index}: {e}")))
+    }
+}
+
+impl Drop for SpillPartitions {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            if let Err(e) = std::fs::remove_dir_all(dir) {
+                log::warn!("Failed to remove spill directory {}: {e}", dir.display());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int32Array};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_spill_partitions_keep_keys_together() {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "PNR",
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("b"),
+                    None,
+                    Some("a"),
+                    Some("c"),
+                ])) as ArrayRef,
+            ),
+            (
+                "VALUE",
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let execution = ExecutionConfig {
+            memory_limit: Some(1),
+            spill_dir: Some(std::env::temp_dir()),
+        };
+
+        let mut partitions = SpillPartitions::new(batch.schema(), "PNR", 4, &execution).unwrap();
+        // Write every batch to disk
+        partitions.buffer_bytes = 0;
+        partitions.push(&batch).unwrap();
+        partitions.push(&batch).unwrap();
+        let dir = partitions.dir.clone().unwrap();
+        assert!(dir.exists());
+        assert_eq!(partitions.num_rows(), 10);
+
+        let mut rows = 0;
+        for index in 0..partitions.partitions() {
+            let partition = partitions.read_batch(index).unwrap();
+            rows += partition.num_rows();
+            let keys = partition
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            for key in keys.iter().flatten() {
+                assert_eq!(partition_of(key, 4), index);
+            }
+            if index == partitions.partition_of("a") {
+                assert_eq!(keys.iter().filter(|key| *key == Some("a")).count(), 4);
+            }
+        }
+        assert_eq!(rows, 10);
+
+        drop(partitions);
+        assert!(!dir.exists());
+    }
+}
@@ -8,6 +8,7 @@
 //! use ids_rs::data::prelude::*;
 //! ```
 
+pub mod cache;
 pub mod io;
 pub mod prelude;
 pub mod query;
@@ -0,0 +1,335 @@
+//! PNR pruning with Parquet bloom filters and page indexes
+//!
+//! PNRs are effectively random strings, so row group min/max statistics rarely exclude
+//! anything unless a file is sorted by PNR. For point lookups of a PNR set this module
+//! combines three levels of Parquet metadata:
+//!
+//! - row group statistics: a row group is read only if a PNR of the set lies within its
+//!   min/max range;
+//! - split-block bloom filters: of those, only row groups whose bloom filter may contain
+//!   a PNR in range are read;
+//! - the column and offset index: within a row group, only pages whose min/max range
+//!   contains a PNR of the set are decoded.
+//!
+//! Files written by [`crate::data::io::rewrite`] are sorted by PNR and carry bloom filters
+//! and page indexes, so a cohort extraction reads a small fraction of each file. Files
+//! without this metadata are read in full and filtered.
+
+use arrow::array::{Array, BooleanArray, LargeStringArray, StringArray, StringViewArray};
+use arrow::compute::filter_record_batch;
+use arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::arrow_reader::{
+    ArrowReaderOptions, ParquetRecordBatchReaderBuilder, RowSelection, RowSelector,
+};
+use datafusion::parquet::arrow::ProjectionMask;
+use datafusion::parquet::basic::ColumnOrder;
+use datafusion::parquet::file::metadata::ParquetMetaData;
+use datafusion::parquet::file::page_index::index::Index;
+use datafusion::parquet::file::properties::ReaderProperties;
+use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+use datafusion::parquet::file::serialized_reader::ReadOptionsBuilder;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+use crate::error::{IdsError, Result};
+
+/// Row groups, pages and rows read for a PNR lookup in one file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PnrPruningReport {
+    /// Row groups in the file
+    pub row_groups: usize,
+    /// Row groups kept by statistics and bloom filters
+    pub row_groups_read: usize,
+    /// Rows in the file
+    pub rows: usize,
+    /// Rows decoded after page pruning
+    pub rows_read: usize,
+    /// Rows matching the PNR set
+    pub rows_matched: usize,
+}
+
+/// The parts of a file that can contain a PNR set
+#[derive(Debug, Clone)]
+pub struct PnrSelection {
+    /// Row groups that can contain a PNR of the set
+    pub row_groups: Vec<usize>,
+    /// Rows of the selected row groups on pages that can contain a PNR of the set
+    pub rows: Option<RowSelection>,
+    /// Pruning counts (without `rows_matched`)
+    pub report: PnrPruningReport,
+}
+
+/// The PNRs of a sorted set that lie within a min/max range
+fn pnrs_in_range<'a>(sorted: &'a [&'a str], min: &[u8], max: &[u8]) -> &'a [&'a str] {
+    let start = sorted.partition_point(|pnr| pnr.as_bytes() < min);
+    let end = sorted.partition_point(|pnr| pnr.as_bytes() <= max);
+    &sorted[start..end.max(start)]
+}
+
+/// Open a file with bloom filters and the page index
+fn open_reader(path: &Path) -> Result<SerializedFileReader<File>> {
+    let file = File::open(path)?;
+    let options = ReadOptionsBuilder::new()
+        .with_reader_properties(
+            ReaderProperties::builder()
+                .set_read_bloom_filter(true)
+                .build(),
+        )
+        .with_page_index()
+        .build();
+    SerializedFileReader::new_with_options(file, options).map_err(|e| {
+        IdsError::Data(format!(
+            "Failed to read Parquet metadata of {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+/// Index of a top-level column in the Parquet schema
+fn column_index(metadata: &ParquetMetaData, column: &str) -> Option<usize> {
+    metadata
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .position(|descriptor| descriptor.path().parts() == [column])
+}
+
+/// Row selection for the pages of a row group whose range contains a PNR of the set
+fn select_pages(
+    metadata: &ParquetMetaData,
+    row_group: usize,
+    column: usize,
+    sorted: &[&str],
+) -> Option<Vec<RowSelector>> {
+    let index = metadata.column_index()?.get(row_group)?.get(column)?;
+    let locations = metadata
+        .offset_index()?
+        .get(row_group)?
+        .get(column)?
+        .page_locations();
+    let Index::BYTE_ARRAY(index) = index else {
+        return None;
+    };
+    if index.indexes.len() != locations.len() {
+        return None;
+    }
+
+    let num_rows = usize::try_from(metadata.row_group(row_group).num_rows()).ok()?;
+    let mut selectors = Vec::with_capacity(locations.len());
+    for (page, location) in index.indexes.iter().zip(locations) {
+        let first_row = usize::try_from(location.first_row_index).ok()?;
+        let page_rows = locations
+            .iter()
+            .find(|next| next.first_row_index > location.first_row_index)
+            .and_then(|next| usize::try_from(next.first_row_index).ok())
+            .unwrap_or(num_rows)
+            .saturating_sub(first_row);
+        let may_contain = match (&page.min, &page.max) {
+            (Some(min), Some(max)) => !pnrs_in_range(sorted, min.data(), max.data()).is_empty(),
+            // Pages of only nulls
+            (None, None) => false,
+            _ => true,
+        };
+        selectors.push(if may_contain {
+            RowSelector::select(page_rows)
+        } else {
+            RowSelector::skip(page_rows)
+        });
+    }
+    Some(selectors)
+}
+
+/// Find the row groups and pages of a file that can contain a PNR of the set
+pub fn select_pnr_rows(path: &Path, column: &str, pnrs: &HashSet<String>) -> Result<PnrSelection> {
+    let reader = open_reader(path)?;
+    let metadata = reader.metadata();
+    let mut report = PnrPruningReport {
+        row_groups: metadata.num_row_groups(),
+        rows: usize::try_from(metadata.file_metadata().num_rows()).unwrap_or_default(),
+        ..PnrPruningReport::default()
+    };
+
+    let Some(column_idx) = column_index(metadata, column) else {
+        return Err(IdsError::Validation(format!(
+            "Column {column} not found in {}",
+            path.display()
+        )));
+    };
+
+    let mut sorted: Vec<&str> = pnrs.iter().map(String::as_str).collect();
+    sorted.sort_unstable();
+
+    // Legacy files order string statistics as signed bytes, which is not PNR order
+    let trusted_statistics = matches!(
+        metadata.file_metadata().column_order(column_idx),
+        ColumnOrder::TYPE_DEFINED_ORDER(_)
+    );
+
+    let mut row_groups = Vec::new();
+    let mut selectors = Vec::new();
+    let mut page_pruned = false;
+    for row_group in 0..metadata.num_row_groups() {
+        let statistics = metadata
+            .row_group(row_group)
+            .column(column_idx)
+            .statistics();
+        let candidates = match statistics
+            .filter(|_| trusted_statistics)
+            .and_then(|statistics| Some((statistics.min_bytes_opt()?, statistics.max_bytes_opt()?)))
+        {
+            Some((min, max)) => pnrs_in_range(&sorted, min, max),
+            None => &sorted[..],
+        };
+        if candidates.is_empty() {
+            continue;
+        }
+
+        let row_group_reader = reader.get_row_group(row_group).map_err(|e| {
+            IdsError::Data(format!(
+                "Failed to read row group of {}: {e}",
+                path.display()
+            ))
+        })?;
+        if let Some(bloom_filter) = row_group_reader.get_column_bloom_filter(column_idx) {
+            if !candidates.iter().any(|pnr| bloom_filter.check(pnr)) {
+                continue;
+            }
+        }
+
+        row_groups.push(row_group);
+        let num_rows =
+            usize::try_from(metadata.row_group(row_group).num_rows()).unwrap_or_default();
+        match select_pages(metadata, row_group, column_idx, candidates) {
+            Some(pages) if trusted_statistics => {
+                page_pruned = true;
+                selectors.extend(pages);
+            }
+            _ => selectors.push(RowSelector::select(num_rows)),
+        }
+    }
+
+    report.row_groups_read = row_groups.len();
+    let rows = page_pruned.then(|| RowSelection::from(selectors.clone()));
+    report.rows_read = selectors
+        .iter()
+        .filter(|selector| !selector.skip)
+        .map(|selector| selector.row_count)
+        .sum();
+
+    Ok(PnrSelection {
+        row_groups,
+        rows,
+        report,
+    })
+}
+
+/// Whether a file can contain any PNR of the set, by statistics and bloom filters
+pub fn file_may_contain_pnrs(path: &Path, column: &str, pnrs: &HashSet<String>) -> Result<bool> {
+    Ok(!select_pnr_rows(path, column, pnrs)?.row_groups.is_empty())
+}
+
+/// Membership mask of a string column in a PNR set
+fn membership_mask(array: &dyn Array, pnrs: &HashSet<String>) -> Option<BooleanArray> {
+    let contains = |value: Option<&str>| Some(value.is_some_and(|pnr| pnrs.contains(pnr)));
+    let any = array.as_any();
+    if let Some(array) = any.downcast_ref::<StringArray>() {
+        Some(array.iter().map(contains).collect())
+    } else if let Some(array) = any.downcast_ref::<LargeStringArray>() {
+        Some(array.iter().map(contains).collect())
+    } else {
+        any.downcast_ref::<StringViewArray>()
+            .map(|array| array.iter().map(contains).collect())
+    }
+}
+
+/// Read the rows of a PNR set from a Parquet file, skipping row groups and pages
+///
+/// `projection` lists the top-level columns to return (all if `None`); the PNR column is
+/// read for filtering either way.
+pub fn read_parquet_for_pnrs(
+    path: &Path,
+    column: &str,
+    pnrs: &HashSet<String>,
+    projection: Option<&[String]>,
+) -> Result<(Vec<RecordBatch>, PnrPruningReport)> {
+    let selection = select_pnr_rows(path, column, pnrs)?;
+    let mut report = selection.report;
+    if selection.row_groups.is_empty() {
+        return Ok((Vec::new(), report));
+    }
+
+    let file = File::open(path)?;
+    let mut builder = ParquetRecordBatchReaderBuilder::try_new_with_options(
+        file,
+        ArrowReaderOptions::new().with_page_index(true),
+    )?;
+
+    let arrow_schema = builder.schema().clone();
+    let mut columns: Vec<usize> = match projection {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| arrow_schema.index_of(name).ok())
+            .collect(),
+        None => (0..arrow_schema.fields().len()).collect(),
+    };
+    let pnr_idx = arrow_schema.index_of(column)?;
+    if !columns.contains(&pnr_idx) {
+        columns.push(pnr_idx);
+    }
+    columns.sort_unstable();
+    let mask = ProjectionMask::roots(builder.parquet_schema(), columns.iter().copied());
+
+    builder = builder
+        .with_projection(mask)
+        .with_row_groups(selection.row_groups);
+    if let Some(rows) = selection.rows {
+        builder = builder.with_row_selection(rows);
+    }
+
+    let output_columns: Option<Vec<usize>> = projection.map(|names| {
+        names
+            .iter()
+            .filter_map(|name| {
+                columns
+                    .iter()
+                    .position(|&idx| arrow_schema.field(idx).name() == name)
+            })
+            .collect()
+    });
+    let pnr_position = columns
+        .iter()
+        .position(|&idx| idx == pnr_idx)
+        .unwrap_or_default();
+
+    let mut batches = Vec::new();
+    for batch in builder.build()? {
+        let batch = batch?;
+        let mask = membership_mask(batch.column(pnr_position).as_ref(), pnrs).ok_or_else(|| {
+            IdsError::Validation(format!("Column {column} is not a string column"))
+        })?;
+        let batch = filter_record_batch(&batch, &mask)?;
+        let batch = match &output_columns {
+            Some(indices) => batch.project(indices)?,
+            None => batch,
+        };
+        if batch.num_rows() > 0 {
+            report.rows_matched += batch.num_rows();
+            batches.push(batch);
+        }
+    }
+
+    log::debug!(
+        "{}: read {} of {} row groups and {} of {} rows for {} PNRs ({} matched)",
+        path.display(),
+        report.row_groups_read,
+        report.row_groups,
+        report.rows_read,
+        report.rows,
+        pnrs.len(),
+        report.rows_matched
+    );
+
+    Ok((batches, report))
+}
@@ -0,0 +1,1263 @@
+//! Persistent statistics index for registry directories
+//!
+//! Opening thousands of yearly register files to read their footers dominates cold starts.
+//! [`RegistryIndex`] keeps what pruning needs in sidecar files in each registry directory:
+//!
+//! - `.ids_index.json`, per file and row group: row counts and min/max of integer and
+//!   string columns, and per file the years it covers;
+//! - `.ids_index.sketches`, per file: a bloom sketch of the distinct PNRs, sized for the
+//!   file's number of distinct PNRs, in a compact binary format.
+//!
+//! Entries are keyed by relative path and invalidated when a file's size or modification
+//! time changes, so only new and changed files are read when the index is loaded. Loaders
+//! register only the files that can contain a PNR set ([`register_indexed_parquet`]) or
+//! cover a range of years ([`select_indexed_files_for_years`]), and
+//! [`PrunableTableProvider::from_index`](super::PrunableTableProvider::from_index) prunes
+//! with the indexed statistics.
+//!
+//! The index of a registry directory that cannot be written (e.g. a read-only data
+//! directory) is kept in the cache directory (`--cache-dir`) instead, so it is not rebuilt
+//! on every run.
+
+use arrow::array::{Array, LargeStringArray, StringArray, StringViewArray};
+use arrow::datatypes::{DataType, Schema, SchemaRef};
+use chrono::{Datelike, NaiveDate};
+use datafusion::common::ScalarValue;
+use datafusion::datasource::MemTable;
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use datafusion::parquet::arrow::ProjectionMask;
+use datafusion::parquet::basic::{ColumnOrder, LogicalType};
+use datafusion::parquet::file::reader::{FileReader, SerializedFileReader};
+use datafusion::parquet::file::statistics::Statistics;
+use datafusion::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use super::statistics::{FileStatistics, RegistryPruningStatistics};
+use crate::data::cache::{cache_config, create_private_dir};
+use crate::data::io::parquet::ParquetReader;
+use crate::data::io::partitioned::{
+    hive_partition_columns, hive_partition_values, read_partitioned_files,
+};
+use crate::error::{IdsError, Result};
+
+/// File name of the index in a registry directory
+pub const INDEX_FILE_NAME: &str = ".ids_index.json";
+
+/// File name of the PNR sketches of the index in a registry directory
+pub const SKETCH_FILE_NAME: &str = ".ids_index.sketches";
+
+/// Directory below the cache directory holding indexes of read-only registry directories
+const FALLBACK_INDEX_DIR: &str = "registry-index";
+
+/// Index format version; indexes of other versions are rebuilt
+const INDEX_VERSION: u32 = 3;
+
+/// Date ranges spanning more years than this (e.g. sentinel dates) give no year coverage
+const MAX_DATE_RANGE_YEARS: i32 = 150;
+
+/// Magic bytes at the start of the sketch file
+const SKETCH_FILE_MAGIC: &[u8; 8] = b"IDSSKTCH";
+
+/// PNR column names, in order of preference
+const PNR_COLUMNS: [&str; 2] = ["PNR", "CPR"];
+
+/// Target false positive probability of the PNR sketches
+const SKETCH_FPP: f64 = 0.01;
+
+/// A bloom filter over the distinct PNRs of a file
+///
+/// Uses FNV-1a with double hashing, which is stable across builds, so persisted sketches
+/// stay valid. The bit set is sized for the number of PNRs (about 9.6 bits per PNR), so
+/// the false positive rate stays near [`SKETCH_FPP`] for files of any size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PnrSketch {
+    /// Number of hash functions
+    pub hashes: u32,
+    /// Number of distinct PNRs in the sketch
+    pub distinct: u64,
+    /// Bit set as 64-bit words
+    bits: Vec<u64>,
+}
+
+impl PnrSketch {
+    /// Build a sketch sized for the given PNRs
+    #[must_use]
+    pub fn from_pnrs<'a>(pnrs: impl ExactSizeIterator<Item = &'a str>) -> Self {
+        let distinct = pnrs.len().max(1);
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let wanted =
+            (-(distinct as f64) * SKETCH_FPP.ln() / std::f64::consts::LN_2.powi(2)).ceil() as usize;
+        let words = wanted.max(64).div_ceil(64);
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let hashes = ((words * 64) as f64 / distinct as f64 * std::f64::consts::LN_2)
+            .round()
+            .clamp(1.0, 16.0) as u32;
+
+        let mut sketch = Self {
+            hashes,
+            distinct: pnrs.len() as u64,
+            bits: vec![0; words],
+        };
+        for pnr in pnrs {
+            for bit in sketch.bit_positions(pnr) {
+                sketch.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        sketch
+    }
+
+    /// Bit positions of a PNR
+    fn bit_positions(&self, pnr: &str) -> impl Iterator<Item = usize> {
+        let h1 = fnv1a(pnr.as_bytes());
+        let h2 = h1.rotate_left(32) ^ 0x9e37_79b9_7f4a_7c15 | 1;
+        let bits = (self.bits.len() * 64) as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        (0..u64::from(self.hashes))
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+
+    /// Whether the sketch may contain a PNR (false positives are possible)
+    #[must_use]
+    pub fn may_contain(&self, pnr: &str) -> bool {
+        !self.bits.is_empty()
+            && self
+                .bit_positions(pnr)
+                .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Size of the bit set in bytes
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    /// Write the sketch in little-endian binary form
+    fn write_to(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(&self.hashes.to_le_bytes())?;
+        out.write_all(&self.distinct.to_le_bytes())?;
+        out.write_all(&(self.bits.len() as u64).to_le_bytes())?;
+        for word in &self.bits {
+            out.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a sketch written by [`PnrSketch::write_to`]
+    fn read_from(input: &mut &[u8]) -> std::io::Result<Self> {
+        let hashes = read_u32(input)?;
+        let distinct = read_u64(input)?;
+        let words = read_u64(input)?;
+        let bits = read_bytes(input, words.saturating_mul(8))?
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunks of 8 bytes")))
+            .collect();
+        Ok(Self {
+            hashes,
+            distinct,
+            bits,
+        })
+    }
+}
+
+/// Take `len` bytes, failing instead of allocating when fewer are left
+fn read_bytes<'a>(input: &mut &'a [u8], len: u64) -> std::io::Result<&'a [u8]> {
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= input.len())
+        .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_u32(input: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(input: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// 64-bit FNV-1a hash
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// An indexed statistics value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndexValue {
+    /// Integer (and date, as days since the epoch) statistics
+    Int(i64),
+    /// String statistics
+    Str(String),
+}
+
+/// Min/max of a column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnRange {
+    pub min: IndexValue,
+    pub max: IndexValue,
+}
+
+impl ColumnRange {
+    /// Whether a string value lies within the range (true for integer ranges)
+    fn may_contain_str(&self, value: &str) -> bool {
+        match (&self.min, &self.max) {
+            (IndexValue::Str(min), IndexValue::Str(max)) => {
+                min.as_str() <= value && value <= max.as_str()
+            }
+            _ => true,
+        }
+    }
+
+    /// The union of two ranges of the same type
+    fn merge(&self, other: &Self) -> Option<Self> {
+        let (min, max) = match (&self.min, &self.max, &other.min, &other.max) {
+            (IndexValue::Int(a), IndexValue::Int(b), IndexValue::Int(c), IndexValue::Int(d)) => {
+                (IndexValue::Int(*a.min(c)), IndexValue::Int(*b.max(d)))
+            }
+            (IndexValue::Str(a), IndexValue::Str(b), IndexValue::Str(c), IndexValue::Str(d)) => (
+                IndexValue::Str(a.min(c).clone()),
+                IndexValue::Str(b.max(d).clone()),
+            ),
+            _ => return None,
+        };
+        Some(Self { min, max })
+    }
+}
+
+/// Statistics of a row group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedRowGroup {
+    pub rows: u64,
+    pub columns: BTreeMap<String, ColumnRange>,
+}
+
+/// Statistics of a file, valid for its size and modification time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// Path relative to the registry directory
+    pub path: String,
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+    pub rows: u64,
+    pub row_groups: Vec<IndexedRowGroup>,
+    /// File-level min/max of columns with statistics in every row group
+    pub columns: BTreeMap<String, ColumnRange>,
+    /// Years the file covers, from a `year=` partition directory, a year in the file name
+    /// or the range of its date columns; empty if unknown
+    pub years: BTreeSet<i32>,
+    /// The PNR column the sketch was built from
+    pub pnr_column: Option<String>,
+    /// Stored in the sketch file rather than the JSON index
+    #[serde(skip)]
+    pub pnr_sketch: Option<PnrSketch>,
+}
+
+impl IndexedFile {
+    /// Whether the file can contain any of the values of a column
+    #[must_use]
+    pub fn may_contain_any(&self, column: &str, values: &HashSet<String>) -> bool {
+        if self.rows == 0 {
+            return false;
+        }
+        let sketch = self
+            .pnr_sketch
+            .as_ref()
+            .filter(|_| self.pnr_column.as_deref() == Some(column));
+        let range = self.columns.get(column);
+        values.iter().any(|value| {
+            range.is_none_or(|range| range.may_contain_str(value))
+                && sketch.is_none_or(|sketch| sketch.may_contain(value))
+        })
+    }
+
+    /// Whether the file covers any of the years (files with unknown years do)
+    #[must_use]
+    pub fn covers_any_year(&self, years: &RangeInclusive<i32>) -> bool {
+        self.years.is_empty() || self.years.range(years.clone()).next().is_some()
+    }
+}
+
+impl IndexedFile {
+    /// Whether the entry is valid for a file with the given size and modification time
+    fn matches_stamp(&self, (size, secs, nanos): FileStamp) -> bool {
+        self.size == size && self.modified_secs == secs && self.modified_nanos == nanos
+    }
+}
+
+/// Write a file through a temporary file next to it, replacing it atomically
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!("{file_name}.{}", uuid::Uuid::new_v4()));
+    let written = File::create(&temp_path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?
+            .sync_all()
+    });
+    written
+        .and_then(|()| std::fs::rename(&temp_path, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp_path);
+        })?;
+    Ok(())
+}
+
+/// Write the sketch file: magic, entry count, then per file its path, size, modification
+/// time and sketch, all little-endian
+fn write_sketches(out: &mut impl Write, files: &[IndexedFile]) -> std::io::Result<()> {
+    let sketched: Vec<(&IndexedFile, &PnrSketch)> = files
+        .iter()
+        .filter_map(|file| Some((file, file.pnr_sketch.as_ref()?)))
+        .collect();
+    out.write_all(SKETCH_FILE_MAGIC)?;
+    out.write_all(&INDEX_VERSION.to_le_bytes())?;
+    out.write_all(&(sketched.len() as u64).to_le_bytes())?;
+    for (file, sketch) in sketched {
+        let path = file.path.as_bytes();
+        out.write_all(&(path.len() as u64).to_le_bytes())?;
+        out.write_all(path)?;
+        out.write_all(&file.size.to_le_bytes())?;
+        out.write_all(&file.modified_secs.to_le_bytes())?;
+        out.write_all(&file.modified_nanos.to_le_bytes())?;
+        sketch.write_to(out)?;
+    }
+    Ok(())
+}
+
+/// Read the sketch file, keyed by relative path with the size and modification time of
+/// the file each sketch was built from
+fn read_sketches(path: &Path) -> std::io::Result<HashMap<String, (FileStamp, PnrSketch)>> {
+    let invalid = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, reason);
+    let content = std::fs::read(path)?;
+    let mut input = content.as_slice();
+    if read_bytes(&mut input, 8)? != SKETCH_FILE_MAGIC || read_u32(&mut input)? != INDEX_VERSION {
+        return Err(invalid("not a sketch file of this version"));
+    }
+    let entries = read_u64(&mut input)?;
+    let mut sketches = HashMap::new();
+    for _ in 0..entries {
+        let path_len = read_u64(&mut input)?;
+        let path = std::str::from_utf8(read_bytes(&mut input, path_len)?)
+            .map_err(|_| invalid("path is not UTF-8"))?
+            .to_string();
+        let stamp = (
+            read_u64(&mut input)?,
+            read_u64(&mut input)?,
+            read_u32(&mut input)?,
+        );
+        sketches.insert(path, (stamp, PnrSketch::read_from(&mut input)?));
+    }
+    Ok(sketches)
+}
+
+/// Size and modification time (seconds and nanoseconds) of a file
+pub(crate) type FileStamp = (u64, u64, u32);
+
+/// Size and modification time of a file
+pub(crate) fn file_stamp(path: &Path) -> Result<FileStamp> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+/// A four-digit year (1900-2099) in a file name, e.g. `bef201812.parquet`
+pub(crate) fn year_from_file_name(path: &Path) -> Option<i32> {
+    let name = path.file_stem()?.to_str()?;
+    let bytes = name.as_bytes();
+    (0..bytes.len().saturating_sub(3)).find_map(|start| {
+        let digits = bytes.get(start..start + 4)?;
+        let preceded_by_digit = start > 0 && bytes[start - 1].is_ascii_digit();
+        let year: i32 = std::str::from_utf8(digits).ok()?.parse().ok()?;
+        (!preceded_by_digit
+            && digits.iter().all(u8::is_ascii_digit)
+            && (1900..=2099).contains(&year))
+        .then_some(year)
+    })
+}
+
+/// The year of a file in a Hive `year=YYYY` directory below `dir`
+fn year_from_partition(dir: &Path, path: &Path) -> Option<i32> {
+    hive_partition_values(dir, path)
+        .into_iter()
+        .find(|(key, _)| key == "year")?
+        .1
+        .parse()
+        .ok()
+}
+
+/// The year of a date stored as days since the Unix epoch
+fn year_of_days(days: &IndexValue) -> Option<i32> {
+    match days {
+        IndexValue::Int(days) => {
+            NaiveDate::from_num_days_from_ce_opt(i32::try_from(*days).ok()?.checked_add(719_163)?)
+                .map(|date| date.year())
+        }
+        IndexValue::Str(_) => None,
+    }
+}
+
+/// Min/max of a column chunk from its footer statistics
+fn column_range(statistics: &Statistics, trusted_order: bool) -> Option<ColumnRange> {
+    let (min, max) = match statistics {
+        Statistics::Int32(s) => (
+            IndexValue::Int(i64::from(*s.min_opt()?)),
+            IndexValue::Int(i64::from(*s.max_opt()?)),
+        ),
+        Statistics::Int64(s) => (
+            IndexValue::Int(*s.min_opt()?),
+            IndexValue::Int(*s.max_opt()?),
+        ),
+        Statistics::ByteArray(s) if trusted_order => (
+            IndexValue::Str(s.min_opt()?.as_utf8().ok()?.to_string()),
+            IndexValue::Str(s.max_opt()?.as_utf8().ok()?.to_string()),
+        ),
+        _ => return None,
+    };
+    Some(ColumnRange { min, max })
+}
+
+/// The distinct values of a string column of a file
+fn distinct_strings(path: &Path, column_idx: usize) -> Result<HashSet<String>> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let mask = ProjectionMask::leaves(builder.parquet_schema(), [column_idx]);
+    let mut values = HashSet::new();
+    for batch in builder.with_projection(mask).build()? {
+        let batch = batch?;
+        let column = batch.column(0);
+        let any = column.as_any();
+        if let Some(array) = any.downcast_ref::<StringArray>() {
+            values.extend(array.iter().flatten().map(str::to_string));
+        } else if let Some(array) = any.downcast_ref::<LargeStringArray>() {
+            values.extend(array.iter().flatten().map(str::to_string));
+        } else if let Some(array) = any.downcast_ref::<StringViewArray>() {
+            values.extend(array.iter().flatten().map(str::to_string));
+        }
+    }
+    Ok(values)
+}
+
+/// Read the statistics of a file
+fn index_file(dir: &Path, path: &Path) -> Result<IndexedFile> {
+    let (size, modified_secs, modified_nanos) = file_stamp(path)?;
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let metadata = reader.metadata();
+    let file_metadata = metadata.file_metadata();
+    let schema = file_metadata.schema_descr();
+
+    // Top-level leaf columns: (leaf index, name, is a date, has trusted string order)
+    let columns: Vec<(usize, String, bool, bool)> = schema
+        .columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, descriptor)| descriptor.path().parts().len() == 1)
+        .map(|(idx, descriptor)| {
+            (
+                idx,
+                descriptor.name().to_string(),
+                descriptor.logical_type() == Some(LogicalType::Date),
+                matches!(
+                    file_metadata.column_order(idx),
+                    ColumnOrder::TYPE_DEFINED_ORDER(_)
+                ),
+            )
+        })
+        .collect();
+
+    let row_groups: Vec<IndexedRowGroup> = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| IndexedRowGroup {
+            rows: u64::try_from(row_group.num_rows()).unwrap_or_default(),
+            columns: columns
+                .iter()
+                .filter_map(|(idx, name, _, trusted)| {
+                    let range = column_range(row_group.column(*idx).statistics()?, *trusted)?;
+                    Some((name.clone(), range))
+                })
+                .collect(),
+        })
+        .collect();
+
+    let file_columns: BTreeMap<String, ColumnRange> = columns
+        .iter()
+        .filter_map(|(_, name, _, _)| {
+            let mut ranges = row_groups
+                .iter()
+                .map(|row_group| row_group.columns.get(name));
+            let first = ranges.next()??.clone();
+            ranges
+                .try_fold(first, |range, next| range.merge(next?))
+                .map(|range| (name.clone(), range))
+        })
+        .collect();
+
+    let years: BTreeSet<i32> =
+        match year_from_partition(dir, path).or_else(|| year_from_file_name(path)) {
+            Some(year) => BTreeSet::from([year]),
+            None => columns
+                .iter()
+                .filter(|(_, _, is_date, _)| *is_date)
+                .filter_map(|(_, name, _, _)| file_columns.get(name))
+                .filter_map(|range| Some((year_of_days(&range.min)?, year_of_days(&range.max)?)))
+                .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+                .filter(|(first, last)| last - first < MAX_DATE_RANGE_YEARS)
+                .map(|(first, last)| (first..=last).collect())
+                .unwrap_or_default(),
+        };
+
+    let pnr_column = PNR_COLUMNS.into_iter().find_map(|pnr_column| {
+        columns
+            .iter()
+            .find(|(_, name, _, _)| name == pnr_column)
+            .map(|(idx, name, _, _)| (*idx, name.clone()))
+    });
+    let pnr_sketch = match &pnr_column {
+        Some((idx, _)) => {
+            let pnrs = distinct_strings(path, *idx)?;
+            Some(PnrSketch::from_pnrs(pnrs.iter().map(String::as_str)))
+        }
+        None => None,
+    };
+
+    Ok(IndexedFile {
+        path: path
+            .strip_prefix(dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string(),
+        size,
+        modified_secs,
+        modified_nanos,
+        rows: u64::try_from(file_metadata.num_rows()).unwrap_or_default(),
+        row_groups,
+        columns: file_columns,
+        years,
+        pnr_column: pnr_column.map(|(_, name)| name),
+        pnr_sketch,
+    })
+}
+
+/// The statistics index of a registry directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    version: u32,
+    /// Indexed files, ordered by path
+    pub files: Vec<IndexedFile>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl RegistryIndex {
+    /// Load the index of a directory, indexing new and changed files and saving it if it
+    /// changed
+    ///
+    /// An index that cannot be saved in the directory (e.g. a read-only data directory) is
+    /// saved in the cache directory, if one is configured, and still returned otherwise.
+    pub fn load_or_build(dir: &Path) -> Result<Self> {
+        let fallback_root = cache_config().dir;
+        Self::load_or_build_with_fallback(dir, fallback_root.as_deref())
+    }
+
+    /// [`RegistryIndex::load_or_build`] with the index kept below `fallback_root` when the
+    /// directory cannot be written
+    fn load_or_build_with_fallback(dir: &Path, fallback_root: Option<&Path>) -> Result<Self> {
+        if !dir.is_dir() {
+            return Err(IdsError::Validation(format!(
+                "Not a directory: {}",
+                dir.display()
+            )));
+        }
+        let fallback = fallback_root.map(|root| fallback_index_dir(root, dir));
+
+        // The most recently saved index of this version, in the directory or the fallback
+        let saved = std::iter::once(dir.to_path_buf())
+            .chain(fallback.clone())
+            .filter_map(|location| {
+                let index_path = location.join(INDEX_FILE_NAME);
+                let modified = std::fs::metadata(&index_path).ok()?.modified().ok()?;
+                let index = std::fs::read_to_string(&index_path)
+                    .ok()
+                    .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+                    .filter(|index| index.version == INDEX_VERSION)?;
+                Some((modified, location, index))
+            })
+            .max_by_key(|(modified, _, _)| *modified);
+        let (location, mut previous): (PathBuf, HashMap<String, IndexedFile>) = match saved {
+            Some((_, location, index)) => (
+                location,
+                index
+                    .files
+                    .into_iter()
+                    .map(|file| (file.path.clone(), file))
+                    .collect(),
+            ),
+            None => (dir.to_path_buf(), HashMap::new()),
+        };
+        if !previous.is_empty() {
+            let sketch_path = location.join(SKETCH_FILE_NAME);
+            match read_sketches(&sketch_path) {
+                Ok(sketches) => {
+                    for (path, (stamp, sketch)) in sketches {
+                        if let Some(file) = previous
+                            .get_mut(&path)
+                            .filter(|file| file.matches_stamp(stamp))
+                        {
+                            file.pnr_sketch = Some(sketch);
+                        }
+                    }
+                }
+                Err(e) => log::warn!("Ignoring sketches {}: {e}", sketch_path.display()),
+            }
+        }
+
+        let mut paths = ParquetReader::list_parquet_files(dir)?;
+        paths.sort();
+
+        let mut files = Vec::with_capacity(paths.len());
+        let mut indexed = 0;
+        for path in &paths {
+            let relative = path
+                .strip_prefix(dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .to_string();
+            let (size, modified_secs, modified_nanos) = file_stamp(path)?;
+            match previous.get(&relative) {
+                // Files whose sketch is missing from the sketch file are re-indexed
+                Some(file)
+                    if file.matches_stamp((size, modified_secs, modified_nanos))
+                        && (file.pnr_column.is_none() || file.pnr_sketch.is_some()) =>
+                {
+                    files.push(file.clone());
+                }
+                _ => {
+                    files.push(index_file(dir, path)?);
+                    indexed += 1;
+                }
+            }
+        }
+
+        let index = Self {
+            version: INDEX_VERSION,
+            files,
+            dir: dir.to_path_buf(),
+        };
+        if indexed > 0 || previous.len() != index.files.len() {
+            log::info!(
+                "Indexed {indexed} of {} files in {}",
+                index.files.len(),
+                dir.display()
+            );
+            index.save_or_fall_back(fallback.as_deref());
+        }
+        Ok(index)
+    }
+
+    /// Save the index in the directory, or in `fallback` if the directory cannot be
+    /// written
+    fn save_or_fall_back(&self, fallback: Option<&Path>) {
+        let Err(e) = self.save() else {
+            return;
+        };
+        let Some(fallback) = fallback else {
+            log::warn!(
+                "Could not save the index of {} ({e}); it is rebuilt on every run unless a \
+                 cache directory is set with --cache-dir",
+                self.dir.display()
+            );
+            return;
+        };
+        match create_private_dir(fallback).and_then(|()| self.save_to(fallback)) {
+            Ok(()) => log::info!(
+                "Saved the index of {} in {} ({e})",
+                self.dir.display(),
+                fallback.display()
+            ),
+            Err(fallback_error) => log::warn!(
+                "Could not save the index of {} ({e}) or in {} ({fallback_error}); it is \
+                 rebuilt on every run",
+                self.dir.display(),
+                fallback.display()
+            ),
+        }
+    }
+
+    /// Write the index and its sketches to the directory, replacing them atomically
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&self.dir)
+    }
+
+    /// Write the index and its sketches to a directory, replacing them atomically
+    fn save_to(&self, location: &Path) -> Result<()> {
+        replace_file(&location.join(SKETCH_FILE_NAME), |out| {
+            write_sketches(out, &self.files)
+        })?;
+        let content = serde_json::to_string(self)
+            .map_err(|e| IdsError::Data(format!("Failed to serialize index: {e}")))?;
+        replace_file(&location.join(INDEX_FILE_NAME), |out| {
+            out.write_all(content.as_bytes())
+        })
+    }
+
+    /// The registry directory
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Absolute paths of all indexed files
+    #[must_use]
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|file| self.dir.join(&file.path))
+            .collect()
+    }
+
+    /// Files that can contain any of the values of a string column
+    #[must_use]
+    pub fn files_for_values(&self, column: &str, values: &HashSet<String>) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| file.may_contain_any(column, values))
+            .map(|file| self.dir.join(&file.path))
+            .collect()
+    }
+
+    /// Files covering any of the years (files with unknown years included)
+    #[must_use]
+    pub fn files_for_years(&self, years: &RangeInclusive<i32>) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| file.covers_any_year(years))
+            .map(|file| self.dir.join(&file.path))
+            .collect()
+    }
+
+    /// Min/max of a column per file, like
+    /// [`extract_column_statistics`](super::extract_column_statistics)
+    #[must_use]
+    pub fn column_statistics(
+        &self,
+        column: &str,
+        data_type: &DataType,
+    ) -> HashMap<PathBuf, (ScalarValue, ScalarValue)> {
+        self.files
+            .iter()
+            .filter_map(|file| {
+                let range = file.columns.get(column)?;
+                Some((
+                    self.dir.join(&file.path),
+                    (
+                        to_scalar(&range.min, data_type)?,
+                        to_scalar(&range.max, data_type)?,
+                    ),
+                ))
+            })
+            .collect()
+    }
+
+    /// File statistics for pruning with the columns of a schema
+    #[must_use]
+    pub fn file_statistics(&self, schema: &Schema) -> Vec<FileStatistics> {
+        self.files
+            .iter()
+            .map(|file| {
+                let mut statistics =
+                    FileStatistics::new(self.dir.join(&file.path), file.size, file.rows);
+                for field in schema.fields() {
+                    let Some(range) = file.columns.get(field.name()) else {
+                        continue;
+                    };
+                    if let (Some(min), Some(max)) = (
+                        to_scalar(&range.min, field.data_type()),
+                        to_scalar(&range.max, field.data_type()),
+                    ) {
+                        statistics = statistics
+                            .with_min_value(field.name(), min)
+                            .with_max_value(field.name(), max);
+                    }
+                }
+                if let (Some(column), Some(sketch)) = (&file.pnr_column, &file.pnr_sketch) {
+                    statistics = statistics.with_pnr_sketch(column, Arc::new(sketch.clone()));
+                }
+                statistics
+            })
+            .collect()
+    }
+
+    /// Pruning statistics for a schema
+    #[must_use]
+    pub fn pruning_statistics(&self, schema: SchemaRef) -> RegistryPruningStatistics {
+        let files = self.file_statistics(&schema);
+        RegistryPruningStatistics::new(schema).with_files(files)
+    }
+}
+
+/// Location of the index of a registry directory below the cache directory, named by a
+/// digest of the registry directory's path
+fn fallback_index_dir(root: &Path, dir: &Path) -> PathBuf {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let digest = Sha256::digest(dir.to_string_lossy().as_bytes());
+    root.join(FALLBACK_INDEX_DIR).join(format!("{digest:x}"))
+}
+
+/// Convert an indexed value to a scalar of a column type
+fn to_scalar(value: &IndexValue, data_type: &DataType) -> Option<ScalarValue> {
+    match (value, data_type) {
+        (IndexValue::Str(s), DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => {
+            ScalarValue::Utf8(Some(s.clone())).cast_to(data_type).ok()
+        }
+        (IndexValue::Int(days), DataType::Date32) => {
+            Some(ScalarValue::Date32(Some(i32::try_from(*days).ok()?)))
+        }
+        (IndexValue::Int(i), _) if data_type.is_integer() => {
+            ScalarValue::Int64(Some(*i)).cast_to(data_type).ok()
+        }
+        _ => None,
+    }
+}
+
+/// The files of a Parquet file or registry directory that can contain the filter values
+///
+/// Without a filter (or if the index is unavailable) all files are returned.
+pub fn select_indexed_files(
+    path: &Path,
+    filter: Option<(&str, &HashSet<String>)>,
+) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if let Some((column, values)) = filter.filter(|(_, values)| !values.is_empty()) {
+        match RegistryIndex::load_or_build(path) {
+            Ok(index) => return Ok(index.files_for_values(column, values)),
+            Err(e) => log::warn!("Registry index unavailable for {}: {e}", path.display()),
+        }
+    }
+    let mut files = ParquetReader::list_parquet_files(path)?;
+    files.sort();
+    Ok(files)
+}
+
+/// The files of a Parquet file or registry directory covering any of the years that can
+/// contain the filter values
+///
+/// Without an index, files are selected by the year in their file name (files without one
+/// are kept).
+pub fn select_indexed_files_for_years(
+    path: &Path,
+    filter: Option<(&str, &HashSet<String>)>,
+    years: &RangeInclusive<i32>,
+) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    match RegistryIndex::load_or_build(path) {
+        Ok(index) => Ok(index
+            .files
+            .iter()
+            .filter(|file| file.covers_any_year(years))
+            .filter(|file| {
+                filter.is_none_or(|(column, values)| {
+                    values.is_empty() || file.may_contain_any(column, values)
+                })
+            })
+            .map(|file| path.join(&file.path))
+            .collect()),
+        Err(e) => {
+            log::warn!("Registry index unavailable for {}: {e}", path.display());
+            let mut files = ParquetReader::list_parquet_files(path)?;
+            files.retain(|file| year_from_file_name(file).is_none_or(|year| years.contains(&year)));
+            files.sort();
+            Ok(files)
+        }
+    }
+}
+
+/// Register a Parquet file or registry directory as a table, using the directory index
+/// to register only files that can contain the filter values
+///
+/// `filter` is a string column and its values (usually the PNR column and a PNR set).
+/// The table is not filtered; apply the filter to the table as before. The keys of a
+/// Hive-partitioned directory become columns that prune partitions when filtered on, and
+/// the partitions of the selected files are read whole.
+pub async fn register_indexed_parquet(
+    ctx: &SessionContext,
+    table_name: &str,
+    path: impl AsRef<Path>,
+    options: ParquetReadOptions<'_>,
+    filter: Option<(&str, &HashSet<String>)>,
+) -> Result<()> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        let all_files = select_indexed_files(path, None)?;
+        let columns = hive_partition_columns(path, &all_files);
+        if !columns.is_empty() {
+            let files = match filter {
+                Some(_) => select_indexed_files(path, filter)?,
+                None => all_files.clone(),
+            };
+            let df = if files.is_empty() {
+                // No file matches: an empty table with the schema of the files
+                read_partitioned_files(ctx, path, &all_files, options, &columns)
+                    .await?
+                    .limit(0, Some(0))?
+            } else {
+                read_partitioned_files(ctx, path, &files, options, &columns).await?
+            };
+            ctx.register_table(table_name, df.into_view())?;
+            return Ok(());
+        }
+    }
+    let path_str = path.to_string_lossy().to_string();
+    let Some((column, values)) = filter.filter(|(_, values)| !values.is_empty() && path.is_dir())
+    else {
+        ctx.register_parquet(table_name, &path_str, options).await?;
+        return Ok(());
+    };
+
+    let index = match RegistryIndex::load_or_build(path) {
+        Ok(index) => index,
+        Err(e) => {
+            log::warn!("Registry index unavailable for {}: {e}", path.display());
+            ctx.register_parquet(table_name, &path_str, options).await?;
+            return Ok(());
+        }
+    };
+
+    let files = index.files_for_values(column, values);
+    log::info!(
+        "Index selected {} of {} files in {} for {} values of {column}",
+        files.len(),
+        index.files.len(),
+        path.display(),
+        values.len()
+    );
+    if files.len() == index.files.len() {
+        ctx.register_parquet(table_name, &path_str, options).await?;
+        return Ok(());
+    }
+
+    let df = match (files.is_empty(), index.paths().first()) {
+        (false, _) => {
+            let files: Vec<String> = files
+                .iter()
+                .map(|file| file.to_string_lossy().to_string())
+                .collect();
+            ctx.read_parquet(files, options).await?
+        }
+        // No file matches: register an empty table with the schema of the files
+        (true, Some(first)) => {
+            let schema = ctx
+                .read_parquet(first.to_string_lossy().to_string(), options)
+                .await?
+                .schema()
+                .inner()
+                .clone();
+            ctx.read_table(Arc::new(MemTable::try_new(schema, vec![vec![]])?))?
+        }
+        (true, None) => {
+            ctx.register_parquet(table_name, &path_str, options).await?;
+            return Ok(());
+        }
+    };
+    ctx.register_table(table_name, df.into_view())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::datatypes::Field;
+    use arrow::record_batch::RecordBatch;
+
+    /// Write a registry file with a single `PNR` column
+    fn write_pnrs(path: &Path, pnrs: Vec<&str>) {
+        let schema = Arc::new(Schema::new(vec![Field::new("PNR", DataType::Utf8, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(pnrs))]).unwrap();
+        write_parquet(path, &batch);
+    }
+
+    fn pnr_set(pnrs: &[&str]) -> HashSet<String> {
+        pnrs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_index_selects_files() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        write_pnrs(
+            &dir.join("bef2000.parquet"),
+            vec!["0101001234", "0505051234", "0909091234"],
+        );
+        write_pnrs(
+            &dir.join("bef2001.parquet"),
+            vec!["0101001234", "0303031234"],
+        );
+
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        assert!(dir.join(INDEX_FILE_NAME).exists());
+        assert!(dir.join(SKETCH_FILE_NAME).exists());
+        let json = std::fs::read_to_string(dir.join(INDEX_FILE_NAME)).unwrap();
+        assert!(!json.contains("pnr_sketch"));
+        assert_eq!(index.files[0].pnr_sketch.as_ref().unwrap().distinct, 3);
+
+        // In range of both files, but only in the sketch of the first
+        assert_eq!(
+            index.files_for_values("PNR", &pnr_set(&["0505051234"])),
+            vec![dir.join("bef2000.parquet")]
+        );
+        assert!(index
+            .files_for_values("PNR", &pnr_set(&["1212121234"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_index_year_coverage() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        write_pnrs(&dir.join("bef2000.parquet"), vec!["0101001234"]);
+        write_pnrs(&dir.join("bef2001.parquet"), vec!["0303031234"]);
+        write_pnrs(
+            &dir.join("year=2002").join("part.parquet"),
+            vec!["0303031234"],
+        );
+        write_pnrs(&dir.join("other.parquet"), vec!["0303031234"]);
+
+        // 2001-06-01 to 2003-02-01
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, false),
+            Field::new("D_INDDTO", DataType::Date32, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["0303031234", "0303031234"])),
+                Arc::new(arrow::array::Date32Array::from(vec![11_474, 12_084])),
+            ],
+        )
+        .unwrap();
+        write_parquet(&dir.join("contacts.parquet"), &batch);
+
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        let years = |name: &str| {
+            index
+                .files
+                .iter()
+                .find(|file| file.path == name)
+                .map(|file| file.years.iter().copied().collect::<Vec<_>>())
+                .unwrap()
+        };
+        assert_eq!(years("bef2000.parquet"), vec![2000]);
+        assert_eq!(years("year=2002/part.parquet"), vec![2002]);
+        assert_eq!(years("contacts.parquet"), vec![2001, 2002, 2003]);
+        assert!(years("other.parquet").is_empty());
+
+        // Files with unknown years are kept
+        assert_eq!(
+            index.files_for_years(&(2002..=2002)),
+            vec![
+                dir.join("contacts.parquet"),
+                dir.join("other.parquet"),
+                dir.join("year=2002/part.parquet"),
+            ]
+        );
+        assert_eq!(
+            select_indexed_files_for_years(
+                dir,
+                Some(("PNR", &pnr_set(&["0303031234"]))),
+                &(2000..=2001)
+            )
+            .unwrap(),
+            vec![
+                dir.join("bef2001.parquet"),
+                dir.join("contacts.parquet"),
+                dir.join("other.parquet"),
+            ]
+        );
+    }
+
+    /// The index of a directory that cannot be written is kept in the cache directory
+    #[test]
+    fn test_index_falls_back_to_cache_dir() {
+        let temp = temp_dir();
+        let dir = temp.path().join("registry");
+        let cache = temp.path().join("cache");
+        write_pnrs(&dir.join("bef2000.parquet"), vec!["0101001234"]);
+        // A directory in place of the index file makes saving it fail
+        std::fs::create_dir(dir.join(INDEX_FILE_NAME)).unwrap();
+
+        let index = RegistryIndex::load_or_build_with_fallback(&dir, Some(&cache)).unwrap();
+        let fallback = fallback_index_dir(&cache, &dir);
+        let saved = fallback.join(INDEX_FILE_NAME);
+        assert!(saved.is_file());
+        assert!(fallback.join(SKETCH_FILE_NAME).is_file());
+        let modified = std::fs::metadata(&saved).unwrap().modified().unwrap();
+
+        // The next load uses the saved index instead of indexing again
+        let reloaded = RegistryIndex::load_or_build_with_fallback(&dir, Some(&cache)).unwrap();
+        assert_eq!(reloaded.files, index.files);
+        assert!(reloaded.files[0].pnr_sketch.is_some());
+        assert_eq!(
+            std::fs::metadata(&saved).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+
+    #[test]
+    fn test_changed_file_is_reindexed() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        write_pnrs(&dir.join("bef2000.parquet"), vec!["0101001234"]);
+        write_pnrs(
+            &dir.join("bef2001.parquet"),
+            vec!["0101001234", "0303031234"],
+        );
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        let unchanged = index.files[0].clone();
+
+        write_pnrs(&dir.join("bef2001.parquet"), vec!["1212121234"]);
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        assert_eq!(index.files[0], unchanged);
+        assert_eq!(
+            index.files_for_values("PNR", &pnr_set(&["1212121234"])),
+            vec![dir.join("bef2001.parquet")]
+        );
+    }
+
+    /// A file rewritten with the same size is re-indexed because its modification time
+    /// changed
+    #[test]
+    fn test_mtime_change_invalidates_entry() {
+        let temp = temp_dir();
+        let path = temp.path().join("bef2000.parquet");
+        write_pnrs(&path, vec!["0101001234"]);
+        let index = RegistryIndex::load_or_build(temp.path()).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        write_pnrs(&path, vec!["1212121234"]);
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified + std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(file.metadata().unwrap().len(), index.files[0].size);
+
+        let index = RegistryIndex::load_or_build(temp.path()).unwrap();
+        assert_eq!(
+            index.files_for_values("PNR", &pnr_set(&["1212121234"])),
+            vec![path]
+        );
+    }
+
+    #[test]
+    fn test_missing_sketch_file_rebuilds_sketches() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        write_pnrs(&dir.join("bef2000.parquet"), vec!["0101001234"]);
+        RegistryIndex::load_or_build(dir).unwrap();
+
+        std::fs::remove_file(dir.join(SKETCH_FILE_NAME)).unwrap();
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        assert!(index.files.iter().all(|file| file.pnr_sketch.is_some()));
+        assert!(dir.join(SKETCH_FILE_NAME).exists());
+    }
+
+    /// Unreadable index and sketch files are rebuilt rather than failing the load
+    #[test]
+    fn test_unreadable_index_is_rebuilt() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        write_pnrs(&dir.join("bef2000.parquet"), vec!["0101001234"]);
+        RegistryIndex::load_or_build(dir).unwrap();
+
+        std::fs::write(dir.join(INDEX_FILE_NAME), "{ not json").unwrap();
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        assert_eq!(index.files.len(), 1);
+        let json = std::fs::read_to_string(dir.join(INDEX_FILE_NAME)).unwrap();
+        assert!(serde_json::from_str::<RegistryIndex>(&json).is_ok());
+
+        std::fs::write(dir.join(SKETCH_FILE_NAME), b"IDSSKTCH\xff").unwrap();
+        let index = RegistryIndex::load_or_build(dir).unwrap();
+        assert!(index.files[0].pnr_sketch.is_some());
+        assert!(read_sketches(&dir.join(SKETCH_FILE_NAME)).is_ok());
+    }
+
+    #[test]
+    fn test_sketch_sized_for_pnr_count() {
+        let pnrs: Vec<String> = (0..200_000).map(|i| format!("{i:010}")).collect();
+        let sketch = PnrSketch::from_pnrs(pnrs.iter().map(String::as_str));
+        assert_eq!(sketch.distinct, 200_000);
+        assert!(pnrs.iter().all(|pnr| sketch.may_contain(pnr)));
+        // About 9.6 bits per PNR for a 1% false positive rate
+        assert!(sketch.size_bytes() < 200_000 * 10 / 8 + 8);
+
+        let false_positives = (200_000..220_000)
+            .filter(|i| sketch.may_contain(&format!("{i:010}")))
+            .count();
+        assert!(false_positives < 400, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn test_sketches_round_trip_through_sketch_file() {
+        let pnrs = ["0101001234", "0505051234"];
+        let file = IndexedFile {
+            path: "year=2000/part.parquet".to_string(),
+            size: 10,
+            modified_secs: 20,
+            modified_nanos: 30,
+            rows: 2,
+            row_groups: Vec::new(),
+            columns: BTreeMap::new(),
+            years: BTreeSet::from([2000]),
+            pnr_column: Some("PNR".to_string()),
+            pnr_sketch: Some(PnrSketch::from_pnrs(pnrs.into_iter())),
+        };
+        let mut bytes = Vec::new();
+        write_sketches(&mut bytes, std::slice::from_ref(&file)).unwrap();
+
+        let dir = temp_dir();
+        let path = dir.path().join(SKETCH_FILE_NAME);
+        std::fs::write(&path, &bytes).unwrap();
+        let sketches = read_sketches(&path).unwrap();
+        let (stamp, sketch) = &sketches["year=2000/part.parquet"];
+        assert!(file.matches_stamp(*stamp));
+        assert_eq!(Some(sketch), file.pnr_sketch.as_ref());
+
+        // A truncated file is rejected rather than read as a partial sketch
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(read_sketches(&path).is_err());
+    }
+}
@@ -4,10 +4,14 @@
 //! It allows for filtering files based on statistics about their contents without
 //! reading the entire file, significantly improving performance for large datasets.
 
+mod bloom;
+mod index;
 mod statistics;
 mod predicate;
 mod provider;
 
+pub use bloom::*;
+pub use index::*;
 pub use statistics::*;
 pub use predicate::*;
 pub use provider::*;
\ No newline at end of file
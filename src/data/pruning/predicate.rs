@@ -1,7 +1,7 @@
 use crate::error::{IdsError, Result};
-use crate::data::filter::PnrFilter;
+use crate::data::filter::{pnr_range_expr, PnrFilter};
 use arrow::datatypes::SchemaRef;
-use datafusion::common::DFSchema;
+use datafusion::common::{Column, DFSchema};
 use datafusion::logical_expr::{col, lit, Expr};
 use datafusion::physical_expr::create_physical_expr;
 use datafusion::physical_expr::execution_props::ExecutionProps;
@@ -21,10 +21,14 @@ pub fn create_pnr_pruning_predicate(
         let physical_expr = create_physical_expr(&expr, &df_schema, &props)?;
         Ok(PruningPredicate::try_new(physical_expr, schema)?)
     } else {
-        // Create IN expression for PNR
+        // The PNR range prunes by min/max statistics, the IN list by contained values
         let pnr_values: Vec<Expr> = pnrs.iter().map(|pnr| lit(pnr.clone())).collect();
 
         let expr = col("PNR").in_list(pnr_values, false);
+        let expr = match pnr_range_expr(pnrs, "PNR") {
+            Some(range) => range.and(expr),
+            None => expr,
+        };
 
         // Create pruning predicate
         let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
@@ -61,10 +65,14 @@ pub fn create_pruning_predicate(
         )));
     }
 
-    // Create IN expression
+    // The PNR range prunes by min/max statistics, the IN list by contained values
     let pnr_values: Vec<Expr> = filter.pnrs().iter().map(|pnr| lit(pnr.clone())).collect();
 
-    let expr = col(column_name).in_list(pnr_values, false);
+    let expr = Expr::Column(Column::from_name(column_name)).in_list(pnr_values, false);
+    let expr = match filter.to_pruning_expr() {
+        Some(range) => range.and(expr),
+        None => expr,
+    };
 
     // Create pruning predicate
     let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
@@ -81,10 +89,10 @@ pub async fn create_context_with_pruning(
     pnr_filter: Option<&PnrFilter>,
     table_name: &str,
 ) -> Result<datafusion::execution::context::SessionContext> {
-    use datafusion::execution::context::{SessionConfig, SessionContext};
+    use datafusion::execution::context::SessionConfig;
     use datafusion::prelude::*;
     
-    let ctx = SessionContext::new_with_config(
+    let ctx = crate::data::io::execution::session_context_with_config(
         SessionConfig::new()
             .with_target_partitions(4)
             .with_batch_size(8192),
@@ -96,16 +104,14 @@ pub async fn create_context_with_pruning(
     // Register the table
     ctx.register_parquet(table_name, path, read_options).await?;
 
-    // Apply filter if provided
-    if let Some(filter) = pnr_filter {
-        if let Some(expr) = filter.to_expr() {
-            // Get the dataframe and apply filter
-            let df = ctx.table(table_name).await?;
-            let filtered_df = df.filter(expr)?;
+    // Apply filter if provided, as a semi-join against the PNR set
+    if let Some(filter) = pnr_filter.filter(|filter| !filter.is_empty()) {
+        let df = ctx.table(table_name).await?;
+        let filtered_df = filter.apply_to_dataframe(df)?;
 
-            // Register the filtered dataframe
-            ctx.register_table(table_name, filtered_df.into_view())?;
-        }
+        // Register the filtered dataframe
+        ctx.deregister_table(table_name)?;
+        ctx.register_table(table_name, filtered_df.into_view())?;
     }
 
     Ok(ctx)
@@ -1,3 +1,4 @@
+use super::index::RegistryIndex;
 use super::statistics::RegistryPruningStatistics;
 use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
@@ -32,6 +33,12 @@ impl PrunableTableProvider {
             file_list,
         }
     }
+
+    /// Create a provider over the files of a registry index, pruning with its statistics
+    #[must_use] pub fn from_index(schema: SchemaRef, index: &RegistryIndex) -> Self {
+        let statistics = index.pruning_statistics(schema.clone());
+        Self::new(schema, statistics, index.paths())
+    }
 }
 
 #[async_trait::async_trait]
@@ -1,3 +1,4 @@
+use super::index::PnrSketch;
 use crate::error::Result;
 use arrow::array::{ArrayRef, BooleanArray, StringArray, UInt32Array, UInt64Array};
 use arrow::datatypes::{DataType, SchemaRef};
@@ -24,8 +25,10 @@ pub struct FileStatistics {
     pub max_values: HashMap<String, ScalarValue>,
     /// Optional list of all unique values for certain columns (for IN filtering)
     pub unique_values: HashMap<String, HashSet<String>>,
-    /// Optional bloom filter for efficient membership testing
-    pub bloom_filters: HashMap<String, Vec<u8>>,
+    /// Columns with Parquet bloom filters in the file, checked for IN filtering
+    pub bloom_filter_columns: HashSet<String>,
+    /// PNR sketches from the registry index, checked for IN filtering
+    pub pnr_sketches: HashMap<String, Arc<PnrSketch>>,
 }
 
 impl FileStatistics {
@@ -38,7 +41,8 @@ impl FileStatistics {
             min_values: HashMap::new(),
             max_values: HashMap::new(),
             unique_values: HashMap::new(),
-            bloom_filters: HashMap::new(),
+            bloom_filter_columns: HashSet::new(),
+            pnr_sketches: HashMap::new(),
         }
     }
 
@@ -60,9 +64,41 @@ impl FileStatistics {
         self
     }
 
+    /// Mark a column as having Parquet bloom filters
+    #[must_use] pub fn with_bloom_filter(mut self, column: &str) -> Self {
+        self.bloom_filter_columns.insert(column.to_string());
+        self
+    }
+
+    /// Add an indexed PNR sketch for a column
+    #[must_use] pub fn with_pnr_sketch(mut self, column: &str, sketch: Arc<PnrSketch>) -> Self {
+        self.pnr_sketches.insert(column.to_string(), sketch);
+        self
+    }
+
+    /// Check the file's PNR sketch and bloom filters (and page statistics) for a set of values
+    ///
+    /// Returns `None` if the column has neither or the file cannot be read.
+    fn may_contain_values(&self, column: &str, values: &HashSet<String>) -> Option<bool> {
+        if let Some(sketch) = self.pnr_sketches.get(column) {
+            if !values.iter().any(|value| sketch.may_contain(value)) {
+                return Some(false);
+            }
+        }
+        if !self.bloom_filter_columns.contains(column) {
+            return self.pnr_sketches.contains_key(column).then_some(true);
+        }
+        super::file_may_contain_pnrs(&self.path, column, values).ok()
+    }
+
     /// Check if a file should be processed based on a filter
     #[must_use] pub fn should_process(&self, expr: &Expr) -> bool {
         match expr {
+            // A conjunction can be skipped if either side can
+            Expr::BinaryExpr(binary) if binary.op == datafusion::logical_expr::Operator::And => {
+                self.should_process(&binary.left) && self.should_process(&binary.right)
+            }
+
             // Handle binary expressions (like =, >, <, etc.)
             Expr::BinaryExpr(binary) => {
                 // Extract column name if left side is a column
@@ -114,6 +150,40 @@ impl FileStatistics {
                             }
                         }
 
+                        // For column >= value (e.g. the lower bound of a PNR range)
+                        if binary.op == datafusion::logical_expr::Operator::GtEq {
+                            if let Expr::Literal(val) = &*binary.right {
+                                return match (max_value, val) {
+                                    (
+                                        ScalarValue::Utf8(Some(max)),
+                                        ScalarValue::Utf8(Some(val)),
+                                    ) => max >= val,
+                                    (
+                                        ScalarValue::UInt32(Some(max)),
+                                        ScalarValue::UInt32(Some(val)),
+                                    ) => max >= val,
+                                    _ => true,
+                                };
+                            }
+                        }
+
+                        // For column <= value (e.g. the upper bound of a PNR range)
+                        if binary.op == datafusion::logical_expr::Operator::LtEq {
+                            if let Expr::Literal(val) = &*binary.right {
+                                return match (min_value, val) {
+                                    (
+                                        ScalarValue::Utf8(Some(min)),
+                                        ScalarValue::Utf8(Some(val)),
+                                    ) => min <= val,
+                                    (
+                                        ScalarValue::UInt32(Some(min)),
+                                        ScalarValue::UInt32(Some(val)),
+                                    ) => min <= val,
+                                    _ => true,
+                                };
+                            }
+                        }
+
                         // For column < value
                         if binary.op == datafusion::logical_expr::Operator::Lt {
                             if let Expr::Literal(val) = &*binary.right {
@@ -159,11 +229,18 @@ impl FileStatistics {
 
                     // For PNR IN list - special optimization for most common case
                     if column_name == "PNR" || column_name == "CPR" {
-                        // If we have a bloom filter, use it for faster checking
-                        if let Some(_bloom_filter) = self.bloom_filters.get(column_name) {
-                            // TODO: Implement bloom filter checking
-                            // This would check if any value in the IN list has a chance of being in the filter
-                            return true;
+                        let values: HashSet<String> = in_list
+                            .list
+                            .iter()
+                            .filter_map(|list_expr| match list_expr {
+                                Expr::Literal(ScalarValue::Utf8(Some(val))) => Some(val.clone()),
+                                _ => None,
+                            })
+                            .collect();
+                        if values.len() == in_list.list.len() && !in_list.negated {
+                            if let Some(may_contain) = self.may_contain_values(column_name, &values) {
+                                return may_contain;
+                            }
                         }
                     }
                 }
@@ -356,6 +433,10 @@ impl PruningStatistics for RegistryPruningStatistics {
                         }
                     }
                     false // No overlap found
+                } else if let Some(may_contain) = utf8_values(values)
+                    .and_then(|strings| file.may_contain_values(column_name, &strings))
+                {
+                    may_contain
                 } else {
                     // If we don't have unique values, check min/max bounds
                     if let (Some(min), Some(max)) = (
@@ -395,18 +476,28 @@ impl PruningStatistics for RegistryPruningStatistics {
     }
 }
 
+/// The values of a set if they are all non-null strings
+fn utf8_values(values: &HashSet<ScalarValue>) -> Option<HashSet<String>> {
+    values
+        .iter()
+        .map(|value| match value {
+            ScalarValue::Utf8(Some(s)) => Some(s.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
 /// Extract statistics from parquet files for a column
 pub async fn extract_column_statistics(
     paths: &[impl AsRef<Path>],
     column_name: &str,
 ) -> Result<HashMap<PathBuf, (ScalarValue, ScalarValue)>> {
-    use datafusion::execution::context::SessionContext;
     use datafusion::functions_aggregate::expr_fn::{max, min};
     use datafusion::logical_expr::col;
     use datafusion::prelude::*;
 
     let mut result = HashMap::new();
-    let ctx = SessionContext::new();
+    let ctx = crate::data::io::execution::session_context();
 
     for path in paths {
         let path = path.as_ref();
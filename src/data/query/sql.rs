@@ -23,7 +23,7 @@ impl RegistrySqlEngine {
     /// Create a new SQL engine
     #[must_use] pub fn new() -> Self {
         Self {
-            ctx: SessionContext::new(),
+            ctx: crate::data::io::execution::session_context(),
             registered_tables: HashMap::new(),
         }
     }
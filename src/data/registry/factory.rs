@@ -1,5 +1,11 @@
 use crate::data::registry::traits::{AnyRegisterLoader, RegisterLoader};
+use crate::data::schema::drift::DriftRules;
+use crate::data::schema::registry::{
+    AkmSchema, BefSchema, DodSchema, DodsaarsagSchema, IdanSchema, IndSchema, Lpr2Schema,
+    Lpr3Schema, MfrSchema, UddfSchema, VndsSchema,
+};
 use crate::data::schema::traits::RegistrySchema;
+use arrow::datatypes::SchemaRef;
 use crate::error::{IdsError, Result};
 use std::collections::HashMap;
 use std::path::Path;
@@ -15,6 +21,29 @@ use super::loaders::mfr::MfrRegister;
 use super::loaders::uddf::UddfRegister;
 use super::loaders::vnds::VndsRegister;
 
+/// The schema of a registry and its declared column roles
+#[derive(Debug, Clone)]
+pub struct RegistrySchemaInfo {
+    /// Registry name, as returned by the loader's `register_name`
+    pub name: &'static str,
+    pub schema: SchemaRef,
+    pub drift_rules: DriftRules,
+    pub key_columns: Vec<&'static str>,
+    pub pnr_columns: Vec<&'static str>,
+}
+
+impl RegistrySchemaInfo {
+    fn of<S: RegistrySchema>(name: &'static str) -> Self {
+        Self {
+            name,
+            schema: S::schema_arc(),
+            drift_rules: S::drift_rules(),
+            key_columns: S::key_columns(),
+            pnr_columns: S::pnr_columns(),
+        }
+    }
+}
+
 /// Factory for creating registry loaders with any schema type
 pub struct RegistryFactory;
 
@@ -39,31 +68,36 @@ impl RegistryFactory {
 
     /// Create a registry loader from a path (inferring the registry type from the path)
     pub fn from_path(path: &Path) -> Result<AnyRegisterLoader> {
+        Self::from_name(Self::registry_name_from_path(path)?)
+    }
+
+    /// Infer the registry name (as accepted by [`Self::from_name`]) from a path
+    pub fn registry_name_from_path(path: &Path) -> Result<&'static str> {
         let path_str = path.to_string_lossy().to_lowercase();
 
         // Try to infer registry from directory name
         if path_str.contains("akm") {
-            return Ok(Box::new(AkmRegister));
+            return Ok("akm");
         } else if path_str.contains("bef") {
-            return Ok(Box::new(BefRegister));
+            return Ok("bef");
         } else if path_str.contains("dod") && !path_str.contains("dodsaarsag") {
-            return Ok(Box::new(DodRegister));
+            return Ok("dod");
         } else if path_str.contains("dodsaarsag") {
-            return Ok(Box::new(DodsaarsagRegister));
+            return Ok("dodsaarsag");
         } else if path_str.contains("idan") {
-            return Ok(Box::new(IdanRegister));
+            return Ok("idan");
         } else if path_str.contains("ind") && !path_str.contains("idan") {
-            return Ok(Box::new(IndRegister));
+            return Ok("ind");
         } else if path_str.contains("lpr_") || path_str.contains("lpr2") {
-            return Ok(Box::new(Lpr2Register));
+            return Ok("lpr2");
         } else if path_str.contains("lpr3") {
-            return Ok(Box::new(Lpr3Register));
+            return Ok("lpr3");
         } else if path_str.contains("mfr") {
-            return Ok(Box::new(MfrRegister));
+            return Ok("mfr");
         } else if path_str.contains("uddf") {
-            return Ok(Box::new(UddfRegister));
+            return Ok("uddf");
         } else if path_str.contains("vnds") {
-            return Ok(Box::new(VndsRegister));
+            return Ok("vnds");
         }
 
         // If we can't infer from the path, return an error
@@ -73,6 +107,29 @@ impl RegistryFactory {
         )))
     }
 
+    /// Get the schema of a registry from its name
+    pub fn schema_info_from_name(name: &str) -> Result<RegistrySchemaInfo> {
+        match name.to_lowercase().as_str() {
+            "akm" => Ok(RegistrySchemaInfo::of::<AkmSchema>("AKM")),
+            "bef" => Ok(RegistrySchemaInfo::of::<BefSchema>("BEF")),
+            "dod" => Ok(RegistrySchemaInfo::of::<DodSchema>("DOD")),
+            "dodsaarsag" => Ok(RegistrySchemaInfo::of::<DodsaarsagSchema>("DODSAARSAG")),
+            "idan" => Ok(RegistrySchemaInfo::of::<IdanSchema>("IDAN")),
+            "ind" => Ok(RegistrySchemaInfo::of::<IndSchema>("IND")),
+            "lpr2" => Ok(RegistrySchemaInfo::of::<Lpr2Schema>("LPR2")),
+            "lpr3" => Ok(RegistrySchemaInfo::of::<Lpr3Schema>("LPR3")),
+            "mfr" => Ok(RegistrySchemaInfo::of::<MfrSchema>("MFR")),
+            "uddf" => Ok(RegistrySchemaInfo::of::<UddfSchema>("UDDF")),
+            "vnds" => Ok(RegistrySchemaInfo::of::<VndsSchema>("VNDS")),
+            _ => Err(IdsError::Validation(format!("Unknown registry: {name}"))),
+        }
+    }
+
+    /// Get the schema of a registry from a path (inferring the registry type from the path)
+    pub fn schema_info_from_path(path: &Path) -> Result<RegistrySchemaInfo> {
+        Self::schema_info_from_name(Self::registry_name_from_path(path)?)
+    }
+
     /// Create instances of all available loaders, mapped by name
     #[must_use] pub fn create_all() -> HashMap<&'static str, AnyRegisterLoader> {
         let mut loaders = HashMap::<&'static str, AnyRegisterLoader>::new();
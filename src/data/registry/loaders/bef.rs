@@ -1,9 +1,9 @@
 use crate::data::registry::traits::{PnrFilter, RegisterLoader};
 use crate::data::schema::registry::bef::BefSchema;
-use crate::data::schema::traits::RegistrySchema;
+use crate::data::schema::drift::{register_reconciled_parquet, SchemaReconciler};
 use crate::error::{IdsError, Result};
 use arrow::record_batch::RecordBatch;
-use datafusion::prelude::*;
+use crate::data::io::execution::session_context;
 use std::path::Path;
 
 /// BEF registry loader for population demographic information
@@ -32,10 +32,7 @@ impl RegisterLoader for BefRegister {
         pnr_filter: Option<&PnrFilter>,
     ) -> Result<Vec<RecordBatch>> {
         // Create a context
-        let ctx = SessionContext::new();
-        
-        // Get schema
-        let schema = Self::SchemaType::schema_arc();
+        let ctx = session_context();
         
         // Register the Parquet file/directory as a table
         let table_name = self.register_name().to_lowercase();
@@ -49,44 +46,24 @@ impl RegisterLoader for BefRegister {
             )));
         }
         
-        // Register the parquet data
-        ctx.register_parquet(
+        // Register the parquet data, reconciling files whose schema drifted
+        let reconciler = SchemaReconciler::for_registry::<Self::SchemaType>();
+        register_reconciled_parquet(
+            &ctx,
             &table_name,
             base_path,
-            ParquetReadOptions::default().schema(schema.as_ref()),
+            &reconciler,
+            pnr_filter.map(|filter| filter.index_lookup("PNR")),
         )
-        .await?;
+        .await?
+        .log(self.register_name());
         
         // Get the table as a dataframe
         let mut df = ctx.table(&table_name).await?;
         
         // Apply filter if provided
         if let Some(filter) = pnr_filter {
-            if filter.is_direct_filter() {
-                // Convert HashSet to a list of literals for IN expression
-                let pnr_list: Vec<Expr> = filter
-                    .pnrs()
-                    .iter()
-                    .map(|pnr| lit(pnr.clone()))
-                    .collect();
-                
-                if !pnr_list.is_empty() {
-                    // Create filter: PNR IN (pnr1, pnr2, ...), false = not negated
-                    df = df.filter(col("PNR").in_list(pnr_list, false))?;
-                }
-            } else if let Some(relation_col) = filter.relation_column() {
-                // Similar approach for relation filtering
-                let pnr_list: Vec<Expr> = filter
-                    .pnrs()
-                    .iter()
-                    .map(|pnr| lit(pnr.clone()))
-                    .collect();
-                
-                if !pnr_list.is_empty() {
-                    // Create filter: relation_col IN (pnr1, pnr2, ...), false = not negated
-                    df = df.filter(col(relation_col).in_list(pnr_list, false))?;
-                }
-            }
+            df = filter.apply_to_dataframe(df, "PNR")?;
         }
         
         // Execute and collect the results
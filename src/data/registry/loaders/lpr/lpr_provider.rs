@@ -1,24 +1,43 @@
 //! LPR table provider for `DataFusion` integration
 //!
 //! This module contains a custom table provider for LPR data.
+//!
+//! The query's PNR and date predicates are evaluated in the Parquet scan: row groups are
+//! pruned by their statistics and bloom filters, pages by the page index, and the
+//! remaining rows are filtered exactly. The provider's PNR set is applied as a semi-join
+//! against an in-memory table ([`semi_join_values`]), whose PNR range is pushed into the
+//! scan, so the plan does not grow with the cohort.
+//!
+//! The LPR loaders do not use this provider; they select files with the registry index
+//! ([`crate::data::pruning::register_indexed_parquet`]) and filter with the same semi-join.
 
+use crate::data::filter::pnr::semi_join_values;
 use crate::data::registry::loaders::lpr::{LprPaths, LprVersion};
 use crate::error::{IdsError, Result};
-use arrow::datatypes::SchemaRef;
+use arrow::datatypes::{DataType, SchemaRef};
 use datafusion::catalog::Session;
-use datafusion::datasource::listing::{ListingTableUrl, PartitionedFile};
+use datafusion::common::DFSchema;
+use datafusion::datasource::listing::PartitionedFile;
 use datafusion::datasource::object_store::ObjectStoreUrl;
 use datafusion::datasource::physical_plan::{FileScanConfigBuilder, ParquetSource};
+use datafusion::datasource::provider_as_source;
 use datafusion::datasource::source::DataSourceExec;
 use datafusion::datasource::{TableProvider, TableType};
-use datafusion::error::Result as DFResult;
+use datafusion::error::{DataFusionError, Result as DFResult};
+use datafusion::execution::SessionState;
 use datafusion::logical_expr::TableProviderFilterPushDown;
-use datafusion::logical_expr::{Expr, LogicalPlan};
+use datafusion::logical_expr::{Expr, LogicalPlan, LogicalPlanBuilder, Operator};
+use datafusion::physical_expr::expressions::Column as PhysicalColumn;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::limit::LocalLimitExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::DataFrame;
 
-// ExecutionPlan is used through type annotations so we don't need to import it directly
 use std::any::Any;
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -35,8 +54,7 @@ pub struct LprTableProvider {
     paths: LprPaths,
     /// Schema
     schema: SchemaRef,
-    /// PNR filter
-    #[allow(dead_code)]
+    /// PNR filter, applied to every scan as a semi-join
     pnr_filter: Option<HashSet<String>>,
 }
 
@@ -56,6 +74,120 @@ impl LprTableProvider {
         }
     }
 
+    /// The PNR column of the LPR version (LPR3 uses `CPR`)
+    const fn pnr_column(&self) -> &'static str {
+        match self.version {
+            LprVersion::V2 => "PNR",
+            LprVersion::V3 => "CPR",
+        }
+    }
+
+    /// Whether predicates on a column are evaluated in the scan
+    ///
+    /// These are the PNR column and date columns (`D_INDDTO`, `dato_start`, ...).
+    fn is_pushdown_column(&self, expr: &Expr) -> bool {
+        let Expr::Column(column) = expr else {
+            return false;
+        };
+        column.name == self.pnr_column()
+            || self.schema.field_with_name(&column.name).is_ok_and(|field| {
+                matches!(
+                    field.data_type(),
+                    DataType::Date32 | DataType::Date64 | DataType::Timestamp(_, _)
+                )
+            })
+    }
+
+    /// Whether a filter compares PNR or date columns with literals only
+    fn is_pushdown_filter(&self, expr: &Expr) -> bool {
+        let is_literal = |expr: &Expr| match expr {
+            Expr::Literal(_) => true,
+            Expr::Cast(cast) => matches!(cast.expr.as_ref(), Expr::Literal(_)),
+            _ => false,
+        };
+
+        match expr {
+            Expr::BinaryExpr(binary) if binary.op == Operator::And => {
+                self.is_pushdown_filter(&binary.left) && self.is_pushdown_filter(&binary.right)
+            }
+            Expr::BinaryExpr(binary) => {
+                matches!(
+                    binary.op,
+                    Operator::Eq
+                        | Operator::NotEq
+                        | Operator::Lt
+                        | Operator::LtEq
+                        | Operator::Gt
+                        | Operator::GtEq
+                ) && ((self.is_pushdown_column(&binary.left) && is_literal(&binary.right))
+                    || (is_literal(&binary.left) && self.is_pushdown_column(&binary.right)))
+            }
+            Expr::InList(in_list) => {
+                self.is_pushdown_column(&in_list.expr) && in_list.list.iter().all(is_literal)
+            }
+            Expr::Between(between) => {
+                self.is_pushdown_column(&between.expr)
+                    && is_literal(&between.low)
+                    && is_literal(&between.high)
+            }
+            _ => false,
+        }
+    }
+
+    /// The predicate evaluated in the scan: the conjunction of the pushed-down filters
+    fn scan_predicate(&self, filters: &[Expr]) -> Option<Expr> {
+        filters
+            .iter()
+            .filter(|filter| self.is_pushdown_filter(filter))
+            .cloned()
+            .reduce(Expr::and)
+    }
+
+    /// Scan the rows of the PNR set: an unfiltered scan semi-joined with the PNRs
+    ///
+    /// The semi-join's range predicate is pushed into the scan of the unfiltered provider
+    /// together with the query's filters.
+    async fn scan_pnrs(
+        &self,
+        state: &dyn Session,
+        pnrs: &HashSet<String>,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> DFResult<Arc<dyn ExecutionPlan>> {
+        let Some(state) = state.as_any().downcast_ref::<SessionState>() else {
+            return Err(DataFusionError::NotImplemented(
+                "PNR-filtered LPR scans need a DataFusion SessionState".to_string(),
+            ));
+        };
+
+        let unfiltered = Self::new(self.version, self.paths.clone(), self.schema.clone(), None);
+        let plan = LogicalPlanBuilder::scan_with_filters(
+            "lpr",
+            provider_as_source(Arc::new(unfiltered)),
+            None,
+            filters.to_vec(),
+        )?
+        .build()?;
+        let df = semi_join_values(DataFrame::new(state.clone(), plan), pnrs, self.pnr_column())
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+
+        let columns: Vec<&str> = match projection {
+            Some(proj) => proj
+                .iter()
+                .map(|index| self.schema.field(*index).name().as_str())
+                .collect(),
+            None => self
+                .schema
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect(),
+        };
+        let df = df.select_columns(&columns)?.limit(0, limit)?;
+        state.create_physical_plan(df.logical_plan()).await
+    }
+
     /// Find LPR files based on the version and paths
     fn find_lpr_files(&self) -> Result<Vec<PathBuf>> {
         let mut files = Vec::new();
@@ -164,39 +296,34 @@ impl TableProvider for LprTableProvider {
     }
 
     fn supports_filters_pushdown(&self, filters: &[&Expr]) -> DFResult<Vec<FilterPushdown>> {
-        // We can push down PNR filter but not complex filters
-        let mut pushdowns = Vec::new();
-
-        for &filter in filters {
-            if let Expr::BinaryExpr(expr) = filter {
-                if let Expr::Column(col) = expr.left.as_ref() {
-                    // LPR v2 uses PNR, LPR v3 uses CPR
-                    let pnr_col = match self.version {
-                        LprVersion::V2 => "PNR",
-                        LprVersion::V3 => "CPR",
-                    };
-
-                    if col.name == pnr_col {
-                        pushdowns.push(FilterPushdown::Exact);
-                    }
+        // PNR and date predicates are evaluated exactly in the scan
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if self.is_pushdown_filter(filter) {
+                    FilterPushdown::Exact
+                } else {
+                    FilterPushdown::Unsupported
                 }
-            }
-        }
-
-        if pushdowns.is_empty() {
-            Ok(vec![FilterPushdown::Unsupported])
-        } else {
-            Ok(pushdowns)
-        }
+            })
+            .collect())
     }
 
     async fn scan(
         &self,
-        _state: &dyn Session,
+        state: &dyn Session,
         projection: Option<&Vec<usize>>,
-        _filters: &[Expr],
+        filters: &[Expr],
         limit: Option<usize>,
-    ) -> DFResult<Arc<dyn datafusion::physical_plan::ExecutionPlan>> {
+    ) -> DFResult<Arc<dyn ExecutionPlan>> {
+        if let Some(pnrs) = &self.pnr_filter {
+            if self.schema.field_with_name(self.pnr_column()).is_ok() {
+                return self
+                    .scan_pnrs(state, pnrs, projection, filters, limit)
+                    .await;
+            }
+        }
+
         // Find all the parquet files
         let parquet_files = self.find_lpr_files().map_err(|e| {
             datafusion::error::DataFusionError::Execution(format!("Error finding LPR files: {e}"))
@@ -208,9 +335,6 @@ impl TableProvider for LprTableProvider {
             ));
         }
 
-        // Create the listing table URL
-        let _url = ListingTableUrl::parse(self.paths.base_path.to_string_lossy())?;
-
         // Create the partitioned files list
         let mut files = Vec::new();
         for file_path in parquet_files {
@@ -228,14 +352,28 @@ impl TableProvider for LprTableProvider {
 
         // In DataFusion 47.0.0, we use DataSourceExec with a FileScanConfig
 
+        // Create the scan predicate from the pushed-down filters
+        let predicate_expr = self.scan_predicate(filters);
+        let predicate = match &predicate_expr {
+            Some(expr) => {
+                let df_schema = DFSchema::try_from(self.schema.as_ref().clone())?;
+                Some(state.create_physical_expr(expr.clone(), &df_schema)?)
+            }
+            None => None,
+        };
+
         // Create the format (ParquetSource as the FileSource implementation)
-        let format = ParquetSource::default()
+        let mut format = ParquetSource::default()
             .with_enable_page_index(true) // Enable page-level pruning
+            .with_bloom_filter_on_read(true) // Use bloom filters where present
             .with_pushdown_filters(true); // Enable predicate pushdown
+        if let Some(predicate) = &predicate {
+            format = format.with_predicate(self.schema.clone(), predicate.clone());
+        }
         let format_arc = Arc::new(format);
 
         // Create FileScanConfig using builder
-        let url = ObjectStoreUrl::parse(self.paths.base_path.to_string_lossy())?;
+        let url = ObjectStoreUrl::parse("file://")?;
 
         // Start building the config
         let mut config_builder = FileScanConfigBuilder::new(url, self.schema.clone(), format_arc);
@@ -245,20 +383,167 @@ impl TableProvider for LprTableProvider {
             config_builder = config_builder.with_file(file);
         }
 
-        // Add projection and limit if provided
+        let Some(predicate_expr) = predicate_expr else {
+            // Add projection and limit if provided
+            if let Some(proj) = projection {
+                config_builder = config_builder.with_projection(Some(proj.clone()));
+            }
+
+            if let Some(lim) = limit {
+                config_builder = config_builder.with_limit(Some(lim));
+            }
+
+            // Create DataSourceExec with the config and return it
+            return Ok(DataSourceExec::from_data_source(config_builder.build()));
+        };
+
+        // The filters are reported as exact, so the rows that survive pruning are filtered
+        // here, before projection and limit. The scan reads the projected columns and the
+        // columns of the predicate.
+        let scan_columns: Vec<usize> = match projection {
+            Some(proj) => {
+                let mut columns: BTreeSet<usize> = proj.iter().copied().collect();
+                for column in predicate_expr.column_refs() {
+                    columns.insert(self.schema.index_of(&column.name)?);
+                }
+                columns.into_iter().collect()
+            }
+            None => (0..self.schema.fields().len()).collect(),
+        };
+        let scan_schema = DFSchema::try_from(self.schema.project(&scan_columns)?)?;
+        let filter = state.create_physical_expr(predicate_expr, &scan_schema)?;
+        config_builder = config_builder.with_projection(Some(scan_columns.clone()));
+
+        let scan: Arc<dyn ExecutionPlan> = DataSourceExec::from_data_source(config_builder.build());
+        let mut plan: Arc<dyn ExecutionPlan> = Arc::new(FilterExec::try_new(filter, scan)?);
+
         if let Some(proj) = projection {
-            config_builder = config_builder.with_projection(Some(proj.clone()));
+            let exprs = proj
+                .iter()
+                .map(|index| {
+                    let name = self.schema.field(*index).name().clone();
+                    let position = scan_columns.binary_search(index).map_err(|_| {
+                        DataFusionError::Internal(format!(
+                            "Projected column {name} is not in the LPR scan"
+                        ))
+                    })?;
+                    let column: Arc<dyn PhysicalExpr> =
+                        Arc::new(PhysicalColumn::new(&name, position));
+                    Ok((column, name))
+                })
+                .collect::<DFResult<Vec<_>>>()?;
+            plan = Arc::new(ProjectionExec::try_new(exprs, plan)?);
         }
 
         if let Some(lim) = limit {
-            config_builder = config_builder.with_limit(Some(lim));
+            plan = Arc::new(LocalLimitExec::new(plan, lim));
         }
 
-        // Build the config
-        let config = config_builder.build();
+        Ok(plan)
+    }
+}
 
-        // Create DataSourceExec with the config and return it
-        // DataSourceExec implements ExecutionPlan trait
-        Ok(DataSourceExec::from_data_source(config) as Arc<dyn datafusion::physical_plan::ExecutionPlan>)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Date32Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use datafusion::physical_plan::displayable;
+    use datafusion::prelude::{col, lit};
+
+    #[test]
+    fn test_pnr_and_date_pushdown() {
+        let temp = crate::test_utils::temp_dir();
+        let dir = temp.path().to_path_buf();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("CPR", DataType::Utf8, true),
+            Field::new("dato_start", DataType::Date32, true),
+            Field::new("kontakt_id", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "b", "c"])) as ArrayRef,
+                Arc::new(Date32Array::from(vec![18_000, 18_000, 19_000, 19_000])) as ArrayRef,
+                Arc::new(StringArray::from(vec!["1", "2", "3", "4"])) as ArrayRef,
+            ],
+        )
+        .unwrap();
+        crate::test_utils::write_parquet(&dir.join("kontakter.parquet"), &batch);
+
+        let mut paths = LprPaths::new(&dir);
+        paths.kontakter_path = Some(dir.clone());
+        let pnrs: HashSet<String> = ["b", "c"].iter().map(ToString::to_string).collect();
+        let provider = LprTableProvider::new(LprVersion::V3, paths, schema.clone(), Some(pnrs));
+
+        let date_filter =
+            col("dato_start").gt_eq(lit(datafusion::scalar::ScalarValue::Date32(Some(18_500))));
+        let other_filter = col("kontakt_id").eq(lit("3"));
+        assert_eq!(
+            provider
+                .supports_filters_pushdown(&[&date_filter, &other_filter])
+                .unwrap(),
+            vec![FilterPushdown::Exact, FilterPushdown::Unsupported]
+        );
+
+        let rows = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(async {
+                let ctx = crate::data::io::execution::session_context();
+                ctx.register_table("kontakter", Arc::new(provider)).unwrap();
+                let df = ctx.table("kontakter").await.unwrap().filter(date_filter.clone()).unwrap();
+                let batches = df.select_columns(&["kontakt_id"]).unwrap().collect().await.unwrap();
+                batches.iter().map(RecordBatch::num_rows).sum::<usize>()
+            });
+        // Only b and c are in the PNR set, and only their 2022 contacts pass the date filter
+        assert_eq!(rows, 2);
+
+        // The PNR set is joined as a table, not inlined as a list of literals
+        let many: HashSet<String> = (0..10_000).map(|i| format!("{i:010}")).collect();
+        let mut paths = LprPaths::new(&dir);
+        paths.kontakter_path = Some(dir.clone());
+        let provider = LprTableProvider::new(LprVersion::V3, paths, schema.clone(), Some(many));
+        let plan = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(async {
+                let ctx = crate::data::io::execution::session_context();
+                provider
+                    .scan(&ctx.state(), Some(&vec![2]), &[], None)
+                    .await
+                    .unwrap()
+            });
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(display.contains("HashJoinExec"), "{display}");
+        assert!(!display.contains("0000005000"), "{display}");
+        assert_eq!(plan.schema().field(0).name(), "kontakt_id");
+
+        // Without a PNR set the scan reads the projected and filtered columns only
+        let mut paths = LprPaths::new(&dir);
+        paths.kontakter_path = Some(dir.clone());
+        let provider = LprTableProvider::new(LprVersion::V3, paths, schema, None);
+        let plan = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(async {
+                let ctx = crate::data::io::execution::session_context();
+                provider
+                    .scan(&ctx.state(), Some(&vec![2]), &[date_filter], None)
+                    .await
+                    .unwrap()
+            });
+        let names = |plan: &Arc<dyn ExecutionPlan>| -> Vec<String> {
+            plan.schema()
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect()
+        };
+        assert_eq!(names(&plan), vec!["kontakt_id"]);
+        let mut scan = plan.clone();
+        while let Some(child) = scan.children().first() {
+            scan = Arc::clone(child);
+        }
+        assert_eq!(names(&scan), vec!["dato_start", "kontakt_id"]);
     }
 }
@@ -2,18 +2,22 @@
 //!
 //! This module contains registry loaders for the LPR (Landspatientregistret) registry.
 
-use crate::data::registry::traits::RegisterLoader;
+use crate::data::filter::pnr::semi_join_values;
+use crate::data::pruning::register_indexed_parquet;
+use crate::data::registry::traits::{PnrFilter, RegisterLoader};
 use crate::error::{IdsError, Result};
+use arrow::array::StringArray;
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
 use arrow::record_batch::RecordBatch;
 use datafusion::prelude::*;
+use futures::StreamExt;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::fs;
 use std::sync::Arc;
 use uuid::Uuid;
 use datafusion::datasource::MemTable;
-use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::sync::Mutex;
 
 pub mod lpr2_loader;
 pub mod lpr3_loader;
@@ -86,158 +90,6 @@ impl LprComponents {
     }
 }
 
-/// Global cache for LPR data to avoid multiple loadings of the same data
-static LPR_CACHE: Lazy<Mutex<LprDataCache>> = Lazy::new(|| Mutex::new(LprDataCache::new()));
-
-/// Cache for LPR data
-#[derive(Debug, Default)]
-pub struct LprDataCache {
-    /// Cache for LPR2 admin data
-    lpr2_admin_cache: HashMap<String, Vec<RecordBatch>>,
-    /// Cache for LPR2 diag data
-    lpr2_diag_cache: HashMap<String, Vec<RecordBatch>>,
-    /// Cache for LPR2 bes/proc data
-    lpr2_bes_cache: HashMap<String, Vec<RecordBatch>>,
-    /// Cache for LPR3 kontakter data
-    lpr3_kontakter_cache: HashMap<String, Vec<RecordBatch>>,
-    /// Cache for LPR3 diagnoser data
-    lpr3_diagnoser_cache: HashMap<String, Vec<RecordBatch>>,
-    /// Cache for LPR3 procedurer data
-    lpr3_procedurer_cache: HashMap<String, Vec<RecordBatch>>,
-}
-
-impl LprDataCache {
-    /// Create a new empty cache
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    /// Clear the cache
-    pub fn clear(&mut self) {
-        self.lpr2_admin_cache.clear();
-        self.lpr2_diag_cache.clear();
-        self.lpr2_bes_cache.clear();
-        self.lpr3_kontakter_cache.clear();
-        self.lpr3_diagnoser_cache.clear();
-        self.lpr3_procedurer_cache.clear();
-    }
-
-    /// Get normalized path string (for use as cache key)
-    fn normalize_path(path: &Path) -> String {
-        match path.canonicalize() {
-            Ok(p) => p.to_string_lossy().to_string(),
-            Err(_) => path.to_string_lossy().to_string(),
-        }
-    }
-
-    /// Store LPR2 admin data in the cache
-    pub fn store_lpr2_admin(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR2 admin data in cache for path: {}", key);
-        self.lpr2_admin_cache.insert(key, data);
-    }
-
-    /// Get LPR2 admin data from the cache
-    pub fn get_lpr2_admin(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr2_admin_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR2 admin data from cache for path: {}", key);
-        }
-        result
-    }
-
-    /// Store LPR2 diag data in the cache
-    pub fn store_lpr2_diag(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR2 diag data in cache for path: {}", key);
-        self.lpr2_diag_cache.insert(key, data);
-    }
-
-    /// Get LPR2 diag data from the cache
-    pub fn get_lpr2_diag(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr2_diag_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR2 diag data from cache for path: {}", key);
-        }
-        result
-    }
-
-    /// Store LPR2 bes data in the cache
-    pub fn store_lpr2_bes(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR2 bes data in cache for path: {}", key);
-        self.lpr2_bes_cache.insert(key, data);
-    }
-
-    /// Get LPR2 bes data from the cache
-    pub fn get_lpr2_bes(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr2_bes_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR2 bes data from cache for path: {}", key);
-        }
-        result
-    }
-
-    /// Store LPR3 kontakter data in the cache
-    pub fn store_lpr3_kontakter(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR3 kontakter data in cache for path: {}", key);
-        self.lpr3_kontakter_cache.insert(key, data);
-    }
-
-    /// Get LPR3 kontakter data from the cache
-    pub fn get_lpr3_kontakter(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr3_kontakter_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR3 kontakter data from cache for path: {}", key);
-        }
-        result
-    }
-
-    /// Store LPR3 diagnoser data in the cache
-    pub fn store_lpr3_diagnoser(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR3 diagnoser data in cache for path: {}", key);
-        self.lpr3_diagnoser_cache.insert(key, data);
-    }
-
-    /// Get LPR3 diagnoser data from the cache
-    pub fn get_lpr3_diagnoser(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr3_diagnoser_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR3 diagnoser data from cache for path: {}", key);
-        }
-        result
-    }
-
-    /// Store LPR3 procedurer data in the cache
-    pub fn store_lpr3_procedurer(&mut self, path: &Path, data: Vec<RecordBatch>) {
-        let key = Self::normalize_path(path);
-        info_log!(Component::Lpr, "cache", "Storing LPR3 procedurer data in cache for path: {}", key);
-        self.lpr3_procedurer_cache.insert(key, data);
-    }
-
-    /// Get LPR3 procedurer data from the cache
-    pub fn get_lpr3_procedurer(&self, path: &Path) -> Option<Vec<RecordBatch>> {
-        let key = Self::normalize_path(path);
-        let result = self.lpr3_procedurer_cache.get(&key).cloned();
-        if result.is_some() {
-            info_log!(Component::Lpr, "cache", "Retrieved LPR3 procedurer data from cache for path: {}", key);
-        }
-        result
-    }
-}
-
-/// Helper function to get the LPR cache
-pub fn get_lpr_cache() -> &'static Mutex<LprDataCache> {
-    &LPR_CACHE
-}
-
 /// Helper function to find all parquet files in a directory and subdirectories
 /// using structured logging
 pub fn find_parquet_files_in_dir(dir_path: &Path) -> Result<Vec<PathBuf>> {
@@ -422,6 +274,90 @@ pub async fn batches_to_dataframe(
     }
 }
 
+/// How the rows of an LPR component are selected
+#[derive(Clone, Copy)]
+pub(crate) enum ComponentFilter<'a> {
+    /// All rows
+    All,
+    /// Rows of a PNR filter, on the component's PNR column
+    Pnrs(&'a PnrFilter, &'a str),
+    /// Rows whose contact key (`RECNUM`, `kontakt_id`) is one of the keys
+    Keys(&'a HashSet<String>, &'a str),
+}
+
+/// Register an LPR component directory and select its rows as a `DataFrame`
+///
+/// The PNR filter selects files with the registry index; both filters are applied as
+/// semi-joins against in-memory tables. An empty key set selects no rows.
+pub(crate) async fn component_frame(
+    table_name: &str,
+    path: &Path,
+    filter: ComponentFilter<'_>,
+) -> Result<DataFrame> {
+    let ctx = crate::data::io::execution::session_context();
+    let index_filter = match filter {
+        ComponentFilter::Pnrs(pnr_filter, column) => Some(pnr_filter.index_lookup(column)),
+        ComponentFilter::All | ComponentFilter::Keys(..) => None,
+    };
+    register_indexed_parquet(
+        &ctx,
+        table_name,
+        path,
+        ParquetReadOptions::default(),
+        index_filter,
+    )
+    .await?;
+
+    let df = ctx.table(table_name).await?;
+    match filter {
+        ComponentFilter::All => Ok(df),
+        ComponentFilter::Pnrs(pnr_filter, column) => pnr_filter.apply_to_dataframe(df, column),
+        ComponentFilter::Keys(keys, _) if keys.is_empty() => Ok(df.limit(0, Some(0))?),
+        ComponentFilter::Keys(keys, column) => semi_join_values(df, keys, column),
+    }
+}
+
+/// Read the selected rows of an LPR component
+///
+/// The rows are streamed batch by batch, so only the selected rows are held in memory.
+pub(crate) async fn read_component(df: DataFrame) -> Result<Vec<RecordBatch>> {
+    let mut stream = df.execute_stream().await?;
+    let mut batches = Vec::new();
+    while let Some(batch) = stream.next().await {
+        let batch = batch?;
+        if batch.num_rows() > 0 {
+            batches.push(batch);
+        }
+    }
+    Ok(batches)
+}
+
+/// The distinct contact keys (`RECNUM`, `kontakt_id`) of component records, for selecting
+/// the rows of the dependent components
+pub(crate) fn component_keys(batches: &[RecordBatch], column: &str) -> Result<HashSet<String>> {
+    let mut keys = HashSet::new();
+    for batch in batches {
+        let Some(values) = batch.column_by_name(column) else {
+            continue;
+        };
+        let values = cast(values, &DataType::Utf8)?;
+        if let Some(values) = values.as_any().downcast_ref::<StringArray>() {
+            keys.extend(values.iter().flatten().map(str::to_string));
+        }
+    }
+    Ok(keys)
+}
+
+/// Read an LPR component with [`crate::data::io::parquet::load_parquet_directory`], for
+/// directories that cannot be registered with DataFusion
+pub(crate) async fn read_component_fallback(
+    path: &Path,
+    pnr_filter: Option<&PnrFilter>,
+) -> Result<Vec<RecordBatch>> {
+    let io_filter = pnr_filter.map(PnrFilter::to_io_filter);
+    crate::data::io::parquet::load_parquet_directory(path, None, io_filter.as_ref()).await
+}
+
 /// LPR version
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LprVersion {
@@ -441,7 +377,7 @@ impl std::fmt::Display for LprVersion {
 }
 
 /// Paths to LPR data files
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LprPaths {
     /// Base path
     pub base_path: PathBuf,
@@ -742,9 +678,86 @@ pub trait LprRegistry: RegisterLoader {
     /// 
     /// This method loads the individual LPR components (adm, diag, bes for LPR2 or
     /// kontakter, diagnoser, procedurer for LPR3) without joining them.
+    ///
+    /// The selected rows are held in memory, so this is for PNR-filtered loads; whole
+    /// registers are harmonised out of core by
+    /// [`partition_lpr`](crate::algorithm::health::lpr_partitioned::partition_lpr).
     async fn load_components(
         &self,
         base_path: &str,
         pnr_filter: Option<&crate::data::registry::traits::PnrFilter>,
     ) -> Result<LprComponents>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::array::ArrayRef;
+
+    fn strings(values: &[&str]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    fn column_values(batches: &[RecordBatch], column: &str) -> Vec<String> {
+        let mut values: Vec<String> = component_keys(batches, column)
+            .unwrap()
+            .into_iter()
+            .collect();
+        values.sort();
+        values
+    }
+
+    /// Diagnoses are selected by the contact keys of the PNR-filtered contacts
+    #[test]
+    fn test_components_linked_by_contact_keys() {
+        let temp = temp_dir();
+        let adm = temp.path().join("adm");
+        let diag = temp.path().join("diag");
+        write_parquet(
+            &adm.join("adm.parquet"),
+            &RecordBatch::try_from_iter(vec![
+                ("PNR", strings(&["p1", "p1", "p2"])),
+                ("RECNUM", strings(&["r1", "r2", "r3"])),
+            ])
+            .unwrap(),
+        );
+        write_parquet(
+            &diag.join("diag.parquet"),
+            &RecordBatch::try_from_iter(vec![
+                ("RECNUM", strings(&["r1", "r2", "r3", "r3"])),
+                ("C_DIAG", strings(&["DA00", "DB00", "DC00", "DD00"])),
+            ])
+            .unwrap(),
+        );
+
+        let pnr_filter = PnrFilter::new(HashSet::from(["p1".to_string()]));
+        let (recnums, diagnoses, unlinked) = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(async {
+                let admin = component_frame("adm", &adm, ComponentFilter::Pnrs(&pnr_filter, "PNR"))
+                    .await
+                    .unwrap();
+                let admin = read_component(admin).await.unwrap();
+                let recnums = component_keys(&admin, "RECNUM").unwrap();
+
+                let linked = ComponentFilter::Keys(&recnums, "RECNUM");
+                let diagnoses =
+                    read_component(component_frame("diag", &diag, linked).await.unwrap())
+                        .await
+                        .unwrap();
+                let none = HashSet::new();
+                let unlinked = ComponentFilter::Keys(&none, "RECNUM");
+                let unlinked =
+                    read_component(component_frame("diag", &diag, unlinked).await.unwrap())
+                        .await
+                        .unwrap();
+                (recnums, diagnoses, unlinked)
+            });
+
+        assert_eq!(recnums, HashSet::from(["r1".to_string(), "r2".to_string()]));
+        assert_eq!(column_values(&diagnoses, "C_DIAG"), vec!["DA00", "DB00"]);
+        // Without selected contacts no diagnoses are selected, rather than all of them
+        assert!(unlinked.is_empty());
+    }
+}
@@ -1,10 +1,15 @@
+use crate::data::schema::drift::{register_reconciled_parquet, SchemaReconciler};
 use crate::data::schema::traits::RegistrySchema;
 use crate::error::Result;
 use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
+use datafusion::execution::SendableRecordBatchStream;
 use datafusion::prelude::*;
 use std::collections::HashSet;
 
+/// Name of the PNR column of most registers
+const PNR_COLUMN: &str = "PNR";
+
 /// A type for PNR filtering
 pub struct PnrFilter {
     pnrs: HashSet<String>,
@@ -46,6 +51,27 @@ impl PnrFilter {
         self.direct_filter
     }
     
+    /// The column the filter applies to, for a register whose PNR column is `pnr_column`
+    #[must_use] pub fn filter_column<'a>(&'a self, pnr_column: &'a str) -> &'a str {
+        match self.relation_column() {
+            Some(relation_col) if !self.direct_filter => relation_col,
+            _ => pnr_column,
+        }
+    }
+
+    /// Keep the rows of a `DataFrame` that belong to the filtered PNRs
+    ///
+    /// Applied as a semi-join against an in-memory PNR table, see
+    /// [`crate::data::filter::pnr::semi_join_values`]. An empty filter keeps all rows.
+    pub fn apply_to_dataframe(&self, df: DataFrame, pnr_column: &str) -> Result<DataFrame> {
+        crate::data::filter::pnr::semi_join_values(df, &self.pnrs, self.filter_column(pnr_column))
+    }
+
+    /// The filter column and PNRs, for selecting files with the registry index
+    #[must_use] pub fn index_lookup<'a>(&'a self, pnr_column: &'a str) -> (&'a str, &'a HashSet<String>) {
+        (self.filter_column(pnr_column), &self.pnrs)
+    }
+
     /// Convert to a data::filter::pnr::PnrFilter
     #[must_use] pub fn to_io_filter(&self) -> crate::data::filter::pnr::PnrFilter {
         if self.is_direct_filter() {
@@ -81,60 +107,27 @@ pub trait RegisterLoader: Send + Sync + 'static {
         base_path: &str,
         pnr_filter: Option<&PnrFilter>,
     ) -> Result<SessionContext> {
-        let ctx = SessionContext::new();
-        let schema = Self::SchemaType::schema_arc();
-
-        // Register source as a table
-        ctx.register_parquet(
-            self.register_name().to_lowercase(),
+        let ctx = crate::data::io::execution::session_context();
+
+        // Register source as a table, with only the indexed files that can match the filter
+        // and files whose schema drifted reconciled to the registry schema
+        let reconciler = SchemaReconciler::for_registry::<Self::SchemaType>();
+        register_reconciled_parquet(
+            &ctx,
+            &self.register_name().to_lowercase(),
             base_path,
-            ParquetReadOptions::default().schema(schema.as_ref()),
+            &reconciler,
+            pnr_filter.map(|filter| filter.index_lookup(PNR_COLUMN)),
         )
-        .await?;
+        .await?
+        .log(self.register_name());
 
-        // Apply PNR filter if provided
+        // Apply PNR filter if provided, as a semi-join against an in-memory PNR table
         if let Some(filter) = pnr_filter {
-            // Create SQL execution for the filter
             let table_name = self.register_name().to_lowercase();
-            if filter.is_direct_filter() {
-                // Create PNR IN list SQL
-                let pnrs_list = filter
-                    .pnrs()
-                    .iter()
-                    .map(|p| format!("'{p}'"))
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                if !pnrs_list.is_empty() {
-                    let sql = format!(
-                        "
-                        CREATE OR REPLACE TABLE {table_name} AS
-                        SELECT * FROM {table_name}
-                        WHERE PNR IN ({pnrs_list})
-                    "
-                    );
-                    ctx.sql(&sql).await?;
-                }
-            } else if let Some(relation_col) = filter.relation_column() {
-                // Handle relation filtering
-                let pnrs_list = filter
-                    .pnrs()
-                    .iter()
-                    .map(|p| format!("'{p}'"))
-                    .collect::<Vec<_>>()
-                    .join(",");
-
-                if !pnrs_list.is_empty() {
-                    let sql = format!(
-                        "
-                        CREATE OR REPLACE TABLE {table_name} AS
-                        SELECT * FROM {table_name}
-                        WHERE {relation_col} IN ({pnrs_list})
-                    "
-                    );
-                    ctx.sql(&sql).await?;
-                }
-            }
+            let df = filter.apply_to_dataframe(ctx.table(&table_name).await?, PNR_COLUMN)?;
+            ctx.deregister_table(&table_name)?;
+            ctx.register_table(&table_name, df.into_view())?;
         }
 
         Ok(ctx)
@@ -154,6 +147,17 @@ pub trait RegisterLoader: Send + Sync + 'static {
         Ok(result)
     }
 
+    /// Stream records from the register batch by batch, without collecting them in memory
+    async fn load_stream(
+        &self,
+        base_path: &str,
+        pnr_filter: Option<&PnrFilter>,
+    ) -> Result<SendableRecordBatchStream> {
+        let ctx = self.create_context(base_path, pnr_filter).await?;
+        let df = ctx.table(&self.register_name().to_lowercase()).await?;
+        Ok(df.execute_stream().await?)
+    }
+
     /// Get the schema for this registry
     fn get_schema(&self) -> SchemaRef {
         Self::SchemaType::schema_arc()
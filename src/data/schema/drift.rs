@@ -0,0 +1,766 @@
+//! Schema drift reconciliation across register years
+//!
+//! Register files from different years disagree on column types and names: `KOM` is
+//! stored as Int8, Int16 or Utf8, `CPRTJEK` as Utf8 or Int8, `HF_VFRA` as date strings,
+//! and older exports use lower-case or since-renamed columns. Reading such a directory
+//! with a fixed schema fails or silently nulls columns.
+//!
+//! [`SchemaReconciler`] inspects each file's physical schema and maps it to the registry
+//! schema:
+//!
+//! - columns are matched by name, then by the registry's declared renames
+//!   ([`RegistrySchema::drift_rules`]), then case-insensitively;
+//! - strings and YYYYMMDD integers are parsed into date columns;
+//! - other type differences are cast, with unconvertible values becoming null;
+//! - missing nullable columns are filled with nulls, and extra columns dropped.
+//!
+//! A [`DriftReport`] lists which files needed which coercions, including how many values
+//! a coercion turned into nulls.
+
+use arrow::array::{new_null_array, Array, ArrayRef, BooleanArray, StringArray};
+use arrow::compute::{cast_with_options, filter_record_batch, CastOptions};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use datafusion::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::data::io::partitioned::{
+    hive_partition_columns, hive_partition_values, partition_files, read_partitioned_files,
+    with_partition_values,
+};
+use crate::data::pruning::select_indexed_files;
+use crate::data::schema::traits::RegistrySchema;
+use crate::error::{IdsError, Result};
+use crate::utils::date_utils::convert_to_date32_array;
+
+/// Declared rename rules of a registry
+#[derive(Debug, Clone, Default)]
+pub struct DriftRules {
+    renames: Vec<(String, String)>,
+}
+
+impl DriftRules {
+    /// Create empty rules
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare that column `from` in older files is column `to` of the registry schema
+    #[must_use]
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        self.renames.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// The declared renames (old name, current name)
+    #[must_use]
+    pub fn renames(&self) -> &[(String, String)] {
+        &self.renames
+    }
+}
+
+/// A type coercion applied to a column of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnCoercion {
+    /// Column in the registry schema
+    pub column: String,
+    /// Type in the file
+    pub from: String,
+    /// Type in the registry schema
+    pub to: String,
+    /// Non-null values that could not be converted and became null
+    pub nulled: usize,
+}
+
+/// The reconciliation of one file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileDrift {
+    pub path: PathBuf,
+    /// Columns matched under another name (file name, registry name)
+    pub renamed: Vec<(String, String)>,
+    pub coerced: Vec<ColumnCoercion>,
+    /// Registry columns missing from the file, filled with nulls
+    pub missing: Vec<String>,
+    /// File columns not in the registry schema
+    pub dropped: Vec<String>,
+    /// Text values per column that could not be parsed as a date or number and became null
+    pub unparsed: BTreeMap<String, usize>,
+}
+
+impl FileDrift {
+    /// Whether the file matches the registry schema as is
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.renamed.is_empty()
+            && self.coerced.is_empty()
+            && self.missing.is_empty()
+            && self.dropped.is_empty()
+            && self.unparsed.is_empty()
+    }
+
+    /// One-line description of the reconciliation
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.renamed.is_empty() {
+            let renamed: Vec<String> = self
+                .renamed
+                .iter()
+                .map(|(from, to)| format!("{from}->{to}"))
+                .collect();
+            parts.push(format!("renamed {}", renamed.join(", ")));
+        }
+        if !self.coerced.is_empty() {
+            let coerced: Vec<String> = self
+                .coerced
+                .iter()
+                .map(|c| match c.nulled {
+                    0 => format!("{} {}->{}", c.column, c.from, c.to),
+                    n => format!("{} {}->{} ({n} nulled)", c.column, c.from, c.to),
+                })
+                .collect();
+            parts.push(format!("coerced {}", coerced.join(", ")));
+        }
+        if !self.missing.is_empty() {
+            parts.push(format!("missing {}", self.missing.join(", ")));
+        }
+        if !self.dropped.is_empty() {
+            parts.push(format!("dropped {}", self.dropped.join(", ")));
+        }
+        if !self.unparsed.is_empty() {
+            let unparsed: Vec<String> = self
+                .unparsed
+                .iter()
+                .map(|(column, count)| format!("{column} ({count})"))
+                .collect();
+            parts.push(format!("unparsed {}", unparsed.join(", ")));
+        }
+        format!("{}: {}", self.path.display(), parts.join("; "))
+    }
+}
+
+/// Reconciliations of the files of a registry
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DriftReport {
+    /// Number of files inspected
+    pub files_checked: usize,
+    /// Files that needed reconciliation
+    pub files: Vec<FileDrift>,
+}
+
+impl DriftReport {
+    /// Log the files that needed reconciliation
+    pub fn log(&self, registry: &str) {
+        if self.files.is_empty() {
+            return;
+        }
+        log::info!(
+            "{registry}: reconciled the schema of {} of {} files",
+            self.files.len(),
+            self.files_checked
+        );
+        for file in &self.files {
+            log::info!("  {}", file.summary());
+        }
+    }
+}
+
+/// How a registry column is read from a file
+#[derive(Debug, Clone)]
+enum ColumnSource {
+    /// Column index in the file, and whether it must be converted
+    Column(usize, bool),
+    Missing,
+}
+
+/// Maps file schemas to a registry schema
+#[derive(Debug, Clone)]
+pub struct SchemaReconciler {
+    target: SchemaRef,
+    rules: DriftRules,
+}
+
+impl SchemaReconciler {
+    /// Create a reconciler for a target schema
+    #[must_use]
+    pub fn new(target: SchemaRef, rules: DriftRules) -> Self {
+        Self { target, rules }
+    }
+
+    /// Create a reconciler for a registry schema and its declared rules
+    #[must_use]
+    pub fn for_registry<S: RegistrySchema>() -> Self {
+        Self::new(S::schema_arc(), S::drift_rules())
+    }
+
+    /// The registry schema
+    #[must_use]
+    pub fn target(&self) -> SchemaRef {
+        self.target.clone()
+    }
+
+    /// Plan the reconciliation of a file schema (coercions without null counts)
+    fn plan(&self, path: &Path, file_schema: &Schema) -> Result<(Vec<ColumnSource>, FileDrift)> {
+        let mut drift = FileDrift {
+            path: path.to_path_buf(),
+            ..FileDrift::default()
+        };
+        let mut used = HashSet::new();
+        let mut sources = Vec::with_capacity(self.target.fields().len());
+
+        for field in self.target.fields() {
+            let name = field.name();
+            let renamed = self
+                .rules
+                .renames
+                .iter()
+                .filter(|(_, to)| to == name)
+                .find_map(|(from, _)| file_schema.index_of(from).ok());
+            let found = file_schema.index_of(name).ok().or(renamed).or_else(|| {
+                file_schema
+                    .fields()
+                    .iter()
+                    .position(|f| f.name().eq_ignore_ascii_case(name))
+            });
+
+            let Some(idx) = found else {
+                if !field.is_nullable() {
+                    return Err(IdsError::Validation(format!(
+                        "Required column {name} missing from {}",
+                        path.display()
+                    )));
+                }
+                drift.missing.push(name.clone());
+                sources.push(ColumnSource::Missing);
+                continue;
+            };
+            used.insert(idx);
+
+            let file_field = file_schema.field(idx);
+            if file_field.name() != name {
+                drift
+                    .renamed
+                    .push((file_field.name().clone(), name.clone()));
+            }
+            let convert = file_field.data_type() != field.data_type();
+            if convert {
+                drift.coerced.push(ColumnCoercion {
+                    column: name.clone(),
+                    from: file_field.data_type().to_string(),
+                    to: field.data_type().to_string(),
+                    nulled: 0,
+                });
+            }
+            sources.push(ColumnSource::Column(idx, convert));
+        }
+
+        drift.dropped = file_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !used.contains(idx))
+            .map(|(_, f)| f.name().clone())
+            .collect();
+
+        Ok((sources, drift))
+    }
+
+    /// Inspect a file's physical schema
+    pub fn inspect(&self, path: &Path) -> Result<FileDrift> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+        Ok(self.plan(path, builder.schema())?.1)
+    }
+
+    /// Read a file reconciled to the registry schema
+    ///
+    /// With a filter (a registry column and its values), only matching rows are kept.
+    pub fn read_file(
+        &self,
+        path: &Path,
+        filter: Option<(&str, &HashSet<String>)>,
+    ) -> Result<(Vec<RecordBatch>, FileDrift)> {
+        let filter_idx = match filter {
+            Some((column, _)) => Some(self.target.index_of(column)?),
+            None => None,
+        };
+
+        let mut batches = Vec::new();
+        let drift = self.for_each_batch(path, |batch| {
+            let batch = match (filter, filter_idx) {
+                (Some((_, values)), Some(idx)) => {
+                    let column = cast_with_options(
+                        batch.column(idx),
+                        &DataType::Utf8,
+                        &CastOptions::default(),
+                    )?;
+                    let column =
+                        column
+                            .as_any()
+                            .downcast_ref::<StringArray>()
+                            .ok_or_else(|| {
+                                IdsError::Data("Filter column is not a string column".to_string())
+                            })?;
+                    let mask: BooleanArray = column
+                        .iter()
+                        .map(|value| Some(value.is_some_and(|value| values.contains(value))))
+                        .collect();
+                    filter_record_batch(&batch, &mask)?
+                }
+                _ => batch,
+            };
+            if batch.num_rows() > 0 {
+                batches.push(batch);
+            }
+            Ok(())
+        })?;
+        Ok((batches, drift))
+    }
+
+    /// Stream a file batch by batch, reconciled to the registry schema
+    pub fn for_each_batch(
+        &self,
+        path: &Path,
+        f: impl FnMut(RecordBatch) -> Result<()>,
+    ) -> Result<FileDrift> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+        let schema = builder.schema().clone();
+        let batches = builder
+            .build()?
+            .map(|batch| batch.map_err(IdsError::from));
+        self.reconcile_batches(path, &schema, batches, f)
+    }
+
+    /// Reconcile batches of another source (e.g. a SAS or CSV file) with schema `schema`
+    ///
+    /// `source` names the source in the returned [`FileDrift`] and in errors.
+    pub fn reconcile_batches(
+        &self,
+        source: &Path,
+        schema: &Schema,
+        batches: impl IntoIterator<Item = Result<RecordBatch>>,
+        mut f: impl FnMut(RecordBatch) -> Result<()>,
+    ) -> Result<FileDrift> {
+        let (sources, mut drift) = self.plan(source, schema)?;
+        for batch in batches {
+            f(self.reconcile_batch(&batch?, &sources, &mut drift)?)?;
+        }
+        Ok(drift)
+    }
+
+    /// The registry field a source column is matched to, by name, declared rename or
+    /// case-insensitively
+    #[must_use]
+    pub fn target_field(&self, column: &str) -> Option<&Field> {
+        let renamed = self
+            .rules
+            .renames
+            .iter()
+            .find(|(from, _)| from == column)
+            .map(|(_, to)| to.as_str());
+        self.target
+            .field_with_name(column)
+            .ok()
+            .or_else(|| renamed.and_then(|to| self.target.field_with_name(to).ok()))
+            .or_else(|| {
+                self.target
+                    .fields()
+                    .iter()
+                    .find(|field| field.name().eq_ignore_ascii_case(column))
+                    .map(AsRef::as_ref)
+            })
+    }
+
+    /// Reconcile a batch read from a file
+    fn reconcile_batch(
+        &self,
+        batch: &RecordBatch,
+        sources: &[ColumnSource],
+        drift: &mut FileDrift,
+    ) -> Result<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(sources.len());
+        for (field, source) in self.target.fields().iter().zip(sources) {
+            let column = match source {
+                ColumnSource::Missing => new_null_array(field.data_type(), batch.num_rows()),
+                ColumnSource::Column(idx, false) => batch.column(*idx).clone(),
+                ColumnSource::Column(idx, true) => {
+                    let column = batch.column(*idx);
+                    let converted = coerce_column(column, field.data_type())?;
+                    let nulled = converted.null_count().saturating_sub(column.null_count());
+                    if let Some(coercion) =
+                        drift.coerced.iter_mut().find(|c| &c.column == field.name())
+                    {
+                        coercion.nulled += nulled;
+                    }
+                    converted
+                }
+            };
+            columns.push(column);
+        }
+        RecordBatch::try_new(self.target.clone(), columns).map_err(|e| {
+            IdsError::Data(format!(
+                "Failed to reconcile {} with the registry schema: {e}",
+                drift.path.display()
+            ))
+        })
+    }
+}
+
+/// Convert a column to a registry type; unconvertible values become null
+fn coerce_column(column: &ArrayRef, to: &DataType) -> Result<ArrayRef> {
+    let safe = CastOptions {
+        safe: true,
+        ..CastOptions::default()
+    };
+    if to == &DataType::Date32 && !matches!(column.data_type(), DataType::Date32 | DataType::Date64)
+    {
+        // Date strings (in any of the usual formats) and YYYYMMDD integers
+        let source = match column.data_type() {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                cast_with_options(column, &DataType::Int32, &safe)?
+            }
+            _ => cast_with_options(column, &DataType::Utf8, &safe)?,
+        };
+        return Ok(Arc::new(convert_to_date32_array(source.as_ref())?));
+    }
+    if to == &DataType::Utf8 && matches!(column.data_type(), DataType::Float32 | DataType::Float64)
+    {
+        // SAS exports store codes as doubles; keep 101 rather than 101.0
+        let integers = cast_with_options(column, &DataType::Int64, &safe)?;
+        return Ok(cast_with_options(&integers, to, &safe)?);
+    }
+    if to.is_integer()
+        && matches!(
+            column.data_type(),
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        )
+    {
+        // Codes with surrounding spaces, e.g. " 101"
+        let strings = cast_with_options(column, &DataType::Utf8, &safe)?;
+        let trimmed: StringArray = strings
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| IdsError::Data("Failed to cast codes to strings".to_string()))?
+            .iter()
+            .map(|value| value.map(str::trim))
+            .collect();
+        return Ok(cast_with_options(&trimmed, to, &safe)?);
+    }
+    Ok(cast_with_options(column, to, &safe)?)
+}
+
+/// Register a Parquet file or registry directory as a table with the registry schema,
+/// reconciling files whose schema drifted
+///
+/// Files that match the schema are scanned as usual; drifted files are read, reconciled
+/// and, with a `filter`, reduced to matching rows. Files are selected with the registry
+/// index like [`register_indexed_parquet`](crate::data::pruning::register_indexed_parquet),
+/// which also describes how Hive-partitioned directories are read. Partitions are read
+/// whole, and all files of a partition with a drifted file are reconciled.
+pub async fn register_reconciled_parquet(
+    ctx: &SessionContext,
+    table_name: &str,
+    path: impl AsRef<Path>,
+    reconciler: &SchemaReconciler,
+    filter: Option<(&str, &HashSet<String>)>,
+) -> Result<DriftReport> {
+    let path = path.as_ref();
+    let target = reconciler.target();
+    let mut files = select_indexed_files(path, filter)?;
+    let partition_columns = if path.is_dir() {
+        hive_partition_columns(path, &files)
+    } else {
+        Vec::new()
+    };
+    if !partition_columns.is_empty() {
+        files = partition_files(path, &select_indexed_files(path, None)?, &files);
+    }
+
+    let mut report = DriftReport {
+        files_checked: files.len(),
+        ..DriftReport::default()
+    };
+    let drifts = files
+        .iter()
+        .map(|file| reconciler.inspect(file))
+        .collect::<Result<Vec<_>>>()?;
+    let drifted_partitions: HashSet<Vec<(String, String)>> = files
+        .iter()
+        .zip(&drifts)
+        .filter(|(_, drift)| !partition_columns.is_empty() && !drift.is_clean())
+        .map(|(file, _)| hive_partition_values(path, file))
+        .collect();
+
+    let mut clean = Vec::new();
+    let mut reconciled = Vec::new();
+    let mut partitions = Vec::new();
+    for (file, drift) in files.iter().zip(drifts) {
+        let values = hive_partition_values(path, file);
+        if drift.is_clean() && !drifted_partitions.contains(&values) {
+            clean.push(file.clone());
+            continue;
+        }
+        let (batches, drift) = reconciler.read_file(file, filter)?;
+        if partition_columns.is_empty() {
+            reconciled.extend(batches);
+        } else {
+            let df = ctx.read_table(Arc::new(MemTable::try_new(target.clone(), vec![batches])?))?;
+            partitions.push(with_partition_values(df, &values, &partition_columns)?);
+        }
+        if !drift.is_clean() {
+            report.files.push(drift);
+        }
+    }
+
+    let mut df = if reconciled.is_empty() {
+        None
+    } else {
+        Some(ctx.read_table(Arc::new(MemTable::try_new(
+            target.clone(),
+            vec![reconciled],
+        )?))?)
+    };
+    if !clean.is_empty() {
+        let options = ParquetReadOptions::default().schema(target.as_ref());
+        let scanned = if !partition_columns.is_empty() {
+            read_partitioned_files(ctx, path, &clean, options, &partition_columns).await?
+        } else if clean.len() == files.len() && path.is_dir() && filter.is_none() {
+            ctx.read_parquet(path.to_string_lossy().to_string(), options)
+                .await?
+        } else {
+            let clean: Vec<String> = clean
+                .iter()
+                .map(|file| file.to_string_lossy().to_string())
+                .collect();
+            ctx.read_parquet(clean, options).await?
+        };
+        partitions.push(scanned);
+    }
+    for part in partitions {
+        df = Some(match df {
+            Some(df) => df.union(part)?,
+            None => part,
+        });
+    }
+    let df = match df {
+        Some(df) => df,
+        None => with_partition_values(
+            ctx.read_table(Arc::new(MemTable::try_new(target, vec![vec![]])?))?,
+            &[],
+            &partition_columns,
+        )?,
+    };
+
+    ctx.register_table(table_name, df.into_view())?;
+    Ok(report)
+}
+
+/// Inspect the files of a registry for schema drift without reading their data
+pub fn inspect_registry_drift(path: &Path, reconciler: &SchemaReconciler) -> Result<DriftReport> {
+    let files = select_indexed_files(path, None)?;
+    let mut report = DriftReport {
+        files_checked: files.len(),
+        ..DriftReport::default()
+    };
+    for file in &files {
+        let drift = reconciler.inspect(file)?;
+        if !drift.is_clean() {
+            report.files.push(drift);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::array::{Date32Array, Int16Array, Int32Array, Int8Array};
+    use arrow::datatypes::Field;
+
+    /// Write a drifted 2000 file and a clean 2010 file to `dir`, registered as `reg`
+    fn register_drifted_years(dir: &Path, ctx: &SessionContext) -> DriftReport {
+        let target = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, false),
+            Field::new("KOM", DataType::Int16, true),
+            Field::new("HF_VFRA", DataType::Date32, true),
+            Field::new("INCOME", DataType::Utf8, true),
+        ]));
+        let reconciler = SchemaReconciler::new(
+            target.clone(),
+            DriftRules::new().rename("INDKOMST", "INCOME"),
+        );
+
+        write_parquet(
+            &dir.join("reg2000.parquet"),
+            &RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new("pnr", DataType::Utf8, false),
+                    Field::new("KOM", DataType::Utf8, true),
+                    Field::new("HF_VFRA", DataType::Utf8, true),
+                    Field::new("INDKOMST", DataType::Utf8, true),
+                    Field::new("EXTRA", DataType::Int8, true),
+                ])),
+                vec![
+                    Arc::new(StringArray::from(vec!["1", "2"])),
+                    Arc::new(StringArray::from(vec![" 101", "x"])),
+                    Arc::new(StringArray::from(vec!["01JAN2000", "31-12-2000"])),
+                    Arc::new(StringArray::from(vec!["100", "200"])),
+                    Arc::new(Int8Array::from(vec![1, 2])),
+                ],
+            )
+            .unwrap(),
+        );
+        write_parquet(
+            &dir.join("reg2010.parquet"),
+            &RecordBatch::try_new(
+                target,
+                vec![
+                    Arc::new(StringArray::from(vec!["3"])),
+                    Arc::new(Int16Array::from(vec![851])),
+                    Arc::new(Date32Array::from(vec![14610])),
+                    Arc::new(StringArray::from(vec!["300"])),
+                ],
+            )
+            .unwrap(),
+        );
+
+        crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(register_reconciled_parquet(
+                ctx,
+                "reg",
+                dir,
+                &reconciler,
+                None,
+            ))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reconcile_reports_drifted_years() {
+        let dir = temp_dir();
+        let report = register_drifted_years(dir.path(), &SessionContext::new());
+        assert_eq!(report.files_checked, 2);
+        assert_eq!(report.files.len(), 1);
+        let drift = &report.files[0];
+        assert_eq!(
+            drift.renamed,
+            vec![
+                ("pnr".to_string(), "PNR".to_string()),
+                ("INDKOMST".to_string(), "INCOME".to_string())
+            ]
+        );
+        assert_eq!(drift.dropped, vec!["EXTRA".to_string()]);
+        let kom = drift.coerced.iter().find(|c| c.column == "KOM").unwrap();
+        assert_eq!(kom.nulled, 1);
+        let dates = drift
+            .coerced
+            .iter()
+            .find(|c| c.column == "HF_VFRA")
+            .unwrap();
+        assert_eq!(dates.nulled, 0);
+    }
+
+    #[test]
+    fn test_reconciled_years_read_as_target_schema() {
+        let dir = temp_dir();
+        let ctx = SessionContext::new();
+        register_drifted_years(dir.path(), &ctx);
+
+        let batches = crate::utils::runtime::get_runtime()
+            .unwrap()
+            .block_on(async {
+                ctx.sql("SELECT * FROM reg ORDER BY \"PNR\"")
+                    .await?
+                    .collect()
+                    .await
+            })
+            .unwrap();
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, 3);
+        let kom = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int16Array>()
+            .unwrap();
+        assert_eq!(kom.value(0), 101);
+    }
+
+    /// A clean file in the partition of a drifted file is reconciled with it, not read twice
+    #[test]
+    fn test_reconcile_partitioned_directory() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let target = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, false),
+            Field::new("KOM", DataType::Int16, true),
+        ]));
+        let write = |name: &str, pnr: &str, kom: ArrayRef| {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("PNR", DataType::Utf8, false),
+                Field::new("KOM", kom.data_type().clone(), true),
+            ]));
+            let batch =
+                RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(vec![pnr])), kom])
+                    .unwrap();
+            write_parquet(&dir.join(name), &batch);
+        };
+        write(
+            "year=2019/a.parquet",
+            "1",
+            Arc::new(StringArray::from(vec!["101"])),
+        );
+        write(
+            "year=2019/b.parquet",
+            "2",
+            Arc::new(Int16Array::from(vec![147])),
+        );
+        write(
+            "year=2020/a.parquet",
+            "3",
+            Arc::new(Int16Array::from(vec![851])),
+        );
+
+        let ctx = SessionContext::new();
+        let runtime = crate::utils::runtime::get_runtime().unwrap();
+        let report = runtime
+            .block_on(register_reconciled_parquet(
+                &ctx,
+                "reg",
+                dir,
+                &SchemaReconciler::new(target, DriftRules::new()),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(report.files_checked, 3);
+        assert_eq!(report.files.len(), 1);
+
+        let years = |sql: &str| -> Vec<i32> {
+            runtime
+                .block_on(async { ctx.sql(sql).await?.collect().await })
+                .unwrap()
+                .iter()
+                .flat_map(|batch| {
+                    let years = batch
+                        .column(0)
+                        .as_any()
+                        .downcast_ref::<Int32Array>()
+                        .unwrap();
+                    years.iter().flatten().collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        assert_eq!(
+            years("SELECT year FROM reg ORDER BY \"PNR\""),
+            vec![2019, 2019, 2020]
+        );
+        assert_eq!(years("SELECT year FROM reg WHERE year = 2020"), vec![2020]);
+    }
+}
@@ -2,11 +2,15 @@
 //!
 //! This module contains schema definitions for various Danish registry data sources.
 
+pub mod drift;
 pub mod registry;
 pub mod traits;
+pub mod validation;
 
 // Re-export schema traits
 pub use traits::RegistrySchema;
+pub use drift::{DriftReport, DriftRules, SchemaReconciler};
+pub use validation::{PnrFormat, RegistryValidator, ValidationConfig, ValidationReport};
 
 // Re-export registry schemas
 pub use registry::*;
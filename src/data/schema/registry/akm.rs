@@ -25,6 +25,11 @@ impl RegistrySchema for AkmSchema {
         vec!["PNR", "SOCIO", "SOCIO02", "SOCIO13", "CPRTJEK", "CPRTYPE", "VERSION", "SENR"]
     }
 
+    /// One row per person and file
+    fn key_columns() -> Vec<&'static str> {
+        vec!["PNR"]
+    }
+
     /// Get default metadata for this schema
     fn default_metadata() -> HashMap<String, String> {
         let mut metadata = HashMap::new();
@@ -31,7 +31,8 @@ impl RegistrySchema for BefSchema {
             Field::new("HUSTYPE", DataType::Int8, true),
             Field::new("IE_TYPE", DataType::Utf8, true),
             Field::new("KOEN", DataType::Utf8, true),
-            Field::new("KOM", DataType::Int8, true),
+            // Municipality codes go up to 860; older years store them as Int8 or Utf8
+            Field::new("KOM", DataType::Int16, true),
             Field::new("OPR_LAND", DataType::Utf8, true),
             Field::new("PLADS", DataType::Int8, true),
             Field::new("REG", DataType::Int8, true),
@@ -51,6 +52,16 @@ impl RegistrySchema for BefSchema {
         ]
     }
 
+    /// One row per person and file
+    fn key_columns() -> Vec<&'static str> {
+        vec!["PNR"]
+    }
+
+    /// The person and parents
+    fn pnr_columns() -> Vec<&'static str> {
+        vec!["PNR", "FAR_ID", "MOR_ID"]
+    }
+
     /// Get default metadata for this schema
     fn default_metadata() -> HashMap<String, String> {
         let mut metadata = HashMap::new();
@@ -19,6 +19,11 @@ impl RegistrySchema for DodSchema {
         vec!["PNR", "DODDATO"]
     }
 
+    /// One row per person and file
+    fn key_columns() -> Vec<&'static str> {
+        vec!["PNR"]
+    }
+
     /// Get default metadata for this schema
     fn default_metadata() -> HashMap<String, String> {
         let mut metadata = HashMap::new();
@@ -1,3 +1,4 @@
+use crate::data::schema::drift::DriftRules;
 use crate::data::schema::traits::RegistrySchema;
 use arrow::datatypes::{DataType, Field, Schema};
 use std::collections::HashMap;
@@ -25,6 +26,19 @@ impl RegistrySchema for IndSchema {
         vec!["BESKST13", "CPRTJEK", "CPRTYPE", "LOENMV_13", "PERINDKIALT_13", "PNR", "PRE_SOCIO", "VERSION"]
     }
 
+    /// One row per person and file
+    fn key_columns() -> Vec<&'static str> {
+        vec!["PNR"]
+    }
+
+    /// Income variables were suffixed with `_13` in the 2013 revision
+    fn drift_rules() -> DriftRules {
+        DriftRules::new()
+            .rename("BESKST", "BESKST13")
+            .rename("LOENMV", "LOENMV_13")
+            .rename("PERINDKIALT", "PERINDKIALT_13")
+    }
+
     /// Get default metadata for this schema
     fn default_metadata() -> HashMap<String, String> {
         let mut metadata = HashMap::new();
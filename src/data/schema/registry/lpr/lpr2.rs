@@ -153,7 +153,10 @@ impl LprSchema for Lpr2Schema {
 /// Get the Arrow schema for LPR2 Procedure (`LPR_BES`) data
 #[must_use] pub fn lpr2_proc_schema() -> Schema {
     Schema::new(vec![
+        Field::new("C_OPR", DataType::Utf8, true),
+        Field::new("C_OPRART", DataType::Utf8, true),
         Field::new("D_AMBDTO", DataType::Utf8, true),
+        Field::new("D_ODTO", DataType::Utf8, true),
         Field::new("RECNUM", DataType::Utf8, true),
     ])
 }
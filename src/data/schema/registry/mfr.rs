@@ -38,6 +38,16 @@ impl RegistrySchema for MfrSchema {
         ]
     }
 
+    /// One row per child
+    fn key_columns() -> Vec<&'static str> {
+        vec!["CPR_BARN"]
+    }
+
+    /// The child and parents
+    fn pnr_columns() -> Vec<&'static str> {
+        vec!["CPR_BARN", "CPR_MODER", "CPR_FADER"]
+    }
+
     /// Get default metadata for this schema
     fn default_metadata() -> HashMap<String, String> {
         let mut metadata = HashMap::new();
@@ -14,8 +14,9 @@ impl RegistrySchema for UddfSchema {
             Field::new("CPRTYPE", DataType::Utf8, true),
             Field::new("HFAUDD", DataType::Utf8, true),
             Field::new("HF_KILDE", DataType::Utf8, true),
-            Field::new("HF_VFRA", DataType::Utf8, true),
-            Field::new("HF_VTIL", DataType::Utf8, true),
+            // Stored as date strings in some years, parsed on load
+            Field::new("HF_VFRA", DataType::Date32, true),
+            Field::new("HF_VTIL", DataType::Date32, true),
             Field::new("INSTNR", DataType::Int8, true),
             Field::new("VERSION", DataType::Utf8, true),
         ])
@@ -1,6 +1,8 @@
 use arrow::datatypes::{Schema, SchemaRef};
 use std::sync::Arc;
 
+use super::drift::DriftRules;
+
 /// Registry schema trait that defines schema behaviors
 pub trait RegistrySchema: 'static + Send + Sync {
     /// Get the Arrow schema for this registry
@@ -21,6 +23,24 @@ pub trait RegistrySchema: 'static + Send + Sync {
     /// Get column names for this schema
     fn column_names() -> Vec<&'static str> where Self: Sized;
 
+    /// Rename rules for files from years that used other column names
+    #[must_use] fn drift_rules() -> DriftRules where Self: Sized {
+        DriftRules::default()
+    }
+
+    /// Columns that identify a row within a file (none if rows need not be unique)
+    #[must_use] fn key_columns() -> Vec<&'static str> where Self: Sized {
+        Vec::new()
+    }
+
+    /// Columns holding PNRs
+    #[must_use] fn pnr_columns() -> Vec<&'static str> where Self: Sized {
+        Self::column_names()
+            .into_iter()
+            .filter(|column| matches!(*column, "PNR" | "CPR"))
+            .collect()
+    }
+
     /// Get default metadata for this schema
     #[must_use] fn default_metadata() -> std::collections::HashMap<String, String> where Self: Sized {
         let mut metadata = std::collections::HashMap::new();
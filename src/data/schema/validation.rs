@@ -0,0 +1,632 @@
+//! Validation of registry files against their schema
+//!
+//! Loaders reconcile what they can (see [`super::drift`]) and fail or silently null the
+//! rest, so a broken delivery tends to surface as a confusing error deep in a pipeline.
+//! [`RegistryValidator`] checks each Parquet file of a registry up front for:
+//!
+//! - missing, extra and type-mismatched columns, and values a type coercion would lose;
+//! - nulls in columns the schema declares non-nullable;
+//! - malformed PNRs in the registry's PNR columns;
+//! - dates outside a plausible range (the open-ended sentinel 9999-12-31 is accepted);
+//! - duplicate keys within a file, for registries with one row per key.
+//!
+//! Findings that the loaders handle without losing data (renames, lossless coercions,
+//! missing nullable or extra columns) are warnings; everything else fails the file.
+
+use arrow::array::{Array, Date32Array, StringArray};
+use arrow::compute::{cast_with_options, CastOptions};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::drift::{FileDrift, SchemaReconciler};
+use crate::data::pruning::select_indexed_files;
+use crate::data::registry::factory::RegistrySchemaInfo;
+use crate::error::{IdsError, Result};
+use crate::model::pnr::validate_pnr;
+use crate::utils::date_utils::{date_to_days_since_epoch, days_since_epoch_to_date};
+
+/// Open-ended validity date used by Danish registers
+const OPEN_END_DATE: (i32, u32, u32) = (9999, 12, 31);
+
+/// Accepted PNR format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PnrFormat {
+    /// Any non-blank value (pseudonymised identifiers)
+    Any,
+    /// Ten digits, optionally as DDMMYY-XXXX
+    #[default]
+    Digits,
+    /// Ten digits starting with a valid birth date
+    Cpr,
+}
+
+impl PnrFormat {
+    /// Whether a value is a PNR in this format
+    #[must_use]
+    pub fn accepts(self, value: &str) -> bool {
+        if !value.is_ascii() {
+            return self == Self::Any && !value.trim().is_empty();
+        }
+        let dashed = match (value.len(), value.as_bytes().get(6)) {
+            (10, _) => format!("{}-{}", &value[..6], &value[6..]),
+            (11, Some(b'-')) => value.to_string(),
+            _ => return self == Self::Any && !value.trim().is_empty(),
+        };
+        match self {
+            Self::Any => !value.trim().is_empty(),
+            Self::Digits => {
+                dashed
+                    .char_indices()
+                    .all(|(i, c)| if i == 6 { c == '-' } else { c.is_ascii_digit() })
+            }
+            Self::Cpr => validate_pnr(&dashed),
+        }
+    }
+}
+
+impl FromStr for PnrFormat {
+    type Err = IdsError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "any" => Ok(Self::Any),
+            "digits" => Ok(Self::Digits),
+            "cpr" => Ok(Self::Cpr),
+            _ => Err(IdsError::Validation(format!(
+                "Unknown PNR format: {s} (expected any, digits or cpr)"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PnrFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Digits => write!(f, "digits"),
+            Self::Cpr => write!(f, "cpr"),
+        }
+    }
+}
+
+/// Validation settings
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    pub pnr_format: PnrFormat,
+    /// Earliest plausible date
+    pub min_date: NaiveDate,
+    /// Latest plausible date
+    pub max_date: NaiveDate,
+    /// Treat warnings as failures
+    pub strict: bool,
+    /// Example values reported per finding
+    pub max_examples: usize,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            pnr_format: PnrFormat::default(),
+            min_date: NaiveDate::from_ymd_opt(1900, 1, 1).unwrap_or_default(),
+            max_date: chrono::Local::now().date_naive(),
+            strict: false,
+            max_examples: 5,
+        }
+    }
+}
+
+/// A column whose file type differs from the schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TypeMismatch {
+    pub column: String,
+    pub expected: String,
+    pub found: String,
+    /// Non-null values that cannot be converted to the expected type
+    pub unconvertible: usize,
+}
+
+/// Offending values of a column
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ColumnFinding {
+    pub column: String,
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+impl ColumnFinding {
+    fn record(&mut self, value: impl FnOnce() -> String, max_examples: usize) {
+        self.count += 1;
+        if self.examples.len() < max_examples {
+            self.examples.push(value());
+        }
+    }
+}
+
+/// Rows sharing a key within a file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DuplicateKeys {
+    pub columns: Vec<String>,
+    /// Rows whose key occurred earlier in the file
+    pub count: usize,
+    pub examples: Vec<String>,
+}
+
+/// Validation result of one file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileValidation {
+    pub path: PathBuf,
+    pub passed: bool,
+    pub rows: usize,
+    /// Set if the file could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Columns matched under another name (file name, schema name)
+    pub renamed_columns: Vec<(String, String)>,
+    /// Non-nullable schema columns missing from the file
+    pub missing_required_columns: Vec<String>,
+    /// Nullable schema columns missing from the file
+    pub missing_columns: Vec<String>,
+    /// File columns not in the schema
+    pub extra_columns: Vec<String>,
+    pub type_mismatches: Vec<TypeMismatch>,
+    /// Nulls in non-nullable columns
+    pub null_violations: Vec<ColumnFinding>,
+    pub malformed_pnrs: Vec<ColumnFinding>,
+    pub out_of_range_dates: Vec<ColumnFinding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_keys: Option<DuplicateKeys>,
+}
+
+impl FileValidation {
+    /// Whether the file has findings that lose or corrupt data
+    #[must_use]
+    pub fn has_errors(&self) -> bool {
+        self.error.is_some()
+            || !self.missing_required_columns.is_empty()
+            || self.type_mismatches.iter().any(|m| m.unconvertible > 0)
+            || !self.null_violations.is_empty()
+            || !self.malformed_pnrs.is_empty()
+            || !self.out_of_range_dates.is_empty()
+            || self.duplicate_keys.is_some()
+    }
+
+    /// Whether the file has findings the loaders reconcile
+    #[must_use]
+    pub fn has_warnings(&self) -> bool {
+        !self.renamed_columns.is_empty()
+            || !self.missing_columns.is_empty()
+            || !self.extra_columns.is_empty()
+            || !self.type_mismatches.is_empty()
+    }
+}
+
+/// Validation result of a registry file or directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub registry: String,
+    pub path: PathBuf,
+    pub passed: bool,
+    pub files_checked: usize,
+    pub files_failed: usize,
+    pub files: Vec<FileValidation>,
+}
+
+impl ValidationReport {
+    /// The report as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| IdsError::Data(format!("Failed to serialize validation report: {e}")))
+    }
+}
+
+/// Per-file state of the value checks
+struct FileChecks {
+    nulls: BTreeMap<String, usize>,
+    pnrs: BTreeMap<String, ColumnFinding>,
+    dates: BTreeMap<String, ColumnFinding>,
+    keys: HashSet<String>,
+    duplicates: DuplicateKeys,
+    rows: usize,
+}
+
+/// Checks registry files against a registry schema
+#[derive(Debug, Clone)]
+pub struct RegistryValidator {
+    info: RegistrySchemaInfo,
+    config: ValidationConfig,
+    /// Reads files with the schema made nullable, so violations can be counted
+    reconciler: SchemaReconciler,
+}
+
+impl RegistryValidator {
+    /// Create a validator for a registry schema
+    #[must_use]
+    pub fn new(info: RegistrySchemaInfo, config: ValidationConfig) -> Self {
+        let relaxed: Vec<Field> = info
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.as_ref().clone().with_nullable(true))
+            .collect();
+        let reconciler =
+            SchemaReconciler::new(Arc::new(Schema::new(relaxed)), info.drift_rules.clone());
+        Self {
+            info,
+            config,
+            reconciler,
+        }
+    }
+
+    /// The registry schema
+    #[must_use]
+    pub fn schema(&self) -> SchemaRef {
+        self.info.schema.clone()
+    }
+
+    /// Validate a Parquet file or every Parquet file of a directory
+    pub fn validate_path(&self, path: &Path) -> Result<ValidationReport> {
+        let files = select_indexed_files(path, None)?;
+        let mut report = ValidationReport {
+            registry: self.info.name.to_string(),
+            path: path.to_path_buf(),
+            files_checked: files.len(),
+            ..ValidationReport::default()
+        };
+        for file in &files {
+            let validation = self.validate_file(file);
+            if !validation.passed {
+                report.files_failed += 1;
+            }
+            report.files.push(validation);
+        }
+        report.passed = report.files_failed == 0 && report.files_checked > 0;
+        Ok(report)
+    }
+
+    /// Validate one Parquet file; read errors are reported in the result
+    #[must_use]
+    pub fn validate_file(&self, path: &Path) -> FileValidation {
+        let mut checks = FileChecks {
+            nulls: BTreeMap::new(),
+            pnrs: BTreeMap::new(),
+            dates: BTreeMap::new(),
+            keys: HashSet::new(),
+            duplicates: DuplicateKeys {
+                columns: self
+                    .info
+                    .key_columns
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                ..DuplicateKeys::default()
+            },
+            rows: 0,
+        };
+
+        let drift = match self.reconciler.inspect(path) {
+            Ok(drift) => drift,
+            Err(e) => return self.failed(path, &e),
+        };
+        let absent: HashSet<&str> = drift.missing.iter().map(String::as_str).collect();
+        let drift = match self
+            .reconciler
+            .for_each_batch(path, |batch| self.check_batch(&batch, &absent, &mut checks))
+        {
+            Ok(drift) => drift,
+            Err(e) => return self.failed(path, &e),
+        };
+
+        let mut validation = self.structure(path, drift);
+        validation.rows = checks.rows;
+        validation.null_violations = checks
+            .nulls
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(column, count)| ColumnFinding {
+                column,
+                count,
+                examples: Vec::new(),
+            })
+            .collect();
+        validation.malformed_pnrs = checks.pnrs.into_values().filter(|f| f.count > 0).collect();
+        validation.out_of_range_dates =
+            checks.dates.into_values().filter(|f| f.count > 0).collect();
+        validation.duplicate_keys = (checks.duplicates.count > 0).then_some(checks.duplicates);
+        validation.passed = self.passes(&validation);
+        validation
+    }
+
+    /// Column-level findings of a file
+    fn structure(&self, path: &Path, drift: FileDrift) -> FileValidation {
+        let (missing_required_columns, missing_columns) =
+            drift.missing.into_iter().partition(|column| {
+                self.info
+                    .schema
+                    .field_with_name(column)
+                    .is_ok_and(|field| !field.is_nullable())
+            });
+        FileValidation {
+            path: path.to_path_buf(),
+            renamed_columns: drift.renamed,
+            missing_required_columns,
+            missing_columns,
+            extra_columns: drift.dropped,
+            type_mismatches: drift
+                .coerced
+                .into_iter()
+                .map(|coercion| TypeMismatch {
+                    column: coercion.column,
+                    expected: coercion.to,
+                    found: coercion.from,
+                    unconvertible: coercion.nulled,
+                })
+                .collect(),
+            ..FileValidation::default()
+        }
+    }
+
+    /// A file that could not be read
+    fn failed(&self, path: &Path, error: &IdsError) -> FileValidation {
+        FileValidation {
+            path: path.to_path_buf(),
+            error: Some(error.to_string()),
+            ..FileValidation::default()
+        }
+    }
+
+    fn passes(&self, validation: &FileValidation) -> bool {
+        !(validation.has_errors() || self.config.strict && validation.has_warnings())
+    }
+
+    /// Value checks of a reconciled batch; `absent` lists columns missing from the file
+    fn check_batch(
+        &self,
+        batch: &RecordBatch,
+        absent: &HashSet<&str>,
+        checks: &mut FileChecks,
+    ) -> Result<()> {
+        checks.rows += batch.num_rows();
+        let max_examples = self.config.max_examples;
+
+        for (field, column) in self.info.schema.fields().iter().zip(batch.columns()) {
+            let name = field.name();
+            if absent.contains(name.as_str()) {
+                continue;
+            }
+            if !field.is_nullable() {
+                *checks.nulls.entry(name.clone()).or_default() += column.null_count();
+            }
+            if field.data_type() == &DataType::Date32 {
+                self.check_dates(name, column.as_ref(), checks);
+            }
+        }
+
+        for name in &self.info.pnr_columns {
+            if absent.contains(name) {
+                continue;
+            }
+            let Ok(idx) = batch.schema().index_of(name) else {
+                continue;
+            };
+            let values = string_column(batch.column(idx))?;
+            let finding = checks
+                .pnrs
+                .entry((*name).to_string())
+                .or_insert_with(|| ColumnFinding {
+                    column: (*name).to_string(),
+                    ..ColumnFinding::default()
+                });
+            for value in values.iter().flatten() {
+                if !self.config.pnr_format.accepts(value) {
+                    finding.record(|| value.to_string(), max_examples);
+                }
+            }
+        }
+
+        self.check_keys(batch, absent, checks)
+    }
+
+    fn check_dates(&self, name: &str, column: &dyn Array, checks: &mut FileChecks) {
+        let Some(dates) = column.as_any().downcast_ref::<Date32Array>() else {
+            return;
+        };
+        let min = date_to_days_since_epoch(self.config.min_date);
+        let max = date_to_days_since_epoch(self.config.max_date);
+        let open_end = NaiveDate::from_ymd_opt(OPEN_END_DATE.0, OPEN_END_DATE.1, OPEN_END_DATE.2)
+            .map(date_to_days_since_epoch);
+        let finding = checks
+            .dates
+            .entry(name.to_string())
+            .or_insert_with(|| ColumnFinding {
+                column: name.to_string(),
+                ..ColumnFinding::default()
+            });
+        for days in dates.iter().flatten() {
+            if (days < min || days > max) && Some(days) != open_end {
+                finding.record(
+                    || days_since_epoch_to_date(days).to_string(),
+                    self.config.max_examples,
+                );
+            }
+        }
+    }
+
+    fn check_keys(
+        &self,
+        batch: &RecordBatch,
+        absent: &HashSet<&str>,
+        checks: &mut FileChecks,
+    ) -> Result<()> {
+        let key_columns = &self.info.key_columns;
+        if key_columns.is_empty() || key_columns.iter().any(|column| absent.contains(column)) {
+            return Ok(());
+        }
+        let columns = key_columns
+            .iter()
+            .map(|column| string_column(batch.column(batch.schema().index_of(column)?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        for row in 0..batch.num_rows() {
+            let parts: Option<Vec<&str>> = columns
+                .iter()
+                .map(|column| column.is_valid(row).then(|| column.value(row)))
+                .collect();
+            // Rows with a null key are reported as null violations
+            let Some(parts) = parts else {
+                continue;
+            };
+            let key = parts.join("|");
+            if !checks.keys.insert(key.clone()) {
+                checks.duplicates.count += 1;
+                if checks.duplicates.examples.len() < self.config.max_examples {
+                    checks.duplicates.examples.push(key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A column as strings
+fn string_column(column: &arrow::array::ArrayRef) -> Result<StringArray> {
+    let strings = cast_with_options(column, &DataType::Utf8, &CastOptions::default())?;
+    strings
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .cloned()
+        .ok_or_else(|| IdsError::Data("Failed to cast column to strings".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::registry::factory::RegistryFactory;
+    use crate::test_utils::{temp_dir, write_parquet};
+    use arrow::array::{Date32Array, StringArray};
+
+    fn day(year: i32, month: u32, day: u32) -> i32 {
+        date_to_days_since_epoch(NaiveDate::from_ymd_opt(year, month, day).unwrap())
+    }
+
+    /// Write a BEF file with PNR, birth date and an extra column
+    fn write_bef(path: &Path, pnrs: Vec<Option<&str>>, dates: Vec<i32>) {
+        let rows = pnrs.len();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("PNR", DataType::Utf8, true),
+            Field::new("FOED_DAG", DataType::Date32, true),
+            Field::new("EXTRA", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(pnrs)),
+                Arc::new(Date32Array::from(dates)),
+                Arc::new(StringArray::from(vec![Some("x"); rows])),
+            ],
+        )
+        .unwrap();
+        write_parquet(path, &batch);
+    }
+
+    fn bef_validator(config: ValidationConfig) -> RegistryValidator {
+        RegistryValidator::new(
+            RegistryFactory::schema_info_from_name("bef").unwrap(),
+            config,
+        )
+    }
+
+    #[test]
+    fn test_schema_info_from_path() {
+        let info = RegistryFactory::schema_info_from_path(Path::new("/registers/bef/2020.parquet"))
+            .unwrap();
+        assert_eq!(info.name, "BEF");
+    }
+
+    #[test]
+    fn test_clean_file_passes_with_warnings() {
+        let dir = temp_dir();
+        let path = dir.path().join("bef2020.parquet");
+        write_bef(
+            &path,
+            vec![Some("0101801234"), Some("020280-1234")],
+            vec![day(2020, 1, 1), day(2020, 6, 1)],
+        );
+
+        let validation = bef_validator(ValidationConfig::default()).validate_file(&path);
+        assert!(validation.passed);
+        assert_eq!(validation.extra_columns, vec!["EXTRA".to_string()]);
+
+        // Strict validation also fails on warnings
+        let strict = bef_validator(ValidationConfig {
+            strict: true,
+            ..ValidationConfig::default()
+        });
+        assert!(!strict.validate_file(&path).passed);
+    }
+
+    #[test]
+    fn test_validate_reports_findings() {
+        let dir = temp_dir();
+        write_bef(
+            &dir.path().join("bef2020.parquet"),
+            vec![Some("0101801234")],
+            vec![day(2020, 1, 1)],
+        );
+        write_bef(
+            &dir.path().join("bef2021.parquet"),
+            vec![Some("0101801234"), Some("0101801234"), Some("12345"), None],
+            vec![
+                day(2021, 1, 1),
+                day(1800, 1, 1),
+                day(9999, 12, 31),
+                day(2021, 2, 1),
+            ],
+        );
+
+        let report = bef_validator(ValidationConfig::default())
+            .validate_path(dir.path())
+            .unwrap();
+        assert_eq!(report.files_checked, 2);
+        assert_eq!(report.files_failed, 1);
+        assert!(!report.passed);
+
+        let broken = &report.files[1];
+        assert_eq!(broken.null_violations[0].count, 1);
+        assert_eq!(broken.malformed_pnrs[0].examples, vec!["12345".to_string()]);
+        assert_eq!(
+            broken.out_of_range_dates[0].examples,
+            vec!["1800-01-01".to_string()]
+        );
+        assert_eq!(broken.duplicate_keys.as_ref().unwrap().count, 1);
+        assert!(report.to_json().unwrap().contains("\"malformed_pnrs\""));
+    }
+
+    #[test]
+    fn test_unreadable_file_fails() {
+        let dir = temp_dir();
+        let path = dir.path().join("bef2020.parquet");
+        std::fs::write(&path, b"not parquet").unwrap();
+
+        let validation = bef_validator(ValidationConfig::default()).validate_file(&path);
+        assert!(!validation.passed);
+        assert!(validation.error.is_some());
+    }
+
+    #[test]
+    fn test_empty_directory_does_not_pass() {
+        let dir = temp_dir();
+        let report = bef_validator(ValidationConfig::default())
+            .validate_path(dir.path())
+            .unwrap();
+        assert_eq!(report.files_checked, 0);
+        assert!(!report.passed);
+    }
+}
@@ -70,6 +70,12 @@ impl From<arrow::error::ArrowError> for IdsError {
     }
 }
 
+impl From<datafusion::parquet::errors::ParquetError> for IdsError {
+    fn from(err: datafusion::parquet::errors::ParquetError) -> Self {
+        Self::External(Box::new(err))
+    }
+}
+
 impl From<datafusion::error::DataFusionError> for IdsError {
     fn from(err: datafusion::error::DataFusionError) -> Self {
         Self::DataFusion(err)
@@ -16,6 +16,9 @@ pub mod model;
 pub mod store;
 pub mod utils;
 
+#[cfg(test)]
+mod test_utils;
+
 // Re-export commonly used items
 pub use model::pnr::Pnr;
 pub use utils::date_utils::DateExtensions;
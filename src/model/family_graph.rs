@@ -0,0 +1,506 @@
+//! Family relationship graph over all persons
+//!
+//! [`FamilyRelations`] only describes a child and its two parents. The [`FamilyGraph`]
+//! links every PNR to its parents and children from BEF/MFR parent edges, so relatives
+//! across generations (grandparents, siblings, cousins, co-parents) can be traversed.
+//! It also keeps date-aware household membership from BEF `FAMILIE_ID`/`BOP_VFRA`.
+
+use arrow::array::{Array, StringArray};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDate;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::{IdsError, Result};
+use crate::model::family::FamilyRelations;
+use crate::utils::date_utils::extract_date_from_array;
+
+/// Kind of relationship to traverse from a person
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Relation {
+    /// The person's mother
+    Mother,
+    /// The person's father
+    Father,
+    /// Both parents
+    Parent,
+    /// The person's children
+    Child,
+    /// All four grandparents
+    Grandparent,
+    /// The mother's parents
+    MaternalGrandparent,
+    /// The father's parents
+    PaternalGrandparent,
+    /// Anyone sharing at least one parent
+    Sibling,
+    /// Siblings sharing both parents
+    FullSibling,
+    /// Siblings sharing exactly one parent
+    HalfSibling,
+    /// Siblings sharing the mother (full or half)
+    MaternalSibling,
+    /// Siblings sharing the father (full or half)
+    PaternalSibling,
+    /// Children of the parents' siblings
+    Cousin,
+    /// Persons who have a child with this person
+    CoParent,
+}
+
+/// A person node with parent and child edges
+#[derive(Debug, Clone, Default)]
+struct PersonNode {
+    birth_date: Option<NaiveDate>,
+    mother: Option<String>,
+    father: Option<String>,
+    children: BTreeSet<String>,
+}
+
+/// A period of household membership, valid from a date until the next period starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HouseholdPeriod {
+    valid_from: NaiveDate,
+    family_id: String,
+}
+
+/// Get a non-empty string value from an array
+fn non_empty_value(array: &StringArray, i: usize) -> Option<&str> {
+    (!array.is_null(i) && !array.value(i).is_empty()).then(|| array.value(i))
+}
+
+/// Graph of parent-child relationships over all PNRs
+#[derive(Debug, Clone, Default)]
+pub struct FamilyGraph {
+    persons: HashMap<String, PersonNode>,
+    households: HashMap<String, Vec<HouseholdPeriod>>,
+    /// Everyone who has ever belonged to each household, by `FAMILIE_ID`
+    household_index: HashMap<String, BTreeSet<String>>,
+}
+
+impl FamilyGraph {
+    /// Create an empty family graph
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of persons in the graph (children and parents)
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.persons.len()
+    }
+
+    /// Whether the graph has no persons
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.persons.is_empty()
+    }
+
+    /// Add a person with optional parent edges
+    ///
+    /// Parent edges already in the graph are kept, so when BEF and MFR batches are
+    /// added in turn the first source added takes precedence and later sources only
+    /// fill in missing parents.
+    pub fn add_person(
+        &mut self,
+        pnr: &str,
+        birth_date: Option<NaiveDate>,
+        mother: Option<&str>,
+        father: Option<&str>,
+    ) {
+        let node = self.persons.entry(pnr.to_string()).or_default();
+        if node.birth_date.is_none() {
+            node.birth_date = birth_date;
+        }
+
+        let mut new_parents = Vec::with_capacity(2);
+        if let (None, Some(mother)) = (&node.mother, mother) {
+            node.mother = Some(mother.to_string());
+            new_parents.push(mother);
+        }
+        if let (None, Some(father)) = (&node.father, father) {
+            node.father = Some(father.to_string());
+            new_parents.push(father);
+        }
+
+        for parent in new_parents {
+            self.persons
+                .entry(parent.to_string())
+                .or_default()
+                .children
+                .insert(pnr.to_string());
+        }
+    }
+
+    /// Record that a person belongs to a household from a given date
+    pub fn add_household_membership(&mut self, pnr: &str, family_id: &str, valid_from: NaiveDate) {
+        self.household_index
+            .entry(family_id.to_string())
+            .or_default()
+            .insert(pnr.to_string());
+
+        let periods = self.households.entry(pnr.to_string()).or_default();
+        let period = HouseholdPeriod {
+            valid_from,
+            family_id: family_id.to_string(),
+        };
+        let position = periods.partition_point(|p| p.valid_from <= valid_from);
+        // Keep a single period per start date; the latest record for a date wins
+        if position > 0 && periods[position - 1].valid_from == valid_from {
+            periods[position - 1] = period;
+        } else {
+            periods.insert(position, period);
+        }
+    }
+
+    /// Add persons from a record batch with `PNR`, `MOR_ID` and `FAR_ID` columns
+    ///
+    /// This accepts BEF data, the output of `create_family_data`, or MFR children as
+    /// extracted by `extract_mfr_children`. `FOED_DAG` is used for birth dates when
+    /// present, and household membership is recorded when both `FAMILIE_ID` and
+    /// `BOP_VFRA` are present.
+    pub fn add_record_batch(&mut self, batch: &RecordBatch) -> Result<()> {
+        let string_column = |name: &str| -> Result<&StringArray> {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| IdsError::Data(format!("{name} column not found in family data")))?
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| IdsError::Data(format!("{name} column is not a StringArray")))
+        };
+
+        let pnr_array = string_column("PNR")?;
+        let mother_array = string_column("MOR_ID")?;
+        let father_array = string_column("FAR_ID")?;
+        let birth_column = batch.column_by_name("FOED_DAG");
+        let family_array = batch
+            .column_by_name("FAMILIE_ID")
+            .and_then(|col| col.as_any().downcast_ref::<StringArray>());
+        let valid_from_column = batch.column_by_name("BOP_VFRA");
+
+        for i in 0..batch.num_rows() {
+            let Some(pnr) = non_empty_value(pnr_array, i) else {
+                continue;
+            };
+            let birth_date =
+                birth_column.and_then(|col| extract_date_from_array(col.as_ref(), i));
+            self.add_person(pnr, birth_date, non_empty_value(mother_array, i), non_empty_value(father_array, i));
+
+            if let (Some(families), Some(valid_from)) = (family_array, valid_from_column) {
+                if let (Some(family_id), Some(date)) = (
+                    non_empty_value(families, i),
+                    extract_date_from_array(valid_from.as_ref(), i),
+                ) {
+                    self.add_household_membership(pnr, family_id, date);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build a graph from record batches, in order of precedence
+    pub fn from_record_batches(batches: &[RecordBatch]) -> Result<Self> {
+        let mut graph = Self::new();
+        for batch in batches {
+            graph.add_record_batch(batch)?;
+        }
+        Ok(graph)
+    }
+
+    /// Whether the person is in the graph
+    #[must_use]
+    pub fn contains(&self, pnr: &str) -> bool {
+        self.persons.contains_key(pnr)
+    }
+
+    /// The person's birth date, if known
+    #[must_use]
+    pub fn birth_date(&self, pnr: &str) -> Option<NaiveDate> {
+        self.persons.get(pnr).and_then(|node| node.birth_date)
+    }
+
+    /// The person's mother, if known
+    #[must_use]
+    pub fn mother(&self, pnr: &str) -> Option<&str> {
+        self.persons.get(pnr).and_then(|node| node.mother.as_deref())
+    }
+
+    /// The person's father, if known
+    #[must_use]
+    pub fn father(&self, pnr: &str) -> Option<&str> {
+        self.persons.get(pnr).and_then(|node| node.father.as_deref())
+    }
+
+    /// The person's known parents
+    #[must_use]
+    pub fn parents(&self, pnr: &str) -> Vec<&str> {
+        self.mother(pnr).into_iter().chain(self.father(pnr)).collect()
+    }
+
+    /// The person's children
+    #[must_use]
+    pub fn children(&self, pnr: &str) -> Vec<&str> {
+        self.persons
+            .get(pnr)
+            .map(|node| node.children.iter().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Find all relatives of a person of the given kind
+    ///
+    /// Results are sorted and contain each PNR once; the person is never included.
+    #[must_use]
+    pub fn relatives(&self, pnr: &str, relation: Relation) -> Vec<&str> {
+        let mut result: BTreeSet<&str> = match relation {
+            Relation::Mother => self.mother(pnr).into_iter().collect(),
+            Relation::Father => self.father(pnr).into_iter().collect(),
+            Relation::Parent => self.parents(pnr).into_iter().collect(),
+            Relation::Child => self.children(pnr).into_iter().collect(),
+            Relation::Grandparent => self
+                .parents(pnr)
+                .into_iter()
+                .flat_map(|parent| self.parents(parent))
+                .collect(),
+            Relation::MaternalGrandparent => self
+                .mother(pnr)
+                .map(|mother| self.parents(mother).into_iter().collect())
+                .unwrap_or_default(),
+            Relation::PaternalGrandparent => self
+                .father(pnr)
+                .map(|father| self.parents(father).into_iter().collect())
+                .unwrap_or_default(),
+            Relation::Sibling => self
+                .parents(pnr)
+                .into_iter()
+                .flat_map(|parent| self.children(parent))
+                .collect(),
+            Relation::MaternalSibling => self
+                .mother(pnr)
+                .map(|mother| self.children(mother).into_iter().collect())
+                .unwrap_or_default(),
+            Relation::PaternalSibling => self
+                .father(pnr)
+                .map(|father| self.children(father).into_iter().collect())
+                .unwrap_or_default(),
+            Relation::FullSibling => self
+                .relatives(pnr, Relation::Sibling)
+                .into_iter()
+                .filter(|sibling| self.is_full_sibling(pnr, sibling))
+                .collect(),
+            Relation::HalfSibling => self
+                .relatives(pnr, Relation::Sibling)
+                .into_iter()
+                .filter(|sibling| !self.is_full_sibling(pnr, sibling))
+                .collect(),
+            Relation::Cousin => {
+                let siblings: BTreeSet<&str> =
+                    self.relatives(pnr, Relation::Sibling).into_iter().collect();
+                self.parents(pnr)
+                    .into_iter()
+                    .flat_map(|parent| self.relatives(parent, Relation::Sibling))
+                    .flat_map(|aunt_or_uncle| self.children(aunt_or_uncle))
+                    .filter(|cousin| !siblings.contains(cousin))
+                    .collect()
+            }
+            Relation::CoParent => self
+                .children(pnr)
+                .into_iter()
+                .flat_map(|child| self.parents(child))
+                .collect(),
+        };
+
+        result.remove(pnr);
+        result.into_iter().collect()
+    }
+
+    /// Whether any relative of the given kind satisfies a predicate
+    ///
+    /// This is the building block for family-history exposures, e.g. "grandparent
+    /// with diagnosis X" given the set of PNRs with that diagnosis.
+    pub fn any_relative<F>(&self, pnr: &str, relation: Relation, predicate: F) -> bool
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.relatives(pnr, relation).into_iter().any(predicate)
+    }
+
+    /// Whether two persons share both known parents
+    #[must_use]
+    pub fn is_full_sibling(&self, a: &str, b: &str) -> bool {
+        match (self.persons.get(a), self.persons.get(b)) {
+            (Some(a), Some(b)) => {
+                a.mother.is_some()
+                    && a.father.is_some()
+                    && a.mother == b.mother
+                    && a.father == b.father
+            }
+            _ => false,
+        }
+    }
+
+    /// The household (`FAMILIE_ID`) a person belonged to on a given date
+    #[must_use]
+    pub fn household_at(&self, pnr: &str, date: NaiveDate) -> Option<&str> {
+        let periods = self.households.get(pnr)?;
+        let position = periods.partition_point(|p| p.valid_from <= date);
+        (position > 0).then(|| periods[position - 1].family_id.as_str())
+    }
+
+    /// All persons sharing a household with the person on a given date
+    ///
+    /// Only persons who have ever belonged to the household are checked, so the cost
+    /// depends on the household's size rather than the graph's.
+    #[must_use]
+    pub fn household_members(&self, pnr: &str, date: NaiveDate) -> Vec<&str> {
+        let Some(family_id) = self.household_at(pnr, date) else {
+            return Vec::new();
+        };
+
+        // The index is sorted, and may list persons who have since moved out
+        self.household_index
+            .get(family_id)
+            .into_iter()
+            .flatten()
+            .filter(|other| other.as_str() != pnr)
+            .filter(|other| self.household_at(other, date) == Some(family_id))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Get the person's immediate family as a [`FamilyRelations`] record
+    ///
+    /// Returns `None` if the person is unknown or has no birth date.
+    #[must_use]
+    pub fn family_relations(&self, pnr: &str) -> Option<FamilyRelations> {
+        let node = self.persons.get(pnr)?;
+        let mut relations = FamilyRelations::new(pnr, node.birth_date?);
+        if let Some(mother) = &node.mother {
+            relations = relations.with_mother(mother.as_str(), self.birth_date(mother));
+        }
+        if let Some(father) = &node.father {
+            relations = relations.with_father(father.as_str(), self.birth_date(father));
+        }
+        Some(relations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    /// Three generations: grandparents GM1+GF1 have M and an aunt A; grandparents
+    /// GM2+GF2 have F. M and F have children C1 and C2; M has C3 with F2; A has a
+    /// child K with U.
+    fn three_generations() -> FamilyGraph {
+        let mut graph = FamilyGraph::new();
+        graph.add_person("M", Some(date(1970, 1, 1)), Some("GM1"), Some("GF1"));
+        graph.add_person("A", Some(date(1972, 1, 1)), Some("GM1"), Some("GF1"));
+        graph.add_person("F", Some(date(1968, 1, 1)), Some("GM2"), Some("GF2"));
+        graph.add_person("C1", Some(date(2000, 1, 1)), Some("M"), Some("F"));
+        graph.add_person("C2", Some(date(2002, 1, 1)), Some("M"), Some("F"));
+        graph.add_person("C3", Some(date(2006, 1, 1)), Some("M"), Some("F2"));
+        graph.add_person("K", Some(date(2001, 1, 1)), Some("A"), Some("U"));
+        graph
+    }
+
+    #[test]
+    fn test_parents_and_children() {
+        let graph = three_generations();
+        assert_eq!(graph.relatives("C1", Relation::Mother), vec!["M"]);
+        assert_eq!(graph.relatives("C1", Relation::Father), vec!["F"]);
+        assert_eq!(graph.relatives("C1", Relation::Parent), vec!["F", "M"]);
+        assert_eq!(graph.relatives("M", Relation::Child), vec!["C1", "C2", "C3"]);
+        assert_eq!(graph.relatives("M", Relation::CoParent), vec!["F", "F2"]);
+        assert!(graph.relatives("GM1", Relation::Parent).is_empty());
+        assert!(graph.relatives("unknown", Relation::Sibling).is_empty());
+    }
+
+    #[test]
+    fn test_grandparents() {
+        let graph = three_generations();
+        assert_eq!(
+            graph.relatives("C1", Relation::Grandparent),
+            vec!["GF1", "GF2", "GM1", "GM2"]
+        );
+        assert_eq!(
+            graph.relatives("C1", Relation::MaternalGrandparent),
+            vec!["GF1", "GM1"]
+        );
+        assert_eq!(
+            graph.relatives("C1", Relation::PaternalGrandparent),
+            vec!["GF2", "GM2"]
+        );
+        // C3's father has no known parents
+        assert!(graph.relatives("C3", Relation::PaternalGrandparent).is_empty());
+    }
+
+    #[test]
+    fn test_siblings() {
+        let graph = three_generations();
+        assert_eq!(graph.relatives("C1", Relation::Sibling), vec!["C2", "C3"]);
+        assert_eq!(graph.relatives("C1", Relation::FullSibling), vec!["C2"]);
+        assert_eq!(graph.relatives("C1", Relation::HalfSibling), vec!["C3"]);
+        assert_eq!(graph.relatives("C3", Relation::MaternalSibling), vec!["C1", "C2"]);
+        assert!(graph.relatives("C3", Relation::PaternalSibling).is_empty());
+        assert!(graph.is_full_sibling("C1", "C2"));
+        assert!(!graph.is_full_sibling("C1", "C3"));
+    }
+
+    #[test]
+    fn test_cousins() {
+        let graph = three_generations();
+        assert_eq!(graph.relatives("C1", Relation::Cousin), vec!["K"]);
+        assert_eq!(graph.relatives("K", Relation::Cousin), vec!["C1", "C2", "C3"]);
+    }
+
+    #[test]
+    fn test_any_relative() {
+        let graph = three_generations();
+        assert!(graph.any_relative("C1", Relation::Grandparent, |pnr| pnr == "GM2"));
+        assert!(!graph.any_relative("C3", Relation::Cousin, |pnr| pnr == "C1"));
+    }
+
+    #[test]
+    fn test_first_source_takes_precedence() {
+        let mut graph = FamilyGraph::new();
+        graph.add_person("C", None, Some("M"), None);
+        graph.add_person("C", Some(date(2000, 1, 1)), Some("M2"), Some("F"));
+
+        assert_eq!(graph.mother("C"), Some("M"));
+        assert_eq!(graph.father("C"), Some("F"));
+        assert_eq!(graph.birth_date("C"), Some(date(2000, 1, 1)));
+        assert!(graph.children("M2").is_empty());
+        assert_eq!(graph.len(), 3);
+
+        let relations = graph.family_relations("C").unwrap();
+        assert_eq!(relations.mother.unwrap().pnr.value(), "M");
+        assert!(graph.family_relations("M").is_none());
+    }
+
+    #[test]
+    fn test_household_membership_by_date() {
+        let mut graph = FamilyGraph::new();
+        graph.add_household_membership("M", "H1", date(2000, 1, 1));
+        graph.add_household_membership("C", "H1", date(2000, 1, 1));
+        graph.add_household_membership("F", "H1", date(2000, 1, 1));
+        graph.add_household_membership("F", "H2", date(2005, 1, 1));
+        graph.add_household_membership("X", "H2", date(2003, 1, 1));
+
+        assert_eq!(graph.household_at("F", date(1999, 1, 1)), None);
+        assert_eq!(graph.household_at("F", date(2004, 12, 31)), Some("H1"));
+        assert_eq!(graph.household_at("F", date(2005, 1, 1)), Some("H2"));
+
+        assert_eq!(
+            graph.household_members("C", date(2004, 1, 1)),
+            vec!["F", "M"]
+        );
+        assert_eq!(graph.household_members("C", date(2006, 1, 1)), vec!["M"]);
+        assert_eq!(graph.household_members("F", date(2006, 1, 1)), vec!["X"]);
+        assert!(graph.household_members("nobody", date(2006, 1, 1)).is_empty());
+    }
+}
@@ -1,13 +1,13 @@
 //! Diagnosis pattern matching for ICD-10 codes
 //!
 //! This module provides pattern matching utilities for ICD-10 diagnosis codes,
-//! supporting both prefix matching and regex-based pattern matching.
+//! supporting prefix matching, regex-based pattern matching and inclusive code ranges.
 
 use regex::Regex;
 use std::fmt;
 
 /// Represents a normalized diagnosis code with prefix and full code
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NormalizedDiagnosis {
     /// The first 3 characters of a normalized ICD-10 code
     pub prefix: String,
@@ -22,6 +22,9 @@ pub struct DiagnosisPattern {
     pub prefix: Option<String>,
     /// Regular expression pattern for more complex matching
     pub regex: Option<Regex>,
+    /// Inclusive code range (e.g., "I30" to "I52"), compared on codes truncated to the
+    /// length of the bounds
+    pub range: Option<(String, String)>,
     /// Description of what this pattern represents
     pub description: String,
 }
@@ -31,6 +34,7 @@ impl fmt::Debug for DiagnosisPattern {
         f.debug_struct("DiagnosisPattern")
             .field("prefix", &self.prefix)
             .field("regex", &self.regex.as_ref().map(regex::Regex::as_str))
+            .field("range", &self.range)
             .field("description", &self.description)
             .finish()
     }
@@ -42,6 +46,7 @@ impl DiagnosisPattern {
         Self {
             prefix: Some(prefix.to_string()),
             regex: None,
+            range: None,
             description: description.to_string(),
         }
     }
@@ -51,19 +56,56 @@ impl DiagnosisPattern {
         Ok(Self {
             prefix: None,
             regex: Some(Regex::new(&format!("^{pattern}"))?),
+            range: None,
+            description: description.to_string(),
+        })
+    }
+
+    /// Create a new inclusive range pattern (e.g., "I30" to "I52")
+    ///
+    /// Codes are truncated to the length of the bounds and compared lexicographically,
+    /// so "I30"-"I52" matches I30 through I52 with any subcategory, and "P941"-"P949"
+    /// matches only four-character codes in that span.
+    pub fn new_range(from: &str, to: &str, description: &str) -> Result<Self, String> {
+        let from = from.trim().to_ascii_uppercase();
+        let to = to.trim().to_ascii_uppercase();
+        if from.is_empty() || from.len() != to.len() {
+            return Err(format!(
+                "Range bounds '{from}' and '{to}' must be non-empty and of equal length"
+            ));
+        }
+        if from > to {
+            return Err(format!("Range start '{from}' is after range end '{to}'"));
+        }
+
+        Ok(Self {
+            prefix: None,
+            regex: None,
+            range: Some((from, to)),
             description: description.to_string(),
         })
     }
 
     /// Check if this pattern matches the given diagnosis code
+    ///
+    /// A prefix matches the start of the full code, so `I519` matches `I519` and `I5190`
+    /// but not `I510`. Prefixes used to be compared with the three-character category
+    /// only, so prefixes of four or more characters never matched.
     #[must_use] pub fn matches(&self, diagnosis: &NormalizedDiagnosis) -> bool {
         // Check prefix match first (faster)
         if let Some(prefix) = &self.prefix {
-            if &diagnosis.prefix == prefix {
+            if diagnosis.full_code.starts_with(prefix.as_str()) {
                 return true;
             }
         }
 
+        if let Some((from, to)) = &self.range {
+            let Some(code) = diagnosis.full_code.get(..from.len()) else {
+                return false;
+            };
+            return code >= from.as_str() && code <= to.as_str();
+        }
+
         // Fall back to regex match if needed
         if let Some(regex) = &self.regex {
             return regex.is_match(&diagnosis.full_code);
@@ -103,6 +145,22 @@ impl DiagnosisPattern {
     })
 }
 
+/// Normalize an SKS procedure code for matching (upper case, without dots)
+///
+/// Unlike diagnosis codes, procedure codes keep their leading letter (e.g. `BJFD2` for
+/// dialysis, `KJDB` for gastrostomy).
+#[must_use] pub fn normalize_procedure_code(code: &str) -> Option<NormalizedDiagnosis> {
+    let full_code = code.trim().replace('.', "").to_ascii_uppercase();
+    if full_code.is_empty() {
+        return None;
+    }
+
+    Some(NormalizedDiagnosis {
+        prefix: full_code.chars().take(3).collect(),
+        full_code,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,6 +241,21 @@ mod tests {
         assert!(!pattern.matches(&code3));
     }
 
+    /// Prefixes longer than the category match subcategories
+    #[test]
+    fn test_diagnosis_pattern_subcategory_prefix() {
+        let pattern = DiagnosisPattern::new_prefix("I519", "Heart disease, unspecified");
+        let matches = |code| pattern.matches(&normalize_diagnosis_code(code).unwrap());
+
+        assert!(matches("I519"));
+        assert!(matches("DI5190"));
+        assert!(!matches("I51"));
+        assert!(!matches("I510"));
+
+        let chapter = DiagnosisPattern::new_prefix("I5", "Heart failure and others");
+        assert!(chapter.matches(&normalize_diagnosis_code("I50").unwrap()));
+    }
+
     #[test]
     fn test_diagnosis_pattern_regex() {
         let pattern = DiagnosisPattern::new_regex("I1[01]", "Hypertension").unwrap();
@@ -195,4 +268,23 @@ mod tests {
         assert!(pattern.matches(&code2));
         assert!(!pattern.matches(&code3));
     }
+
+    #[test]
+    fn test_diagnosis_pattern_range() {
+        let pattern = DiagnosisPattern::new_range("I30", "I52", "Heart disease").unwrap();
+
+        assert!(pattern.matches(&normalize_diagnosis_code("DI30").unwrap()));
+        assert!(pattern.matches(&normalize_diagnosis_code("DI429").unwrap()));
+        assert!(pattern.matches(&normalize_diagnosis_code("DI528").unwrap()));
+        assert!(!pattern.matches(&normalize_diagnosis_code("DI29").unwrap()));
+        assert!(!pattern.matches(&normalize_diagnosis_code("DI53").unwrap()));
+
+        let pattern = DiagnosisPattern::new_range("P941", "P949", "Hypotonia").unwrap();
+        assert!(pattern.matches(&normalize_diagnosis_code("DP942").unwrap()));
+        assert!(!pattern.matches(&normalize_diagnosis_code("DP94").unwrap()));
+        assert!(!pattern.matches(&normalize_diagnosis_code("DP940").unwrap()));
+
+        assert!(DiagnosisPattern::new_range("I52", "I30", "Reversed").is_err());
+        assert!(DiagnosisPattern::new_range("I30", "I5", "Uneven").is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,344 @@
+//! SKS/ICD-10 code dictionary
+//!
+//! The Danish SKS classification of diseases (`schemas/icd10/sks_dump.tsv`) lists every
+//! ICD-10 chapter, block and code with its Danish description, in hierarchical order:
+//!
+//! ```text
+//! Kap. I: Visse infektiøse og parasitære sygdomme [DA00-DB99]
+//! Infektiøse tarmsygdomme [DA00-DA09]
+//! Kolera<TAB>DA00
+//! Kolera forårsaget af Vibrio cholerae<TAB>DA000
+//! ```
+//!
+//! Chapter and block headings have no code column; codes belong to the block and chapter
+//! heading preceding them. Codes are stored without the Danish `D` prefix, in the same
+//! form as [`normalize_diagnosis_code`], and all lookups accept either form.
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::normalize_diagnosis_code;
+
+/// The bundled SKS dump
+const BUNDLED_SKS: &str = include_str!("../../../schemas/icd10/sks_dump.tsv");
+
+static BUNDLED_DICTIONARY: Lazy<Icd10Dictionary> =
+    Lazy::new(|| Icd10Dictionary::parse(BUNDLED_SKS).expect("bundled SKS dump should be valid"));
+
+/// An ICD-10 chapter, e.g. `I: Visse infektiøse og parasitære sygdomme [A00-B99]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SksChapter {
+    /// Roman chapter number, e.g. `XIX`
+    pub number: String,
+    /// Danish chapter title
+    pub title: String,
+    /// First three-character category of the chapter
+    pub start: String,
+    /// Last three-character category of the chapter
+    pub end: String,
+}
+
+/// A block of three-character categories within a chapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SksBlock {
+    /// Danish block title
+    pub title: String,
+    /// First three-character category of the block
+    pub start: String,
+    /// Last three-character category of the block
+    pub end: String,
+    /// Index of the chapter in [`Icd10Dictionary::chapters`]
+    pub chapter: usize,
+}
+
+/// A code in the dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SksCode {
+    /// Code without the `D` prefix, e.g. `A000`
+    pub code: String,
+    /// Danish description
+    pub description: String,
+    /// Index of the block in [`Icd10Dictionary::blocks`]
+    pub block: Option<usize>,
+    /// Index of the chapter in [`Icd10Dictionary::chapters`]
+    pub chapter: Option<usize>,
+}
+
+/// The SKS/ICD-10 hierarchy of chapters, blocks and codes
+#[derive(Debug, Clone, Default)]
+pub struct Icd10Dictionary {
+    chapters: Vec<SksChapter>,
+    blocks: Vec<SksBlock>,
+    codes: BTreeMap<String, SksCode>,
+}
+
+/// Parse a `[DA00-DB99]` heading suffix into the heading text and the range
+fn parse_heading(line: &str) -> Option<(&str, String, String)> {
+    let (text, range) = line.trim_end().strip_suffix(']')?.rsplit_once(" [")?;
+    let (start, end) = range.split_once('-')?;
+    let strip = |code: &str| {
+        normalize_diagnosis_code(code).map_or_else(|| code.to_string(), |n| n.full_code)
+    };
+    Some((text.trim(), strip(start), strip(end)))
+}
+
+impl Icd10Dictionary {
+    /// The dictionary parsed from the bundled SKS dump
+    #[must_use]
+    pub fn bundled() -> &'static Self {
+        &BUNDLED_DICTIONARY
+    }
+
+    /// The text of the bundled SKS dump
+    #[must_use]
+    pub fn bundled_source() -> &'static str {
+        BUNDLED_SKS
+    }
+
+    /// Load a dictionary from an SKS dump file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            IdsError::Validation(format!("Failed to read SKS dump {}: {e}", path.display()))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parse an SKS dump (`Tekst<TAB>Kode` lines with chapter and block headings)
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut dictionary = Self::default();
+        let mut chapter: Option<usize> = None;
+        let mut block: Option<usize> = None;
+
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || (line_number == 0 && line.starts_with("Tekst\t")) {
+                continue;
+            }
+
+            match line.split_once('\t') {
+                Some((description, code)) => {
+                    let code = code.trim();
+                    // The root entry ("Klassifikation af sygdomme ...", `D`) has no code
+                    let Some(normalized) = normalize_diagnosis_code(code) else {
+                        continue;
+                    };
+                    dictionary.codes.insert(
+                        normalized.full_code.clone(),
+                        SksCode {
+                            code: normalized.full_code,
+                            description: description.trim().to_string(),
+                            block,
+                            chapter,
+                        },
+                    );
+                }
+                None => {
+                    // Headings without a range (codes not classified in SKS) have no codes
+                    let Some((text, start, end)) = parse_heading(line) else {
+                        continue;
+                    };
+                    if let Some(heading) = text.strip_prefix("Kap. ") {
+                        let (number, title) = heading.split_once(':').ok_or_else(|| {
+                            IdsError::Validation(format!(
+                                "Invalid SKS chapter heading on line {}: {line}",
+                                line_number + 1
+                            ))
+                        })?;
+                        dictionary.chapters.push(SksChapter {
+                            number: number.trim().to_string(),
+                            title: title.trim().to_string(),
+                            start,
+                            end,
+                        });
+                        chapter = Some(dictionary.chapters.len() - 1);
+                        block = None;
+                    } else {
+                        let chapter_index = chapter.ok_or_else(|| {
+                            IdsError::Validation(format!(
+                                "SKS block heading before the first chapter on line {}: {line}",
+                                line_number + 1
+                            ))
+                        })?;
+                        dictionary.blocks.push(SksBlock {
+                            title: text.to_string(),
+                            start,
+                            end,
+                            chapter: chapter_index,
+                        });
+                        block = Some(dictionary.blocks.len() - 1);
+                    }
+                }
+            }
+        }
+
+        if dictionary.codes.is_empty() {
+            return Err(IdsError::Validation(
+                "SKS dump contains no codes".to_string(),
+            ));
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Number of codes in the dictionary
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Whether the dictionary has no codes
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// All chapters in classification order
+    #[must_use]
+    pub fn chapters(&self) -> &[SksChapter] {
+        &self.chapters
+    }
+
+    /// All blocks in classification order
+    #[must_use]
+    pub fn blocks(&self) -> &[SksBlock] {
+        &self.blocks
+    }
+
+    /// Look up a code (with or without the `D` prefix and dots)
+    #[must_use]
+    pub fn get(&self, code: &str) -> Option<&SksCode> {
+        let normalized = normalize_diagnosis_code(code)?;
+        self.codes.get(&normalized.full_code)
+    }
+
+    /// Whether the code exists in the classification
+    #[must_use]
+    pub fn is_valid(&self, code: &str) -> bool {
+        self.get(code).is_some()
+    }
+
+    /// Danish description of a code
+    #[must_use]
+    pub fn description(&self, code: &str) -> Option<&str> {
+        self.get(code).map(|entry| entry.description.as_str())
+    }
+
+    /// The code itself, or its nearest ancestor for over-specified or local codes
+    ///
+    /// E.g. `F9000X` resolves to `F900` if only the latter is in the classification.
+    #[must_use]
+    pub fn nearest(&self, code: &str) -> Option<&SksCode> {
+        let normalized = normalize_diagnosis_code(code)?;
+        let code = normalized.full_code;
+        (3..=code.len())
+            .rev()
+            .filter_map(|len| code.get(..len))
+            .find_map(|prefix| self.codes.get(prefix))
+    }
+
+    /// The parent of a code in the classification (`A000` -> `A00`)
+    #[must_use]
+    pub fn parent(&self, code: &str) -> Option<&SksCode> {
+        let entry = self.get(code)?;
+        (3..entry.code.len())
+            .rev()
+            .filter_map(|len| entry.code.get(..len))
+            .find_map(|prefix| self.codes.get(prefix))
+    }
+
+    /// Chapter of a code
+    ///
+    /// Codes not in the classification are placed by their nearest ancestor, or else by
+    /// the chapter ranges.
+    #[must_use]
+    pub fn chapter(&self, code: &str) -> Option<&SksChapter> {
+        if let Some(index) = self.nearest(code).and_then(|entry| entry.chapter) {
+            return self.chapters.get(index);
+        }
+        let category = normalize_diagnosis_code(code)?.prefix;
+        self.chapters
+            .iter()
+            .find(|chapter| chapter.start <= category && category <= chapter.end)
+    }
+
+    /// Block of a code, placed like [`Self::chapter`]
+    #[must_use]
+    pub fn block(&self, code: &str) -> Option<&SksBlock> {
+        if let Some(index) = self.nearest(code).and_then(|entry| entry.block) {
+            return self.blocks.get(index);
+        }
+        let category = normalize_diagnosis_code(code)?.prefix;
+        self.blocks
+            .iter()
+            .find(|block| block.start <= category && category <= block.end)
+    }
+
+    /// All codes below a code in the hierarchy, in code order (excluding the code itself)
+    #[must_use]
+    pub fn descendants(&self, code: &str) -> Vec<&SksCode> {
+        let Some(normalized) = normalize_diagnosis_code(code) else {
+            return Vec::new();
+        };
+        let code = normalized.full_code;
+        self.codes
+            .range(code.clone()..)
+            .skip_while(|(key, _)| **key == code)
+            .take_while(|(key, _)| key.starts_with(&code))
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+
+    /// Human-readable label `CODE description` for reports, or the code itself if unknown
+    #[must_use]
+    pub fn label(&self, code: &str) -> String {
+        let Some(normalized) = normalize_diagnosis_code(code) else {
+            return code.trim().to_string();
+        };
+        match self.nearest(&normalized.full_code) {
+            Some(entry) if entry.code == normalized.full_code => {
+                format!("{} {}", entry.code, entry.description)
+            }
+            Some(entry) => format!(
+                "{} ({} {})",
+                normalized.full_code, entry.code, entry.description
+            ),
+            None => normalized.full_code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_dictionary() {
+        let dictionary = Icd10Dictionary::bundled();
+        assert_eq!(dictionary.chapters().len(), 21);
+        assert!(dictionary.len() > 19_000);
+
+        assert!(dictionary.is_valid("DA000"));
+        assert!(dictionary.is_valid("A00.0"));
+        assert!(!dictionary.is_valid("A0099"));
+        assert_eq!(dictionary.description("DA00"), Some("Kolera"));
+        assert_eq!(dictionary.parent("DA001").unwrap().code, "A00");
+
+        assert_eq!(dictionary.chapter("DF900").unwrap().number, "V");
+        assert_eq!(dictionary.chapter("H65").unwrap().number, "VIII");
+        assert_eq!(
+            dictionary.block("A001").unwrap().title,
+            "Infektiøse tarmsygdomme"
+        );
+        assert_eq!(dictionary.nearest("A0099").unwrap().code, "A009");
+
+        let descendants: Vec<&str> = dictionary
+            .descendants("DA00")
+            .iter()
+            .map(|entry| entry.code.as_str())
+            .collect();
+        assert_eq!(descendants, vec!["A000", "A001", "A009"]);
+        assert_eq!(dictionary.label("DA00"), "A00 Kolera");
+    }
+}
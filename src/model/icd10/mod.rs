@@ -1,9 +1,13 @@
 //! ICD-10 chapter classification system
 //!
 //! This module implements the ICD-10 chapter classification system
-//! for categorizing diagnosis codes according to WHO standards.
+//! for categorizing diagnosis codes according to WHO standards. Chapters
+//! are looked up in the SKS code dictionary ([`dictionary`]).
 
 pub mod diagnosis_pattern;
+pub mod dictionary;
+
+pub use dictionary::{Icd10Dictionary, SksBlock, SksChapter, SksCode};
 
 /// Represents the 22 main chapters in the ICD-10 classification system
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -33,10 +37,56 @@ pub enum Icd10Chapter {
 }
 
 impl Icd10Chapter {
+    /// All chapters in classification order
+    pub const ALL: [Self; 22] = [
+        Self::InfectiousParasitic,
+        Self::Neoplasms,
+        Self::BloodImmuneDisorders,
+        Self::EndocrineMetabolicNutritional,
+        Self::MentalBehavioral,
+        Self::NervousSystem,
+        Self::EyeAdnexa,
+        Self::EarMastoid,
+        Self::CirculatorySystem,
+        Self::RespiratorySystem,
+        Self::DigestiveSystem,
+        Self::SkinSubcutaneous,
+        Self::MusculoskeletalConnective,
+        Self::GenitourinarySystem,
+        Self::PregnancyChildbirth,
+        Self::PerinatalPeriod,
+        Self::CongenitalMalformations,
+        Self::SymptomsSignsAbnormalities,
+        Self::InjuryPoisoning,
+        Self::ExternalCauses,
+        Self::FactorsHealthStatus,
+        Self::SpecialPurposes,
+    ];
+
     /// Determine the ICD-10 chapter from a diagnosis code
+    ///
+    /// The chapter is looked up in the bundled SKS dictionary; codes outside the Danish
+    /// classification (e.g. transport accidents `V01-V99` or special purpose `U` codes)
+    /// fall back to the WHO chapter of their first letter.
     #[must_use] pub fn from_code(code: &str) -> Option<Self> {
-        // Return None for empty or too short codes
-        if code.is_empty() || code.is_empty() {
+        Icd10Dictionary::bundled()
+            .chapter(code)
+            .and_then(|chapter| Self::from_number(&chapter.number))
+            .or_else(|| Self::from_first_letter(code))
+    }
+
+    /// The chapter with the given Roman number (`I` to `XXII`)
+    #[must_use] pub fn from_number(number: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|chapter| chapter.number() == number.trim())
+    }
+
+    /// WHO chapter from the first letter of a code
+    fn from_first_letter(code: &str) -> Option<Self> {
+        // Return None for empty codes
+        if code.is_empty() {
             return None;
         }
 
@@ -103,6 +153,43 @@ impl Icd10Chapter {
         }
     }
 
+    /// Get the Roman chapter number
+    #[must_use] pub const fn number(&self) -> &'static str {
+        match self {
+            Self::InfectiousParasitic => "I",
+            Self::Neoplasms => "II",
+            Self::BloodImmuneDisorders => "III",
+            Self::EndocrineMetabolicNutritional => "IV",
+            Self::MentalBehavioral => "V",
+            Self::NervousSystem => "VI",
+            Self::EyeAdnexa => "VII",
+            Self::EarMastoid => "VIII",
+            Self::CirculatorySystem => "IX",
+            Self::RespiratorySystem => "X",
+            Self::DigestiveSystem => "XI",
+            Self::SkinSubcutaneous => "XII",
+            Self::MusculoskeletalConnective => "XIII",
+            Self::GenitourinarySystem => "XIV",
+            Self::PregnancyChildbirth => "XV",
+            Self::PerinatalPeriod => "XVI",
+            Self::CongenitalMalformations => "XVII",
+            Self::SymptomsSignsAbnormalities => "XVIII",
+            Self::InjuryPoisoning => "XIX",
+            Self::ExternalCauses => "XX",
+            Self::FactorsHealthStatus => "XXI",
+            Self::SpecialPurposes => "XXII",
+        }
+    }
+
+    /// Get the Danish SKS title of the chapter, if it is part of the SKS classification
+    #[must_use] pub fn sks_title(&self) -> Option<&'static str> {
+        Icd10Dictionary::bundled()
+            .chapters()
+            .iter()
+            .find(|chapter| chapter.number == self.number())
+            .map(|chapter| chapter.title.as_str())
+    }
+
     /// Get the description of the ICD-10 chapter
     #[must_use] pub const fn description(&self) -> &'static str {
         match self {
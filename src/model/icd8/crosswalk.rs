@@ -0,0 +1,288 @@
+//! ICD-8 to ICD-10 crosswalk
+//!
+//! The bundled crosswalk (`schemas/icd8/icd8_icd10_crosswalk.tsv`) maps Danish ICD-8
+//! codes to ICD-10 categories. Leading `# key: value` lines record the crosswalk's
+//! provenance (`source`, `version`). Each line after the header maps either a code
+//! prefix (`295`, `29639`) or an inclusive range of three-digit categories (`010-019`)
+//! to an ICD-10 code:
+//!
+//! ```text
+//! # source: ...
+//! # version: 2
+//! icd8     icd10  description
+//! 295      F20    Skizofreni
+//! 29639    F31    Manio-depressiv psykose, cirkulær form
+//! 010-019  A16    Tuberkulose
+//! ```
+//!
+//! The longest matching prefix wins, and prefixes take precedence over ranges. ICD-8
+//! categories whose subcategories belong to different ICD-10 categories (such as `296`,
+//! affective psychoses) are only mapped subcategory by subcategory, so an unspecified
+//! or unlisted subcategory stays unmapped rather than being assigned a guess.
+//!
+//! The bundled crosswalk is curated, not an official conversion table, and maps to
+//! categories rather than full codes; it is meant for phenotype matching and
+//! chapter-level reporting. Codes without a mapping have no ICD-10 code.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::{IdsError, Result};
+use crate::model::icd10::diagnosis_pattern::{normalize_diagnosis_code, NormalizedDiagnosis};
+use crate::model::icd8::normalize_icd8_code;
+
+/// The bundled crosswalk
+const BUNDLED_CROSSWALK: &str = include_str!("../../../schemas/icd8/icd8_icd10_crosswalk.tsv");
+
+static BUNDLED: Lazy<Icd8Crosswalk> = Lazy::new(|| {
+    Icd8Crosswalk::parse(BUNDLED_CROSSWALK).expect("bundled ICD-8 crosswalk should be valid")
+});
+
+/// A crosswalk line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrosswalkEntry {
+    /// The ICD-8 prefix or range as written in the crosswalk
+    pub icd8: String,
+    /// The ICD-10 code
+    pub icd10: String,
+    /// Description of the mapping
+    pub description: String,
+}
+
+/// Where a crosswalk comes from, from its `# key: value` header lines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrosswalkProvenance {
+    /// The `source` line
+    pub source: Option<String>,
+    /// The `version` line
+    pub version: Option<String>,
+}
+
+/// An ICD-8 to ICD-10 crosswalk
+#[derive(Debug, Clone, Default)]
+pub struct Icd8Crosswalk {
+    provenance: CrosswalkProvenance,
+    entries: Vec<CrosswalkEntry>,
+    prefixes: HashMap<String, usize>,
+    /// Inclusive category ranges with the index of their entry
+    ranges: Vec<(u16, u16, usize)>,
+}
+
+impl Icd8Crosswalk {
+    /// The crosswalk parsed from the bundled TSV file
+    #[must_use]
+    pub fn bundled() -> &'static Self {
+        &BUNDLED
+    }
+
+    /// The text of the bundled crosswalk file
+    #[must_use]
+    pub fn bundled_source() -> &'static str {
+        BUNDLED_CROSSWALK
+    }
+
+    /// Load a crosswalk from a TSV file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            IdsError::Validation(format!(
+                "Failed to read ICD-8 crosswalk {}: {e}",
+                path.display()
+            ))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parse a crosswalk (`icd8<TAB>icd10<TAB>description` lines with a header, after
+    /// optional `# key: value` provenance lines)
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut crosswalk = Self::default();
+        let mut lines = content.lines().enumerate().peekable();
+
+        while let Some((_, comment)) = lines.next_if(|(_, line)| line.starts_with('#')) {
+            let Some((key, value)) = comment.trim_start_matches('#').split_once(':') else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "source" => crosswalk.provenance.source = value,
+                "version" => crosswalk.provenance.version = value,
+                _ => {}
+            }
+        }
+        // Skip the column header
+        lines.next();
+
+        for (line_number, line) in lines {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| {
+                IdsError::Validation(format!(
+                    "Invalid ICD-8 crosswalk line {}: {reason}: {line}",
+                    line_number + 1
+                ))
+            };
+
+            let mut fields = line.split('\t');
+            let icd8 = fields.next().unwrap_or_default().trim();
+            let icd10 = fields.next().map(str::trim).unwrap_or_default();
+            let description = fields.next().map(str::trim).unwrap_or_default();
+            let icd10_code = normalize_diagnosis_code(icd10)
+                .ok_or_else(|| invalid("invalid ICD-10 code"))?
+                .full_code;
+
+            let index = crosswalk.entries.len();
+            if let Some((from, to)) = icd8.split_once('-') {
+                let parse = |category: &str| {
+                    category
+                        .trim()
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|_| category.trim().len() == 3)
+                };
+                let (Some(from), Some(to)) = (parse(from), parse(to)) else {
+                    return Err(invalid("ranges must be three-digit categories"));
+                };
+                if from > to {
+                    return Err(invalid("range start is after range end"));
+                }
+                crosswalk.ranges.push((from, to, index));
+            } else {
+                let prefix = normalize_icd8_code(icd8)
+                    .ok_or_else(|| invalid("invalid ICD-8 code"))?
+                    .full_code;
+                if crosswalk.prefixes.insert(prefix, index).is_some() {
+                    return Err(invalid("duplicate ICD-8 code"));
+                }
+            }
+
+            crosswalk.entries.push(CrosswalkEntry {
+                icd8: icd8.to_string(),
+                icd10: icd10_code,
+                description: description.to_string(),
+            });
+        }
+
+        // Narrower ranges first, so the narrowest matching range wins
+        crosswalk.ranges.sort_by_key(|(from, to, _)| to - from);
+
+        Ok(crosswalk)
+    }
+
+    /// Where the crosswalk comes from
+    #[must_use]
+    pub fn provenance(&self) -> &CrosswalkProvenance {
+        &self.provenance
+    }
+
+    /// All crosswalk lines in file order
+    #[must_use]
+    pub fn entries(&self) -> &[CrosswalkEntry] {
+        &self.entries
+    }
+
+    /// Find the crosswalk line for an ICD-8 code
+    #[must_use]
+    pub fn lookup(&self, code: &str) -> Option<&CrosswalkEntry> {
+        let normalized = normalize_icd8_code(code)?;
+        let code = normalized.full_code;
+
+        let by_prefix = (3..=code.len())
+            .rev()
+            .filter_map(|len| code.get(..len))
+            .find_map(|prefix| self.prefixes.get(prefix));
+        let index = by_prefix.copied().or_else(|| {
+            let category: u16 = normalized.prefix.parse().ok()?;
+            self.ranges
+                .iter()
+                .find(|(from, to, _)| (*from..=*to).contains(&category))
+                .map(|(_, _, index)| *index)
+        })?;
+        self.entries.get(index)
+    }
+
+    /// Map an ICD-8 code to its ICD-10 code
+    #[must_use]
+    pub fn to_icd10(&self, code: &str) -> Option<NormalizedDiagnosis> {
+        normalize_diagnosis_code(&self.lookup(code)?.icd10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::icd10::Icd10Dictionary;
+
+    /// Every crosswalk target must exist in the SKS classification
+    #[test]
+    fn test_bundled_crosswalk_targets() {
+        let crosswalk = Icd8Crosswalk::bundled();
+        let dictionary = Icd10Dictionary::bundled();
+        for entry in crosswalk.entries() {
+            assert!(
+                dictionary.is_valid(&entry.icd10),
+                "{} maps to unknown ICD-10 code {}",
+                entry.icd8,
+                entry.icd10
+            );
+        }
+
+        assert_eq!(crosswalk.lookup("01199").unwrap().icd10, "A16");
+        assert_eq!(crosswalk.lookup("42793").unwrap().icd10, "I48");
+        assert_eq!(crosswalk.lookup("42709").unwrap().icd10, "I50");
+        assert!(crosswalk.lookup("79699").is_none());
+    }
+
+    #[test]
+    fn test_bundled_crosswalk_provenance() {
+        let provenance = Icd8Crosswalk::bundled().provenance();
+        assert!(provenance.source.is_some());
+        assert_eq!(provenance.version.as_deref(), Some("2"));
+    }
+
+    /// Categories spanning several ICD-10 categories are mapped per subcategory
+    #[test]
+    fn test_subcategory_mappings() {
+        let crosswalk = Icd8Crosswalk::bundled();
+        let icd10 = |code| crosswalk.to_icd10(code).map(|d| d.full_code);
+
+        assert_eq!(icd10("29619").as_deref(), Some("F30"));
+        assert_eq!(icd10("29629").as_deref(), Some("F33"));
+        assert_eq!(icd10("29639").as_deref(), Some("F31"));
+        assert_eq!(icd10("29699").as_deref(), Some("F39"));
+        assert_eq!(icd10("296"), None);
+
+        assert_eq!(icd10("21109").as_deref(), Some("D13"));
+        assert_eq!(icd10("21809").as_deref(), Some("D25"));
+        assert_eq!(icd10("22509").as_deref(), Some("D33"));
+        assert_eq!(icd10("23009").as_deref(), Some("D37"));
+        assert_eq!(icd10("23309"), None);
+    }
+
+    #[test]
+    fn test_parse_provenance_and_entries() {
+        let crosswalk = Icd8Crosswalk::parse(
+            "# source: Test table\n# version: 2024-01\nicd8\ticd10\tdescription\n\
+             29509\tF20\tSkizofreni\n010-019\tA16\tTuberkulose\n",
+        )
+        .unwrap();
+        assert_eq!(
+            crosswalk.provenance(),
+            &CrosswalkProvenance {
+                source: Some("Test table".to_string()),
+                version: Some("2024-01".to_string()),
+            }
+        );
+        assert_eq!(crosswalk.entries().len(), 2);
+        assert_eq!(crosswalk.lookup("01509").unwrap().icd10, "A16");
+
+        // Without provenance lines the first line is the header
+        let bare = Icd8Crosswalk::parse("icd8\ticd10\n29509\tF20\n").unwrap();
+        assert_eq!(bare.provenance(), &CrosswalkProvenance::default());
+        assert_eq!(bare.entries().len(), 1);
+
+        assert!(Icd8Crosswalk::parse("icd8\ticd10\n1\tF20\n").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! ICD-8 support for pre-1994 LPR data
+//!
+//! LPR2 used the Danish edition of ICD-8 until the end of 1993. ICD-8 codes are numeric
+//! (a three-digit category with up to two Danish extension digits, e.g. `29699`), with
+//! `E` codes for external causes of injury and `Y` codes for supplementary
+//! classifications. Since `E` and `Y` codes look like ICD-10 codes, the code system of a
+//! diagnosis is detected from both the contact date and the shape of the code.
+//!
+//! ICD-8 codes are mapped to ICD-10 with the bundled crosswalk ([`crosswalk`]) so that
+//! disease definitions written for ICD-10 also find diagnoses from the 1970s and 1980s.
+//! ICD-8 codes without a mapping have no ICD-10 code ([`HarmonizedDiagnosis::icd10_code`])
+//! and must not be mixed into ICD-10 columns.
+
+pub mod crosswalk;
+
+use chrono::NaiveDate;
+use std::fmt;
+
+use crate::model::icd10::diagnosis_pattern::{normalize_diagnosis_code, NormalizedDiagnosis};
+use crate::model::icd10::Icd10Chapter;
+
+pub use crosswalk::{CrosswalkEntry, CrosswalkProvenance, Icd8Crosswalk};
+
+/// The diagnosis classification a code belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeSystem {
+    /// The Danish edition of ICD-8, used in LPR until 31 December 1993
+    Icd8,
+    /// ICD-10 (SKS), used from 1 January 1994
+    Icd10,
+}
+
+impl fmt::Display for CodeSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Icd8 => write!(f, "icd8"),
+            Self::Icd10 => write!(f, "icd10"),
+        }
+    }
+}
+
+/// The date ICD-10 replaced ICD-8 in the Danish National Patient Register
+#[must_use]
+pub fn icd10_introduction_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1994, 1, 1).expect("valid date")
+}
+
+/// Remove dots and whitespace and convert to upper case
+fn clean_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| *c != '.' && !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// Whether a cleaned code has the shape of an ICD-8 code
+///
+/// Numeric and `E` codes have 3 to 5 digits (`E` codes cover E800-E999); `Y` codes have
+/// 2 to 5 digits.
+fn has_icd8_shape(code: &str) -> bool {
+    let (digits, min_digits) = match code.strip_prefix('Y') {
+        Some(digits) => (digits, 2),
+        None => (code.strip_prefix('E').unwrap_or(code), 3),
+    };
+    (min_digits..=5).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Detect the code system of a diagnosis code
+///
+/// Purely numeric codes are always ICD-8 and Danish `D`-prefixed codes are always
+/// ICD-10. Codes that fit both systems (`E` and `Y` codes) are ICD-8 if the contact was
+/// before 1994 and ICD-10 otherwise, including when the contact date is unknown.
+#[must_use]
+pub fn detect_code_system(code: &str, contact_date: Option<NaiveDate>) -> Option<CodeSystem> {
+    let code = clean_code(code);
+    if code.is_empty() {
+        return None;
+    }
+
+    if code.bytes().all(|b| b.is_ascii_digit()) {
+        return has_icd8_shape(&code).then_some(CodeSystem::Icd8);
+    }
+
+    let before_icd10 = contact_date.is_some_and(|date| date < icd10_introduction_date());
+    if before_icd10 && has_icd8_shape(&code) {
+        return Some(CodeSystem::Icd8);
+    }
+
+    normalize_diagnosis_code(&code).map(|_| CodeSystem::Icd10)
+}
+
+/// Normalize an ICD-8 code
+///
+/// The prefix is the three-digit category (`296` for `296.99`), or the letter and
+/// three digits for `E` and `Y` codes. Returns `None` for codes without an ICD-8 shape.
+#[must_use]
+pub fn normalize_icd8_code(code: &str) -> Option<NormalizedDiagnosis> {
+    let full_code = clean_code(code);
+    if !has_icd8_shape(&full_code) {
+        return None;
+    }
+
+    let category_len = if full_code.starts_with(['E', 'Y']) {
+        4
+    } else {
+        3
+    };
+    Some(NormalizedDiagnosis {
+        prefix: full_code.chars().take(category_len).collect(),
+        full_code,
+    })
+}
+
+/// The ICD-10 chapter corresponding to the ICD-8 chapter of a code
+#[must_use]
+pub fn icd8_chapter(code: &str) -> Option<Icd10Chapter> {
+    let normalized = normalize_icd8_code(code)?;
+    match normalized.full_code.as_bytes()[0] {
+        b'E' => return Some(Icd10Chapter::ExternalCauses),
+        b'Y' => return Some(Icd10Chapter::FactorsHealthStatus),
+        _ => {}
+    }
+
+    let category: u16 = normalized.prefix.parse().ok()?;
+    let chapter = match category {
+        0..=136 => Icd10Chapter::InfectiousParasitic,
+        140..=239 => Icd10Chapter::Neoplasms,
+        240..=279 => Icd10Chapter::EndocrineMetabolicNutritional,
+        280..=289 => Icd10Chapter::BloodImmuneDisorders,
+        290..=315 => Icd10Chapter::MentalBehavioral,
+        320..=358 => Icd10Chapter::NervousSystem,
+        360..=379 => Icd10Chapter::EyeAdnexa,
+        380..=389 => Icd10Chapter::EarMastoid,
+        390..=458 => Icd10Chapter::CirculatorySystem,
+        460..=519 => Icd10Chapter::RespiratorySystem,
+        520..=577 => Icd10Chapter::DigestiveSystem,
+        580..=629 => Icd10Chapter::GenitourinarySystem,
+        630..=678 => Icd10Chapter::PregnancyChildbirth,
+        680..=709 => Icd10Chapter::SkinSubcutaneous,
+        710..=738 => Icd10Chapter::MusculoskeletalConnective,
+        740..=759 => Icd10Chapter::CongenitalMalformations,
+        760..=779 => Icd10Chapter::PerinatalPeriod,
+        780..=796 => Icd10Chapter::SymptomsSignsAbnormalities,
+        800..=999 => Icd10Chapter::InjuryPoisoning,
+        _ => return None,
+    };
+    Some(chapter)
+}
+
+/// A diagnosis code harmonised to ICD-10
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarmonizedDiagnosis {
+    /// The ICD-10 code, or the normalized ICD-8 code if it has no ICD-10 mapping
+    pub code: NormalizedDiagnosis,
+    /// The code system of the registered code
+    pub system: CodeSystem,
+    /// The normalized ICD-8 code, for ICD-8 diagnoses
+    pub icd8_code: Option<String>,
+    /// Whether an ICD-8 code was mapped to ICD-10
+    pub mapped: bool,
+}
+
+impl HarmonizedDiagnosis {
+    /// The ICD-10 code, or `None` for an ICD-8 code without a mapping
+    #[must_use]
+    pub fn icd10_code(&self) -> Option<&str> {
+        (self.system == CodeSystem::Icd10 || self.mapped).then_some(self.code.full_code.as_str())
+    }
+
+    /// The ICD-10 chapter of the diagnosis (the ICD-8 chapter for unmapped ICD-8 codes)
+    #[must_use]
+    pub fn chapter(&self) -> Option<Icd10Chapter> {
+        match (self.system, self.mapped) {
+            (CodeSystem::Icd8, false) => icd8_chapter(&self.code.full_code),
+            _ => Icd10Chapter::from_code(&self.code.full_code),
+        }
+    }
+}
+
+/// Harmonise a registered diagnosis code to ICD-10
+///
+/// ICD-10 codes are normalized with [`normalize_diagnosis_code`]; ICD-8 codes are mapped
+/// with the bundled crosswalk.
+#[must_use]
+pub fn harmonize_diagnosis_code(
+    code: &str,
+    contact_date: Option<NaiveDate>,
+) -> Option<HarmonizedDiagnosis> {
+    match detect_code_system(code, contact_date)? {
+        CodeSystem::Icd10 => Some(HarmonizedDiagnosis {
+            code: normalize_diagnosis_code(code)?,
+            system: CodeSystem::Icd10,
+            icd8_code: None,
+            mapped: false,
+        }),
+        CodeSystem::Icd8 => {
+            let icd8 = normalize_icd8_code(code)?;
+            let mapped = Icd8Crosswalk::bundled().to_icd10(&icd8.full_code);
+            Some(HarmonizedDiagnosis {
+                mapped: mapped.is_some(),
+                code: mapped.unwrap_or_else(|| icd8.clone()),
+                system: CodeSystem::Icd8,
+                icd8_code: Some(icd8.full_code),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icd8_detection_and_mapping() {
+        let date = |year| NaiveDate::from_ymd_opt(year, 6, 1);
+
+        assert_eq!(detect_code_system("29699", None), Some(CodeSystem::Icd8));
+        assert_eq!(
+            detect_code_system("296.99", date(2000)),
+            Some(CodeSystem::Icd8)
+        );
+        assert_eq!(
+            detect_code_system("DF200", date(1990)),
+            Some(CodeSystem::Icd10)
+        );
+        assert_eq!(
+            detect_code_system("E8889", date(1990)),
+            Some(CodeSystem::Icd8)
+        );
+        assert_eq!(
+            detect_code_system("E8889", date(2000)),
+            Some(CodeSystem::Icd10)
+        );
+        assert_eq!(detect_code_system("E8889", None), Some(CodeSystem::Icd10));
+
+        assert_eq!(normalize_icd8_code("295.09").unwrap().prefix, "295");
+        assert!(normalize_icd8_code("F20").is_none());
+        assert_eq!(icd8_chapter("29509"), Some(Icd10Chapter::MentalBehavioral));
+        assert_eq!(icd8_chapter("38199"), Some(Icd10Chapter::EarMastoid));
+
+        let schizophrenia = harmonize_diagnosis_code("295.09", date(1985)).unwrap();
+        assert_eq!(schizophrenia.code.full_code, "F20");
+        assert_eq!(schizophrenia.icd8_code.as_deref(), Some("29509"));
+        assert!(schizophrenia.mapped);
+
+        let depression = harmonize_diagnosis_code("29609", date(1985)).unwrap();
+        assert_eq!(depression.code.full_code, "F32");
+
+        let unmapped = harmonize_diagnosis_code("79699", date(1985)).unwrap();
+        assert!(!unmapped.mapped);
+        assert_eq!(unmapped.icd10_code(), None);
+        assert_eq!(
+            unmapped.chapter(),
+            Some(Icd10Chapter::SymptomsSignsAbnormalities)
+        );
+
+        let icd10 = harmonize_diagnosis_code("DF200", date(1990)).unwrap();
+        assert_eq!(icd10.code.full_code, "F200");
+        assert_eq!(icd10.system, CodeSystem::Icd10);
+        assert_eq!(icd10.icd10_code(), Some("F200"));
+    }
+}
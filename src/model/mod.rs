@@ -4,6 +4,8 @@
 
 pub mod pnr;
 pub mod family;
+pub mod family_graph;
 pub mod covariate;
 pub mod population;
-pub mod icd10;
\ No newline at end of file
+pub mod icd10;
+pub mod icd8;
\ No newline at end of file
@@ -0,0 +1,26 @@
+//! Fixtures shared by the unit tests
+
+use arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// A new temporary directory, removed with its contents when dropped
+pub(crate) fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("ids_test_")
+        .tempdir()
+        .expect("temporary directory should be created")
+}
+
+/// Write a batch to a Parquet file, creating missing parent directories
+pub(crate) fn write_parquet(path: &Path, batch: &RecordBatch) {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+    let mut writer =
+        ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), None).unwrap();
+    writer.write(batch).unwrap();
+    writer.close().unwrap();
+}
@@ -34,6 +34,8 @@ pub fn parse_flexible(date_str: &str) -> Result<NaiveDate> {
         "%d.%m.%Y",
         // Compact formats
         "%Y%m%d",
+        // SAS DATE9 format, 01JAN2020
+        "%d%b%Y",
         // With time components (ignoring time)
         "%Y-%m-%d %H:%M:%S",
         "%Y-%m-%dT%H:%M:%S",
@@ -121,6 +121,8 @@ impl StructuredLogger {
     }
     
     /// Log a message with structured context
+    ///
+    /// Log lines go to stderr so that commands can write results to stdout.
     pub fn log_with_context(&self, 
                            level: Level, 
                            context: &LogContext, 
@@ -161,7 +163,7 @@ impl StructuredLogger {
             "".to_string()
         };
         
-        println!(
+        eprintln!(
             "[{}] {} {} {}{}: {}",
             chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
             level_str,
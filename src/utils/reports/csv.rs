@@ -63,6 +63,15 @@ pub fn generate_balance_report(report_path: &str, report: &BalanceReport) -> Res
     Ok(())
 }
 
+/// Quote a CSV field if it contains a separator, quote or line break
+fn escape_csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
 /// Write a generic CSV report to a file
 ///
 /// # Arguments
@@ -78,7 +87,11 @@ pub fn write_csv_report(path: &Path, rows: &[Vec<String>]) -> Result<()> {
     let mut writer = BufWriter::new(file);
     
     for row in rows {
-        let line = row.join(",");
+        let line = row
+            .iter()
+            .map(|field| escape_csv_field(field))
+            .collect::<Vec<_>>()
+            .join(",");
         writeln!(writer, "{line}")
             .map_err(IdsError::Io)?;
     }
@@ -0,0 +1,128 @@
+//! Diagnosis frequency reports
+//!
+//! Counts the primary diagnoses of harmonised LPR data and labels them with their SKS
+//! description and ICD-10 chapter, so reports show diagnosis names rather than bare codes.
+
+use arrow::array::{Array, StringArray};
+use arrow::record_batch::RecordBatch;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::error::{IdsError, Result};
+use crate::model::icd10::{Icd10Chapter, Icd10Dictionary};
+use crate::utils::reports::write_csv_report;
+
+/// Contact and distinct patient counts per diagnosis code, accumulated batch by batch
+#[derive(Debug, Default)]
+pub struct DiagnosisFrequencies {
+    counts: HashMap<String, (usize, HashSet<String>)>,
+}
+
+impl DiagnosisFrequencies {
+    /// Count the `diagnosis_column` codes of a batch, optionally only for the given patients
+    pub fn add(
+        &mut self,
+        lpr_data: &RecordBatch,
+        patient_id_column: &str,
+        diagnosis_column: &str,
+        patients: Option<&HashSet<String>>,
+    ) -> Result<()> {
+        let string_column = |name: &str| {
+            lpr_data
+                .column_by_name(name)
+                .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+                .ok_or_else(|| {
+                    IdsError::Data(format!("Column '{name}' not found or not a string array"))
+                })
+        };
+        let patient_ids = string_column(patient_id_column)?;
+        let diagnoses = string_column(diagnosis_column)?;
+
+        for row in 0..lpr_data.num_rows() {
+            if patient_ids.is_null(row) || diagnoses.is_null(row) {
+                continue;
+            }
+            let patient_id = patient_ids.value(row);
+            if patients.is_some_and(|patients| !patients.contains(patient_id)) {
+                continue;
+            }
+            let (contacts, patients) = self
+                .counts
+                .entry(diagnoses.value(row).to_string())
+                .or_default();
+            *contacts += 1;
+            if !patients.contains(patient_id) {
+                patients.insert(patient_id.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Report rows (with a header row) for at most `limit` codes ordered by contact count
+    #[must_use]
+    pub fn rows(&self, limit: usize) -> Vec<Vec<String>> {
+        let mut counts: Vec<(&str, usize, usize)> = self
+            .counts
+            .iter()
+            .map(|(code, (contacts, patients))| (code.as_str(), *contacts, patients.len()))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        let dictionary = Icd10Dictionary::bundled();
+        let mut rows = vec![vec![
+            "Code".to_string(),
+            "Description".to_string(),
+            "Chapter".to_string(),
+            "Contacts".to_string(),
+            "Patients".to_string(),
+        ]];
+        for (code, contacts, patients) in counts.into_iter().take(limit) {
+            rows.push(vec![
+                code.to_string(),
+                dictionary
+                    .nearest(code)
+                    .map(|entry| entry.description.clone())
+                    .unwrap_or_default(),
+                Icd10Chapter::from_code(code)
+                    .map(|chapter| chapter.description().to_string())
+                    .unwrap_or_default(),
+                contacts.to_string(),
+                patients.to_string(),
+            ]);
+        }
+        rows
+    }
+
+    /// Save the report of the primary diagnoses counted so far
+    pub fn save(&self, path: &Path, limit: usize) -> Result<()> {
+        write_csv_report(path, &self.rows(limit))
+    }
+}
+
+/// Build diagnosis frequency rows (with a header row) for the most frequent diagnoses
+///
+/// Counts contacts and distinct patients per `diagnosis_column` code, optionally only for
+/// the given patients, and returns at most `limit` codes ordered by contact count.
+pub fn diagnosis_frequency_rows(
+    lpr_data: &RecordBatch,
+    patient_id_column: &str,
+    diagnosis_column: &str,
+    patients: Option<&HashSet<String>>,
+    limit: usize,
+) -> Result<Vec<Vec<String>>> {
+    let mut frequencies = DiagnosisFrequencies::default();
+    frequencies.add(lpr_data, patient_id_column, diagnosis_column, patients)?;
+    Ok(frequencies.rows(limit))
+}
+
+/// Save a diagnosis frequency report of the primary diagnoses in harmonised LPR data
+pub fn save_diagnosis_report(
+    path: &Path,
+    lpr_data: &RecordBatch,
+    patients: Option<&HashSet<String>>,
+    limit: usize,
+) -> Result<()> {
+    let rows =
+        diagnosis_frequency_rows(lpr_data, "patient_id", "primary_diagnosis", patients, limit)?;
+    write_csv_report(path, &rows)
+}
@@ -3,8 +3,10 @@
 //! This module provides functions for generating various kinds of reports.
 
 pub mod csv;
+mod diagnoses;
 mod population;
 
 pub use csv::generate_balance_report;
 pub use csv::write_csv_report;
-pub use population::save_population_summary;
+pub use diagnoses::{diagnosis_frequency_rows, save_diagnosis_report, DiagnosisFrequencies};
+pub use population::{save_exclusion_report, save_population_summary};
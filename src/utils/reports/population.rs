@@ -8,8 +8,9 @@ use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
-use crate::algorithm::population::PopulationSummary;
+use crate::algorithm::population::{PopulationSummary, ResidencyResult};
 use crate::error::{IdsError, Result};
+use crate::utils::reports::write_csv_report;
 
 /// Save population summary statistics to CSV files
 pub fn save_population_summary(
@@ -33,6 +34,9 @@ pub fn save_population_summary(
         &output_dir.join("population_summary_after.csv"),
     )?;
 
+    // Save BEF/MFR disagreements
+    save_parent_conflicts(summary_after, &output_dir.join("parent_conflicts.csv"))?;
+
     // Save basic statistics
     save_basic_stats(family_data, &output_dir.join("basic_stats.csv"))?;
 
@@ -48,6 +52,58 @@ pub fn save_population_summary(
     Ok(())
 }
 
+/// Save a flowchart-style exclusion report for residency eligibility rules
+///
+/// Writes `exclusion_flowchart.csv` with a row for rows without a PNR and one row per rule
+/// in the order applied, and `excluded_persons.csv` listing each excluded PNR with the
+/// rule that excluded it.
+pub fn save_exclusion_report(result: &ResidencyResult, output_dir: &Path) -> Result<()> {
+    fs::create_dir_all(output_dir)?;
+
+    let file = File::create(output_dir.join("exclusion_flowchart.csv"))?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "Step,Rule,Description,Remaining Before,Excluded,Remaining After")?;
+    for (i, step) in result.steps.iter().enumerate() {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            i + 1,
+            step.reason,
+            step.reason.description(),
+            step.remaining_before,
+            step.excluded,
+            step.remaining_after()
+        )?;
+    }
+    writer.flush()?;
+
+    let file = File::create(output_dir.join("excluded_persons.csv"))?;
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "PNR,Rule")?;
+    for (pnr, rule) in &result.excluded {
+        writeln!(writer, "{pnr},{rule}")?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Save the BEF/MFR parent and birth date disagreements found during merge
+fn save_parent_conflicts(summary: &PopulationSummary, output_file: &Path) -> Result<()> {
+    let header = ["PNR", "Field", "BEF Value", "MFR Value", "Chosen Source"];
+    let mut rows = vec![header.iter().map(ToString::to_string).collect()];
+    rows.extend(summary.parent_conflicts.iter().map(|conflict| {
+        vec![
+            conflict.pnr.clone(),
+            conflict.field.to_string(),
+            conflict.bef_value.clone(),
+            conflict.mfr_value.clone(),
+            conflict.chosen.as_str().to_string(),
+        ]
+    }));
+    write_csv_report(output_file, &rows)
+}
+
 /// Save summary statistics before data merge
 fn save_summary_before(summary: &PopulationSummary, output_file: &Path) -> Result<()> {
     let file = File::create(output_file)?;
@@ -102,6 +158,14 @@ fn save_summary_after(summary: &PopulationSummary, output_file: &Path) -> Result
         "records_only_in_mfr,{}",
         summary.records_only_in_mfr
     )?;
+    for field in ["MOR_ID", "FAR_ID", "FOED_DAG"] {
+        let count = summary
+            .parent_conflicts
+            .iter()
+            .filter(|conflict| conflict.field == field)
+            .count();
+        writeln!(writer, "{}_conflicts,{}", field.to_lowercase(), count)?;
+    }
 
     writer.flush()?;
     Ok(())
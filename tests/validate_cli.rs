@@ -0,0 +1,104 @@
+//! The validate command prints its JSON report on stdout and fails on invalid files
+
+use arrow::array::{Date32Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use datafusion::parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+/// Write a BEF file with the given PNRs, all born 2020-01-01
+fn write_bef(path: &Path, pnrs: Vec<Option<&str>>) {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("PNR", DataType::Utf8, true),
+        Field::new("FOED_DAG", DataType::Date32, true),
+    ]));
+    let rows = pnrs.len();
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from(pnrs)),
+            Arc::new(Date32Array::from(vec![18262; rows])),
+        ],
+    )
+    .unwrap();
+    let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+    writer.write(&batch).unwrap();
+    writer.close().unwrap();
+}
+
+fn validate(path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ids-rs"))
+        .args(["validate", "--registry", "bef", "--input"])
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+fn stdout_json(output: &Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+        panic!(
+            "stdout is not JSON ({e}):\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })
+}
+
+#[test]
+fn test_validate_prints_only_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bef2020.parquet");
+    write_bef(&path, vec![Some("0101801234"), Some("0202801234")]);
+
+    let output = validate(&path);
+    assert!(output.status.success());
+    let report = stdout_json(&output);
+    assert_eq!(report["passed"], true);
+    assert_eq!(report["files_checked"], 1);
+}
+
+#[test]
+fn test_validate_failure_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bef2020.parquet");
+    write_bef(&path, vec![Some("0101801234"), Some("12345"), None]);
+
+    let output = validate(&path);
+    assert!(!output.status.success());
+    let report = stdout_json(&output);
+    assert_eq!(report["passed"], false);
+    assert_eq!(report["files_failed"], 1);
+}
+
+#[test]
+fn test_unreadable_file_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bef2020.parquet");
+    std::fs::write(&path, b"not parquet").unwrap();
+
+    let output = validate(&path);
+    assert!(!output.status.success());
+    let report = stdout_json(&output);
+    assert_eq!(report["files_failed"], 1);
+    assert!(report["files"][0]["error"].is_string());
+}
+
+#[test]
+fn test_empty_directory_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = validate(dir.path());
+    assert!(!output.status.success());
+    assert_eq!(stdout_json(&output)["files_checked"], 0);
+}
+
+#[test]
+fn test_missing_input_exits_non_zero() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let output = validate(&dir.path().join("missing"));
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}